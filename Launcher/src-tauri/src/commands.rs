@@ -1,6 +1,9 @@
 use tauri::State;
 use crate::core::models::*;
 use crate::core::logic;
+use crate::export::{einvoice, facturx, journal, pdf, template};
+use crate::import::bank;
+use crate::mail;
 use crate::storage::db::{Database, PaySummary, AnalyticsData};
 use std::collections::HashMap;
 
@@ -46,6 +49,12 @@ pub fn get_sessions_by_type_id(db: State<Database>, session_type_id: i64) -> Res
     db.get_sessions_by_type_id(session_type_id).map_err(|e| format!("Failed to get sessions by type: {}", e))
 }
 
+#[tauri::command]
+pub fn query_sessions(db: State<Database>, query: SessionQuery) -> Result<SessionPage, String> {
+    query.filter.validate()?;
+    db.query_sessions(&query).map_err(|e| format!("Failed to query sessions: {}", e))
+}
+
 #[tauri::command]
 pub fn add_session(db: State<Database>, session: NewSession) -> Result<i64, String> {
     if session.hours < 0.1 || session.hours > 24.0 {
@@ -65,6 +74,10 @@ pub fn update_session(db: State<Database>, session: WorkSession) -> Result<(), S
 
 #[tauri::command]
 pub fn delete_session(db: State<Database>, id: i64) -> Result<(), String> {
+    let invoice_number = db.get_session_invoice_number(id).map_err(|e| format!("Failed to delete session: {}", e))?;
+    if let Some(number) = invoice_number {
+        return Err(format!("This session is on invoice {}, which has been issued, so it can't be deleted", number));
+    }
     db.delete_session(id).map_err(|e| format!("Failed to delete session: {}", e))
 }
 
@@ -141,8 +154,8 @@ pub fn get_today_summary(db: State<Database>) -> Result<TodaySummary, String> {
 }
 
 #[tauri::command]
-pub fn get_pay_summary(db: State<Database>) -> Result<PaySummary, String> {
-    db.get_pay_summary().map_err(|e| format!("Failed to get pay summary: {}", e))
+pub fn get_pay_summary(db: State<Database>, basis: Option<ReportingBasis>) -> Result<PaySummary, String> {
+    db.get_pay_summary(basis.unwrap_or_default()).map_err(|e| format!("Failed to get pay summary: {}", e))
 }
 
 #[tauri::command]
@@ -174,8 +187,8 @@ pub fn get_current_date() -> String {
 // ========== ANALYTICS COMMANDS ==========
 
 #[tauri::command]
-pub fn get_analytics(db: State<Database>, range_start: String, range_end: String) -> Result<AnalyticsData, String> {
-    db.get_analytics(&range_start, &range_end)
+pub fn get_analytics(db: State<Database>, range_start: String, range_end: String, basis: Option<ReportingBasis>) -> Result<AnalyticsData, String> {
+    db.get_analytics(&range_start, &range_end, basis.unwrap_or_default())
         .map_err(|e| format!("Failed to get analytics: {}", e))
 }
 
@@ -388,8 +401,13 @@ pub fn simulate_financial_scenario(
     db: State<Database>,
     hours_per_week: f64,
     hourly_rate: f64,
-    weekly_expenses: f64,
+    weekly_expenses: Option<f64>,
 ) -> Result<SimulationResult, String> {
+    // Without a figure, simulate with what is actually being spent
+    let weekly_expenses = match weekly_expenses {
+        Some(amount) => amount,
+        None => actual_weekly_expenses(&db)?,
+    };
     let weekly_income = hours_per_week * hourly_rate;
     let weekly_savings = weekly_income - weekly_expenses;
     
@@ -468,8 +486,9 @@ pub fn simulate_financial_scenario(
     })
 }
 
+/// Average weekly hours, hourly rate and expenses
 #[tauri::command]
-pub fn get_current_financial_baseline(db: State<Database>) -> Result<(f64, f64), String> {
+pub fn get_current_financial_baseline(db: State<Database>) -> Result<(f64, f64, f64), String> {
     let sessions = db.get_all_sessions().map_err(|e| format!("Failed: {}", e))?;
     let avg_weekly_hours = logic::calculate_avg_weekly_hours(&sessions);
     let avg_weekly_income = logic::calculate_avg_weekly_income(&sessions);
@@ -478,7 +497,13 @@ pub fn get_current_financial_baseline(db: State<Database>) -> Result<(f64, f64),
     } else {
         30.0 // Default
     };
-    Ok((avg_weekly_hours, avg_hourly_rate))
+    Ok((avg_weekly_hours, avg_hourly_rate, actual_weekly_expenses(&db)?))
+}
+
+/// Typical weekly spend from the expense history, business and personal
+fn actual_weekly_expenses(db: &Database) -> Result<f64, String> {
+    let expenses = db.get_all_expenses().map_err(|e| format!("Failed: {}", e))?;
+    Ok(logic::weekly_expense_rate(&expenses, chrono::Local::now().date_naive()))
 }
 
 // ========== FOCUS & BURNOUT COMMANDS ==========
@@ -490,7 +515,7 @@ pub fn get_focus_metrics(db: State<Database>, days: i32) -> Result<FocusMetrics,
     let start_str = start_date.format("%Y-%m-%d").to_string();
     let end_str = end_date.format("%Y-%m-%d").to_string();
     
-    let analytics = db.get_analytics(&start_str, &end_str)
+    let analytics = db.get_analytics(&start_str, &end_str, ReportingBasis::Earned)
         .map_err(|e| format!("Failed: {}", e))?;
     
     let session_count = analytics.summary.total_sessions;
@@ -499,11 +524,8 @@ pub fn get_focus_metrics(db: State<Database>, days: i32) -> Result<FocusMetrics,
     
     // Calculate fragmentation (many short sessions = fragmented)
     let short_session_threshold = 1.0; // hours
-    let sessions = db.get_all_sessions().map_err(|e| format!("Failed: {}", e))?;
-    let short_sessions = sessions.iter()
-        .filter(|s| s.date >= start_str && s.date <= end_str)
-        .filter(|s| s.hours < short_session_threshold)
-        .count();
+    let short_sessions = db.count_short_sessions(&start_str, &end_str, short_session_threshold)
+        .map_err(|e| format!("Failed: {}", e))?;
     
     let fragmentation_score = if session_count > 0 {
         100.0 - (short_sessions as f64 / session_count as f64 * 100.0)
@@ -512,7 +534,8 @@ pub fn get_focus_metrics(db: State<Database>, days: i32) -> Result<FocusMetrics,
     };
     
     // Calculate streaks
-    let (current_streak, longest_streak) = calculate_streaks(&sessions);
+    let session_dates = db.get_session_dates().map_err(|e| format!("Failed: {}", e))?;
+    let (current_streak, longest_streak) = calculate_streaks(&session_dates);
     
     // Focus score combines avg session length, fragmentation, consistency
     let focus_score = (
@@ -532,19 +555,12 @@ pub fn get_focus_metrics(db: State<Database>, days: i32) -> Result<FocusMetrics,
     })
 }
 
-fn calculate_streaks(sessions: &[WorkSession]) -> (i32, i32) {
-    if sessions.is_empty() {
+/// Current and longest streaks of consecutive days, from ascending distinct dates
+fn calculate_streaks(dates_vec: &[String]) -> (i32, i32) {
+    if dates_vec.is_empty() {
         return (0, 0);
     }
     
-    let mut dates: std::collections::HashSet<String> = std::collections::HashSet::new();
-    for s in sessions {
-        dates.insert(s.date.clone());
-    }
-    
-    let mut dates_vec: Vec<_> = dates.into_iter().collect();
-    dates_vec.sort();
-    
     let today = chrono::Local::now().format("%Y-%m-%d").to_string();
     let yesterday = (chrono::Local::now() - chrono::Duration::days(1))
         .format("%Y-%m-%d").to_string();
@@ -554,8 +570,8 @@ fn calculate_streaks(sessions: &[WorkSession]) -> (i32, i32) {
     let mut check_date = chrono::Local::now().date_naive();
     
     // Allow starting from today or yesterday
-    if dates_vec.contains(&today) || dates_vec.contains(&yesterday) {
-        while dates_vec.contains(&check_date.format("%Y-%m-%d").to_string()) {
+    if dates_vec.binary_search(&today).is_ok() || dates_vec.binary_search(&yesterday).is_ok() {
+        while dates_vec.binary_search(&check_date.format("%Y-%m-%d").to_string()).is_ok() {
             current_streak += 1;
             check_date = check_date.pred_opt().unwrap_or(check_date);
         }
@@ -589,16 +605,13 @@ pub fn get_burnout_risk(db: State<Database>, days: i64) -> Result<BurnoutRisk, S
     let start_str = start_date.format("%Y-%m-%d").to_string();
     let end_str = end_date.format("%Y-%m-%d").to_string();
     
-    let sessions = db.get_all_sessions().map_err(|e| format!("Failed: {}", e))?;
-    let recent_sessions: Vec<_> = sessions.iter()
-        .filter(|s| s.date >= start_str && s.date <= end_str)
-        .collect();
+    let daily_totals = db.get_daily_totals(&start_str, &end_str).map_err(|e| format!("Failed: {}", e))?;
     
     let mut factors: Vec<BurnoutFactor> = Vec::new();
     let mut risk_score: f64 = 0.0;
     
     // Factor 1: Weekly hours
-    let total_hours: f64 = recent_sessions.iter().map(|s| s.hours).sum();
+    let total_hours: f64 = daily_totals.iter().map(|d| d.hours).sum();
     let weekly_hours = total_hours / 2.0; // 2 weeks
     
     let (hours_severity, hours_points) = if weekly_hours > 60.0 {
@@ -618,7 +631,7 @@ pub fn get_burnout_risk(db: State<Database>, days: i64) -> Result<BurnoutRisk, S
     risk_score += hours_points;
     
     // Factor 2: Longest session
-    let max_session = recent_sessions.iter().map(|s| s.hours).fold(0.0_f64, f64::max);
+    let max_session = db.get_longest_session(&start_str, &end_str).map_err(|e| format!("Failed: {}", e))?;
     let (max_severity, max_points) = if max_session > 10.0 {
         ("danger", 25.0)
     } else if max_session > 8.0 {
@@ -636,10 +649,7 @@ pub fn get_burnout_risk(db: State<Database>, days: i64) -> Result<BurnoutRisk, S
     risk_score += max_points;
     
     // Factor 3: Rest days
-    let unique_days: std::collections::HashSet<&String> = recent_sessions.iter()
-        .map(|s| &s.date)
-        .collect();
-    let rest_days = 14 - unique_days.len() as i32;
+    let rest_days = 14 - daily_totals.len() as i32;
     
     let (rest_severity, rest_points) = if rest_days < 2 {
         ("danger", 30.0)
@@ -658,17 +668,11 @@ pub fn get_burnout_risk(db: State<Database>, days: i64) -> Result<BurnoutRisk, S
     risk_score += rest_points;
     
     // Factor 4: Work pattern consistency
-    let hours_per_day: std::collections::HashMap<&String, f64> = recent_sessions.iter()
-        .fold(std::collections::HashMap::new(), |mut acc, s| {
-            *acc.entry(&s.date).or_insert(0.0) += s.hours;
-            acc
-        });
-    
-    let variance = if !hours_per_day.is_empty() {
-        let mean: f64 = hours_per_day.values().sum::<f64>() / hours_per_day.len() as f64;
-        let var: f64 = hours_per_day.values()
-            .map(|h| (h - mean).powi(2))
-            .sum::<f64>() / hours_per_day.len() as f64;
+    let variance = if !daily_totals.is_empty() {
+        let mean: f64 = total_hours / daily_totals.len() as f64;
+        let var: f64 = daily_totals.iter()
+            .map(|d| (d.hours - mean).powi(2))
+            .sum::<f64>() / daily_totals.len() as f64;
         var.sqrt()
     } else {
         0.0
@@ -740,26 +744,872 @@ pub fn get_invoice(db: State<Database>, id: i64) -> Result<Invoice, String> {
 }
 
 #[tauri::command]
-pub fn create_invoice(db: State<Database>, invoice: NewInvoice) -> Result<i64, String> {
+pub fn create_invoice(db: State<Database>, mut invoice: NewInvoice) -> Result<i64, String> {
+    validate_new_invoice(&db, &mut invoice)?;
     db.create_invoice(invoice).map_err(|e| format!("Failed: {}", e))
 }
 
+/// The invoice `create_invoice_from_criteria` would create, without saving it
+#[tauri::command]
+pub fn preview_invoice_from_criteria(db: State<Database>, criteria: InvoiceCriteria, invoice: NewInvoice) -> Result<InvoicePreview, String> {
+    let (invoice, sessions) = invoice_from_criteria(&db, &criteria, invoice)?;
+    let invoice = db.preview_invoice(invoice).map_err(|e| format!("Failed: {}", e))?;
+    Ok(InvoicePreview { invoice, sessions })
+}
+
+/// Bill the sessions of a preview. `invoice.session_ids` must be the
+/// previewed sessions: if the sessions matching `criteria` have changed
+/// since, nothing is created. A blank client is filled as in the preview.
+#[tauri::command]
+pub fn create_invoice_from_criteria(db: State<Database>, criteria: InvoiceCriteria, mut invoice: NewInvoice) -> Result<Invoice, String> {
+    let mut previewed = std::mem::take(&mut invoice.session_ids);
+    if previewed.is_empty() {
+        return Err("Preview the invoice first, then create it from the previewed sessions".to_string());
+    }
+    let (invoice, _) = invoice_from_criteria(&db, &criteria, invoice)?;
+    let mut matching = invoice.session_ids.clone();
+    previewed.sort_unstable();
+    matching.sort_unstable();
+    if previewed != matching {
+        return Err("The matching sessions have changed since the preview; preview the invoice again".to_string());
+    }
+    db.create_invoice_for_sessions(invoice).map_err(|e| format!("Failed: {}", e))
+}
+
+fn invoice_from_criteria(db: &Database, criteria: &InvoiceCriteria, mut invoice: NewInvoice) -> Result<(NewInvoice, Vec<WorkSession>), String> {
+    let filter = criteria.to_session_filter();
+    filter.validate()?;
+    let sessions = db.find_sessions(&filter).map_err(|e| format!("Failed: {}", e))?;
+    if sessions.is_empty() {
+        return Err("No uninvoiced paid sessions match".to_string());
+    }
+    invoice.session_ids = sessions.iter().map(|s| s.id).collect();
+    
+    let mut projects = sessions.iter().map(|s| s.project_name.clone()).collect::<Vec<_>>();
+    projects.sort();
+    projects.dedup();
+    let clients = db.get_last_clients_for_projects(&projects).map_err(|e| format!("Failed: {}", e))?;
+    if clients.len() > 1 {
+        let names = clients.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        return Err(format!(
+            "The matching projects were billed to different clients ({}); narrow the criteria to one client",
+            names.join(", ")
+        ));
+    }
+    match clients.into_iter().next() {
+        Some((name, email)) if invoice.client_name.trim().is_empty() => {
+            invoice.client_name = name;
+            invoice.client_email = invoice.client_email.or(email);
+        }
+        Some((name, email)) if name == invoice.client_name.trim() => {
+            invoice.client_email = invoice.client_email.or(email);
+        }
+        None if invoice.client_name.trim().is_empty() => {
+            return Err("No earlier invoice for these projects; enter the client name".to_string());
+        }
+        _ => {}
+    }
+    
+    validate_new_invoice(db, &mut invoice)?;
+    Ok((invoice, sessions))
+}
+
+fn validate_new_invoice(db: &Database, invoice: &mut NewInvoice) -> Result<(), String> {
+    for item in &invoice.items {
+        item.validate()?;
+    }
+    if let Some(discount) = &invoice.discount {
+        discount.validate()?;
+    }
+    require_tax_rates(db, &invoice.tax_rate_ids)?;
+    invoice.template_id = require_profile_and_template(db, invoice.profile_id, invoice.template_id)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_all_tax_rates(db: State<Database>) -> Result<Vec<TaxRate>, String> {
+    db.get_all_tax_rates().map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn add_tax_rate(db: State<Database>, tax_rate: NewTaxRate) -> Result<i64, String> {
+    tax_rate.validate()?;
+    db.add_tax_rate(&tax_rate).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn update_tax_rate(db: State<Database>, id: i64, tax_rate: NewTaxRate) -> Result<(), String> {
+    tax_rate.validate()?;
+    db.update_tax_rate(id, &tax_rate).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn delete_tax_rate(db: State<Database>, id: i64) -> Result<(), String> {
+    db.delete_tax_rate(id).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn get_invoice_numbering(db: State<Database>) -> Result<InvoiceNumbering, String> {
+    db.get_invoice_numbering().map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn set_invoice_numbering(db: State<Database>, numbering: InvoiceNumbering) -> Result<(), String> {
+    numbering.validate()?;
+    db.set_invoice_numbering(&numbering).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn preview_invoice_number(db: State<Database>, client_name: String) -> Result<String, String> {
+    db.preview_invoice_number(&client_name).map_err(|e| format!("Failed: {}", e))
+}
+
 #[tauri::command]
 pub fn update_invoice_status(db: State<Database>, id: i64, status: String) -> Result<(), String> {
     let status = InvoiceStatus::from_string(&status)?;
+    if status == InvoiceStatus::Void {
+        return Err("Use void_invoice to void an invoice".to_string());
+    }
+    let invoice = db.get_invoice(id).map_err(|e| format!("Failed: {}", e))?;
+    if invoice.is_credit_note() {
+        return Err("Credit notes are issued on creation and have no payment status".to_string());
+    }
+    match (invoice.status, status) {
+        (InvoiceStatus::Draft, InvoiceStatus::Sent) => {}
+        (InvoiceStatus::Paid, InvoiceStatus::Paid) => return Err(format!("{} is already paid", invoice.invoice_number)),
+        (InvoiceStatus::Void, _) => return Err(format!("{} is void", invoice.invoice_number)),
+        (_, InvoiceStatus::Paid) => {}
+        (_, InvoiceStatus::Sent) => return Err("Only draft invoices can be marked as sent; delete payments to reopen a paid invoice".to_string()),
+        (_, InvoiceStatus::Draft) => return Err("A sent invoice cannot return to Draft; void it or issue a credit note".to_string()),
+        (_, other) => return Err(format!("{} is set from payments and due dates, not by hand", other.to_string())),
+    }
     db.update_invoice_status(id, status).map_err(|e| format!("Failed: {}", e))
 }
 
+/// Delete a draft outright. Issued invoices and credit notes stay on record:
+/// void them or issue a credit note instead.
 #[tauri::command]
 pub fn delete_invoice(db: State<Database>, id: i64) -> Result<(), String> {
+    let invoice = db.get_invoice(id).map_err(|e| format!("Failed: {}", e))?;
+    if invoice.status != InvoiceStatus::Draft {
+        return Err(format!(
+            "{} has been issued; void it or issue a credit note instead of deleting",
+            invoice.invoice_number
+        ));
+    }
     db.delete_invoice(id).map_err(|e| format!("Failed: {}", e))
 }
 
+#[tauri::command]
+pub fn get_credit_notes(db: State<Database>, invoice_id: i64) -> Result<Vec<Invoice>, String> {
+    db.get_credit_notes(invoice_id).map_err(|e| format!("Failed: {}", e))
+}
+
+/// Credit all or part of a sent invoice. Each line can be credited up to the
+/// quantity not credited yet; no lines credits everything that remains.
+/// Returns the new credit note.
+#[tauri::command]
+pub fn create_credit_note(db: State<Database>, invoice_id: i64, credit_note: NewCreditNote) -> Result<Invoice, String> {
+    credit_note.validate()?;
+    let id = db.create_credit_note(invoice_id, &credit_note.lines, credit_note.notes.as_deref(), credit_note.release_sessions)
+        .map_err(|e| format!("Failed: {}", e))?;
+    db.get_invoice(id).map_err(|e| format!("Failed: {}", e))
+}
+
+/// Void a sent invoice or a credit note, keeping it on record. Invoices with
+/// payments need a credit note instead. With `release_sessions` the invoice's
+/// sessions become billable again.
+#[tauri::command]
+pub fn void_invoice(db: State<Database>, id: i64, release_sessions: bool) -> Result<Invoice, String> {
+    db.void_invoice(id, release_sessions).map_err(|e| format!("Failed: {}", e))?;
+    db.get_invoice(id).map_err(|e| format!("Failed: {}", e))
+}
+
+fn require_tax_rates(db: &Database, ids: &[i64]) -> Result<(), String> {
+    let rates = db.get_all_tax_rates().map_err(|e| format!("Failed: {}", e))?;
+    match ids.iter().find(|id| !rates.iter().any(|r| r.id == **id)) {
+        Some(id) => Err(format!("Tax rate {} not found", id)),
+        None => Ok(()),
+    }
+}
+
+/// Check that the chosen profile and template exist. Returns the template id
+/// to store: the built-in template (id 0) is stored as none.
+fn require_profile_and_template(db: &Database, profile_id: Option<i64>, template_id: Option<i64>) -> Result<Option<i64>, String> {
+    if let Some(id) = profile_id {
+        db.get_business_profile(id).map_err(|_| format!("Business profile {} not found", id))?;
+    }
+    match template_id {
+        Some(0) | None => Ok(None),
+        Some(id) => {
+            db.get_invoice_template(id).map_err(|_| format!("Invoice template {} not found", id))?;
+            Ok(Some(id))
+        }
+    }
+}
+
+/// Reject edits to invoices that have left Draft (the schema triggers enforce this too)
+fn require_draft(db: &Database, id: i64) -> Result<(), String> {
+    let invoice = db.get_invoice(id).map_err(|e| format!("Failed: {}", e))?;
+    if !invoice.status.is_editable() {
+        return Err(format!(
+            "Invoice {} is {} and can no longer be edited",
+            invoice.invoice_number,
+            invoice.status.to_string()
+        ));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn update_draft_invoice(db: State<Database>, id: i64, mut update: InvoiceDraftUpdate) -> Result<Invoice, String> {
+    if update.client_name.trim().is_empty() {
+        return Err("Client name cannot be empty".to_string());
+    }
+    if let Some(discount) = &update.discount {
+        discount.validate()?;
+    }
+    require_tax_rates(&db, &update.tax_rate_ids)?;
+    update.template_id = require_profile_and_template(&db, update.profile_id, update.template_id)?;
+    require_draft(&db, id)?;
+    db.update_draft_invoice(id, &update).map_err(|e| format!("Failed: {}", e))?;
+    db.get_invoice(id).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn add_sessions_to_invoice(db: State<Database>, invoice_id: i64, session_ids: Vec<i64>) -> Result<Invoice, String> {
+    require_draft(&db, invoice_id)?;
+    db.add_sessions_to_invoice(invoice_id, &session_ids).map_err(|e| format!("Failed: {}", e))?;
+    db.get_invoice(invoice_id).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn add_invoice_item(db: State<Database>, invoice_id: i64, item: NewInvoiceItem) -> Result<Invoice, String> {
+    item.validate()?;
+    require_draft(&db, invoice_id)?;
+    db.add_invoice_item(invoice_id, &item).map_err(|e| format!("Failed: {}", e))?;
+    db.get_invoice(invoice_id).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn update_invoice_item(db: State<Database>, invoice_id: i64, item: InvoiceItemUpdate) -> Result<Invoice, String> {
+    if item.description.trim().is_empty() {
+        return Err("Line item description cannot be empty".to_string());
+    }
+    if let Some(discount) = &item.discount {
+        discount.validate()?;
+    }
+    require_draft(&db, invoice_id)?;
+    db.update_invoice_item(invoice_id, &item).map_err(|e| format!("Failed: {}", e))?;
+    db.get_invoice(invoice_id).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn remove_invoice_item(db: State<Database>, invoice_id: i64, item_id: i64) -> Result<Invoice, String> {
+    require_draft(&db, invoice_id)?;
+    db.remove_invoice_item(invoice_id, item_id).map_err(|e| format!("Failed: {}", e))?;
+    db.get_invoice(invoice_id).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn record_payment(db: State<Database>, invoice_id: i64, payment: NewInvoicePayment) -> Result<Invoice, String> {
+    payment.validate()?;
+    db.record_payment(invoice_id, &payment).map_err(|e| format!("Failed: {}", e))?;
+    db.get_invoice(invoice_id).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn delete_payment(db: State<Database>, invoice_id: i64, payment_id: i64) -> Result<Invoice, String> {
+    db.delete_payment(invoice_id, payment_id).map_err(|e| format!("Failed: {}", e))?;
+    db.get_invoice(invoice_id).map_err(|e| format!("Failed: {}", e))
+}
+
+/// Run the overdue check now (it also runs at startup and daily)
+#[tauri::command]
+pub fn check_overdue_invoices(app: tauri::AppHandle) -> Result<Vec<Invoice>, String> {
+    crate::jobs::run_overdue_check(&app, &logic::get_today())
+}
+
+/// Write the invoice as a PDF to `path`. The font comes from the
+/// `pdf_font_path` setting, else a common system font.
+#[tauri::command]
+pub fn render_invoice_pdf(db: State<Database>, id: i64, path: String) -> Result<(), String> {
+    let invoice = db.get_invoice(id).map_err(|e| format!("Failed: {}", e))?;
+    let profile = invoice_profile(&db, &invoice)?;
+    let font_path = db.get_setting("pdf_font_path").map_err(|e| format!("Failed: {}", e))?;
+    let bytes = pdf::render_invoice(&invoice, profile.as_ref(), pdf::load_font(font_path.as_deref()))?;
+    std::fs::write(&path, bytes).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+/// Render the invoice as HTML with its template and sender profile
+#[tauri::command]
+pub fn render_invoice_html(db: State<Database>, id: i64) -> Result<String, String> {
+    let invoice = db.get_invoice(id).map_err(|e| format!("Failed: {}", e))?;
+    let profile = invoice_profile(&db, &invoice)?;
+    let body = match invoice.template_id {
+        Some(template_id) => db.get_invoice_template(template_id).map_err(|e| format!("Failed: {}", e))?.body,
+        None => template::DEFAULT_TEMPLATE.to_string(),
+    };
+    template::render_invoice_html(&invoice, profile.as_ref(), &body)
+}
+
+/// Write the invoice as UBL 2.1 XML (EN 16931) to `path`.
+/// Seller details come from the invoice's business profile.
+#[tauri::command]
+pub fn export_invoice_ubl(db: State<Database>, id: i64, path: String) -> Result<(), String> {
+    let invoice = db.get_invoice(id).map_err(|e| format!("Failed: {}", e))?;
+    let seller = invoice_profile(&db, &invoice)?
+        .ok_or("Create a business profile to export e-invoices")?;
+    let xml = einvoice::render_ubl(&invoice, &seller)?;
+    std::fs::write(&path, xml).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+/// Write the invoice as a Factur-X / ZUGFeRD PDF (PDF/A-3 with embedded CII XML) to `path`
+#[tauri::command]
+pub fn export_invoice_facturx(db: State<Database>, id: i64, path: String) -> Result<(), String> {
+    let invoice = db.get_invoice(id).map_err(|e| format!("Failed: {}", e))?;
+    let seller = invoice_profile(&db, &invoice)?
+        .ok_or("Create a business profile to export e-invoices")?;
+    let font_path = db.get_setting("pdf_font_path").map_err(|e| format!("Failed: {}", e))?;
+    let bytes = facturx::render_invoice(&invoice, &seller, pdf::load_font(font_path.as_deref()))?;
+    std::fs::write(&path, bytes).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+#[tauri::command]
+pub fn get_smtp_settings(db: State<Database>) -> Result<Option<SmtpSettings>, String> {
+    db.get_smtp_settings().map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn set_smtp_settings(db: State<Database>, settings: SmtpSettings) -> Result<(), String> {
+    settings.validate()?;
+    mail::validate_address(settings.from_address.trim())?;
+    template::validate_template(&settings.subject_template)?;
+    template::validate_template(&settings.body_template)?;
+    let settings = SmtpSettings {
+        host: settings.host.trim().to_string(),
+        from_address: settings.from_address.trim().to_string(),
+        ..settings
+    };
+    db.set_smtp_settings(&settings).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn get_invoice_history(db: State<Database>, invoice_id: i64) -> Result<Vec<InvoiceHistoryEntry>, String> {
+    db.get_invoice_history(invoice_id).map_err(|e| format!("Failed: {}", e))
+}
+
+/// Email the invoice PDF to `to` (default: the client's address) through the
+/// configured SMTP server, log the send and move a draft to Sent. The PDF
+/// shows the draft as issued; it is only issued once the mail has gone.
+#[tauri::command]
+pub async fn send_invoice_email(db: State<'_, Database>, id: i64, to: Option<String>) -> Result<Invoice, String> {
+    let invoice = db.get_invoice(id).map_err(|e| format!("Failed: {}", e))?;
+    if invoice.status == InvoiceStatus::Void {
+        return Err("Void invoices cannot be sent".to_string());
+    }
+    let recipient = to.or_else(|| invoice.client_email.clone())
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty())
+        .ok_or("The invoice has no client email; enter a recipient")?;
+    mail::validate_address(&recipient)?;
+
+    let settings = db.get_smtp_settings().map_err(|e| format!("Failed: {}", e))?
+        .ok_or("Set up an SMTP server in settings to send invoices")?;
+    let credentials = db.get_smtp_credentials().map_err(|e| format!("Failed: {}", e))?;
+
+    let invoice = db.preview_issued_invoice(id).map_err(|e| format!("Failed: {}", e))?;
+    let message = mail::invoice_mail(&db, &invoice, &recipient, &settings.subject_template, &settings.body_template, None)?;
+    let subject = message.subject.clone();
+
+    tokio::task::spawn_blocking(move || mail::send(&settings, credentials.as_ref(), &message))
+        .await
+        .map_err(|e| format!("Failed to send email: {}", e))??;
+
+    db.record_invoice_email(id, &recipient, &subject).map_err(|e| format!("Failed: {}", e))
+}
+
+/// The invoice's own profile, else the current default
+fn invoice_profile(db: &Database, invoice: &Invoice) -> Result<Option<BusinessProfile>, String> {
+    db.get_invoice_profile(invoice).map_err(|e| format!("Failed: {}", e))
+}
+
 #[tauri::command]
 pub fn get_uninvoiced_sessions(db: State<Database>) -> Result<Vec<WorkSession>, String> {
     db.get_uninvoiced_sessions().map_err(|e| format!("Failed: {}", e))
 }
 
+// ========== QUOTE COMMANDS ==========
+
+#[tauri::command]
+pub fn get_all_quotes(db: State<Database>) -> Result<Vec<Quote>, String> {
+    db.get_all_quotes().map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn get_quote(db: State<Database>, id: i64) -> Result<Quote, String> {
+    db.get_quote(id).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn create_quote(db: State<Database>, quote: NewQuote) -> Result<i64, String> {
+    quote.validate()?;
+    require_tax_rates(&db, &quote.tax_rate_ids)?;
+    require_profile_and_template(&db, quote.profile_id, None)?;
+    db.create_quote(&quote).map_err(|e| format!("Failed: {}", e))
+}
+
+/// Reject edits to quotes that have left Draft (the schema triggers enforce this too)
+fn require_draft_quote(db: &Database, id: i64) -> Result<(), String> {
+    let quote = db.get_quote(id).map_err(|e| format!("Failed: {}", e))?;
+    if quote.status != QuoteStatus::Draft {
+        return Err(format!(
+            "Quote {} is {} and can no longer be edited",
+            quote.quote_number,
+            quote.status
+        ));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn update_draft_quote(db: State<Database>, id: i64, update: QuoteDraftUpdate) -> Result<Quote, String> {
+    update.validate()?;
+    require_tax_rates(&db, &update.tax_rate_ids)?;
+    require_profile_and_template(&db, update.profile_id, None)?;
+    require_draft_quote(&db, id)?;
+    db.update_draft_quote(id, &update).map_err(|e| format!("Failed: {}", e))?;
+    db.get_quote(id).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn add_quote_item(db: State<Database>, quote_id: i64, item: NewInvoiceItem) -> Result<Quote, String> {
+    item.validate()?;
+    require_draft_quote(&db, quote_id)?;
+    db.add_quote_item(quote_id, &item).map_err(|e| format!("Failed: {}", e))?;
+    db.get_quote(quote_id).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn update_quote_item(db: State<Database>, quote_id: i64, item: InvoiceItemUpdate) -> Result<Quote, String> {
+    if item.description.trim().is_empty() {
+        return Err("Line item description cannot be empty".to_string());
+    }
+    if let Some(discount) = &item.discount {
+        discount.validate()?;
+    }
+    require_draft_quote(&db, quote_id)?;
+    db.update_quote_item(quote_id, &item).map_err(|e| format!("Failed: {}", e))?;
+    db.get_quote(quote_id).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn remove_quote_item(db: State<Database>, quote_id: i64, item_id: i64) -> Result<Quote, String> {
+    require_draft_quote(&db, quote_id)?;
+    db.remove_quote_item(quote_id, item_id).map_err(|e| format!("Failed: {}", e))?;
+    db.get_quote(quote_id).map_err(|e| format!("Failed: {}", e))
+}
+
+/// Draft -> Sent -> Accepted / Rejected / Expired. Accepting doesn't create
+/// the invoice; use `convert_quote_to_invoice` for that.
+#[tauri::command]
+pub fn update_quote_status(db: State<Database>, id: i64, status: String) -> Result<Quote, String> {
+    let status = QuoteStatus::from_string(&status)?;
+    let quote = db.get_quote(id).map_err(|e| format!("Failed: {}", e))?;
+    if !quote.status.can_become(status) {
+        return Err(format!(
+            "Quote {} is {} and can't become {}",
+            quote.quote_number,
+            quote.status,
+            status
+        ));
+    }
+    db.update_quote_status(id, status).map_err(|e| format!("Failed: {}", e))?;
+    db.get_quote(id).map_err(|e| format!("Failed: {}", e))
+}
+
+/// Only drafts can be deleted
+#[tauri::command]
+pub fn delete_quote(db: State<Database>, id: i64) -> Result<(), String> {
+    db.delete_quote(id).map_err(|e| format!("Failed: {}", e))
+}
+
+/// Turn a sent or accepted quote into a draft invoice; the quote becomes
+/// Accepted. Returns the invoice.
+#[tauri::command]
+pub fn convert_quote_to_invoice(db: State<Database>, id: i64, due_date: String) -> Result<Invoice, String> {
+    chrono::NaiveDate::parse_from_str(&due_date, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid due date: {}", due_date))?;
+    let invoice_id = db.convert_quote_to_invoice(id, &due_date).map_err(|e| format!("Failed: {}", e))?;
+    db.get_invoice(invoice_id).map_err(|e| format!("Failed: {}", e))
+}
+
+/// Run the expiry check now (it also runs at startup and daily)
+#[tauri::command]
+pub fn check_expired_quotes(app: tauri::AppHandle) -> Result<Vec<Quote>, String> {
+    crate::jobs::run_quote_expiry(&app, &logic::get_today())
+}
+
+// ========== BUSINESS PROFILES & TEMPLATES ==========
+
+#[tauri::command]
+pub fn get_all_business_profiles(db: State<Database>) -> Result<Vec<BusinessProfile>, String> {
+    db.get_all_business_profiles().map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn add_business_profile(db: State<Database>, profile: NewBusinessProfile) -> Result<i64, String> {
+    profile.validate()?;
+    db.add_business_profile(&profile).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn update_business_profile(db: State<Database>, id: i64, profile: NewBusinessProfile) -> Result<(), String> {
+    profile.validate()?;
+    db.update_business_profile(id, &profile).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn delete_business_profile(db: State<Database>, id: i64) -> Result<(), String> {
+    db.delete_business_profile(id).map_err(|e| format!("Failed: {}", e))
+}
+
+/// The built-in template first, then the user's own
+#[tauri::command]
+pub fn get_all_invoice_templates(db: State<Database>) -> Result<Vec<InvoiceTemplate>, String> {
+    let mut templates = vec![template::builtin_template()];
+    templates.extend(db.get_all_invoice_templates().map_err(|e| format!("Failed: {}", e))?);
+    Ok(templates)
+}
+
+#[tauri::command]
+pub fn add_invoice_template(db: State<Database>, template: NewInvoiceTemplate) -> Result<i64, String> {
+    validate_invoice_template(&template)?;
+    db.add_invoice_template(&template).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn update_invoice_template(db: State<Database>, id: i64, template: NewInvoiceTemplate) -> Result<(), String> {
+    if id == 0 {
+        return Err("The default template can't be edited; save a copy instead".to_string());
+    }
+    validate_invoice_template(&template)?;
+    db.update_invoice_template(id, &template).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn delete_invoice_template(db: State<Database>, id: i64) -> Result<(), String> {
+    db.delete_invoice_template(id).map_err(|e| format!("Failed: {}", e))
+}
+
+/// Save an editable copy of a template (the default one included)
+#[tauri::command]
+pub fn duplicate_invoice_template(db: State<Database>, id: i64) -> Result<i64, String> {
+    let source = match id {
+        0 => template::builtin_template(),
+        _ => db.get_invoice_template(id).map_err(|e| format!("Failed: {}", e))?,
+    };
+    let copy = NewInvoiceTemplate {
+        name: format!("{} (copy)", source.name),
+        body: source.body,
+    };
+    db.add_invoice_template(&copy).map_err(|e| format!("Failed: {}", e))
+}
+
+fn validate_invoice_template(template: &NewInvoiceTemplate) -> Result<(), String> {
+    if template.name.trim().is_empty() {
+        return Err("Template name cannot be empty".to_string());
+    }
+    template::validate_template(&template.body)
+}
+
+// ========== RETAINER COMMANDS ==========
+
+#[tauri::command]
+pub fn get_all_retainers(db: State<Database>) -> Result<Vec<Retainer>, String> {
+    db.get_all_retainers().map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn add_retainer(db: State<Database>, retainer: NewRetainer) -> Result<i64, String> {
+    retainer.validate()?;
+    db.add_retainer(&retainer).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn update_retainer(db: State<Database>, id: i64, retainer: NewRetainer) -> Result<(), String> {
+    retainer.validate()?;
+    db.update_retainer(id, &retainer).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn delete_retainer(db: State<Database>, id: i64) -> Result<(), String> {
+    db.delete_retainer(id).map_err(|e| format!("Failed: {}", e))
+}
+
+/// Hours used versus included, per period
+#[tauri::command]
+pub fn get_retainer_usage(db: State<Database>, id: i64) -> Result<Vec<RetainerUsage>, String> {
+    db.get_retainer_usage(id, &logic::get_today()).map_err(|e| format!("Failed: {}", e))
+}
+
+/// Bill ended retainer periods now (this also runs at startup and daily)
+#[tauri::command]
+pub fn bill_due_retainers(app: tauri::AppHandle) -> Result<RetainerBilling, String> {
+    crate::jobs::run_retainer_billing(&app, &logic::get_today())
+}
+
+// ========== PAYMENT REMINDER COMMANDS ==========
+
+#[tauri::command]
+pub fn get_reminder_schedules(db: State<Database>) -> Result<Vec<ReminderSchedule>, String> {
+    db.get_reminder_schedules().map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn add_reminder_schedule(db: State<Database>, schedule: NewReminderSchedule) -> Result<i64, String> {
+    validate_reminder_schedule(&schedule)?;
+    db.add_reminder_schedule(&schedule).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn update_reminder_schedule(db: State<Database>, id: i64, schedule: NewReminderSchedule) -> Result<(), String> {
+    validate_reminder_schedule(&schedule)?;
+    db.update_reminder_schedule(id, &schedule).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn delete_reminder_schedule(db: State<Database>, id: i64) -> Result<(), String> {
+    db.delete_reminder_schedule(id).map_err(|e| format!("Failed: {}", e))
+}
+
+fn validate_reminder_schedule(schedule: &NewReminderSchedule) -> Result<(), String> {
+    schedule.validate()?;
+    template::validate_template(&schedule.subject_template)?;
+    template::validate_template(&schedule.body_template)
+}
+
+/// Reminders that the next run would send
+#[tauri::command]
+pub fn get_due_reminders(db: State<Database>) -> Result<Vec<DueReminder>, String> {
+    db.get_due_reminders(&logic::get_today()).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn get_invoice_reminders(db: State<Database>, invoice_id: i64) -> Result<Vec<InvoiceReminder>, String> {
+    db.get_invoice_reminders(invoice_id).map_err(|e| format!("Failed: {}", e))
+}
+
+/// Send the reminders due today now instead of waiting for the daily run
+#[tauri::command]
+pub async fn send_due_reminders(app: tauri::AppHandle) -> Result<Vec<PaymentReminder>, String> {
+    tokio::task::spawn_blocking(move || crate::jobs::run_payment_reminders(&app, &logic::get_today()))
+        .await
+        .map_err(|e| format!("Failed to send reminders: {}", e))?
+}
+
+// ========== RECEIVABLES COMMANDS ==========
+
+/// Outstanding balances aged as of today: current, 1-30, 31-60, 61-90 and 90+ days overdue
+#[tauri::command]
+pub fn get_receivables_report(db: State<Database>) -> Result<ReceivablesReport, String> {
+    db.get_receivables_report(&logic::get_today()).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn get_client_payment_stats(db: State<Database>) -> Result<Vec<ClientPaymentStats>, String> {
+    db.get_client_payment_stats().map_err(|e| format!("Failed: {}", e))
+}
+
+// ========== BANK RECONCILIATION COMMANDS ==========
+
+/// Import an OFX or CSV statement. CSV files are read with `csv_format`,
+/// which is saved for next time, else with the saved layout.
+#[tauri::command]
+pub fn import_bank_statement(db: State<Database>, path: String, csv_format: Option<BankCsvFormat>) -> Result<BankImportResult, String> {
+    let bytes = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let text = String::from_utf8_lossy(&bytes);
+    let csv_format = match csv_format {
+        Some(format) => {
+            format.validate()?;
+            let json = serde_json::to_string(&format).map_err(|e| format!("Failed: {}", e))?;
+            db.set_setting("bank_csv_format", &json).map_err(|e| format!("Failed: {}", e))?;
+            Some(format)
+        }
+        None => saved_bank_csv_format(&db)?,
+    };
+    let transactions = bank::parse_statement(&text, csv_format.as_ref())?;
+    db.import_bank_transactions(&transactions).map_err(|e| format!("Failed: {}", e))
+}
+
+/// CSV layout used for the last import
+#[tauri::command]
+pub fn get_bank_csv_format(db: State<Database>) -> Result<Option<BankCsvFormat>, String> {
+    saved_bank_csv_format(&db)
+}
+
+fn saved_bank_csv_format(db: &Database) -> Result<Option<BankCsvFormat>, String> {
+    let json = db.get_setting("bank_csv_format").map_err(|e| format!("Failed: {}", e))?;
+    Ok(json.and_then(|j| serde_json::from_str(&j).ok()))
+}
+
+#[tauri::command]
+pub fn get_bank_transactions(db: State<Database>, unreconciled_only: bool) -> Result<Vec<BankTransaction>, String> {
+    db.get_bank_transactions(unreconciled_only).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn suggest_bank_matches(db: State<Database>) -> Result<Vec<MatchSuggestion>, String> {
+    db.suggest_bank_matches().map_err(|e| format!("Failed: {}", e))
+}
+
+/// Record a transaction as a payment of an invoice. `amount` defaults to as
+/// much as both the transaction and the invoice's balance allow, and can't
+/// exceed either; a transaction paying several invoices is confirmed once per
+/// invoice.
+#[tauri::command]
+pub fn confirm_bank_match(db: State<Database>, transaction_id: i64, invoice_id: i64, amount: Option<f64>) -> Result<Invoice, String> {
+    db.confirm_bank_match(transaction_id, invoice_id, amount).map_err(|e| format!("Failed: {}", e))?;
+    db.get_invoice(invoice_id).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn ignore_bank_transaction(db: State<Database>, id: i64, ignored: bool) -> Result<(), String> {
+    db.set_bank_transaction_ignored(id, ignored).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn delete_bank_transaction(db: State<Database>, id: i64) -> Result<(), String> {
+    db.delete_bank_transaction(id).map_err(|e| format!("Failed: {}", e))
+}
+
+// ========== ACCOUNTING EXPORT COMMANDS ==========
+
+#[tauri::command]
+pub fn get_accounting_settings(db: State<Database>) -> Result<AccountingSettings, String> {
+    db.get_accounting_settings().map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn set_accounting_settings(db: State<Database>, settings: AccountingSettings) -> Result<(), String> {
+    settings.validate()?;
+    let settings = AccountingSettings {
+        receivable_account: settings.receivable_account.trim().to_string(),
+        income_account: settings.income_account.trim().to_string(),
+        tax_account: settings.tax_account.trim().to_string(),
+        withholding_account: settings.withholding_account.trim().to_string(),
+        bank_account: settings.bank_account.trim().to_string(),
+        ..settings
+    };
+    db.set_accounting_settings(&settings).map_err(|e| format!("Failed: {}", e))
+}
+
+/// Write issued invoices, and settlements of paid ones, as a ledger/hledger or
+/// beancount journal. An incremental export appends to `path` only what has
+/// happened since the last export in that format; otherwise `path` is rewritten
+/// with everything.
+#[tauri::command]
+pub fn export_journal(db: State<Database>, path: String, format: JournalFormat, incremental: bool) -> Result<JournalExportResult, String> {
+    let settings = db.get_accounting_settings().map_err(|e| format!("Failed: {}", e))?;
+    let invoices = db.get_all_invoices().map_err(|e| format!("Failed: {}", e))?;
+    let mut exported: HashMap<i64, Vec<JournalEntryKind>> = HashMap::new();
+    let mut opened: Vec<String> = Vec::new();
+    if incremental {
+        for (invoice_id, kind) in db.get_journal_exports(format).map_err(|e| format!("Failed: {}", e))? {
+            exported.entry(invoice_id).or_default().push(kind);
+        }
+        opened = db.get_journal_accounts(format).map_err(|e| format!("Failed: {}", e))?;
+    }
+
+    // Invoices are booked in their profile's currency
+    let mut currencies: HashMap<Option<i64>, String> = HashMap::new();
+    let mut booked = Vec::new();
+    for invoice in &invoices {
+        let currency = match currencies.entry(invoice.profile_id) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let profile = invoice_profile(&db, invoice)?;
+                entry.insert(profile.and_then(|p| p.currency).unwrap_or_else(|| settings.default_currency.clone()))
+            }
+        };
+        booked.push((invoice, currency.clone()));
+    }
+    let (entries, new_accounts) = journal::pending_entries(&booked, &exported, &opened, &settings, format)?;
+    if incremental && entries.is_empty() {
+        return Ok(JournalExportResult { entries: 0, accounts_opened: 0 });
+    }
+
+    let text = journal::render(&entries, &new_accounts, format, &logic::get_today());
+    if incremental {
+        use std::io::Write;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+        file.write_all(text.as_bytes())
+            .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    } else {
+        std::fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    }
+
+    let written: Vec<(i64, JournalEntryKind)> = entries.iter().map(|e| (e.invoice_id, e.kind)).collect();
+    let accounts: Vec<String> = new_accounts.iter().map(|(_, a)| a.clone()).collect();
+    db.record_journal_export(format, &written, &accounts, !incremental).map_err(|e| format!("Failed: {}", e))?;
+    Ok(JournalExportResult { entries: entries.len(), accounts_opened: accounts.len() })
+}
+
+// ========== EXPENSE COMMANDS ==========
+
+#[tauri::command]
+pub fn get_expenses(db: State<Database>) -> Result<Vec<Expense>, String> {
+    db.get_all_expenses().map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn add_expense(db: State<Database>, expense: NewExpense) -> Result<i64, String> {
+    validate_expense(&expense)?;
+    db.add_expense(&expense).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn update_expense(db: State<Database>, id: i64, expense: NewExpense) -> Result<(), String> {
+    validate_expense(&expense)?;
+    db.update_expense(id, &expense).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn delete_expense(db: State<Database>, id: i64) -> Result<(), String> {
+    db.delete_expense(id).map_err(|e| format!("Failed: {}", e))
+}
+
+#[tauri::command]
+pub fn get_expense_categories(db: State<Database>) -> Result<Vec<String>, String> {
+    db.get_expense_categories().map_err(|e| format!("Failed: {}", e))
+}
+
+/// Income on `basis` less business and personal expenses, per month and category
+#[tauri::command]
+pub fn get_net_income(db: State<Database>, start: String, end: String, basis: Option<ReportingBasis>) -> Result<NetIncomeReport, String> {
+    let basis = basis.unwrap_or_default();
+    let start_date = chrono::NaiveDate::parse_from_str(&start, "%Y-%m-%d").map_err(|_| "Start date must be YYYY-MM-DD")?;
+    let end_date = chrono::NaiveDate::parse_from_str(&end, "%Y-%m-%d").map_err(|_| "End date must be YYYY-MM-DD")?;
+    if end_date < start_date {
+        return Err("End date cannot be before the start date".to_string());
+    }
+    let income = db.get_income_by_month(basis, &start, &end).map_err(|e| format!("Failed: {}", e))?;
+    let expenses = db.get_expenses_between(&start, &end).map_err(|e| format!("Failed: {}", e))?;
+    Ok(logic::net_income_report(&income, &expenses, start_date, end_date, basis))
+}
+
+fn validate_expense(expense: &NewExpense) -> Result<(), String> {
+    expense.validate()?;
+    if let Some(path) = expense.receipt_paths.iter().find(|p| !std::path::Path::new(p.trim()).is_file()) {
+        return Err(format!("Receipt not found: {}", path));
+    }
+    Ok(())
+}
+
 // ========== HABIT COMMANDS ==========
 
 #[tauri::command]
@@ -829,7 +1679,10 @@ pub fn accept_activity_suggestion(
     project_name: String,
 ) -> Result<i64, String> {
     db.accept_suggestion(suggestion_id, session_type_id, &project_name)
-        .map_err(|e| format!("Failed: {}", e))
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => "Suggestion was already accepted or dismissed".to_string(),
+            e => format!("Failed: {}", e),
+        })
 }
 
 #[tauri::command]
@@ -855,35 +1708,31 @@ pub fn delete_tracking_rule(db: State<Database>, id: i64) -> Result<(), String>
 // ========== ENHANCED AI ADVISOR COMMANDS ==========
 
 #[tauri::command]
-pub fn get_financial_analysis(db: State<Database>) -> Result<FinancialAnalysis, String> {
+pub fn get_financial_analysis(db: State<Database>, basis: Option<ReportingBasis>) -> Result<FinancialAnalysis, String> {
+    let basis = basis.unwrap_or_default();
     let sessions = db.get_all_sessions().map_err(|e| format!("Failed: {}", e))?;
     let goals = db.get_all_goals().map_err(|e| format!("Failed: {}", e))?;
-    
-    let avg_weekly_hours = logic::calculate_avg_weekly_hours(&sessions);
-    let avg_weekly_income = logic::calculate_avg_weekly_income(&sessions);
+    let pipeline = db.get_income_pipeline().map_err(|e| format!("Failed: {}", e))?;
     
     // Calculate trend (last 4 weeks vs previous 4 weeks)
-    let now = chrono::Local::now();
-    let four_weeks_ago = now - chrono::Duration::weeks(4);
-    let eight_weeks_ago = now - chrono::Duration::weeks(8);
-    
-    let recent_income: f64 = sessions.iter()
-        .filter(|s| {
-            chrono::NaiveDate::parse_from_str(&s.date, "%Y-%m-%d")
-                .map(|d| d > four_weeks_ago.date_naive())
-                .unwrap_or(false)
-        })
-        .map(|s| s.calculate_pay())
-        .sum();
-        
-    let older_income: f64 = sessions.iter()
-        .filter(|s| {
-            chrono::NaiveDate::parse_from_str(&s.date, "%Y-%m-%d")
-                .map(|d| d > eight_weeks_ago.date_naive() && d <= four_weeks_ago.date_naive())
-                .unwrap_or(false)
-        })
-        .map(|s| s.calculate_pay())
-        .sum();
+    let today = chrono::Local::now().date_naive();
+    let four_weeks_ago = today - chrono::Duration::weeks(4);
+    let eight_weeks_ago = today - chrono::Duration::weeks(8);
+    let income_between = |after: chrono::NaiveDate, until: chrono::NaiveDate| {
+        let from = (after + chrono::Duration::days(1)).format("%Y-%m-%d").to_string();
+        db.get_income_total(basis, &from, &until.format("%Y-%m-%d").to_string())
+            .map_err(|e| format!("Failed: {}", e))
+    };
+    let recent_income = income_between(four_weeks_ago, today)?;
+    let older_income = income_between(eight_weeks_ago, four_weeks_ago)?;
+    
+    let avg_weekly_hours = logic::calculate_avg_weekly_hours(&sessions);
+    let avg_weekly_income = match basis {
+        ReportingBasis::Earned => logic::calculate_avg_weekly_income(&sessions),
+        _ => recent_income / 4.0,
+    };
+    let avg_weekly_expenses = actual_weekly_expenses(&db)?;
+    let weekly_savings = avg_weekly_income - avg_weekly_expenses;
     
     let income_trend = if recent_income > older_income * 1.1 {
         "increasing"
@@ -908,6 +1757,34 @@ pub fn get_financial_analysis(db: State<Database>) -> Result<FinancialAnalysis,
         });
     }
     
+    // Expense insight
+    if avg_weekly_expenses > 0.0 && weekly_savings < 0.0 {
+        insights.push(FinancialInsight {
+            category: "expenses".to_string(),
+            severity: "critical".to_string(),
+            title: "Spending more than you earn".to_string(),
+            message: format!(
+                "Expenses average ${:.0}/week against ${:.0}/week of income.",
+                avg_weekly_expenses, avg_weekly_income
+            ),
+            action: Some("Review recurring expenses or increase billable hours.".to_string()),
+        });
+    }
+    
+    // Pipeline insight
+    if pipeline.unbilled > 0.0 {
+        insights.push(FinancialInsight {
+            category: "income".to_string(),
+            severity: "info".to_string(),
+            title: "Unbilled work".to_string(),
+            message: format!(
+                "${:.2} earned over {:.1} hours is not on an invoice yet; ${:.2} invoiced is still unpaid.",
+                pipeline.unbilled, pipeline.unbilled_hours, pipeline.invoiced_unpaid
+            ),
+            action: Some("Invoice completed work to get paid sooner.".to_string()),
+        });
+    }
+    
     // Goal progress insights
     for goal in &goals {
         let progress = goal.progress_percent();
@@ -936,9 +1813,9 @@ pub fn get_financial_analysis(db: State<Database>) -> Result<FinancialAnalysis,
     // Recommendations
     let mut recommendations: Vec<String> = Vec::new();
     
-    if avg_weekly_income > 0.0 && !goals.is_empty() {
+    if weekly_savings > 0.0 && !goals.is_empty() {
         let total_remaining: f64 = goals.iter().map(|g| g.remaining_amount()).sum();
-        let weeks_to_clear = total_remaining / avg_weekly_income;
+        let weeks_to_clear = total_remaining / weekly_savings;
         recommendations.push(format!(
             "At current pace, all goals complete in ~{:.0} weeks (${:.2} remaining)",
             weeks_to_clear, total_remaining
@@ -952,9 +1829,12 @@ pub fn get_financial_analysis(db: State<Database>) -> Result<FinancialAnalysis,
     Ok(FinancialAnalysis {
         avg_weekly_hours,
         avg_weekly_income,
+        avg_weekly_expenses,
         projected_monthly_income: avg_weekly_income * 4.33,
         projected_yearly_income: avg_weekly_income * 52.0,
         income_trend: income_trend.to_string(),
+        basis,
+        pipeline,
         insights,
         recommendations,
     })
@@ -1,5 +1,5 @@
 use crate::core::models::*;
-use chrono::Local;
+use chrono::{Datelike, Local};
 
 pub fn get_today() -> String {
     Local::now().format("%Y-%m-%d").to_string()
@@ -28,6 +28,618 @@ pub fn calculate_goal_eta(goal: &FinancialGoal, avg_weekly_income: f64) -> Optio
     }
 }
 
+/// Render a document number from a numbering pattern (see `InvoiceNumbering`).
+/// `date` is the document date as YYYY-MM-DD.
+pub fn format_invoice_number(pattern: &str, prefix: &str, date: &str, seq: i64) -> Result<String, String> {
+    let date = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date: {}", date))?;
+
+    let mut out = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find('}')
+            .map(|e| start + e)
+            .ok_or_else(|| format!("Unclosed token in pattern: {}", pattern))?;
+        let token = &rest[start + 1..end];
+        match token {
+            "YYYY" => out.push_str(&date.format("%Y").to_string()),
+            "YY" => out.push_str(&date.format("%y").to_string()),
+            "MM" => out.push_str(&date.format("%m").to_string()),
+            "prefix" => out.push_str(prefix),
+            "seq" => out.push_str(&seq.to_string()),
+            _ => {
+                let width = token.strip_prefix("seq:")
+                    .and_then(|w| w.parse::<usize>().ok())
+                    .filter(|w| *w <= 12)
+                    .ok_or_else(|| format!("Unknown token {{{}}} in pattern", token))?;
+                out.push_str(&format!("{:0width$}", seq, width = width));
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+
+    if !prefix.is_empty() && !pattern.contains("{prefix}") {
+        out = format!("{}-{}", prefix, out);
+    }
+    Ok(out)
+}
+
+/// Round a money amount to cents
+pub fn round_currency(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// Round a quantity so sums of fractional hours compare exactly
+pub fn round_quantity(quantity: f64) -> f64 {
+    (quantity * 1e6).round() / 1e6
+}
+
+/// Net amount of a line: quantity x unit price, less the line discount
+pub fn line_amount(hours: f64, rate: f64, discount: Option<&Discount>) -> f64 {
+    let gross = hours * rate;
+    round_currency(gross - discount.map(|d| d.amount_of(gross)).unwrap_or(0.0))
+}
+
+/// A session to be billed, as read for invoicing
+#[derive(Debug, Clone)]
+pub struct BillableSession {
+    pub id: i64,
+    pub date: String,
+    pub project_name: String,
+    pub session_type: Option<String>,
+    pub hours: f64,
+    pub pay_type: Option<String>,
+    pub hourly_rate: Option<f64>,
+    pub fixed_amount: Option<f64>,
+}
+
+/// Invoice line built from one or more sessions
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionLine {
+    pub description: String,
+    pub quantity: f64,
+    pub unit: &'static str,
+    pub rate: f64,
+    pub amount: f64,
+    pub session_ids: Vec<i64>,
+}
+
+/// Turn sessions (in date order) into invoice lines. Hourly sessions bill
+/// their hours, fixed-price ones one unit at the fixed amount. Within a group
+/// sessions merge only when unit and rate match, so each line is still
+/// quantity x rate; groups keep the order of their first session. `Summary`
+/// is one line of one unit at the combined amount.
+pub fn group_session_lines(sessions: &[BillableSession], grouping: LineGrouping) -> Vec<SessionLine> {
+    let mut lines: Vec<SessionLine> = Vec::new();
+    for session in sessions {
+        let (quantity, unit, rate) = match session.pay_type.as_deref() {
+            Some("Hourly") => (session.hours, "hours", session.hourly_rate.unwrap_or(0.0)),
+            Some("Fixed") => (1.0, "fixed", session.fixed_amount.unwrap_or(0.0)),
+            _ => (session.hours, "hours", 0.0),
+        };
+        let session_type = session.session_type.as_deref().unwrap_or("Work");
+        let description = match grouping {
+            LineGrouping::PerSession => format!("{} - {}", session_type, session.project_name),
+            LineGrouping::PerProject => session.project_name.clone(),
+            LineGrouping::PerDay => session.date.clone(),
+            LineGrouping::PerSessionType => session_type.to_string(),
+            LineGrouping::Summary => String::new(),
+        };
+        let existing = match grouping {
+            LineGrouping::PerSession => None,
+            _ => lines.iter_mut().find(|l| l.description == description && l.unit == unit && l.rate == rate),
+        };
+        match existing {
+            Some(line) => {
+                line.quantity = round_quantity(line.quantity + quantity);
+                line.session_ids.push(session.id);
+            }
+            None => lines.push(SessionLine {
+                description,
+                quantity,
+                unit,
+                rate,
+                amount: 0.0,
+                session_ids: vec![session.id],
+            }),
+        }
+    }
+    
+    for line in &mut lines {
+        line.amount = line_amount(line.quantity, line.rate, None);
+    }
+
+    if grouping == LineGrouping::Summary && !lines.is_empty() {
+        let first = sessions.iter().map(|s| s.date.as_str()).min().unwrap_or_default();
+        let last = sessions.iter().map(|s| s.date.as_str()).max().unwrap_or_default();
+        let description = if first == last {
+            format!("Services {}", first)
+        } else {
+            format!("Services {} to {}", first, last)
+        };
+        let amount = round_currency(lines.iter().map(|l| l.amount).sum());
+        let mut session_ids: Vec<i64> = lines.iter().flat_map(|l| l.session_ids.iter().copied()).collect();
+        session_ids.sort_unstable();
+        return vec![SessionLine {
+            description,
+            quantity: 1.0,
+            unit: "fixed",
+            rate: amount,
+            amount,
+            session_ids,
+        }];
+    }
+    lines
+}
+
+/// Computed money fields of an invoice
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvoiceTotals {
+    pub subtotal: f64,
+    pub discount_amount: f64,
+    /// Additive plus inclusive tax
+    pub tax_amount: f64,
+    pub withholding_amount: f64,
+    pub total: f64,
+}
+
+/// Whether `tax` applies to `item` (see `TaxRate` for the category rule)
+pub fn tax_applies(tax: &InvoiceTax, item: &InvoiceItem) -> bool {
+    item.taxable && (tax.kind == TaxKind::Withholding || tax.category == item.tax_category)
+}
+
+/// Totals from line amounts, filling in each tax's base and amount.
+///
+/// The invoice discount comes off the subtotal and is spread over the lines
+/// proportionally. Inclusive taxes are extracted from the line price, and every
+/// tax's base is the line amount net of inclusive tax. The total is
+/// subtotal - discount + additive tax - withholding.
+pub fn calculate_invoice_totals(items: &[InvoiceItem], discount: Option<&Discount>, taxes: &mut [InvoiceTax]) -> InvoiceTotals {
+    let subtotal: f64 = items.iter().map(|i| i.amount).sum();
+    let discount_amount = round_currency(discount.map(|d| d.amount_of(subtotal)).unwrap_or(0.0));
+    let factor = if subtotal > 0.0 { 1.0 - discount_amount / subtotal } else { 1.0 };
+
+    for tax in taxes.iter_mut() {
+        tax.base = 0.0;
+        tax.amount = 0.0;
+    }
+    for item in items {
+        let inclusive_rate: f64 = taxes.iter()
+            .filter(|t| t.kind == TaxKind::Inclusive && tax_applies(t, item))
+            .map(|t| t.rate)
+            .sum();
+        let net = item.amount * factor / (1.0 + inclusive_rate / 100.0);
+        for tax in taxes.iter_mut().filter(|t| tax_applies(t, item)) {
+            tax.base += net;
+        }
+    }
+
+    let mut tax_amount = 0.0;
+    let mut additive = 0.0;
+    let mut withholding_amount = 0.0;
+    for tax in taxes.iter_mut() {
+        tax.base = round_currency(tax.base);
+        tax.amount = round_currency(tax.base * tax.rate / 100.0);
+        match tax.kind {
+            TaxKind::Additive => {
+                additive += tax.amount;
+                tax_amount += tax.amount;
+            }
+            TaxKind::Inclusive => tax_amount += tax.amount,
+            TaxKind::Withholding => withholding_amount += tax.amount,
+        }
+    }
+
+    let subtotal = round_currency(subtotal);
+    InvoiceTotals {
+        subtotal,
+        discount_amount,
+        tax_amount: round_currency(tax_amount),
+        withholding_amount: round_currency(withholding_amount),
+        total: round_currency(subtotal - discount_amount + additive - withholding_amount),
+    }
+}
+
+/// Status implied by the payments and credit notes on a sent invoice. Drafts
+/// and void invoices are unaffected. An invoice settled by credit notes alone
+/// becomes Void; an Overdue invoice stays Overdue until it is settled.
+pub fn status_after_payments(status: InvoiceStatus, total: f64, amount_paid: f64, amount_credited: f64) -> InvoiceStatus {
+    if status == InvoiceStatus::Draft || status == InvoiceStatus::Void {
+        return status;
+    }
+    let settled = amount_paid + amount_credited;
+    if settled > 0.0 && round_currency(total - settled) <= 0.0 {
+        if amount_paid > 0.0 { InvoiceStatus::Paid } else { InvoiceStatus::Void }
+    } else if status == InvoiceStatus::Overdue {
+        InvoiceStatus::Overdue
+    } else if amount_paid > 0.0 {
+        InvoiceStatus::PartiallyPaid
+    } else {
+        InvoiceStatus::Sent
+    }
+}
+
+/// Quantity of each line of `invoice` not yet credited by `credit_notes`,
+/// keyed by item id. Void credit notes don't count.
+pub fn creditable_quantities(invoice: &Invoice, credit_notes: &[Invoice]) -> std::collections::HashMap<i64, f64> {
+    let mut remaining: std::collections::HashMap<i64, f64> = invoice.items.iter()
+        .map(|item| (item.id, item.hours))
+        .collect();
+    let credited = credit_notes.iter()
+        .filter(|note| note.status != InvoiceStatus::Void)
+        .flat_map(|note| note.items.iter());
+    for line in credited {
+        if let Some(qty) = line.credited_item_id.and_then(|id| remaining.get_mut(&id)) {
+            *qty = round_quantity(*qty - line.hours).max(0.0);
+        }
+    }
+    remaining
+}
+
+/// Start of the `n`th period of a retainer starting on `anchor`. Months are
+/// counted from the anchor, so a retainer starting on the 31st bills from the
+/// last day of shorter months without drifting.
+pub fn retainer_period_start(anchor: chrono::NaiveDate, period: RetainerPeriod, n: u32) -> chrono::NaiveDate {
+    let months = match period {
+        RetainerPeriod::Weekly => return anchor + chrono::Duration::weeks(n as i64),
+        RetainerPeriod::Monthly => n,
+        RetainerPeriod::Quarterly => n * 3,
+        RetainerPeriod::Yearly => n * 12,
+    };
+    anchor.checked_add_months(chrono::Months::new(months)).unwrap_or(chrono::NaiveDate::MAX)
+}
+
+/// Last day of the retainer period containing `date` (on or after `anchor`)
+pub fn retainer_period_end(anchor: chrono::NaiveDate, period: RetainerPeriod, date: chrono::NaiveDate) -> chrono::NaiveDate {
+    let mut n = 1;
+    while retainer_period_start(anchor, period, n) <= date {
+        n += 1;
+    }
+    retainer_period_start(anchor, period, n).pred_opt().unwrap_or(date)
+}
+
+/// Overage hours and hours carried forward for a period in which `hours_used`
+/// were tracked against `included_hours` plus `rollover_in`
+pub fn retainer_usage(retainer: &Retainer, rollover_in: f64, hours_used: f64) -> (f64, f64) {
+    let available = retainer.included_hours + rollover_in;
+    let overage = round_quantity((hours_used - available).max(0.0));
+    let rollover_out = match retainer.rollover {
+        RolloverPolicy::Expire => 0.0,
+        RolloverPolicy::Carry => {
+            let unused = round_quantity((available - hours_used).max(0.0));
+            retainer.rollover_cap.map_or(unused, |cap| unused.min(cap))
+        }
+    };
+    (overage, rollover_out)
+}
+
+/// Latest reminder step reached on `today` for an invoice due on `due_date`,
+/// unless a step at or past it was already sent. Steps missed while the app
+/// was closed are skipped so the client gets one reminder, not a burst.
+pub fn due_reminder_offset(offsets: &[i64], due_date: chrono::NaiveDate, today: chrono::NaiveDate, last_sent: Option<i64>) -> Option<i64> {
+    let days_from_due = (today - due_date).num_days();
+    let step = offsets.iter().copied().filter(|o| *o <= days_from_due).max()?;
+    match last_sent {
+        Some(last) if last >= step => None,
+        _ => Some(step),
+    }
+}
+
+/// Add `amount` to the bucket for an invoice `days_overdue` past its due date
+pub fn add_to_aging(aging: &mut AgingBuckets, days_overdue: i64, amount: f64) {
+    let bucket = match days_overdue {
+        i64::MIN..=0 => &mut aging.current,
+        1..=30 => &mut aging.days_1_30,
+        31..=60 => &mut aging.days_31_60,
+        61..=90 => &mut aging.days_61_90,
+        _ => &mut aging.days_over_90,
+    };
+    *bucket = round_currency(*bucket + amount);
+    aging.total = round_currency(aging.total + amount);
+}
+
+/// Age the balances of `invoices` on `as_of`. Only invoices still owed count:
+/// drafts, void and paid invoices and credit notes are skipped.
+pub fn receivables_report(invoices: &[Invoice], as_of: chrono::NaiveDate) -> ReceivablesReport {
+    let mut totals = AgingBuckets::default();
+    let mut clients: Vec<ClientReceivables> = Vec::new();
+    let owed = invoices.iter().filter(|i| {
+        matches!(i.status, InvoiceStatus::Sent | InvoiceStatus::PartiallyPaid | InvoiceStatus::Overdue)
+            && !i.is_credit_note()
+            && i.balance_due > 0.0
+    });
+    for invoice in owed {
+        let days_overdue = chrono::NaiveDate::parse_from_str(&invoice.due_date, "%Y-%m-%d")
+            .map(|due| (as_of - due).num_days())
+            .unwrap_or(0);
+        add_to_aging(&mut totals, days_overdue, invoice.balance_due);
+        let client = match clients.iter().position(|c| c.client_name == invoice.client_name) {
+            Some(index) => &mut clients[index],
+            None => {
+                clients.push(ClientReceivables {
+                    client_name: invoice.client_name.clone(),
+                    aging: AgingBuckets::default(),
+                    invoice_count: 0,
+                    oldest_due_date: invoice.due_date.clone(),
+                });
+                clients.last_mut().expect("just pushed")
+            }
+        };
+        add_to_aging(&mut client.aging, days_overdue, invoice.balance_due);
+        client.invoice_count += 1;
+        if invoice.due_date < client.oldest_due_date {
+            client.oldest_due_date = invoice.due_date.clone();
+        }
+    }
+    clients.sort_by(|a, b| b.aging.total.total_cmp(&a.aging.total).then_with(|| a.client_name.cmp(&b.client_name)));
+    ReceivablesReport {
+        as_of: as_of.format("%Y-%m-%d").to_string(),
+        totals,
+        clients,
+    }
+}
+
+/// Days-to-pay and on-time rate per client, from paid invoices with a paid date
+pub fn client_payment_stats(invoices: &[Invoice]) -> Vec<ClientPaymentStats> {
+    let parse = |date: &str| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok();
+    // (days to pay, days late) per client
+    let mut by_client: Vec<(String, Vec<(i64, i64)>)> = Vec::new();
+    for invoice in invoices.iter().filter(|i| i.status == InvoiceStatus::Paid && !i.is_credit_note()) {
+        let dates = invoice.paid_date.as_deref().and_then(parse)
+            .zip(parse(invoice.invoice_date()))
+            .zip(parse(&invoice.due_date));
+        let ((paid, issued), due) = match dates {
+            Some(dates) => dates,
+            None => continue,
+        };
+        let entry = ((paid - issued).num_days().max(0), (paid - due).num_days());
+        match by_client.iter_mut().find(|(name, _)| *name == invoice.client_name) {
+            Some((_, entries)) => entries.push(entry),
+            None => by_client.push((invoice.client_name.clone(), vec![entry])),
+        }
+    }
+
+    let mut stats: Vec<ClientPaymentStats> = by_client.into_iter()
+        .map(|(client_name, entries)| {
+            let count = entries.len() as f64;
+            let late: Vec<i64> = entries.iter().map(|(_, late)| *late).filter(|late| *late > 0).collect();
+            ClientPaymentStats {
+                client_name,
+                paid_count: entries.len() as i64,
+                avg_days_to_pay: round_quantity(entries.iter().map(|(days, _)| *days as f64).sum::<f64>() / count),
+                on_time_rate: round_quantity((count - late.len() as f64) / count),
+                avg_days_late: if late.is_empty() {
+                    0.0
+                } else {
+                    round_quantity(late.iter().sum::<i64>() as f64 / late.len() as f64)
+                },
+            }
+        })
+        .collect();
+    stats.sort_by(|a, b| a.client_name.cmp(&b.client_name));
+    stats
+}
+
+/// Suggest `transaction` as a payment of `invoice` when the amount, the
+/// invoice number or the client name line up. A matching amount or invoice
+/// number alone is enough; a client name only counts alongside a partial amount.
+pub fn match_bank_transaction(transaction: &BankTransaction, invoice: &Invoice) -> Option<MatchSuggestion> {
+    let remaining = transaction.unallocated();
+    if remaining <= 0.0 || invoice.balance_due <= 0.0 {
+        return None;
+    }
+    let mut score = 0;
+    let mut reasons = Vec::new();
+
+    if (remaining - invoice.balance_due).abs() < 0.005 {
+        score += 50;
+        reasons.push(MatchReason::ExactAmount);
+    } else if remaining < invoice.balance_due {
+        score += 10;
+        reasons.push(MatchReason::PartialAmount);
+    }
+
+    let text = [Some(&transaction.description), transaction.reference.as_ref(), transaction.counterparty.as_ref()]
+        .into_iter()
+        .flatten()
+        .map(|t| match_key(t))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if contains_number(&text, &match_key(&invoice.invoice_number)) {
+        score += 50;
+        reasons.push(MatchReason::InvoiceNumber);
+    }
+    let client = match_key(&invoice.client_name);
+    if !client.is_empty() && text.replace(' ', "").contains(&client) {
+        score += 20;
+        reasons.push(MatchReason::ClientName);
+    }
+
+    if score < 30 {
+        return None;
+    }
+    Some(MatchSuggestion {
+        transaction_id: transaction.id,
+        invoice_id: invoice.id,
+        invoice_number: invoice.invoice_number.clone(),
+        client_name: invoice.client_name.clone(),
+        balance_due: invoice.balance_due,
+        amount: remaining.min(invoice.balance_due),
+        score,
+        reasons,
+    })
+}
+
+/// Lowercase letters and digits only, so "INV-0042" and "inv 0042" compare equal
+fn match_key(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// `number` appears in `text` without extra digits on either side, so INV-1
+/// isn't found in INV-10
+fn contains_number(text: &str, number: &str) -> bool {
+    if number.is_empty() {
+        return false;
+    }
+    text.match_indices(number).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + number.len()..].chars().next();
+        let digit_edge = |edge: Option<char>, inner: Option<char>| {
+            edge.is_some_and(|e| e.is_ascii_digit()) && inner.is_some_and(|i| i.is_ascii_digit())
+        };
+        !digit_edge(before, number.chars().next()) && !digit_edge(after, number.chars().next_back())
+    })
+}
+
+/// `n`th due date of an expense first due on `first`. Months are counted from
+/// `first`, so an expense on the 31st falls on shorter months' last day.
+pub fn expense_due_date(first: chrono::NaiveDate, recurrence: ExpenseRecurrence, n: u32) -> chrono::NaiveDate {
+    let months = match recurrence {
+        ExpenseRecurrence::None => return first,
+        ExpenseRecurrence::Weekly => return first + chrono::Duration::weeks(n as i64),
+        ExpenseRecurrence::Monthly => n,
+        ExpenseRecurrence::Quarterly => n * 3,
+        ExpenseRecurrence::Yearly => n * 12,
+    };
+    first.checked_add_months(chrono::Months::new(months)).unwrap_or(chrono::NaiveDate::MAX)
+}
+
+/// Dates `expense` is due from `start` to `end` (inclusive)
+pub fn expense_dates(expense: &Expense, start: chrono::NaiveDate, end: chrono::NaiveDate) -> Vec<chrono::NaiveDate> {
+    let first = match chrono::NaiveDate::parse_from_str(&expense.date, "%Y-%m-%d") {
+        Ok(date) => date,
+        Err(_) => return Vec::new(),
+    };
+    let last = expense.end_date.as_deref()
+        .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+        .map_or(end, |d| d.min(end));
+    if expense.recurrence == ExpenseRecurrence::None {
+        return if first >= start && first <= last { vec![first] } else { Vec::new() };
+    }
+    let mut dates = Vec::new();
+    let mut n = 0;
+    loop {
+        let date = expense_due_date(first, expense.recurrence, n);
+        if date > last {
+            break;
+        }
+        if date >= start {
+            dates.push(date);
+        }
+        n += 1;
+    }
+    dates
+}
+
+/// Typical weekly spend as of `today`: recurring expenses still running at
+/// their yearly rate, plus one-off expenses of the last 12 weeks averaged
+pub fn weekly_expense_rate(expenses: &[Expense], today: chrono::NaiveDate) -> f64 {
+    let window_start = today - chrono::Duration::weeks(12) + chrono::Duration::days(1);
+    let today_str = today.format("%Y-%m-%d").to_string();
+    let mut recurring = 0.0;
+    let mut one_off = 0.0;
+    for expense in expenses {
+        if expense.recurrence == ExpenseRecurrence::None {
+            one_off += expense_dates(expense, window_start, today).len() as f64 * expense.amount;
+        } else if expense.date <= today_str && expense.end_date.as_deref().unwrap_or(&today_str) >= today_str.as_str() {
+            recurring += expense.amount * expense.recurrence.per_year() / 52.0;
+        }
+    }
+    round_currency(recurring + one_off / 12.0)
+}
+
+/// Income less expenses from `start` to `end`, with `income_by_month` as
+/// (YYYY-MM, income) pairs. Every month in the range is listed.
+pub fn net_income_report(
+    income_by_month: &[(String, f64)],
+    expenses: &[Expense],
+    start: chrono::NaiveDate,
+    end: chrono::NaiveDate,
+    basis: ReportingBasis,
+) -> NetIncomeReport {
+    let mut months: Vec<MonthlyNetIncome> = Vec::new();
+    let mut month = start.with_day(1).unwrap_or(start);
+    while month <= end {
+        let key = month.format("%Y-%m").to_string();
+        let income = income_by_month.iter().find(|(m, _)| *m == key).map_or(0.0, |(_, amount)| *amount);
+        months.push(MonthlyNetIncome {
+            month: key,
+            income: round_currency(income),
+            business_expenses: 0.0,
+            personal_expenses: 0.0,
+            net_income: 0.0,
+        });
+        month = match month.checked_add_months(chrono::Months::new(1)) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+
+    let mut by_category: Vec<ExpenseCategoryTotal> = Vec::new();
+    for expense in expenses {
+        for date in expense_dates(expense, start, end) {
+            let key = date.format("%Y-%m").to_string();
+            let category = match by_category.iter().position(|c| c.category == expense.category) {
+                Some(index) => &mut by_category[index],
+                None => {
+                    by_category.push(ExpenseCategoryTotal {
+                        category: expense.category.clone(),
+                        business: 0.0,
+                        personal: 0.0,
+                    });
+                    by_category.last_mut().expect("just pushed")
+                }
+            };
+            let month = months.iter_mut().find(|m| m.month == key);
+            if expense.is_business {
+                category.business += expense.amount;
+                if let Some(month) = month {
+                    month.business_expenses += expense.amount;
+                }
+            } else {
+                category.personal += expense.amount;
+                if let Some(month) = month {
+                    month.personal_expenses += expense.amount;
+                }
+            }
+        }
+    }
+    for month in &mut months {
+        month.business_expenses = round_currency(month.business_expenses);
+        month.personal_expenses = round_currency(month.personal_expenses);
+        month.net_income = round_currency(month.income - month.business_expenses);
+    }
+    for category in &mut by_category {
+        category.business = round_currency(category.business);
+        category.personal = round_currency(category.personal);
+    }
+    by_category.sort_by(|a, b| {
+        (b.business + b.personal).total_cmp(&(a.business + a.personal)).then_with(|| a.category.cmp(&b.category))
+    });
+
+    let income = round_currency(months.iter().map(|m| m.income).sum());
+    let business_expenses = round_currency(by_category.iter().map(|c| c.business).sum());
+    let personal_expenses = round_currency(by_category.iter().map(|c| c.personal).sum());
+    let net_income = round_currency(income - business_expenses);
+    NetIncomeReport {
+        start: start.format("%Y-%m-%d").to_string(),
+        end: end.format("%Y-%m-%d").to_string(),
+        basis,
+        income,
+        business_expenses,
+        personal_expenses,
+        net_income,
+        savings: round_currency(net_income - personal_expenses),
+        by_category,
+        months,
+    }
+}
+
 pub fn total_hours_for_date(sessions: &[WorkSession], date: &str) -> f64 {
     sessions.iter().filter(|s| s.date == date).map(|s| s.hours).sum()
 }
@@ -75,3 +687,436 @@ pub fn calculate_avg_weekly_hours(sessions: &[WorkSession]) -> f64 {
 
     total_hours / 4.0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn invoice(id: i64, number: &str, client: &str, status: InvoiceStatus, total: f64, amount_paid: f64) -> Invoice {
+        Invoice {
+            id,
+            invoice_number: number.to_string(),
+            client_name: client.to_string(),
+            client_email: None,
+            client_address: ClientAddress::default(),
+            created_date: "2024-01-01".to_string(),
+            issued_date: None,
+            due_date: "2024-01-31".to_string(),
+            status,
+            subtotal: total,
+            tax_rate: None,
+            tax_amount: 0.0,
+            total,
+            notes: None,
+            discount: None,
+            discount_amount: 0.0,
+            withholding_amount: 0.0,
+            amount_paid,
+            balance_due: round_currency(total - amount_paid),
+            overdue_at: None,
+            profile_id: None,
+            template_id: None,
+            credited_invoice_id: None,
+            credited_invoice_number: None,
+            amount_credited: 0.0,
+            voided_at: None,
+            paid_date: None,
+            line_grouping: LineGrouping::PerSession,
+            include_timesheet: false,
+            quote_id: None,
+            quote_number: None,
+            items: Vec::new(),
+            taxes: Vec::new(),
+            payments: Vec::new(),
+            timesheet: Vec::new(),
+        }
+    }
+
+    fn item(id: i64, quantity: f64, price: f64, tax_category: Option<&str>) -> InvoiceItem {
+        InvoiceItem {
+            id,
+            invoice_id: 1,
+            session_id: None,
+            session_ids: Vec::new(),
+            kind: InvoiceItemKind::Manual,
+            description: format!("Line {}", id),
+            hours: quantity,
+            unit: None,
+            rate: price,
+            discount: None,
+            taxable: true,
+            tax_category: tax_category.map(str::to_string),
+            amount: line_amount(quantity, price, None),
+            credited_item_id: None,
+            session_released: false,
+        }
+    }
+
+    fn tax(name: &str, rate: f64, kind: TaxKind, category: Option<&str>) -> InvoiceTax {
+        InvoiceTax {
+            id: 0,
+            invoice_id: 1,
+            tax_rate_id: None,
+            name: name.to_string(),
+            rate,
+            kind,
+            category: category.map(str::to_string),
+            note: None,
+            base: 0.0,
+            amount: 0.0,
+        }
+    }
+
+    fn session(id: i64, date: &str, project: &str, hours: f64, rate: f64) -> BillableSession {
+        BillableSession {
+            id,
+            date: date.to_string(),
+            project_name: project.to_string(),
+            session_type: Some("Work".to_string()),
+            hours,
+            pay_type: Some("Hourly".to_string()),
+            hourly_rate: Some(rate),
+            fixed_amount: None,
+        }
+    }
+
+
+    fn transaction(amount: f64, description: &str, counterparty: Option<&str>) -> BankTransaction {
+        BankTransaction {
+            id: 1,
+            external_id: "t1".to_string(),
+            date: "2024-02-01".to_string(),
+            amount,
+            description: description.to_string(),
+            reference: None,
+            counterparty: counterparty.map(str::to_string),
+            ignored: false,
+            allocated: 0.0,
+            invoice_numbers: Vec::new(),
+            imported_at: "2024-02-02".to_string(),
+        }
+    }
+
+    fn expense(date: &str, amount: f64, recurrence: ExpenseRecurrence, is_business: bool) -> Expense {
+        Expense {
+            id: 1,
+            date: date.to_string(),
+            amount,
+            description: "Expense".to_string(),
+            category: if is_business { "Software" } else { "Rent" }.to_string(),
+            is_business,
+            client_name: None,
+            project_name: None,
+            recurrence,
+            end_date: None,
+            receipt_paths: Vec::new(),
+            notes: None,
+            created_at: "2024-01-01".to_string(),
+        }
+    }
+
+    #[test]
+    fn invoice_numbers_follow_the_pattern() {
+        assert_eq!(format_invoice_number("INV-{YYYY}-{seq:4}", "", "2024-03-05", 7).unwrap(), "INV-2024-0007");
+        assert_eq!(format_invoice_number("{YY}{MM}-{seq}", "", "2024-03-05", 12).unwrap(), "2403-12");
+        assert_eq!(format_invoice_number("{prefix}/{seq:3}", "ACME", "2024-03-05", 1).unwrap(), "ACME/001");
+        assert_eq!(format_invoice_number("INV-{seq:3}", "ACME", "2024-03-05", 1).unwrap(), "ACME-INV-001");
+        assert!(format_invoice_number("INV-{seq", "", "2024-03-05", 1).is_err());
+        assert!(format_invoice_number("INV-{day}", "", "2024-03-05", 1).is_err());
+        assert!(format_invoice_number("INV-{seq}", "", "2024-13-05", 1).is_err());
+    }
+
+    #[test]
+    fn line_discounts_are_capped_at_the_line() {
+        let percent = Discount { discount_type: DiscountType::Percent, value: 10.0 };
+        let fixed = Discount { discount_type: DiscountType::Fixed, value: 500.0 };
+        assert_eq!(line_amount(2.5, 40.0, None), 100.0);
+        assert_eq!(line_amount(2.5, 40.0, Some(&percent)), 90.0);
+        assert_eq!(line_amount(2.5, 40.0, Some(&fixed)), 0.0);
+        assert_eq!(line_amount(1.0 / 3.0, 10.0, None), 3.33);
+    }
+
+    #[test]
+    fn totals_apply_discount_then_taxes_by_category() {
+        let items = vec![item(1, 10.0, 100.0, None), item(2, 1.0, 200.0, Some("reduced"))];
+        let discount = Discount { discount_type: DiscountType::Percent, value: 10.0 };
+        let mut taxes = vec![
+            tax("VAT", 20.0, TaxKind::Additive, None),
+            tax("VAT reduced", 5.0, TaxKind::Additive, Some("reduced")),
+            tax("Withholding", 15.0, TaxKind::Withholding, None),
+        ];
+        let totals = calculate_invoice_totals(&items, Some(&discount), &mut taxes);
+        assert_eq!(totals.subtotal, 1200.0);
+        assert_eq!(totals.discount_amount, 120.0);
+        assert_eq!((taxes[0].base, taxes[0].amount), (900.0, 180.0));
+        assert_eq!((taxes[1].base, taxes[1].amount), (180.0, 9.0));
+        assert_eq!((taxes[2].base, taxes[2].amount), (1080.0, 162.0));
+        assert_eq!(totals.tax_amount, 189.0);
+        assert_eq!(totals.withholding_amount, 162.0);
+        assert_eq!(totals.total, 1200.0 - 120.0 + 189.0 - 162.0);
+    }
+
+    #[test]
+    fn inclusive_tax_is_extracted_from_the_price() {
+        let items = vec![item(1, 1.0, 120.0, None)];
+        let mut taxes = vec![tax("Sales tax", 20.0, TaxKind::Inclusive, None)];
+        let totals = calculate_invoice_totals(&items, None, &mut taxes);
+        assert_eq!((taxes[0].base, taxes[0].amount), (100.0, 20.0));
+        assert_eq!(totals.tax_amount, 20.0);
+        assert_eq!(totals.total, 120.0);
+    }
+
+    #[test]
+    fn untaxable_lines_skip_every_tax() {
+        let mut untaxed = item(2, 1.0, 50.0, None);
+        untaxed.taxable = false;
+        let mut taxes = vec![tax("VAT", 20.0, TaxKind::Additive, None)];
+        let totals = calculate_invoice_totals(&[item(1, 1.0, 100.0, None), untaxed], None, &mut taxes);
+        assert_eq!(taxes[0].base, 100.0);
+        assert_eq!(totals.total, 170.0);
+    }
+
+    #[test]
+    fn status_follows_payments_and_credits() {
+        use InvoiceStatus::*;
+        assert_eq!(status_after_payments(Draft, 100.0, 100.0, 0.0), Draft);
+        assert_eq!(status_after_payments(Void, 100.0, 0.0, 0.0), Void);
+        assert_eq!(status_after_payments(Sent, 100.0, 0.0, 0.0), Sent);
+        assert_eq!(status_after_payments(Overdue, 100.0, 0.0, 0.0), Overdue);
+        assert_eq!(status_after_payments(Overdue, 100.0, 40.0, 0.0), Overdue);
+        assert_eq!(status_after_payments(Sent, 100.0, 40.0, 0.0), PartiallyPaid);
+        assert_eq!(status_after_payments(PartiallyPaid, 100.0, 60.0, 40.0), Paid);
+        assert_eq!(status_after_payments(Sent, 100.0, 0.0, 100.0), Void);
+        assert_eq!(status_after_payments(Paid, 100.0, 0.0, 0.0), Sent);
+    }
+
+    #[test]
+    fn credited_quantities_ignore_void_notes() {
+        let mut original = invoice(1, "INV-1", "Acme", InvoiceStatus::Sent, 300.0, 0.0);
+        original.items = vec![item(10, 2.0, 100.0, None), item(11, 1.0, 100.0, None)];
+        let note = |id: i64, status: InvoiceStatus, quantity: f64| {
+            let mut note = invoice(id, "CN-1", "Acme", status, 100.0, 0.0);
+            note.credited_invoice_id = Some(1);
+            let mut line = item(100 + id, quantity, 100.0, None);
+            line.credited_item_id = Some(10);
+            note.items = vec![line];
+            note
+        };
+        let remaining = creditable_quantities(&original, &[note(2, InvoiceStatus::Sent, 0.5), note(3, InvoiceStatus::Void, 1.5)]);
+        assert_eq!(remaining[&10], 1.5);
+        assert_eq!(remaining[&11], 1.0);
+        let remaining = creditable_quantities(&original, &[note(2, InvoiceStatus::Sent, 5.0)]);
+        assert_eq!(remaining[&10], 0.0);
+    }
+
+    #[test]
+    fn sessions_group_by_key_and_rate() {
+        let sessions = vec![
+            session(1, "2024-03-01", "Acme", 2.0, 50.0),
+            session(2, "2024-03-01", "Beta", 1.5, 50.0),
+            session(3, "2024-03-02", "Acme", 1.0, 50.0),
+            session(4, "2024-03-03", "Acme", 1.0, 80.0),
+        ];
+
+        let per_session = group_session_lines(&sessions, LineGrouping::PerSession);
+        assert_eq!(per_session.len(), 4);
+        assert_eq!(per_session[0].description, "Work - Acme");
+
+        let per_project = group_session_lines(&sessions, LineGrouping::PerProject);
+        let summary: Vec<(&str, f64, f64, Vec<i64>)> = per_project.iter()
+            .map(|l| (l.description.as_str(), l.quantity, l.amount, l.session_ids.clone()))
+            .collect();
+        assert_eq!(summary, vec![
+            ("Acme", 3.0, 150.0, vec![1, 3]),
+            ("Beta", 1.5, 75.0, vec![2]),
+            ("Acme", 1.0, 80.0, vec![4]),
+        ]);
+
+        let per_day = group_session_lines(&sessions, LineGrouping::PerDay);
+        assert_eq!(per_day.iter().map(|l| l.description.as_str()).collect::<Vec<_>>(), vec!["2024-03-01", "2024-03-02", "2024-03-03"]);
+
+        let services = group_session_lines(&sessions, LineGrouping::Summary);
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].description, "Services 2024-03-01 to 2024-03-03");
+        assert_eq!((services[0].quantity, services[0].rate, services[0].amount), (1.0, 305.0, 305.0));
+        assert_eq!(services[0].session_ids, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn fixed_price_sessions_bill_one_unit() {
+        let mut fixed = session(1, "2024-03-01", "Acme", 6.0, 0.0);
+        fixed.pay_type = Some("Fixed".to_string());
+        fixed.fixed_amount = Some(400.0);
+        let lines = group_session_lines(&[fixed], LineGrouping::PerProject);
+        assert_eq!((lines[0].quantity, lines[0].unit, lines[0].amount), (1.0, "fixed", 400.0));
+    }
+
+    #[test]
+    fn retainer_periods_keep_the_anchor_day() {
+        let anchor = date("2024-01-31");
+        assert_eq!(retainer_period_start(anchor, RetainerPeriod::Monthly, 1), date("2024-02-29"));
+        assert_eq!(retainer_period_start(anchor, RetainerPeriod::Monthly, 2), date("2024-03-31"));
+        assert_eq!(retainer_period_start(anchor, RetainerPeriod::Quarterly, 1), date("2024-04-30"));
+        assert_eq!(retainer_period_start(anchor, RetainerPeriod::Yearly, 1), date("2025-01-31"));
+        assert_eq!(retainer_period_start(anchor, RetainerPeriod::Weekly, 2), date("2024-02-14"));
+        assert_eq!(retainer_period_end(anchor, RetainerPeriod::Monthly, date("2024-01-31")), date("2024-02-28"));
+        assert_eq!(retainer_period_end(anchor, RetainerPeriod::Monthly, date("2024-03-15")), date("2024-03-30"));
+    }
+
+    #[test]
+    fn retainer_usage_rolls_over_up_to_the_cap() {
+        let mut retainer = Retainer {
+            id: 1,
+            client_name: "Acme".to_string(),
+            client_email: None,
+            project_names: vec!["Acme".to_string()],
+            period: RetainerPeriod::Monthly,
+            fee: 1000.0,
+            included_hours: 10.0,
+            overage_rate: 120.0,
+            rollover: RolloverPolicy::Carry,
+            rollover_cap: Some(5.0),
+            start_date: "2024-01-01".to_string(),
+            end_date: None,
+            payment_terms_days: 14,
+            profile_id: None,
+            tax_rate_ids: Vec::new(),
+            active: true,
+        };
+        assert_eq!(retainer_usage(&retainer, 0.0, 2.0), (0.0, 5.0));
+        assert_eq!(retainer_usage(&retainer, 5.0, 12.0), (0.0, 3.0));
+        assert_eq!(retainer_usage(&retainer, 2.0, 14.5), (2.5, 0.0));
+        retainer.rollover_cap = None;
+        assert_eq!(retainer_usage(&retainer, 0.0, 2.0), (0.0, 8.0));
+        retainer.rollover = RolloverPolicy::Expire;
+        assert_eq!(retainer_usage(&retainer, 0.0, 2.0), (0.0, 0.0));
+    }
+
+
+    #[test]
+    fn reminders_send_the_latest_step_once() {
+        let due = date("2024-03-01");
+        let offsets = [-3, 1, 7, 14];
+        assert_eq!(due_reminder_offset(&offsets, due, date("2024-02-20"), None), None);
+        assert_eq!(due_reminder_offset(&offsets, due, date("2024-02-27"), None), Some(-3));
+        assert_eq!(due_reminder_offset(&offsets, due, date("2024-03-01"), Some(-3)), None);
+        assert_eq!(due_reminder_offset(&offsets, due, date("2024-03-10"), Some(-3)), Some(7));
+        assert_eq!(due_reminder_offset(&offsets, due, date("2024-04-01"), Some(7)), Some(14));
+        assert_eq!(due_reminder_offset(&offsets, due, date("2024-04-01"), Some(14)), None);
+    }
+
+
+    #[test]
+    fn receivables_age_open_balances_only() {
+        let mut late = invoice(1, "INV-1", "Acme", InvoiceStatus::Overdue, 500.0, 200.0);
+        late.due_date = "2024-01-01".to_string();
+        let mut current = invoice(2, "INV-2", "Beta", InvoiceStatus::Sent, 100.0, 0.0);
+        current.due_date = "2024-03-31".to_string();
+        let mut older = invoice(3, "INV-3", "Acme", InvoiceStatus::Sent, 50.0, 0.0);
+        older.due_date = "2023-11-01".to_string();
+        let mut credit_note = invoice(4, "CN-1", "Acme", InvoiceStatus::Sent, 40.0, 0.0);
+        credit_note.credited_invoice_id = Some(1);
+        let invoices = vec![
+            late,
+            current,
+            older,
+            credit_note,
+            invoice(5, "INV-5", "Acme", InvoiceStatus::Paid, 80.0, 80.0),
+            invoice(6, "INV-6", "Acme", InvoiceStatus::Draft, 80.0, 0.0),
+        ];
+
+        let report = receivables_report(&invoices, date("2024-03-01"));
+        assert_eq!(report.totals.current, 100.0);
+        assert_eq!(report.totals.days_31_60, 300.0);
+        assert_eq!(report.totals.days_over_90, 50.0);
+        assert_eq!(report.totals.total, 450.0);
+        assert_eq!(report.clients[0].client_name, "Acme");
+        assert_eq!(report.clients[0].invoice_count, 2);
+        assert_eq!(report.clients[0].oldest_due_date, "2023-11-01");
+    }
+
+    #[test]
+    fn payment_stats_count_late_payments() {
+        let paid = |id: i64, paid_date: &str| {
+            let mut invoice = invoice(id, "INV", "Acme", InvoiceStatus::Paid, 100.0, 100.0);
+            invoice.paid_date = Some(paid_date.to_string());
+            invoice
+        };
+        let stats = client_payment_stats(&[paid(1, "2024-01-21"), paid(2, "2024-02-10")]);
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].paid_count, 2);
+        assert_eq!(stats[0].avg_days_to_pay, 30.0);
+        assert_eq!(stats[0].on_time_rate, 0.5);
+        assert_eq!(stats[0].avg_days_late, 10.0);
+
+        // Counted from the issue date when there is one
+        let mut issued_late = paid(1, "2024-01-21");
+        issued_late.issued_date = Some("2024-01-11".to_string());
+        assert_eq!(client_payment_stats(&[issued_late])[0].avg_days_to_pay, 10.0);
+    }
+
+
+    #[test]
+    fn bank_matches_by_amount_number_and_client() {
+        let open = invoice(1, "INV-0042", "Acme Ltd", InvoiceStatus::Sent, 250.0, 0.0);
+
+        let exact = match_bank_transaction(&transaction(250.0, "Transfer", None), &open).unwrap();
+        assert_eq!(exact.reasons, vec![MatchReason::ExactAmount]);
+
+        let by_number = match_bank_transaction(&transaction(100.0, "Payment inv 0042", Some("ACME LTD")), &open).unwrap();
+        assert_eq!(by_number.reasons, vec![MatchReason::PartialAmount, MatchReason::InvoiceNumber, MatchReason::ClientName]);
+        assert_eq!(by_number.amount, 100.0);
+
+        assert!(match_bank_transaction(&transaction(100.0, "Payment INV-00420", None), &open).is_none());
+        assert!(match_bank_transaction(&transaction(400.0, "Unrelated", None), &open).is_none());
+        let paid = invoice(2, "INV-0043", "Acme Ltd", InvoiceStatus::Paid, 250.0, 250.0);
+        assert!(match_bank_transaction(&transaction(250.0, "INV-0043", None), &paid).is_none());
+    }
+
+    #[test]
+    fn recurring_expenses_repeat_until_their_end() {
+        let mut monthly = expense("2024-01-31", 30.0, ExpenseRecurrence::Monthly, true);
+        monthly.end_date = Some("2024-04-15".to_string());
+        assert_eq!(
+            expense_dates(&monthly, date("2024-02-01"), date("2024-12-31")),
+            vec![date("2024-02-29"), date("2024-03-31")]
+        );
+        let once = expense("2024-02-10", 100.0, ExpenseRecurrence::None, true);
+        assert_eq!(expense_dates(&once, date("2024-02-01"), date("2024-02-29")), vec![date("2024-02-10")]);
+        assert!(expense_dates(&once, date("2024-03-01"), date("2024-03-31")).is_empty());
+    }
+
+    #[test]
+    fn weekly_expense_rate_spreads_recurring_and_recent_one_offs() {
+        let expenses = vec![
+            expense("2024-01-01", 52.0, ExpenseRecurrence::Yearly, true),
+            expense("2024-01-01", 10.0, ExpenseRecurrence::Weekly, false),
+            expense("2024-05-01", 120.0, ExpenseRecurrence::None, true),
+            expense("2023-01-01", 999.0, ExpenseRecurrence::None, true),
+        ];
+        assert_eq!(weekly_expense_rate(&expenses, date("2024-06-01")), 1.0 + 10.0 + 10.0);
+    }
+
+    #[test]
+    fn net_income_deducts_business_expenses_only() {
+        let income = vec![("2024-01".to_string(), 3000.0), ("2024-02".to_string(), 2000.0)];
+        let expenses = vec![
+            expense("2024-01-15", 100.0, ExpenseRecurrence::Monthly, true),
+            expense("2024-02-01", 800.0, ExpenseRecurrence::None, false),
+        ];
+        let report = net_income_report(&income, &expenses, date("2024-01-01"), date("2024-03-31"), ReportingBasis::Earned);
+        assert_eq!(report.months.len(), 3);
+        assert_eq!(report.months[0].net_income, 2900.0);
+        assert_eq!(report.months[1].personal_expenses, 800.0);
+        assert_eq!(report.months[2].net_income, -100.0);
+        assert_eq!(report.business_expenses, 300.0);
+        assert_eq!(report.net_income, 4700.0);
+        assert_eq!(report.savings, 3900.0);
+        assert_eq!(report.by_category[0].category, "Rent");
+    }
+}
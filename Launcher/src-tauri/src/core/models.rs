@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 /// User-defined session type
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -101,6 +102,77 @@ pub struct NewSession {
     pub fixed_amount: Option<f64>,
 }
 
+// ========== SESSION QUERIES ==========
+
+/// Column a session query can be sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionSortKey {
+    Date,
+    Hours,
+    Pay,
+    Project,
+    SessionType,
+}
+
+/// Sort direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+/// Filter for session queries. Empty lists and `None` mean "no constraint".
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionFilter {
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    pub session_type_ids: Vec<i64>,
+    pub project_names: Vec<String>,
+    pub pay_types: Vec<PayType>,
+    pub invoiced: Option<bool>,
+    pub min_hours: Option<f64>,
+    pub max_hours: Option<f64>,
+    pub text: Option<String>,
+}
+
+impl SessionFilter {
+    pub fn validate(&self) -> Result<(), String> {
+        if let (Some(min), Some(max)) = (self.min_hours, self.max_hours) {
+            if min > max {
+                return Err("Minimum hours cannot exceed maximum hours".to_string());
+            }
+        }
+        if let (Some(from), Some(to)) = (&self.date_from, &self.date_to) {
+            if from > to {
+                return Err("Start date cannot be after end date".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Paginated session query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionQuery {
+    #[serde(default)]
+    pub filter: SessionFilter,
+    pub sort_by: Option<SessionSortKey>,
+    pub sort_direction: Option<SortDirection>,
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+/// One page of query results plus totals for the whole filtered set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionPage {
+    pub sessions: Vec<WorkSession>,
+    pub next_cursor: Option<String>,
+    pub total_count: i64,
+    pub total_hours: f64,
+    pub total_pay: f64,
+}
+
 /// Type of financial goal
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GoalType {
@@ -412,8 +484,11 @@ pub struct BurnoutFactor {
 pub enum InvoiceStatus {
     Draft,
     Sent,
+    PartiallyPaid,
     Paid,
     Overdue,
+    /// Cancelled; kept for the record but no longer owed
+    Void,
 }
 
 impl InvoiceStatus {
@@ -421,8 +496,10 @@ impl InvoiceStatus {
         match self {
             InvoiceStatus::Draft => "Draft".to_string(),
             InvoiceStatus::Sent => "Sent".to_string(),
+            InvoiceStatus::PartiallyPaid => "PartiallyPaid".to_string(),
             InvoiceStatus::Paid => "Paid".to_string(),
             InvoiceStatus::Overdue => "Overdue".to_string(),
+            InvoiceStatus::Void => "Void".to_string(),
         }
     }
     
@@ -430,11 +507,18 @@ impl InvoiceStatus {
         match s {
             "Draft" => Ok(InvoiceStatus::Draft),
             "Sent" => Ok(InvoiceStatus::Sent),
+            "PartiallyPaid" => Ok(InvoiceStatus::PartiallyPaid),
             "Paid" => Ok(InvoiceStatus::Paid),
             "Overdue" => Ok(InvoiceStatus::Overdue),
+            "Void" => Ok(InvoiceStatus::Void),
             _ => Err(format!("Invalid invoice status: {}", s)),
         }
     }
+
+    /// Only drafts can have their content or line items changed
+    pub fn is_editable(&self) -> bool {
+        matches!(self, InvoiceStatus::Draft)
+    }
 }
 
 /// Invoice record
@@ -444,7 +528,11 @@ pub struct Invoice {
     pub invoice_number: String,
     pub client_name: String,
     pub client_email: Option<String>,
+    /// Buyer address and VAT ID, required for e-invoices
+    pub client_address: ClientAddress,
     pub created_date: String,
+    /// Date the invoice left Draft, shown on its documents and booked in the journal
+    pub issued_date: Option<String>,
     pub due_date: String,
     pub status: InvoiceStatus,
     pub subtotal: f64,
@@ -452,197 +540,1976 @@ pub struct Invoice {
     pub tax_amount: f64,
     pub total: f64,
     pub notes: Option<String>,
+    /// Invoice-level discount, applied to the subtotal
+    pub discount: Option<Discount>,
+    pub discount_amount: f64,
+    /// Withholding deducted from the total
+    pub withholding_amount: f64,
+    /// Sum of recorded payments
+    pub amount_paid: f64,
+    /// Outstanding amount; zero once Paid
+    pub balance_due: f64,
+    /// When the invoice was last moved to Overdue
+    pub overdue_at: Option<String>,
+    /// Sender profile; `None` renders with the default profile
+    pub profile_id: Option<i64>,
+    /// HTML template; `None` renders with the built-in template
+    pub template_id: Option<i64>,
+    /// Set on credit notes: the invoice being credited
+    pub credited_invoice_id: Option<i64>,
+    pub credited_invoice_number: Option<String>,
+    /// Sum of the credit notes issued against this invoice, excluding void ones
+    pub amount_credited: f64,
+    pub voided_at: Option<String>,
+    /// Date the invoice became Paid: its last payment date. `None` while
+    /// unpaid, and for invoices marked paid before payments were recorded.
+    pub paid_date: Option<String>,
+    /// Grouping used for session lines, including ones added later
+    pub line_grouping: LineGrouping,
+    /// Append the per-session breakdown when rendering
+    pub include_timesheet: bool,
+    /// Quote this invoice was converted from
+    pub quote_id: Option<i64>,
+    pub quote_number: Option<String>,
     pub items: Vec<InvoiceItem>,
+    /// Tax summary, one line per tax applied
+    pub taxes: Vec<InvoiceTax>,
+    pub payments: Vec<InvoicePayment>,
+    /// Sessions billed on this invoice; only loaded with `include_timesheet`
+    pub timesheet: Vec<TimesheetEntry>,
+}
+
+impl Invoice {
+    pub fn is_credit_note(&self) -> bool {
+        self.credited_invoice_id.is_some()
+    }
+
+    /// Date the invoice was issued; for drafts (and invoices issued before
+    /// the date was recorded) the date it was created
+    pub fn invoice_date(&self) -> &str {
+        self.issued_date.as_deref().unwrap_or(&self.created_date)
+    }
 }
 
-/// Invoice line item (linked to session)
+/// Postal address and VAT ID of the invoiced client
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientAddress {
+    /// Street lines
+    pub address: Option<String>,
+    pub city: Option<String>,
+    pub postal_code: Option<String>,
+    /// ISO 3166-1 alpha-2, e.g. "FR"
+    pub country_code: Option<String>,
+    pub tax_id: Option<String>,
+}
+
+impl ClientAddress {
+    pub fn is_empty(&self) -> bool {
+        *self == ClientAddress::default()
+    }
+}
+
+/// Payment received against an invoice
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct InvoiceItem {
+pub struct InvoicePayment {
     pub id: i64,
     pub invoice_id: i64,
-    pub session_id: Option<i64>,
-    pub description: String,
-    pub hours: f64,
-    pub rate: f64,
+    pub payment_date: String,
     pub amount: f64,
+    /// e.g. "Bank transfer", "Card", "Cash"
+    pub method: Option<String>,
+    pub reference: Option<String>,
+    pub notes: Option<String>,
+    /// Bank statement line this payment was reconciled from
+    pub bank_transaction_id: Option<i64>,
 }
 
-/// DTO for creating new invoice
+/// DTO for issuing a credit note against an invoice
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NewInvoice {
-    pub client_name: String,
-    pub client_email: Option<String>,
-    pub due_date: String,
-    pub tax_rate: Option<f64>,
+pub struct NewCreditNote {
+    /// Lines to credit; empty credits everything not credited yet
+    #[serde(default)]
+    pub lines: Vec<CreditedLine>,
+    /// Reason printed on the credit note
     pub notes: Option<String>,
-    pub session_ids: Vec<i64>,
+    /// Make fully credited sessions billable again
+    #[serde(default)]
+    pub release_sessions: bool,
 }
 
-// ========== AUTO-TRACKING ==========
-
-/// Detected activity suggestion
+/// Quantity of one invoice line to credit
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ActivitySuggestion {
-    pub id: i64,
-    pub date: String,
-    pub app_name: String,
-    pub window_title: String,
-    pub suggested_project: Option<String>,
-    pub suggested_session_type_id: Option<i64>,
-    pub duration_minutes: f64,
-    pub start_time: String,
-    pub end_time: String,
-    pub status: String, // "pending", "accepted", "dismissed"
+pub struct CreditedLine {
+    pub item_id: i64,
+    pub quantity: f64,
 }
 
-/// Activity tracking rule
+impl NewCreditNote {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.lines.iter().any(|l| !l.quantity.is_finite() || l.quantity <= 0.0) {
+            return Err("Credited quantities must be greater than zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// DTO for recording a payment
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TrackingRule {
-    pub id: i64,
-    pub app_pattern: String,
-    pub title_pattern: Option<String>,
-    pub project_name: String,
-    pub session_type_id: i64,
-    pub is_active: bool,
+pub struct NewInvoicePayment {
+    pub payment_date: String,
+    pub amount: f64,
+    pub method: Option<String>,
+    pub reference: Option<String>,
+    pub notes: Option<String>,
 }
 
-// ========== HABITS ==========
+impl NewInvoicePayment {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.amount.is_finite() || self.amount <= 0.0 {
+            return Err("Payment amount must be greater than zero".to_string());
+        }
+        chrono::NaiveDate::parse_from_str(&self.payment_date, "%Y-%m-%d")
+            .map_err(|_| format!("Invalid payment date: {}", self.payment_date))?;
+        Ok(())
+    }
+}
 
-/// Habit definition
+/// How a tax affects the invoice total
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TaxKind {
+    /// Added on top of the net amount (e.g. VAT on exclusive prices)
+    Additive,
+    /// Deducted from the total (e.g. income tax withheld by the client)
+    Withholding,
+    /// Already contained in the line prices
+    Inclusive,
+}
+
+impl fmt::Display for TaxKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TaxKind::Additive => "Additive",
+            TaxKind::Withholding => "Withholding",
+            TaxKind::Inclusive => "Inclusive",
+        })
+    }
+}
+
+impl TaxKind {
+    pub fn from_string(s: &str) -> Result<Self, String> {
+        match s {
+            "Additive" => Ok(TaxKind::Additive),
+            "Withholding" => Ok(TaxKind::Withholding),
+            "Inclusive" => Ok(TaxKind::Inclusive),
+            _ => Err(format!("Invalid tax kind: {}", s)),
+        }
+    }
+}
+
+/// Named tax rate. Additive and inclusive taxes apply to taxable lines with the
+/// same `category` (no category matches uncategorized lines); withholding
+/// applies to every taxable line. `note` is printed on the invoice, e.g. a
+/// reverse-charge statement.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Habit {
+pub struct TaxRate {
     pub id: i64,
     pub name: String,
-    pub description: Option<String>,
-    pub trigger_type: String,       // "after_session", "after_hours", "daily"
-    pub trigger_value: f64,         // hours threshold or session count
-    pub reward_description: String,
-    pub is_active: bool,
-    // Computed fields
-    pub current_streak: i32,
-    pub best_streak: i32,
-    pub total_completions: i32,
-    pub target_streak: i32,
-    pub trigger_minutes: Option<i32>,
+    /// Percentage, e.g. 20.0 for 20%
+    pub rate: f64,
+    pub kind: TaxKind,
+    pub category: Option<String>,
+    pub note: Option<String>,
 }
 
-/// Habit completion log
+/// DTO for creating or updating a tax rate
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct HabitLog {
-    pub id: i64,
-    pub habit_id: i64,
-    pub completed_at: String,
-    pub notes: Option<String>,
+pub struct NewTaxRate {
+    pub name: String,
+    pub rate: f64,
+    pub kind: TaxKind,
+    pub category: Option<String>,
+    pub note: Option<String>,
 }
 
-// ========== AI ADVISOR ==========
-
-/// Financial insight from AI analysis
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FinancialInsight {
-    pub category: String,           // "income", "expenses", "goals", "sustainability"
-    pub severity: String,           // "info", "warning", "critical", "success"
-    pub title: String,
-    pub message: String,
-    pub action: Option<String>,
+impl NewTaxRate {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Tax name cannot be empty".to_string());
+        }
+        if !self.rate.is_finite() || self.rate < 0.0 || self.rate > 100.0 {
+            return Err("Tax rate must be between 0 and 100".to_string());
+        }
+        Ok(())
+    }
 }
 
-/// Comprehensive financial analysis
+/// Tax applied to an invoice: a snapshot of the rate at the time it was
+/// applied, plus the computed base and amount
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FinancialAnalysis {
-    pub avg_weekly_hours: f64,
-    pub avg_weekly_income: f64,
-    pub projected_monthly_income: f64,
-    pub projected_yearly_income: f64,
-    pub income_trend: String,       // "increasing", "stable", "decreasing"
-    pub insights: Vec<FinancialInsight>,
-    pub recommendations: Vec<String>,
+pub struct InvoiceTax {
+    pub id: i64,
+    pub invoice_id: i64,
+    pub tax_rate_id: Option<i64>,
+    pub name: String,
+    pub rate: f64,
+    pub kind: TaxKind,
+    pub category: Option<String>,
+    pub note: Option<String>,
+    pub base: f64,
+    pub amount: f64,
 }
 
-// ========== LICENSE & TIERS ==========
-
-/// User tier (Free, Pro, Lifetime)
+/// Where an invoice line came from
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-pub enum Tier {
-    Free,
-    Pro,
-    Lifetime,
+pub enum InvoiceItemKind {
+    Session,
+    Manual,
+    Expense,
 }
 
-impl Tier {
-    pub fn to_string(&self) -> String {
-        match self {
-            Tier::Free => "Free".to_string(),
-            Tier::Pro => "Pro".to_string(),
-            Tier::Lifetime => "Lifetime".to_string(),
+impl fmt::Display for InvoiceItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            InvoiceItemKind::Session => "Session",
+            InvoiceItemKind::Manual => "Manual",
+            InvoiceItemKind::Expense => "Expense",
+        })
+    }
+}
+
+impl InvoiceItemKind {
+    pub fn from_string(s: &str) -> Result<Self, String> {
+        match s {
+            "Session" => Ok(InvoiceItemKind::Session),
+            "Manual" => Ok(InvoiceItemKind::Manual),
+            "Expense" => Ok(InvoiceItemKind::Expense),
+            _ => Err(format!("Invalid invoice item kind: {}", s)),
         }
     }
+}
 
-    pub fn from_string(s: &str) -> Self {
+/// How sessions are turned into invoice lines. Sessions in a group are
+/// merged per rate, so one group can still yield several lines; `Summary`
+/// always yields one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LineGrouping {
+    /// One line per session, "Type - Project"
+    #[default]
+    PerSession,
+    PerProject,
+    PerDay,
+    PerSessionType,
+    /// A single "Services" line for the whole period, billed as one unit
+    Summary,
+}
+
+impl fmt::Display for LineGrouping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            LineGrouping::PerSession => "PerSession",
+            LineGrouping::PerProject => "PerProject",
+            LineGrouping::PerDay => "PerDay",
+            LineGrouping::PerSessionType => "PerSessionType",
+            LineGrouping::Summary => "Summary",
+        })
+    }
+}
+
+impl LineGrouping {
+    pub fn from_string(s: &str) -> Result<Self, String> {
         match s {
-            "Pro" => Tier::Pro,
-            "Lifetime" => Tier::Lifetime,
-            _ => Tier::Free,
+            "PerSession" => Ok(LineGrouping::PerSession),
+            "PerProject" => Ok(LineGrouping::PerProject),
+            "PerDay" => Ok(LineGrouping::PerDay),
+            "PerSessionType" => Ok(LineGrouping::PerSessionType),
+            "Summary" => Ok(LineGrouping::Summary),
+            _ => Err(format!("Invalid line grouping: {}", s)),
         }
     }
 }
 
-/// License information stored locally
+/// One session in an invoice's timesheet appendix
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct License {
-    pub tier: Tier,
-    pub license_key: Option<String>,
-    pub activated_at: Option<String>,
-    pub expires_at: Option<String>,
+pub struct TimesheetEntry {
+    pub session_id: i64,
+    pub date: String,
+    pub session_type: Option<String>,
+    pub project_name: String,
+    pub description: Option<String>,
+    pub hours: f64,
 }
 
-impl Default for License {
-    fn default() -> Self {
-        License {
-            tier: Tier::Free,
-            license_key: None,
-            activated_at: None,
-            expires_at: None,
+/// Discount type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiscountType {
+    Percent,
+    Fixed,
+}
+
+impl fmt::Display for DiscountType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            DiscountType::Percent => "Percent",
+            DiscountType::Fixed => "Fixed",
+        })
+    }
+}
+
+impl DiscountType {
+    pub fn from_string(s: &str) -> Result<Self, String> {
+        match s {
+            "Percent" => Ok(DiscountType::Percent),
+            "Fixed" => Ok(DiscountType::Fixed),
+            _ => Err(format!("Invalid discount type: {}", s)),
         }
     }
 }
 
-/// Feature limits based on tier
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FeatureLimits {
-    pub max_session_types: Option<u32>,  // None = unlimited
-    pub max_goals: Option<u32>,          // None = unlimited
-    pub analytics_days: Option<u32>,     // None = unlimited, Some(7) = 7 days
-    pub has_invoices: bool,
-    pub has_ai_advisor: bool,
-    pub has_voice_input: bool,
-    pub has_simulator: bool,
-    pub has_pdf_export: bool,
+/// Discount on a line or a whole invoice (`value` is a percentage or an amount)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Discount {
+    pub discount_type: DiscountType,
+    pub value: f64,
 }
 
-impl FeatureLimits {
-    pub fn for_tier(tier: Tier) -> Self {
-        match tier {
-            Tier::Free => FeatureLimits {
-                max_session_types: Some(2),
-                max_goals: Some(3),
-                analytics_days: Some(7),
-                has_invoices: false,
-                has_ai_advisor: true,  // Free gets 10/day, Pro gets 100/day
-                has_voice_input: false,
-                has_simulator: false,
-                has_pdf_export: false,
-            },
-            Tier::Pro | Tier::Lifetime => FeatureLimits {
-                max_session_types: None,
-                max_goals: None,
-                analytics_days: None,
-                has_invoices: true,
-                has_ai_advisor: true,
-                has_voice_input: true,
-                has_simulator: true,
-                has_pdf_export: true,
-            },
+impl Discount {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.value.is_finite() || self.value < 0.0 {
+            return Err("Discount cannot be negative".to_string());
         }
+        if self.discount_type == DiscountType::Percent && self.value > 100.0 {
+            return Err("Percentage discount cannot exceed 100".to_string());
+        }
+        Ok(())
+    }
+
+    /// Discount taken off `base`, never more than `base` itself
+    pub fn amount_of(&self, base: f64) -> f64 {
+        let amount = match self.discount_type {
+            DiscountType::Percent => base * self.value / 100.0,
+            DiscountType::Fixed => self.value,
+        };
+        amount.clamp(0.0, base.max(0.0))
+    }
+}
+
+/// Invoice line item. For session lines `hours`/`rate` are the session's
+/// hours and hourly rate; for manual and expense lines they are the
+/// quantity and unit price. `amount` is net of the line discount.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceItem {
+    pub id: i64,
+    pub invoice_id: i64,
+    /// The session, when the line bills exactly one
+    pub session_id: Option<i64>,
+    /// Every session billed on the line
+    pub session_ids: Vec<i64>,
+    pub kind: InvoiceItemKind,
+    pub description: String,
+    pub hours: f64,
+    pub unit: Option<String>,
+    pub rate: f64,
+    pub discount: Option<Discount>,
+    pub taxable: bool,
+    /// Selects which taxes apply (see `TaxRate`)
+    pub tax_category: Option<String>,
+    pub amount: f64,
+    /// On credit notes, the invoice line being credited
+    pub credited_item_id: Option<i64>,
+    /// The session was credited or its invoice voided; it can be billed again
+    pub session_released: bool,
+}
+
+/// Invoice numbering configuration (stored as JSON in settings).
+///
+/// Pattern tokens: `{YYYY}`, `{YY}`, `{MM}`, `{prefix}`, `{seq}` and `{seq:N}`
+/// (sequence zero-padded to N digits). A client prefix is prepended as
+/// `PREFIX-` when the pattern has no `{prefix}` token. Credit notes and
+/// quotes use their own patterns and sequences.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceNumbering {
+    pub pattern: String,
+    pub yearly_reset: bool,
+    #[serde(default)]
+    pub client_prefixes: std::collections::HashMap<String, String>,
+    #[serde(default = "default_credit_note_pattern")]
+    pub credit_note_pattern: String,
+    #[serde(default = "default_quote_pattern")]
+    pub quote_pattern: String,
+}
+
+fn default_credit_note_pattern() -> String {
+    "CN-{seq:4}".to_string()
+}
+
+fn default_quote_pattern() -> String {
+    "Q-{seq:4}".to_string()
+}
+
+impl Default for InvoiceNumbering {
+    fn default() -> Self {
+        InvoiceNumbering {
+            pattern: "INV-{seq:4}".to_string(),
+            yearly_reset: false,
+            client_prefixes: std::collections::HashMap::new(),
+            credit_note_pattern: default_credit_note_pattern(),
+            quote_pattern: default_quote_pattern(),
+        }
+    }
+}
+
+impl InvoiceNumbering {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.pattern.contains("{seq") {
+            return Err("Invoice number pattern must contain {seq}".to_string());
+        }
+        if !self.credit_note_pattern.contains("{seq") {
+            return Err("Credit note number pattern must contain {seq}".to_string());
+        }
+        if !self.quote_pattern.contains("{seq") {
+            return Err("Quote number pattern must contain {seq}".to_string());
+        }
+        if self.credit_note_pattern == self.pattern {
+            return Err("Credit notes need a number pattern different from invoices".to_string());
+        }
+        if self.quote_pattern == self.pattern || self.quote_pattern == self.credit_note_pattern {
+            return Err("Quotes need a number pattern different from invoices and credit notes".to_string());
+        }
+        // A sequence restarting each January would repeat numbers without the year in them
+        let has_year = |pattern: &str| pattern.contains("{YYYY}") || pattern.contains("{YY}");
+        if self.yearly_reset && !(has_year(&self.pattern) && has_year(&self.credit_note_pattern) && has_year(&self.quote_pattern)) {
+            return Err("Number patterns must contain {YYYY} or {YY} when numbering restarts every year".to_string());
+        }
+        crate::core::logic::format_invoice_number(&self.pattern, "", "2000-01-01", 1)?;
+        crate::core::logic::format_invoice_number(&self.credit_note_pattern, "", "2000-01-01", 1)?;
+        crate::core::logic::format_invoice_number(&self.quote_pattern, "", "2000-01-01", 1)?;
+        Ok(())
+    }
+
+    /// Sequence counter used for an invoice created on `date`
+    pub fn sequence_scope(&self, date: &str) -> String {
+        self.scope("invoice", date)
+    }
+
+    /// Sequence counter used for a credit note created on `date`
+    pub fn credit_note_scope(&self, date: &str) -> String {
+        self.scope("credit_note", date)
+    }
+
+    /// Sequence counter used for a quote created on `date`
+    pub fn quote_scope(&self, date: &str) -> String {
+        self.scope("quote", date)
+    }
+
+    fn scope(&self, name: &str, date: &str) -> String {
+        if self.yearly_reset {
+            format!("{}:{}", name, date.get(0..4).unwrap_or("0000"))
+        } else {
+            name.to_string()
+        }
+    }
+
+    pub fn prefix_for(&self, client_name: &str) -> &str {
+        self.client_prefixes.get(client_name.trim()).map(|p| p.as_str()).unwrap_or("")
+    }
+}
+
+/// Sender details printed on invoices. Several profiles can exist for people
+/// running more than one business; exactly one is the default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BusinessProfile {
+    pub id: i64,
+    /// Label shown in the app, e.g. "Consulting"
+    pub name: String,
+    pub business_name: String,
+    /// Street lines
+    pub address: Option<String>,
+    pub city: Option<String>,
+    pub postal_code: Option<String>,
+    /// ISO 3166-1 alpha-2, e.g. "DE"
+    pub country_code: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    /// VAT number, EIN, ...
+    pub tax_id: Option<String>,
+    /// Payment instructions, e.g. IBAN and BIC
+    pub bank_details: Option<String>,
+    /// PNG or JPEG file
+    pub logo_path: Option<String>,
+    /// ISO 4217 code invoices are issued in, e.g. "EUR"
+    pub currency: Option<String>,
+    pub is_default: bool,
+}
+
+/// DTO for creating or updating a business profile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewBusinessProfile {
+    pub name: String,
+    pub business_name: String,
+    pub address: Option<String>,
+    #[serde(default)]
+    pub city: Option<String>,
+    #[serde(default)]
+    pub postal_code: Option<String>,
+    #[serde(default)]
+    pub country_code: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub tax_id: Option<String>,
+    pub bank_details: Option<String>,
+    pub logo_path: Option<String>,
+    #[serde(default)]
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub is_default: bool,
+}
+
+impl NewBusinessProfile {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Profile name cannot be empty".to_string());
+        }
+        if self.business_name.trim().is_empty() {
+            return Err("Business name cannot be empty".to_string());
+        }
+        if let Some(code) = &self.country_code {
+            if code.len() != 2 || !code.chars().all(|c| c.is_ascii_uppercase()) {
+                return Err("Country must be a two-letter ISO code, e.g. DE".to_string());
+            }
+        }
+        if let Some(code) = &self.currency {
+            if code.len() != 3 || !code.chars().all(|c| c.is_ascii_uppercase()) {
+                return Err("Currency must be a three-letter ISO code, e.g. EUR".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Handlebars template used to render an invoice as HTML.
+/// The built-in template has id 0 and can't be edited, only copied.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceTemplate {
+    pub id: i64,
+    pub name: String,
+    pub body: String,
+    pub is_builtin: bool,
+}
+
+/// DTO for creating or updating an invoice template
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewInvoiceTemplate {
+    pub name: String,
+    pub body: String,
+}
+
+/// DTO for creating new invoice
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewInvoice {
+    pub client_name: String,
+    pub client_email: Option<String>,
+    /// Defaults to the address on the client's latest invoice
+    #[serde(default)]
+    pub client_address: ClientAddress,
+    pub due_date: String,
+    pub tax_rate: Option<f64>,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub session_ids: Vec<i64>,
+    /// Manual and expense lines added alongside the sessions
+    #[serde(default)]
+    pub items: Vec<NewInvoiceItem>,
+    #[serde(default)]
+    pub discount: Option<Discount>,
+    /// Named tax rates to apply, in addition to the ad-hoc `tax_rate`
+    #[serde(default)]
+    pub tax_rate_ids: Vec<i64>,
+    /// Sender profile; defaults to the default profile
+    #[serde(default)]
+    pub profile_id: Option<i64>,
+    #[serde(default)]
+    pub template_id: Option<i64>,
+    #[serde(default)]
+    pub line_grouping: LineGrouping,
+    #[serde(default)]
+    pub include_timesheet: bool,
+}
+
+/// Which sessions `create_invoice_from_criteria` bills: every uninvoiced
+/// session with a pay type that matches. Empty lists and `None` mean "any".
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InvoiceCriteria {
+    pub project_names: Vec<String>,
+    pub session_type_ids: Vec<i64>,
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+}
+
+impl InvoiceCriteria {
+    pub fn to_session_filter(&self) -> SessionFilter {
+        SessionFilter {
+            date_from: self.date_from.clone(),
+            date_to: self.date_to.clone(),
+            session_type_ids: self.session_type_ids.clone(),
+            project_names: self.project_names.clone(),
+            pay_types: vec![PayType::Hourly, PayType::Fixed],
+            invoiced: Some(false),
+            ..Default::default()
+        }
+    }
+}
+
+/// Invoice computed from criteria but not saved, with the sessions it bills
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoicePreview {
+    /// As it would be created; its ids and number are not reserved
+    pub invoice: Invoice,
+    pub sessions: Vec<WorkSession>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// DTO for a manual or expense line item
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewInvoiceItem {
+    pub kind: InvoiceItemKind,
+    pub description: String,
+    /// Quantity
+    pub hours: f64,
+    pub unit: Option<String>,
+    /// Unit price
+    pub rate: f64,
+    #[serde(default)]
+    pub discount: Option<Discount>,
+    #[serde(default = "default_true")]
+    pub taxable: bool,
+    #[serde(default)]
+    pub tax_category: Option<String>,
+}
+
+impl NewInvoiceItem {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.kind == InvoiceItemKind::Session {
+            return Err("Session line items are created from sessions".to_string());
+        }
+        if self.description.trim().is_empty() {
+            return Err("Line item description cannot be empty".to_string());
+        }
+        if !self.hours.is_finite() || !self.rate.is_finite() {
+            return Err("Line item quantity and price must be numbers".to_string());
+        }
+        if self.hours < 0.0 || self.rate < 0.0 {
+            return Err("Line item quantity and price cannot be negative; use a discount or a credit note".to_string());
+        }
+        if let Some(discount) = &self.discount {
+            discount.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// DTO for editing the header fields of a draft invoice
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceDraftUpdate {
+    pub client_name: String,
+    pub client_email: Option<String>,
+    #[serde(default)]
+    pub client_address: ClientAddress,
+    pub due_date: String,
+    pub tax_rate: Option<f64>,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub discount: Option<Discount>,
+    #[serde(default)]
+    pub tax_rate_ids: Vec<i64>,
+    #[serde(default)]
+    pub profile_id: Option<i64>,
+    #[serde(default)]
+    pub template_id: Option<i64>,
+    #[serde(default)]
+    pub include_timesheet: bool,
+}
+
+/// DTO for editing a line item on a draft invoice; the amount is recomputed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceItemUpdate {
+    pub id: i64,
+    pub description: String,
+    pub hours: f64,
+    pub unit: Option<String>,
+    pub rate: f64,
+    #[serde(default)]
+    pub discount: Option<Discount>,
+    #[serde(default = "default_true")]
+    pub taxable: bool,
+    #[serde(default)]
+    pub tax_category: Option<String>,
+}
+
+// ========== QUOTES ==========
+
+/// Quote status. Drafts are editable; a sent quote is accepted or rejected by
+/// the client, or expires once past its validity date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuoteStatus {
+    Draft,
+    Sent,
+    Accepted,
+    Rejected,
+    Expired,
+}
+
+impl fmt::Display for QuoteStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            QuoteStatus::Draft => "Draft",
+            QuoteStatus::Sent => "Sent",
+            QuoteStatus::Accepted => "Accepted",
+            QuoteStatus::Rejected => "Rejected",
+            QuoteStatus::Expired => "Expired",
+        })
+    }
+}
+
+impl QuoteStatus {
+    pub fn from_string(s: &str) -> Result<Self, String> {
+        match s {
+            "Draft" => Ok(QuoteStatus::Draft),
+            "Sent" => Ok(QuoteStatus::Sent),
+            "Accepted" => Ok(QuoteStatus::Accepted),
+            "Rejected" => Ok(QuoteStatus::Rejected),
+            "Expired" => Ok(QuoteStatus::Expired),
+            _ => Err(format!("Invalid quote status: {}", s)),
+        }
+    }
+
+    /// Whether a quote may be moved from this status to `next`. A client can
+    /// still accept or reject a quote after it expired.
+    pub fn can_become(&self, next: QuoteStatus) -> bool {
+        use QuoteStatus::*;
+        matches!(
+            (self, next),
+            (Draft, Sent) | (Sent, Expired) | (Sent | Expired, Accepted) | (Sent | Expired, Rejected)
+        )
+    }
+}
+
+/// Estimate sent before work starts, priced like an invoice. Lines and taxes
+/// use the invoice types; on a quote their `invoice_id` is the quote's id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Quote {
+    pub id: i64,
+    pub quote_number: String,
+    pub client_name: String,
+    pub client_email: Option<String>,
+    pub created_date: String,
+    /// Last day the offer stands; Sent quotes expire after it
+    pub valid_until: String,
+    pub status: QuoteStatus,
+    pub subtotal: f64,
+    pub discount: Option<Discount>,
+    pub discount_amount: f64,
+    pub tax_amount: f64,
+    pub withholding_amount: f64,
+    pub total: f64,
+    pub notes: Option<String>,
+    pub profile_id: Option<i64>,
+    /// Invoice the quote was converted into
+    pub invoice_id: Option<i64>,
+    pub invoice_number: Option<String>,
+    pub items: Vec<InvoiceItem>,
+    pub taxes: Vec<InvoiceTax>,
+}
+
+/// DTO for creating a quote
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewQuote {
+    pub client_name: String,
+    pub client_email: Option<String>,
+    pub valid_until: String,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub items: Vec<NewInvoiceItem>,
+    #[serde(default)]
+    pub discount: Option<Discount>,
+    #[serde(default)]
+    pub tax_rate_ids: Vec<i64>,
+    #[serde(default)]
+    pub profile_id: Option<i64>,
+}
+
+impl NewQuote {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_quote_header(&self.client_name, &self.valid_until, self.discount.as_ref())?;
+        for item in &self.items {
+            item.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// DTO for editing the header fields of a draft quote
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteDraftUpdate {
+    pub client_name: String,
+    pub client_email: Option<String>,
+    pub valid_until: String,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub discount: Option<Discount>,
+    #[serde(default)]
+    pub tax_rate_ids: Vec<i64>,
+    #[serde(default)]
+    pub profile_id: Option<i64>,
+}
+
+impl QuoteDraftUpdate {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_quote_header(&self.client_name, &self.valid_until, self.discount.as_ref())
+    }
+}
+
+fn validate_quote_header(client_name: &str, valid_until: &str, discount: Option<&Discount>) -> Result<(), String> {
+    if client_name.trim().is_empty() {
+        return Err("Client name cannot be empty".to_string());
+    }
+    chrono::NaiveDate::parse_from_str(valid_until, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid validity date: {}", valid_until))?;
+    if let Some(discount) = discount {
+        discount.validate()?;
+    }
+    Ok(())
+}
+
+// ========== RETAINERS ==========
+
+/// Length of a retainer's billing period
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RetainerPeriod {
+    Weekly,
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl fmt::Display for RetainerPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RetainerPeriod::Weekly => "Weekly",
+            RetainerPeriod::Monthly => "Monthly",
+            RetainerPeriod::Quarterly => "Quarterly",
+            RetainerPeriod::Yearly => "Yearly",
+        })
+    }
+}
+
+impl RetainerPeriod {
+    pub fn from_string(s: &str) -> Result<Self, String> {
+        match s {
+            "Weekly" => Ok(RetainerPeriod::Weekly),
+            "Monthly" => Ok(RetainerPeriod::Monthly),
+            "Quarterly" => Ok(RetainerPeriod::Quarterly),
+            "Yearly" => Ok(RetainerPeriod::Yearly),
+            _ => Err(format!("Invalid retainer period: {}", s)),
+        }
+    }
+}
+
+/// What happens to included hours left unused at the end of a period
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RolloverPolicy {
+    /// Unused hours are lost
+    Expire,
+    /// Unused hours carry into the next period, up to `rollover_cap`
+    Carry,
+}
+
+impl fmt::Display for RolloverPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RolloverPolicy::Expire => "Expire",
+            RolloverPolicy::Carry => "Carry",
+        })
+    }
+}
+
+impl RolloverPolicy {
+    pub fn from_string(s: &str) -> Result<Self, String> {
+        match s {
+            "Expire" => Ok(RolloverPolicy::Expire),
+            "Carry" => Ok(RolloverPolicy::Carry),
+            _ => Err(format!("Invalid rollover policy: {}", s)),
+        }
+    }
+}
+
+/// Recurring agreement with a client: a fixed fee per period covering some
+/// hours, with hours beyond those billed at the overage rate. A draft invoice
+/// is generated the day after each period ends.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Retainer {
+    pub id: i64,
+    pub client_name: String,
+    pub client_email: Option<String>,
+    /// Sessions on these projects count against the included hours
+    pub project_names: Vec<String>,
+    pub period: RetainerPeriod,
+    pub fee: f64,
+    pub included_hours: f64,
+    pub overage_rate: f64,
+    pub rollover: RolloverPolicy,
+    /// Most hours carried into a period; `None` for no limit
+    pub rollover_cap: Option<f64>,
+    /// First day of the first period; later periods start on the same day
+    pub start_date: String,
+    /// Last day covered; the final period is cut short at it
+    pub end_date: Option<String>,
+    /// Days from the invoice date to its due date
+    pub payment_terms_days: i64,
+    pub profile_id: Option<i64>,
+    pub tax_rate_ids: Vec<i64>,
+    /// Paused retainers generate no invoices
+    pub active: bool,
+}
+
+/// DTO for creating or updating a retainer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewRetainer {
+    pub client_name: String,
+    pub client_email: Option<String>,
+    pub project_names: Vec<String>,
+    pub period: RetainerPeriod,
+    pub fee: f64,
+    pub included_hours: f64,
+    pub overage_rate: f64,
+    pub rollover: RolloverPolicy,
+    #[serde(default)]
+    pub rollover_cap: Option<f64>,
+    pub start_date: String,
+    #[serde(default)]
+    pub end_date: Option<String>,
+    pub payment_terms_days: i64,
+    #[serde(default)]
+    pub profile_id: Option<i64>,
+    #[serde(default)]
+    pub tax_rate_ids: Vec<i64>,
+    #[serde(default = "default_true")]
+    pub active: bool,
+}
+
+impl NewRetainer {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.client_name.trim().is_empty() {
+            return Err("Client name cannot be empty".to_string());
+        }
+        if self.project_names.iter().all(|p| p.trim().is_empty()) {
+            return Err("Pick at least one project to track against the retainer".to_string());
+        }
+        let amounts = [self.fee, self.included_hours, self.overage_rate];
+        if amounts.iter().any(|a| !a.is_finite() || *a < 0.0) {
+            return Err("Fee, included hours and overage rate cannot be negative".to_string());
+        }
+        if self.rollover_cap.is_some_and(|c| !c.is_finite() || c < 0.0) {
+            return Err("Rollover cap cannot be negative".to_string());
+        }
+        if self.payment_terms_days < 0 {
+            return Err("Payment terms cannot be negative".to_string());
+        }
+        let start = chrono::NaiveDate::parse_from_str(&self.start_date, "%Y-%m-%d")
+            .map_err(|_| format!("Invalid start date: {}", self.start_date))?;
+        if let Some(end_date) = &self.end_date {
+            let end = chrono::NaiveDate::parse_from_str(end_date, "%Y-%m-%d")
+                .map_err(|_| format!("Invalid end date: {}", end_date))?;
+            if end < start {
+                return Err("End date cannot be before the start date".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of a retainer billing run. Each retainer is billed on its own, so
+/// one failing doesn't hold back the others.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetainerBilling {
+    pub invoices: Vec<Invoice>,
+    pub failures: Vec<RetainerBillingFailure>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetainerBillingFailure {
+    pub retainer_id: i64,
+    pub client_name: String,
+    pub error: String,
+}
+
+/// Hours used against a retainer in one period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetainerUsage {
+    pub retainer_id: i64,
+    pub period_start: String,
+    pub period_end: String,
+    pub included_hours: f64,
+    /// Unused hours carried in from the previous period
+    pub rollover_in: f64,
+    pub hours_used: f64,
+    pub overage_hours: f64,
+    pub rollover_out: f64,
+    /// Draft generated when the period closed
+    pub invoice_id: Option<i64>,
+    pub invoice_number: Option<String>,
+    /// The period still running: figures so far, nothing billed yet
+    pub is_current: bool,
+}
+
+// ========== EMAIL ==========
+
+/// How the SMTP connection is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SmtpSecurity {
+    /// Plain connection; only allowed to a server on this machine
+    None,
+    /// Plain connection upgraded with STARTTLS (usually port 587)
+    StartTls,
+    /// TLS from the first byte (usually port 465)
+    Tls,
+}
+
+/// Outgoing mail server and the templates for invoice emails.
+/// `password` is write-only: it is stored encrypted and never returned,
+/// `has_password` tells whether one is saved. Saving without a password
+/// keeps the stored one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub has_password: bool,
+    pub from_address: String,
+    #[serde(default)]
+    pub from_name: Option<String>,
+    #[serde(default = "default_email_subject")]
+    pub subject_template: String,
+    #[serde(default = "default_email_body")]
+    pub body_template: String,
+}
+
+fn default_email_subject() -> String {
+    "Invoice {{invoice.invoice_number}}{{#if profile}} from {{profile.business_name}}{{/if}}".to_string()
+}
+
+fn default_email_body() -> String {
+    "Hello {{invoice.client_name}},\n\n\
+     Please find attached invoice {{invoice.invoice_number}} for {{money invoice.total}}, \
+     due on {{invoice.due_date}}.\n\n\
+     Thank you,\n\
+     {{#if profile}}{{profile.business_name}}{{/if}}\n".to_string()
+}
+
+impl Default for SmtpSettings {
+    fn default() -> Self {
+        SmtpSettings {
+            host: String::new(),
+            port: 587,
+            security: SmtpSecurity::StartTls,
+            username: None,
+            password: None,
+            has_password: false,
+            from_address: String::new(),
+            from_name: None,
+            subject_template: default_email_subject(),
+            body_template: default_email_body(),
+        }
+    }
+}
+
+impl SmtpSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.host.trim().is_empty() {
+            return Err("SMTP host cannot be empty".to_string());
+        }
+        if self.port == 0 {
+            return Err("SMTP port cannot be 0".to_string());
+        }
+        if self.from_address.trim().is_empty() {
+            return Err("Sender address cannot be empty".to_string());
+        }
+        if self.subject_template.trim().is_empty() {
+            return Err("Email subject cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// SMTP login, kept in the encrypted settings store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// Something that happened to an invoice outside of status changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InvoiceEvent {
+    EmailSent,
+}
+
+impl fmt::Display for InvoiceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            InvoiceEvent::EmailSent => "EmailSent",
+        })
+    }
+}
+
+impl InvoiceEvent {
+    pub fn from_string(s: &str) -> Result<Self, String> {
+        match s {
+            "EmailSent" => Ok(InvoiceEvent::EmailSent),
+            _ => Err(format!("Invalid invoice event: {}", s)),
+        }
+    }
+}
+
+/// Entry in an invoice's history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceHistoryEntry {
+    pub id: i64,
+    pub invoice_id: i64,
+    pub event: InvoiceEvent,
+    pub recipient: Option<String>,
+    /// Free text, e.g. the subject of a sent email
+    pub details: Option<String>,
+    pub created_at: String,
+}
+
+// ========== PAYMENT REMINDERS ==========
+
+/// How a payment reminder reaches its target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReminderChannel {
+    /// Notify the user, who chases the client themselves
+    Notification,
+    /// Email the client the invoice again through the SMTP settings
+    Email,
+}
+
+impl fmt::Display for ReminderChannel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ReminderChannel::Notification => "Notification",
+            ReminderChannel::Email => "Email",
+        })
+    }
+}
+
+impl ReminderChannel {
+    pub fn from_string(s: &str) -> Result<Self, String> {
+        match s {
+            "Notification" => Ok(ReminderChannel::Notification),
+            "Email" => Ok(ReminderChannel::Email),
+            _ => Err(format!("Invalid reminder channel: {}", s)),
+        }
+    }
+}
+
+/// When to remind about unpaid invoices. A schedule with a client name applies
+/// to that client's invoices (an inactive one turns reminders off for them);
+/// the schedule without one is the default for everybody else.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReminderSchedule {
+    pub id: i64,
+    pub client_name: Option<String>,
+    /// Days relative to the due date, ascending: -3 is three days before, 7 a week late
+    pub offsets: Vec<i64>,
+    pub channel: ReminderChannel,
+    pub subject_template: String,
+    pub body_template: String,
+    pub active: bool,
+}
+
+/// DTO for creating or editing a reminder schedule. Templates see `invoice`,
+/// `profile` and `days_overdue` (0 until the due date has passed).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewReminderSchedule {
+    #[serde(default)]
+    pub client_name: Option<String>,
+    pub offsets: Vec<i64>,
+    pub channel: ReminderChannel,
+    #[serde(default = "default_reminder_subject")]
+    pub subject_template: String,
+    #[serde(default = "default_reminder_body")]
+    pub body_template: String,
+    #[serde(default = "default_true")]
+    pub active: bool,
+}
+
+fn default_reminder_subject() -> String {
+    "Reminder: invoice {{invoice.invoice_number}} \
+     {{#if days_overdue}}is {{days_overdue}} days overdue{{else}}is due on {{invoice.due_date}}{{/if}}".to_string()
+}
+
+fn default_reminder_body() -> String {
+    "Hello {{invoice.client_name}},\n\n\
+     This is a friendly reminder that invoice {{invoice.invoice_number}} \
+     {{#if days_overdue}}was due on {{invoice.due_date}}{{else}}is due on {{invoice.due_date}}{{/if}}. \
+     The balance of {{money invoice.balance_due}} is still open. The invoice is attached again.\n\n\
+     If you have already paid, please disregard this message.\n\n\
+     Thank you,\n\
+     {{#if profile}}{{profile.business_name}}{{/if}}\n".to_string()
+}
+
+impl NewReminderSchedule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.client_name.as_ref().is_some_and(|c| c.trim().is_empty()) {
+            return Err("Client name cannot be blank; leave it out for the default schedule".to_string());
+        }
+        if self.offsets.is_empty() {
+            return Err("Add at least one reminder".to_string());
+        }
+        if self.offsets.iter().any(|o| o.abs() > 365) {
+            return Err("Reminders must be within a year of the due date".to_string());
+        }
+        if self.subject_template.trim().is_empty() {
+            return Err("Reminder subject cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A reminder that went out, logged per invoice
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceReminder {
+    pub id: i64,
+    pub invoice_id: i64,
+    pub schedule_id: Option<i64>,
+    pub offset_days: i64,
+    pub channel: ReminderChannel,
+    pub recipient: Option<String>,
+    pub sent_at: String,
+}
+
+/// Reminder step an unpaid invoice has reached and not been reminded of yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DueReminder {
+    pub invoice: Invoice,
+    pub schedule: ReminderSchedule,
+    pub offset_days: i64,
+}
+
+/// Outcome of a due reminder. `error` says why an email reminder didn't go
+/// out (no client email, no SMTP settings or a failed send); such reminders
+/// are not logged and are retried on the next run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentReminder {
+    pub invoice: Invoice,
+    pub offset_days: i64,
+    pub channel: ReminderChannel,
+    pub recipient: Option<String>,
+    pub error: Option<String>,
+}
+
+// ========== RECEIVABLES ==========
+
+/// Outstanding amounts by how far past the due date they are
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgingBuckets {
+    /// Not due yet, or due today
+    pub current: f64,
+    pub days_1_30: f64,
+    pub days_31_60: f64,
+    pub days_61_90: f64,
+    pub days_over_90: f64,
+    pub total: f64,
+}
+
+/// One client's outstanding balance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientReceivables {
+    pub client_name: String,
+    pub aging: AgingBuckets,
+    pub invoice_count: i64,
+    pub oldest_due_date: String,
+}
+
+/// Accounts receivable aging on `as_of`, largest balances first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceivablesReport {
+    pub as_of: String,
+    pub totals: AgingBuckets,
+    pub clients: Vec<ClientReceivables>,
+}
+
+/// How quickly a client pays, from invoices with a known paid date
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientPaymentStats {
+    pub client_name: String,
+    pub paid_count: i64,
+    /// Days from the invoice date to the paid date
+    pub avg_days_to_pay: f64,
+    /// Share of invoices paid on or before the due date, 0 to 1
+    pub on_time_rate: f64,
+    /// Average days past due, counting only late invoices
+    pub avg_days_late: f64,
+}
+
+// ========== REPORTING BASIS ==========
+
+/// When income is counted in reports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReportingBasis {
+    /// On the day a session is worked, at its pay
+    #[default]
+    Earned,
+    /// On the date the invoice is issued, net of discounts and tax; credit notes subtract
+    Invoiced,
+    /// On the payment date (cash basis): the net share of each payment
+    Received,
+}
+
+/// Income not yet in the bank
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IncomePipeline {
+    /// Pay of sessions not on an issued invoice (drafts count as not invoiced)
+    pub unbilled: f64,
+    pub unbilled_hours: f64,
+    /// Balance due on sent, partially paid and overdue invoices
+    pub invoiced_unpaid: f64,
+}
+
+// ========== BANK RECONCILIATION ==========
+
+/// Column layout of a bank's CSV export. Columns are numbered from 0.
+/// Banks either put signed amounts in one column or use separate credit
+/// and debit columns.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BankCsvFormat {
+    #[serde(default = "default_csv_delimiter")]
+    pub delimiter: char,
+    /// Rows to skip before the header or first transaction
+    #[serde(default)]
+    pub skip_rows: usize,
+    #[serde(default = "default_true")]
+    pub has_header: bool,
+    pub date_column: usize,
+    /// chrono format, e.g. "%Y-%m-%d" or "%d/%m/%Y"
+    pub date_format: String,
+    #[serde(default)]
+    pub amount_column: Option<usize>,
+    #[serde(default)]
+    pub credit_column: Option<usize>,
+    #[serde(default)]
+    pub debit_column: Option<usize>,
+    pub description_column: usize,
+    #[serde(default)]
+    pub reference_column: Option<usize>,
+    #[serde(default)]
+    pub counterparty_column: Option<usize>,
+    /// Amounts written as 1.234,56
+    #[serde(default)]
+    pub decimal_comma: bool,
+}
+
+fn default_csv_delimiter() -> char {
+    ','
+}
+
+impl BankCsvFormat {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.delimiter == '"' || self.delimiter == '\n' || self.delimiter == '\r' {
+            return Err("Invalid CSV delimiter".to_string());
+        }
+        if self.amount_column.is_none() && self.credit_column.is_none() {
+            return Err("Pick an amount column or a credit column".to_string());
+        }
+        if self.date_format.trim().is_empty() {
+            return Err("Date format cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Transaction read from a statement file, before it is stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewBankTransaction {
+    /// The bank's id (OFX FITID), or one derived from the line for CSV;
+    /// re-imports with the same id are skipped
+    pub external_id: String,
+    pub date: String,
+    /// Positive for money received
+    pub amount: f64,
+    pub description: String,
+    pub reference: Option<String>,
+    pub counterparty: Option<String>,
+}
+
+/// Imported bank statement line. Incoming money is reconciled by recording
+/// invoice payments against it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BankTransaction {
+    pub id: i64,
+    pub external_id: String,
+    pub date: String,
+    pub amount: f64,
+    pub description: String,
+    pub reference: Option<String>,
+    pub counterparty: Option<String>,
+    /// Not a client payment; hidden from suggestions
+    pub ignored: bool,
+    /// Sum of the invoice payments recorded from this transaction
+    pub allocated: f64,
+    /// Numbers of the invoices those payments went to
+    pub invoice_numbers: Vec<String>,
+    pub imported_at: String,
+}
+
+impl BankTransaction {
+    /// Part of the transaction not yet recorded as a payment
+    pub fn unallocated(&self) -> f64 {
+        crate::core::logic::round_currency(self.amount - self.allocated)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BankImportResult {
+    pub imported: i64,
+    /// Already imported from an earlier statement
+    pub duplicates: i64,
+    /// Outgoing payments, which never settle invoices
+    pub skipped_outgoing: i64,
+}
+
+/// Why a transaction looks like the payment of an invoice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchReason {
+    /// Amount equals the balance due
+    ExactAmount,
+    /// Amount is less than the balance due: a partial payment
+    PartialAmount,
+    /// Reference or description mentions the invoice number
+    InvoiceNumber,
+    /// Payer or description mentions the client
+    ClientName,
+}
+
+/// Suggested pairing of a bank transaction with an open invoice
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchSuggestion {
+    pub transaction_id: i64,
+    pub invoice_id: i64,
+    pub invoice_number: String,
+    pub client_name: String,
+    pub balance_due: f64,
+    /// Amount confirming the match would record
+    pub amount: f64,
+    /// Higher is more likely; an invoice number and exact amount score 100
+    pub score: u32,
+    pub reasons: Vec<MatchReason>,
+}
+
+// ========== ACCOUNTING EXPORT ==========
+
+/// Plain-text accounting journal syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JournalFormat {
+    /// ledger, also read by hledger
+    Ledger,
+    Beancount,
+}
+
+impl fmt::Display for JournalFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            JournalFormat::Ledger => "Ledger",
+            JournalFormat::Beancount => "Beancount",
+        })
+    }
+}
+
+impl JournalFormat {
+    pub fn from_string(s: &str) -> Result<Self, String> {
+        match s {
+            "Ledger" => Ok(JournalFormat::Ledger),
+            "Beancount" => Ok(JournalFormat::Beancount),
+            _ => Err(format!("Invalid journal format: {}", s)),
+        }
+    }
+}
+
+/// Journal entries written for an invoice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JournalEntryKind {
+    /// Revenue and receivable on the invoice date; credit notes reverse them
+    Issued,
+    /// Receivable cleared into the bank account once the invoice is Paid
+    Settled,
+    /// Reversal of an exported Issued entry after the invoice was voided
+    Voided,
+}
+
+impl fmt::Display for JournalEntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            JournalEntryKind::Issued => "Issued",
+            JournalEntryKind::Settled => "Settled",
+            JournalEntryKind::Voided => "Voided",
+        })
+    }
+}
+
+impl JournalEntryKind {
+    pub fn from_string(s: &str) -> Result<Self, String> {
+        match s {
+            "Issued" => Ok(JournalEntryKind::Issued),
+            "Settled" => Ok(JournalEntryKind::Settled),
+            "Voided" => Ok(JournalEntryKind::Voided),
+            _ => Err(format!("Invalid journal entry kind: {}", s)),
+        }
+    }
+}
+
+/// Account names used by the journal export. `{client}` in an account is
+/// replaced by the client name, e.g. "Assets:Receivable:{client}".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountingSettings {
+    pub receivable_account: String,
+    pub income_account: String,
+    /// Additive and inclusive tax collected
+    pub tax_account: String,
+    /// Tax withheld by clients, claimable later
+    pub withholding_account: String,
+    pub bank_account: String,
+    /// Commodity for invoices whose profile has no currency
+    pub default_currency: String,
+}
+
+impl Default for AccountingSettings {
+    fn default() -> Self {
+        AccountingSettings {
+            receivable_account: "Assets:Receivable:{client}".to_string(),
+            income_account: "Income:Consulting".to_string(),
+            tax_account: "Liabilities:Tax".to_string(),
+            withholding_account: "Assets:Tax:Withheld".to_string(),
+            bank_account: "Assets:Bank".to_string(),
+            default_currency: "USD".to_string(),
+        }
+    }
+}
+
+impl AccountingSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        let accounts = [
+            &self.receivable_account,
+            &self.income_account,
+            &self.tax_account,
+            &self.withholding_account,
+            &self.bank_account,
+        ];
+        for account in accounts {
+            if account.split(':').any(|part| part.trim().is_empty()) {
+                return Err(format!("Invalid account name: {}", account));
+            }
+        }
+        let currency = &self.default_currency;
+        if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err("Currency must be a three-letter ISO code, e.g. EUR".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of a journal export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalExportResult {
+    /// Transactions written; 0 when nothing changed since the last export
+    pub entries: usize,
+    /// Accounts opened (beancount)
+    pub accounts_opened: usize,
+}
+
+// ========== EXPENSES ==========
+
+/// How often an expense repeats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExpenseRecurrence {
+    #[default]
+    None,
+    Weekly,
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl fmt::Display for ExpenseRecurrence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ExpenseRecurrence::None => "None",
+            ExpenseRecurrence::Weekly => "Weekly",
+            ExpenseRecurrence::Monthly => "Monthly",
+            ExpenseRecurrence::Quarterly => "Quarterly",
+            ExpenseRecurrence::Yearly => "Yearly",
+        })
+    }
+}
+
+impl ExpenseRecurrence {
+    pub fn from_string(s: &str) -> Result<Self, String> {
+        match s {
+            "None" => Ok(ExpenseRecurrence::None),
+            "Weekly" => Ok(ExpenseRecurrence::Weekly),
+            "Monthly" => Ok(ExpenseRecurrence::Monthly),
+            "Quarterly" => Ok(ExpenseRecurrence::Quarterly),
+            "Yearly" => Ok(ExpenseRecurrence::Yearly),
+            _ => Err(format!("Invalid expense recurrence: {}", s)),
+        }
+    }
+
+    /// Occurrences per year; 0 for one-off expenses
+    pub fn per_year(&self) -> f64 {
+        match self {
+            ExpenseRecurrence::None => 0.0,
+            ExpenseRecurrence::Weekly => 52.0,
+            ExpenseRecurrence::Monthly => 12.0,
+            ExpenseRecurrence::Quarterly => 4.0,
+            ExpenseRecurrence::Yearly => 1.0,
+        }
+    }
+}
+
+/// Expense record. A recurring expense is due on `date` and then every
+/// period after it, up to `end_date` if set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Expense {
+    pub id: i64,
+    pub date: String,
+    pub amount: f64,
+    pub description: String,
+    pub category: String,
+    /// Business expenses reduce business income; personal ones only savings
+    pub is_business: bool,
+    pub client_name: Option<String>,
+    pub project_name: Option<String>,
+    pub recurrence: ExpenseRecurrence,
+    pub end_date: Option<String>,
+    /// Receipt files (PDF, image, ...)
+    pub receipt_paths: Vec<String>,
+    pub notes: Option<String>,
+    pub created_at: String,
+}
+
+/// DTO for creating or updating an expense
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewExpense {
+    pub date: String,
+    pub amount: f64,
+    pub description: String,
+    pub category: String,
+    #[serde(default = "default_true")]
+    pub is_business: bool,
+    #[serde(default)]
+    pub client_name: Option<String>,
+    #[serde(default)]
+    pub project_name: Option<String>,
+    #[serde(default)]
+    pub recurrence: ExpenseRecurrence,
+    #[serde(default)]
+    pub end_date: Option<String>,
+    #[serde(default)]
+    pub receipt_paths: Vec<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+impl NewExpense {
+    pub fn validate(&self) -> Result<(), String> {
+        if chrono::NaiveDate::parse_from_str(&self.date, "%Y-%m-%d").is_err() {
+            return Err("Expense date must be YYYY-MM-DD".to_string());
+        }
+        if !self.amount.is_finite() || self.amount <= 0.0 {
+            return Err("Expense amount must be greater than zero".to_string());
+        }
+        if self.description.trim().is_empty() {
+            return Err("Expense description cannot be empty".to_string());
+        }
+        if self.category.trim().is_empty() {
+            return Err("Expense category cannot be empty".to_string());
+        }
+        if let Some(end_date) = &self.end_date {
+            if self.recurrence == ExpenseRecurrence::None {
+                return Err("Only recurring expenses have an end date".to_string());
+            }
+            if chrono::NaiveDate::parse_from_str(end_date, "%Y-%m-%d").is_err() {
+                return Err("End date must be YYYY-MM-DD".to_string());
+            }
+            if end_date < &self.date {
+                return Err("End date cannot be before the expense date".to_string());
+            }
+        }
+        if self.receipt_paths.iter().any(|p| p.trim().is_empty()) {
+            return Err("Receipt path cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Expenses of one category in a period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpenseCategoryTotal {
+    pub category: String,
+    pub business: f64,
+    pub personal: f64,
+}
+
+/// Income, expenses and what is left, for one month
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonthlyNetIncome {
+    /// YYYY-MM
+    pub month: String,
+    pub income: f64,
+    pub business_expenses: f64,
+    pub personal_expenses: f64,
+    /// Income less business expenses
+    pub net_income: f64,
+}
+
+/// Income less expenses over a period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetIncomeReport {
+    pub start: String,
+    pub end: String,
+    pub basis: ReportingBasis,
+    pub income: f64,
+    pub business_expenses: f64,
+    pub personal_expenses: f64,
+    /// Income less business expenses
+    pub net_income: f64,
+    /// Net income less personal expenses
+    pub savings: f64,
+    /// Largest total first
+    pub by_category: Vec<ExpenseCategoryTotal>,
+    pub months: Vec<MonthlyNetIncome>,
+}
+
+// ========== AUTO-TRACKING ==========
+
+/// Detected activity suggestion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivitySuggestion {
+    pub id: i64,
+    pub date: String,
+    pub app_name: String,
+    pub window_title: String,
+    pub suggested_project: Option<String>,
+    pub suggested_session_type_id: Option<i64>,
+    pub duration_minutes: f64,
+    pub start_time: String,
+    pub end_time: String,
+    pub status: String, // "pending", "accepted", "dismissed"
+}
+
+/// Activity tracking rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackingRule {
+    pub id: i64,
+    pub app_pattern: String,
+    pub title_pattern: Option<String>,
+    pub project_name: String,
+    pub session_type_id: i64,
+    pub is_active: bool,
+}
+
+// ========== HABITS ==========
+
+/// Habit definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Habit {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub trigger_type: String,       // "after_session", "after_hours", "daily"
+    pub trigger_value: f64,         // hours threshold or session count
+    pub reward_description: String,
+    pub is_active: bool,
+    // Computed fields
+    pub current_streak: i32,
+    pub best_streak: i32,
+    pub total_completions: i32,
+    pub target_streak: i32,
+    pub trigger_minutes: Option<i32>,
+}
+
+/// Habit completion log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HabitLog {
+    pub id: i64,
+    pub habit_id: i64,
+    pub completed_at: String,
+    pub notes: Option<String>,
+}
+
+// ========== AI ADVISOR ==========
+
+/// Financial insight from AI analysis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinancialInsight {
+    pub category: String,           // "income", "expenses", "goals", "sustainability"
+    pub severity: String,           // "info", "warning", "critical", "success"
+    pub title: String,
+    pub message: String,
+    pub action: Option<String>,
+}
+
+/// Comprehensive financial analysis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinancialAnalysis {
+    pub avg_weekly_hours: f64,
+    pub avg_weekly_income: f64,
+    /// From the expense history, business and personal
+    pub avg_weekly_expenses: f64,
+    pub projected_monthly_income: f64,
+    pub projected_yearly_income: f64,
+    pub income_trend: String,       // "increasing", "stable", "decreasing"
+    pub basis: ReportingBasis,
+    pub pipeline: IncomePipeline,
+    pub insights: Vec<FinancialInsight>,
+    pub recommendations: Vec<String>,
+}
+
+// ========== LICENSE & TIERS ==========
+
+/// User tier (Free, Pro, Lifetime)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tier {
+    Free,
+    Pro,
+    Lifetime,
+}
+
+impl Tier {
+    pub fn to_string(&self) -> String {
+        match self {
+            Tier::Free => "Free".to_string(),
+            Tier::Pro => "Pro".to_string(),
+            Tier::Lifetime => "Lifetime".to_string(),
+        }
+    }
+
+    pub fn from_string(s: &str) -> Self {
+        match s {
+            "Pro" => Tier::Pro,
+            "Lifetime" => Tier::Lifetime,
+            _ => Tier::Free,
+        }
+    }
+}
+
+/// License information stored locally
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct License {
+    pub tier: Tier,
+    pub license_key: Option<String>,
+    pub activated_at: Option<String>,
+    pub expires_at: Option<String>,
+}
+
+impl Default for License {
+    fn default() -> Self {
+        License {
+            tier: Tier::Free,
+            license_key: None,
+            activated_at: None,
+            expires_at: None,
+        }
+    }
+}
+
+/// Feature limits based on tier
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeatureLimits {
+    pub max_session_types: Option<u32>,  // None = unlimited
+    pub max_goals: Option<u32>,          // None = unlimited
+    pub analytics_days: Option<u32>,     // None = unlimited, Some(7) = 7 days
+    pub has_invoices: bool,
+    pub has_ai_advisor: bool,
+    pub has_voice_input: bool,
+    pub has_simulator: bool,
+    pub has_pdf_export: bool,
+}
+
+impl FeatureLimits {
+    pub fn for_tier(tier: Tier) -> Self {
+        match tier {
+            Tier::Free => FeatureLimits {
+                max_session_types: Some(2),
+                max_goals: Some(3),
+                analytics_days: Some(7),
+                has_invoices: false,
+                has_ai_advisor: true,  // Free gets 10/day, Pro gets 100/day
+                has_voice_input: false,
+                has_simulator: false,
+                has_pdf_export: false,
+            },
+            Tier::Pro | Tier::Lifetime => FeatureLimits {
+                max_session_types: None,
+                max_goals: None,
+                analytics_days: None,
+                has_invoices: true,
+                has_ai_advisor: true,
+                has_voice_input: true,
+                has_simulator: true,
+                has_pdf_export: true,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yearly_reset_needs_year_in_every_pattern() {
+        let mut numbering = InvoiceNumbering { yearly_reset: true, ..InvoiceNumbering::default() };
+        assert!(numbering.validate().is_err());
+
+        numbering.pattern = "INV-{YYYY}-{seq:4}".to_string();
+        numbering.credit_note_pattern = "CN-{YY}-{seq:4}".to_string();
+        assert!(numbering.validate().is_err());
+
+        numbering.quote_pattern = "Q-{YYYY}-{seq:3}".to_string();
+        assert!(numbering.validate().is_ok());
+        assert_eq!(numbering.sequence_scope("2025-01-02"), "invoice:2025");
+
+        numbering.yearly_reset = false;
+        numbering.pattern = "INV-{seq:4}".to_string();
+        assert!(numbering.validate().is_ok());
+        assert_eq!(numbering.sequence_scope("2025-01-02"), "invoice");
+    }
+
+    #[test]
+    fn line_items_reject_negative_quantities_and_prices() {
+        let item = NewInvoiceItem {
+            kind: InvoiceItemKind::Manual,
+            description: "Setup".to_string(),
+            hours: 2.0,
+            unit: None,
+            rate: 50.0,
+            discount: None,
+            taxable: true,
+            tax_category: None,
+        };
+        assert!(item.validate().is_ok());
+        assert!(NewInvoiceItem { hours: -1.0, ..item.clone() }.validate().is_err());
+        assert!(NewInvoiceItem { rate: -50.0, ..item.clone() }.validate().is_err());
+        assert!(NewInvoiceItem { rate: f64::NAN, ..item }.validate().is_err());
     }
 }
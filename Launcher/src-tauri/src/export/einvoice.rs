@@ -0,0 +1,830 @@
+use crate::core::logic;
+use crate::core::models::*;
+
+/// EN 16931 specification identifier, used by both syntaxes
+const EN16931: &str = "urn:cen.eu:en16931:2017";
+/// UNTDID 1001 "Commercial invoice"
+const INVOICE_TYPE_CODE: &str = "380";
+
+const UBL_INVOICE_NS: &str = "urn:oasis:names:specification:ubl:schema:xsd:Invoice-2";
+const UBL_CAC_NS: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2";
+const UBL_CBC_NS: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2";
+
+const CII_RSM_NS: &str = "urn:un:unece:uncefact:data:standard:CrossIndustryInvoice:100";
+const CII_RAM_NS: &str = "urn:un:unece:uncefact:data:standard:ReusableAggregateBusinessInformationEntity:100";
+const CII_UDT_NS: &str = "urn:un:unece:uncefact:data:standard:UnqualifiedDataType:100";
+const CII_QDT_NS: &str = "urn:un:unece:uncefact:data:standard:QualifiedDataType:100";
+
+/// VAT category of a line (UNTDID 5305 code) and its rate
+#[derive(Debug, Clone, PartialEq)]
+struct VatCategory {
+    code: &'static str,
+    rate: f64,
+    exemption_reason: Option<String>,
+}
+
+struct Line<'a> {
+    item: &'a InvoiceItem,
+    vat: VatCategory,
+    /// UN/ECE Recommendation 20 unit code
+    unit_code: &'static str,
+    /// Line discount: quantity x price - line amount
+    allowance: f64,
+}
+
+/// VAT breakdown entry, one per category and rate
+struct VatGroup {
+    vat: VatCategory,
+    line_total: f64,
+    /// Share of the invoice discount
+    allowance: f64,
+    tax: f64,
+}
+
+/// Postal address of the seller or buyer
+struct Address<'a> {
+    street: Vec<&'a str>,
+    city: Option<&'a str>,
+    postal_code: Option<&'a str>,
+    /// ISO 3166-1 alpha-2
+    country_code: &'a str,
+}
+
+impl<'a> Address<'a> {
+    fn new(street: Option<&'a str>, city: Option<&'a str>, postal_code: Option<&'a str>, country_code: &'a str) -> Self {
+        Address {
+            street: street
+                .map(|a| a.lines().map(str::trim).filter(|l| !l.is_empty()).collect())
+                .unwrap_or_default(),
+            city,
+            postal_code,
+            country_code,
+        }
+    }
+}
+
+/// An invoice mapped onto the EN 16931 model, shared by the UBL and CII writers.
+/// Amounts come from the stored invoice; nothing is recomputed except the
+/// split of the invoice discount across VAT groups.
+struct Document<'a> {
+    invoice: &'a Invoice,
+    seller: &'a BusinessProfile,
+    seller_address: Address<'a>,
+    buyer_address: Address<'a>,
+    currency: &'a str,
+    lines: Vec<Line<'a>>,
+    groups: Vec<VatGroup>,
+}
+
+impl<'a> Document<'a> {
+    /// Map the invoice, or explain why it can't be expressed in EN 16931.
+    ///
+    /// Additive taxes are VAT: a positive rate is category S, a zero rate is Z,
+    /// or AE (reverse charge) when the tax carries a note, which becomes the
+    /// exemption reason. Lines without VAT are exempt (E). Inclusive and
+    /// withholding taxes have no EN 16931 equivalent and are rejected.
+    ///
+    /// Every category needs the seller's VAT ID (BR-S-2, BR-Z-2, BR-E-2,
+    /// BR-AE-2), reverse charge also the buyer's, and both parties need a
+    /// country (BR-9, BR-11).
+    fn new(invoice: &'a Invoice, seller: &'a BusinessProfile) -> Result<Self, String> {
+        let country_code = seller.country_code.as_deref()
+            .ok_or("Set a country on the business profile to export e-invoices")?;
+        let currency = seller.currency.as_deref()
+            .ok_or("Set a currency on the business profile to export e-invoices")?;
+        if seller.tax_id.is_none() {
+            return Err("Set a VAT ID on the business profile to export e-invoices".to_string());
+        }
+        let buyer = &invoice.client_address;
+        let buyer_country_code = buyer.country_code.as_deref()
+            .ok_or("Set the client's country on the invoice to export e-invoices")?;
+        if invoice.is_credit_note() {
+            return Err("Credit notes can't be exported as e-invoices yet".to_string());
+        }
+        if let Some(tax) = invoice.taxes.iter().find(|t| t.kind != TaxKind::Additive) {
+            return Err(format!(
+                "{} is a {} tax, which e-invoices can't express",
+                tax.name,
+                tax.kind.to_string().to_lowercase()
+            ));
+        }
+
+        let mut lines = Vec::with_capacity(invoice.items.len());
+        for item in &invoice.items {
+            let mut taxes = invoice.taxes.iter().filter(|t| logic::tax_applies(t, item));
+            let vat = match (taxes.next(), taxes.next()) {
+                (None, _) => VatCategory {
+                    code: "E",
+                    rate: 0.0,
+                    exemption_reason: Some("Exempt from VAT".to_string()),
+                },
+                (Some(tax), None) if tax.rate > 0.0 => VatCategory { code: "S", rate: tax.rate, exemption_reason: None },
+                (Some(tax), None) => match &tax.note {
+                    Some(note) => VatCategory { code: "AE", rate: 0.0, exemption_reason: Some(note.clone()) },
+                    None => VatCategory { code: "Z", rate: 0.0, exemption_reason: None },
+                },
+                (Some(_), Some(_)) => {
+                    return Err(format!("Line \"{}\" has more than one VAT rate", item.description));
+                }
+            };
+            lines.push(Line {
+                item,
+                vat,
+                unit_code: unit_code(item.unit.as_deref()),
+                allowance: logic::round_currency(item.hours * item.rate - item.amount),
+            });
+        }
+        if buyer.tax_id.is_none() && lines.iter().any(|l| l.vat.code == "AE") {
+            return Err("Set the client's VAT ID on the invoice to export a reverse charge invoice".to_string());
+        }
+
+        // One group per category and rate, in line order
+        let mut groups: Vec<VatGroup> = Vec::new();
+        for line in &lines {
+            match groups.iter_mut().find(|g| g.vat == line.vat) {
+                Some(group) => group.line_total += line.item.amount,
+                None => groups.push(VatGroup { vat: line.vat.clone(), line_total: line.item.amount, allowance: 0.0, tax: 0.0 }),
+            }
+        }
+        for tax in &invoice.taxes {
+            let group = groups.iter_mut()
+                .find(|g| g.vat.rate == tax.rate && g.vat.code != "E");
+            if let Some(group) = group {
+                group.tax += tax.amount;
+            }
+        }
+        // Spread the invoice discount proportionally; the last group takes the rounding
+        let mut remaining = invoice.discount_amount;
+        let count = groups.len();
+        for (i, group) in groups.iter_mut().enumerate() {
+            group.line_total = logic::round_currency(group.line_total);
+            group.tax = logic::round_currency(group.tax);
+            group.allowance = if i + 1 == count || invoice.subtotal == 0.0 {
+                logic::round_currency(remaining)
+            } else {
+                logic::round_currency(invoice.discount_amount * group.line_total / invoice.subtotal)
+            };
+            remaining -= group.allowance;
+        }
+
+        Ok(Document {
+            invoice,
+            seller,
+            seller_address: Address::new(seller.address.as_deref(), seller.city.as_deref(), seller.postal_code.as_deref(), country_code),
+            buyer_address: Address::new(buyer.address.as_deref(), buyer.city.as_deref(), buyer.postal_code.as_deref(), buyer_country_code),
+            currency,
+            lines,
+            groups,
+        })
+    }
+
+    fn tax_basis(&self) -> f64 {
+        logic::round_currency(self.invoice.subtotal - self.invoice.discount_amount)
+    }
+
+    fn prepaid(&self) -> f64 {
+        logic::round_currency(self.invoice.total - self.invoice.balance_due)
+    }
+}
+
+/// Render an invoice as UBL 2.1 XML following EN 16931.
+/// `seller` must have a country and currency; the buyer is the invoice's client.
+pub fn render_ubl(invoice: &Invoice, seller: &BusinessProfile) -> Result<String, String> {
+    let doc = Document::new(invoice, seller)?;
+    let cur = [("currencyID", doc.currency)];
+    let mut x = Xml::new();
+
+    x.open("Invoice", &[("xmlns", UBL_INVOICE_NS), ("xmlns:cac", UBL_CAC_NS), ("xmlns:cbc", UBL_CBC_NS)]);
+    x.leaf("cbc:CustomizationID", &[], EN16931);
+    x.leaf("cbc:ID", &[], &invoice.invoice_number);
+    x.leaf("cbc:IssueDate", &[], invoice.invoice_date());
+    x.leaf("cbc:DueDate", &[], &invoice.due_date);
+    x.leaf("cbc:InvoiceTypeCode", &[], INVOICE_TYPE_CODE);
+    if let Some(notes) = &invoice.notes {
+        x.leaf("cbc:Note", &[], notes);
+    }
+    x.leaf("cbc:DocumentCurrencyCode", &[], doc.currency);
+
+    // Seller
+    x.open("cac:AccountingSupplierParty", &[]);
+    x.open("cac:Party", &[]);
+    if let Some(email) = &seller.email {
+        x.leaf("cbc:EndpointID", &[("schemeID", "EM")], email);
+    }
+    x.open("cac:PartyName", &[]);
+    x.leaf("cbc:Name", &[], &seller.business_name);
+    x.close();
+    ubl_address(&mut x, &doc.seller_address);
+    if let Some(tax_id) = &seller.tax_id {
+        x.open("cac:PartyTaxScheme", &[]);
+        x.leaf("cbc:CompanyID", &[], tax_id);
+        ubl_vat_scheme(&mut x);
+        x.close();
+    }
+    x.open("cac:PartyLegalEntity", &[]);
+    x.leaf("cbc:RegistrationName", &[], &seller.business_name);
+    x.close();
+    if seller.phone.is_some() || seller.email.is_some() {
+        x.open("cac:Contact", &[]);
+        if let Some(phone) = &seller.phone {
+            x.leaf("cbc:Telephone", &[], phone);
+        }
+        if let Some(email) = &seller.email {
+            x.leaf("cbc:ElectronicMail", &[], email);
+        }
+        x.close();
+    }
+    x.close();
+    x.close();
+
+    // Buyer
+    x.open("cac:AccountingCustomerParty", &[]);
+    x.open("cac:Party", &[]);
+    if let Some(email) = &invoice.client_email {
+        x.leaf("cbc:EndpointID", &[("schemeID", "EM")], email);
+    }
+    x.open("cac:PartyName", &[]);
+    x.leaf("cbc:Name", &[], &invoice.client_name);
+    x.close();
+    ubl_address(&mut x, &doc.buyer_address);
+    if let Some(tax_id) = &invoice.client_address.tax_id {
+        x.open("cac:PartyTaxScheme", &[]);
+        x.leaf("cbc:CompanyID", &[], tax_id);
+        ubl_vat_scheme(&mut x);
+        x.close();
+    }
+    x.open("cac:PartyLegalEntity", &[]);
+    x.leaf("cbc:RegistrationName", &[], &invoice.client_name);
+    x.close();
+    if let Some(email) = &invoice.client_email {
+        x.open("cac:Contact", &[]);
+        x.leaf("cbc:ElectronicMail", &[], email);
+        x.close();
+    }
+    x.close();
+    x.close();
+
+    // Invoice discount, per VAT group
+    for group in doc.groups.iter().filter(|g| g.allowance != 0.0) {
+        x.open("cac:AllowanceCharge", &[]);
+        x.leaf("cbc:ChargeIndicator", &[], "false");
+        x.leaf("cbc:AllowanceChargeReason", &[], "Discount");
+        x.leaf("cbc:Amount", &cur, &amount(group.allowance));
+        ubl_tax_category(&mut x, "cac:TaxCategory", &group.vat, false);
+        x.close();
+    }
+
+    x.open("cac:TaxTotal", &[]);
+    x.leaf("cbc:TaxAmount", &cur, &amount(invoice.tax_amount));
+    for group in &doc.groups {
+        x.open("cac:TaxSubtotal", &[]);
+        x.leaf("cbc:TaxableAmount", &cur, &amount(group.line_total - group.allowance));
+        x.leaf("cbc:TaxAmount", &cur, &amount(group.tax));
+        ubl_tax_category(&mut x, "cac:TaxCategory", &group.vat, true);
+        x.close();
+    }
+    x.close();
+
+    x.open("cac:LegalMonetaryTotal", &[]);
+    x.leaf("cbc:LineExtensionAmount", &cur, &amount(invoice.subtotal));
+    x.leaf("cbc:TaxExclusiveAmount", &cur, &amount(doc.tax_basis()));
+    x.leaf("cbc:TaxInclusiveAmount", &cur, &amount(invoice.total));
+    x.leaf("cbc:AllowanceTotalAmount", &cur, &amount(invoice.discount_amount));
+    x.leaf("cbc:PrepaidAmount", &cur, &amount(doc.prepaid()));
+    x.leaf("cbc:PayableAmount", &cur, &amount(invoice.balance_due));
+    x.close();
+
+    for (n, line) in doc.lines.iter().enumerate() {
+        x.open("cac:InvoiceLine", &[]);
+        x.leaf("cbc:ID", &[], &(n + 1).to_string());
+        x.leaf("cbc:InvoicedQuantity", &[("unitCode", line.unit_code)], &decimal(line.item.hours));
+        x.leaf("cbc:LineExtensionAmount", &cur, &amount(line.item.amount));
+        if line.allowance != 0.0 {
+            x.open("cac:AllowanceCharge", &[]);
+            x.leaf("cbc:ChargeIndicator", &[], "false");
+            x.leaf("cbc:AllowanceChargeReason", &[], "Discount");
+            x.leaf("cbc:Amount", &cur, &amount(line.allowance));
+            x.close();
+        }
+        x.open("cac:Item", &[]);
+        x.leaf("cbc:Name", &[], &line.item.description);
+        ubl_tax_category(&mut x, "cac:ClassifiedTaxCategory", &line.vat, false);
+        x.close();
+        x.open("cac:Price", &[]);
+        x.leaf("cbc:PriceAmount", &cur, &decimal(line.item.rate));
+        x.close();
+        x.close();
+    }
+
+    x.close();
+    Ok(x.finish())
+}
+
+/// Render an invoice as UN/CEFACT Cross Industry Invoice XML (EN 16931
+/// profile), the XML embedded in Factur-X and ZUGFeRD PDFs
+pub fn render_cii(invoice: &Invoice, seller: &BusinessProfile) -> Result<String, String> {
+    let doc = Document::new(invoice, seller)?;
+    let cur = [("currencyID", doc.currency)];
+    let mut x = Xml::new();
+
+    x.open("rsm:CrossIndustryInvoice", &[
+        ("xmlns:rsm", CII_RSM_NS),
+        ("xmlns:ram", CII_RAM_NS),
+        ("xmlns:udt", CII_UDT_NS),
+        ("xmlns:qdt", CII_QDT_NS),
+    ]);
+    x.open("rsm:ExchangedDocumentContext", &[]);
+    x.open("ram:GuidelineSpecifiedDocumentContextParameter", &[]);
+    x.leaf("ram:ID", &[], EN16931);
+    x.close();
+    x.close();
+
+    x.open("rsm:ExchangedDocument", &[]);
+    x.leaf("ram:ID", &[], &invoice.invoice_number);
+    x.leaf("ram:TypeCode", &[], INVOICE_TYPE_CODE);
+    cii_date(&mut x, "ram:IssueDateTime", invoice.invoice_date());
+    if let Some(notes) = &invoice.notes {
+        x.open("ram:IncludedNote", &[]);
+        x.leaf("ram:Content", &[], notes);
+        x.close();
+    }
+    x.close();
+
+    x.open("rsm:SupplyChainTradeTransaction", &[]);
+    for (n, line) in doc.lines.iter().enumerate() {
+        x.open("ram:IncludedSupplyChainTradeLineItem", &[]);
+        x.open("ram:AssociatedDocumentLineDocument", &[]);
+        x.leaf("ram:LineID", &[], &(n + 1).to_string());
+        x.close();
+        x.open("ram:SpecifiedTradeProduct", &[]);
+        x.leaf("ram:Name", &[], &line.item.description);
+        x.close();
+        x.open("ram:SpecifiedLineTradeAgreement", &[]);
+        x.open("ram:NetPriceProductTradePrice", &[]);
+        x.leaf("ram:ChargeAmount", &[], &decimal(line.item.rate));
+        x.close();
+        x.close();
+        x.open("ram:SpecifiedLineTradeDelivery", &[]);
+        x.leaf("ram:BilledQuantity", &[("unitCode", line.unit_code)], &decimal(line.item.hours));
+        x.close();
+        x.open("ram:SpecifiedLineTradeSettlement", &[]);
+        cii_trade_tax(&mut x, &line.vat, None);
+        if line.allowance != 0.0 {
+            x.open("ram:SpecifiedTradeAllowanceCharge", &[]);
+            cii_indicator(&mut x, false);
+            x.leaf("ram:ActualAmount", &[], &amount(line.allowance));
+            x.leaf("ram:Reason", &[], "Discount");
+            x.close();
+        }
+        x.open("ram:SpecifiedTradeSettlementLineMonetarySummation", &[]);
+        x.leaf("ram:LineTotalAmount", &[], &amount(line.item.amount));
+        x.close();
+        x.close();
+        x.close();
+    }
+
+    x.open("ram:ApplicableHeaderTradeAgreement", &[]);
+    x.open("ram:SellerTradeParty", &[]);
+    x.leaf("ram:Name", &[], &seller.business_name);
+    cii_address(&mut x, &doc.seller_address);
+    if let Some(email) = &seller.email {
+        cii_email(&mut x, email);
+    }
+    if let Some(tax_id) = &seller.tax_id {
+        x.open("ram:SpecifiedTaxRegistration", &[]);
+        x.leaf("ram:ID", &[("schemeID", "VA")], tax_id);
+        x.close();
+    }
+    x.close();
+    x.open("ram:BuyerTradeParty", &[]);
+    x.leaf("ram:Name", &[], &invoice.client_name);
+    cii_address(&mut x, &doc.buyer_address);
+    if let Some(email) = &invoice.client_email {
+        cii_email(&mut x, email);
+    }
+    if let Some(tax_id) = &invoice.client_address.tax_id {
+        x.open("ram:SpecifiedTaxRegistration", &[]);
+        x.leaf("ram:ID", &[("schemeID", "VA")], tax_id);
+        x.close();
+    }
+    x.close();
+    x.close();
+
+    x.empty("ram:ApplicableHeaderTradeDelivery");
+
+    x.open("ram:ApplicableHeaderTradeSettlement", &[]);
+    x.leaf("ram:InvoiceCurrencyCode", &[], doc.currency);
+    for group in &doc.groups {
+        cii_trade_tax(&mut x, &group.vat, Some((group.tax, group.line_total - group.allowance)));
+    }
+    for group in doc.groups.iter().filter(|g| g.allowance != 0.0) {
+        x.open("ram:SpecifiedTradeAllowanceCharge", &[]);
+        cii_indicator(&mut x, false);
+        x.leaf("ram:ActualAmount", &[], &amount(group.allowance));
+        x.leaf("ram:Reason", &[], "Discount");
+        x.open("ram:CategoryTradeTax", &[]);
+        x.leaf("ram:TypeCode", &[], "VAT");
+        x.leaf("ram:CategoryCode", &[], group.vat.code);
+        x.leaf("ram:RateApplicablePercent", &[], &decimal(group.vat.rate));
+        x.close();
+        x.close();
+    }
+    x.open("ram:SpecifiedTradePaymentTerms", &[]);
+    cii_date(&mut x, "ram:DueDateDateTime", &invoice.due_date);
+    x.close();
+    x.open("ram:SpecifiedTradeSettlementHeaderMonetarySummation", &[]);
+    x.leaf("ram:LineTotalAmount", &[], &amount(invoice.subtotal));
+    x.leaf("ram:AllowanceTotalAmount", &[], &amount(invoice.discount_amount));
+    x.leaf("ram:TaxBasisTotalAmount", &[], &amount(doc.tax_basis()));
+    x.leaf("ram:TaxTotalAmount", &cur, &amount(invoice.tax_amount));
+    x.leaf("ram:GrandTotalAmount", &[], &amount(invoice.total));
+    x.leaf("ram:TotalPrepaidAmount", &[], &amount(doc.prepaid()));
+    x.leaf("ram:DuePayableAmount", &[], &amount(invoice.balance_due));
+    x.close();
+    x.close();
+
+    x.close();
+    x.close();
+    Ok(x.finish())
+}
+
+fn ubl_address(x: &mut Xml, address: &Address) {
+    x.open("cac:PostalAddress", &[]);
+    if let Some(first) = address.street.first() {
+        x.leaf("cbc:StreetName", &[], first);
+    }
+    if address.street.len() > 1 {
+        x.leaf("cbc:AdditionalStreetName", &[], &address.street[1..].join(", "));
+    }
+    if let Some(city) = address.city {
+        x.leaf("cbc:CityName", &[], city);
+    }
+    if let Some(postal_code) = address.postal_code {
+        x.leaf("cbc:PostalZone", &[], postal_code);
+    }
+    x.open("cac:Country", &[]);
+    x.leaf("cbc:IdentificationCode", &[], address.country_code);
+    x.close();
+    x.close();
+}
+
+fn ubl_vat_scheme(x: &mut Xml) {
+    x.open("cac:TaxScheme", &[]);
+    x.leaf("cbc:ID", &[], "VAT");
+    x.close();
+}
+
+/// `with_reason` adds the exemption reason (VAT breakdown only)
+fn ubl_tax_category(x: &mut Xml, tag: &'static str, vat: &VatCategory, with_reason: bool) {
+    x.open(tag, &[]);
+    x.leaf("cbc:ID", &[], vat.code);
+    x.leaf("cbc:Percent", &[], &decimal(vat.rate));
+    if let (true, Some(reason)) = (with_reason, &vat.exemption_reason) {
+        x.leaf("cbc:TaxExemptionReason", &[], reason);
+    }
+    ubl_vat_scheme(x);
+    x.close();
+}
+
+/// Line tax when `totals` is `None`, else a VAT breakdown entry with (tax, basis)
+fn cii_trade_tax(x: &mut Xml, vat: &VatCategory, totals: Option<(f64, f64)>) {
+    x.open("ram:ApplicableTradeTax", &[]);
+    if let Some((tax, _)) = totals {
+        x.leaf("ram:CalculatedAmount", &[], &amount(tax));
+    }
+    x.leaf("ram:TypeCode", &[], "VAT");
+    if let (Some(_), Some(reason)) = (totals, &vat.exemption_reason) {
+        x.leaf("ram:ExemptionReason", &[], reason);
+    }
+    if let Some((_, basis)) = totals {
+        x.leaf("ram:BasisAmount", &[], &amount(basis));
+    }
+    x.leaf("ram:CategoryCode", &[], vat.code);
+    x.leaf("ram:RateApplicablePercent", &[], &decimal(vat.rate));
+    x.close();
+}
+
+fn cii_address(x: &mut Xml, address: &Address) {
+    x.open("ram:PostalTradeAddress", &[]);
+    if let Some(postal_code) = address.postal_code {
+        x.leaf("ram:PostcodeCode", &[], postal_code);
+    }
+    for (tag, line) in ["ram:LineOne", "ram:LineTwo"].iter().zip(address.street.iter()) {
+        x.leaf(tag, &[], line);
+    }
+    if address.street.len() > 2 {
+        x.leaf("ram:LineThree", &[], &address.street[2..].join(", "));
+    }
+    if let Some(city) = address.city {
+        x.leaf("ram:CityName", &[], city);
+    }
+    x.leaf("ram:CountryID", &[], address.country_code);
+    x.close();
+}
+
+fn cii_indicator(x: &mut Xml, charge: bool) {
+    x.open("ram:ChargeIndicator", &[]);
+    x.leaf("udt:Indicator", &[], if charge { "true" } else { "false" });
+    x.close();
+}
+
+fn cii_email(x: &mut Xml, email: &str) {
+    x.open("ram:URIUniversalCommunication", &[]);
+    x.leaf("ram:URIID", &[("schemeID", "EM")], email);
+    x.close();
+}
+
+/// CII dates use format 102 (YYYYMMDD)
+fn cii_date(x: &mut Xml, tag: &'static str, date: &str) {
+    x.open(tag, &[]);
+    x.leaf("udt:DateTimeString", &[("format", "102")], &date.replace('-', ""));
+    x.close();
+}
+
+/// Units as stored on items mapped to UN/ECE Recommendation 20 codes
+fn unit_code(unit: Option<&str>) -> &'static str {
+    match unit.map(|u| u.trim().to_ascii_lowercase()).as_deref() {
+        Some("hours") | Some("hour") | Some("h") => "HUR",
+        Some("days") | Some("day") | Some("d") => "DAY",
+        Some("pcs") | Some("piece") | Some("pieces") => "H87",
+        _ => "C62",
+    }
+}
+
+fn amount(value: f64) -> String {
+    format!("{:.2}", logic::round_currency(value))
+}
+
+/// Quantities, prices and rates without trailing zeros
+fn decimal(value: f64) -> String {
+    let formatted = format!("{:.4}", value);
+    formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Minimal indenting XML writer; element names are static, text and
+/// attribute values are escaped
+struct Xml {
+    out: String,
+    stack: Vec<&'static str>,
+}
+
+impl Xml {
+    fn new() -> Self {
+        Xml { out: String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"), stack: Vec::new() }
+    }
+
+    fn start_tag(&mut self, tag: &str, attrs: &[(&str, &str)]) {
+        self.out.push_str(&"  ".repeat(self.stack.len()));
+        self.out.push('<');
+        self.out.push_str(tag);
+        for (name, value) in attrs {
+            self.out.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+        }
+    }
+
+    fn open(&mut self, tag: &'static str, attrs: &[(&str, &str)]) {
+        self.start_tag(tag, attrs);
+        self.out.push_str(">\n");
+        self.stack.push(tag);
+    }
+
+    fn close(&mut self) {
+        let tag = self.stack.pop().expect("unbalanced close");
+        self.out.push_str(&"  ".repeat(self.stack.len()));
+        self.out.push_str(&format!("</{}>\n", tag));
+    }
+
+    fn leaf(&mut self, tag: &str, attrs: &[(&str, &str)], text: &str) {
+        self.start_tag(tag, attrs);
+        self.out.push_str(&format!(">{}</{}>\n", escape(text), tag));
+    }
+
+    fn empty(&mut self, tag: &str) {
+        self.start_tag(tag, &[]);
+        self.out.push_str("/>\n");
+    }
+
+    fn finish(self) -> String {
+        debug_assert!(self.stack.is_empty());
+        self.out
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // Control characters aren't allowed in XML 1.0
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    pub(in crate::export) fn seller() -> BusinessProfile {
+        BusinessProfile {
+            id: 1,
+            name: "Main".to_string(),
+            business_name: "Müller GmbH".to_string(),
+            address: Some("Hauptstr. 1".to_string()),
+            city: Some("Berlin".to_string()),
+            postal_code: Some("10115".to_string()),
+            country_code: Some("DE".to_string()),
+            email: None,
+            phone: None,
+            tax_id: Some("DE123456789".to_string()),
+            bank_details: None,
+            logo_path: None,
+            currency: Some("EUR".to_string()),
+            is_default: true,
+        }
+    }
+
+    /// One 100.00 line; with `vat` a single VAT tax of that rate and note
+    pub(in crate::export) fn invoice(vat: Option<(f64, Option<&str>)>) -> Invoice {
+        let taxes = vat.map(|(rate, note)| InvoiceTax {
+            id: 1,
+            invoice_id: 1,
+            tax_rate_id: None,
+            name: "VAT".to_string(),
+            rate,
+            kind: TaxKind::Additive,
+            category: None,
+            note: note.map(str::to_string),
+            base: 100.0,
+            amount: rate,
+        });
+        let tax_amount = taxes.as_ref().map_or(0.0, |t| t.amount);
+        Invoice {
+            id: 1,
+            invoice_number: "INV-1".to_string(),
+            client_name: "Dupont SARL".to_string(),
+            client_email: None,
+            client_address: ClientAddress {
+                address: Some("12 rue de la Paix\nBâtiment B".to_string()),
+                city: Some("Paris".to_string()),
+                postal_code: Some("75002".to_string()),
+                country_code: Some("FR".to_string()),
+                tax_id: Some("FR12345678901".to_string()),
+            },
+            created_date: "2024-03-01".to_string(),
+            issued_date: None,
+            due_date: "2024-03-31".to_string(),
+            status: InvoiceStatus::Sent,
+            subtotal: 100.0,
+            tax_rate: None,
+            tax_amount,
+            total: 100.0 + tax_amount,
+            notes: None,
+            discount: None,
+            discount_amount: 0.0,
+            withholding_amount: 0.0,
+            amount_paid: 0.0,
+            balance_due: 100.0 + tax_amount,
+            overdue_at: None,
+            profile_id: Some(1),
+            template_id: None,
+            credited_invoice_id: None,
+            credited_invoice_number: None,
+            amount_credited: 0.0,
+            voided_at: None,
+            paid_date: None,
+            line_grouping: LineGrouping::PerSession,
+            include_timesheet: false,
+            quote_id: None,
+            quote_number: None,
+            items: vec![InvoiceItem {
+                id: 1,
+                invoice_id: 1,
+                session_id: None,
+                session_ids: Vec::new(),
+                kind: InvoiceItemKind::Manual,
+                description: "Consulting".to_string(),
+                hours: 1.0,
+                unit: None,
+                rate: 100.0,
+                discount: None,
+                taxable: true,
+                tax_category: None,
+                amount: 100.0,
+                credited_item_id: None,
+                session_released: false,
+            }],
+            taxes: taxes.into_iter().collect(),
+            payments: Vec::new(),
+            timesheet: Vec::new(),
+        }
+    }
+
+    /// Needs xmllint and the schemas described in `schemas/README.md`
+    #[test]
+    #[ignore]
+    fn output_validates_against_the_schemas() {
+        let schemas = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("schemas");
+        let reverse_charge = invoice(Some((0.0, Some("Reverse charge"))));
+        for (i, invoice) in [invoice(None), invoice(Some((19.0, None))), reverse_charge].iter().enumerate() {
+            let documents = [
+                (render_ubl(invoice, &seller()), "ubl-2.1/maindoc/UBL-Invoice-2.1.xsd"),
+                (render_cii(invoice, &seller()), "cii-d16b/CrossIndustryInvoice_100pD16B.xsd"),
+            ];
+            for (xml, schema) in documents {
+                let schema = schemas.join(schema);
+                assert!(schema.exists(), "{} is missing; see schemas/README.md", schema.display());
+                let path = std::env::temp_dir().join(format!("chrono-einvoice-{}-{}.xml", std::process::id(), i));
+                std::fs::write(&path, xml.unwrap()).unwrap();
+                let output = std::process::Command::new("xmllint")
+                    .arg("--noout")
+                    .arg("--schema")
+                    .arg(&schema)
+                    .arg(&path)
+                    .output()
+                    .expect("xmllint not found");
+                let _ = std::fs::remove_file(&path);
+                assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+            }
+        }
+    }
+
+    #[test]
+    fn taxes_map_to_vat_categories() {
+        let categories = |invoice: &Invoice| {
+            let ubl = render_ubl(invoice, &seller()).unwrap();
+            let subtotal = &ubl[ubl.find("<cac:TaxSubtotal>").unwrap()..];
+            subtotal[subtotal.find("<cbc:ID>").unwrap() + 8..subtotal.find("</cbc:ID>").unwrap()].to_string()
+        };
+        assert_eq!(categories(&invoice(None)), "E");
+        assert_eq!(categories(&invoice(Some((19.0, None)))), "S");
+        assert_eq!(categories(&invoice(Some((0.0, None)))), "Z");
+        assert_eq!(categories(&invoice(Some((0.0, Some("Reverse charge"))))), "AE");
+    }
+
+    #[test]
+    fn withholding_taxes_are_rejected() {
+        let mut invoice = invoice(Some((15.0, None)));
+        invoice.taxes[0].kind = TaxKind::Withholding;
+        assert!(render_ubl(&invoice, &seller()).unwrap_err().contains("can't express"));
+        assert!(render_cii(&invoice, &seller()).is_err());
+    }
+
+    #[test]
+    fn buyer_address_and_vat_id_are_written() {
+        let invoice = invoice(Some((19.0, None)));
+        let ubl = render_ubl(&invoice, &seller()).unwrap();
+        let buyer = &ubl[ubl.find("<cac:AccountingCustomerParty>").unwrap()..];
+        assert!(buyer.contains("<cbc:StreetName>12 rue de la Paix</cbc:StreetName>"));
+        assert!(buyer.contains("<cbc:AdditionalStreetName>Bâtiment B</cbc:AdditionalStreetName>"));
+        assert!(buyer.contains("<cbc:IdentificationCode>FR</cbc:IdentificationCode>"));
+        assert!(buyer.contains("<cbc:CompanyID>FR12345678901</cbc:CompanyID>"));
+
+        let cii = render_cii(&invoice, &seller()).unwrap();
+        let buyer = &cii[cii.find("<ram:BuyerTradeParty>").unwrap()..];
+        assert!(buyer.contains("<ram:PostcodeCode>75002</ram:PostcodeCode>"));
+        assert!(buyer.contains("<ram:CountryID>FR</ram:CountryID>"));
+        assert!(buyer.contains("<ram:ID schemeID=\"VA\">FR12345678901</ram:ID>"));
+    }
+
+    #[test]
+    fn documents_carry_the_issue_date() {
+        let mut invoice = invoice(None);
+        assert!(render_ubl(&invoice, &seller()).unwrap().contains("<cbc:IssueDate>2024-03-01</cbc:IssueDate>"));
+        invoice.issued_date = Some("2024-03-04".to_string());
+        assert!(render_ubl(&invoice, &seller()).unwrap().contains("<cbc:IssueDate>2024-03-04</cbc:IssueDate>"));
+        let cii = render_cii(&invoice, &seller()).unwrap();
+        let issued = &cii[cii.find("<ram:IssueDateTime>").unwrap()..cii.find("</ram:IssueDateTime>").unwrap()];
+        assert!(issued.contains(">20240304</udt:DateTimeString>"));
+    }
+
+    #[test]
+    fn buyer_country_is_required() {
+        let mut invoice = invoice(Some((19.0, None)));
+        invoice.client_address = ClientAddress::default();
+        assert!(render_ubl(&invoice, &seller()).unwrap_err().contains("client's country"));
+        assert!(render_cii(&invoice, &seller()).is_err());
+    }
+
+    #[test]
+    fn exempt_invoices_need_the_seller_vat_id() {
+        let mut seller = seller();
+        seller.tax_id = None;
+        let err = render_ubl(&invoice(None), &seller).unwrap_err();
+        assert!(err.contains("VAT ID on the business profile"));
+    }
+
+    #[test]
+    fn reverse_charge_needs_the_buyer_vat_id() {
+        let mut invoice = invoice(Some((0.0, Some("Reverse charge"))));
+        assert!(render_ubl(&invoice, &seller()).unwrap().contains("<cbc:ID>AE</cbc:ID>"));
+        invoice.client_address.tax_id = None;
+        assert!(render_ubl(&invoice, &seller()).unwrap_err().contains("client's VAT ID"));
+    }
+}
@@ -0,0 +1,314 @@
+use crate::core::models::*;
+use super::{einvoice, pdf};
+use printpdf::lopdf::{self, Dictionary, Object, Stream};
+
+/// Name of the embedded XML mandated by Factur-X 1.0 / ZUGFeRD 2.x
+const XML_FILENAME: &str = "factur-x.xml";
+const FX_NS: &str = "urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#";
+
+/// Render a Factur-X invoice: the regular PDF, made PDF/A-3b, with the CII
+/// XML (EN 16931 profile) attached. PDF/A forbids the built-in fonts, so a
+/// TrueType font is required.
+pub fn render_invoice(invoice: &Invoice, seller: &BusinessProfile, font_data: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    if font_data.is_none() {
+        return Err("Factur-X needs an embedded font: set pdf_font_path to a TrueType font".to_string());
+    }
+    let xml = einvoice::render_cii(invoice, seller)?;
+    let pdf = pdf::render_invoice(invoice, Some(seller), font_data)?;
+    let title = format!("Invoice {}", invoice.invoice_number);
+    make_pdfa3(&pdf, &title, xml.as_bytes()).map_err(|e| format!("Failed to build Factur-X PDF: {}", e))
+}
+
+/// Turn printpdf output into PDF/A-3b with `xml` as the associated file
+fn make_pdfa3(pdf: &[u8], title: &str, xml: &[u8]) -> lopdf::Result<Vec<u8>> {
+    let mut doc = lopdf::Document::load_mem(pdf)?;
+    // PDF/A wants a comment of high bytes right after the header; lopdf
+    // writes the version verbatim, so it rides along (each char is two bytes >127)
+    doc.version = "1.7\n%âãÏÓ".to_string();
+    let now = chrono::Local::now();
+
+    // Embedded file and its file specification
+    let mut params = Dictionary::new();
+    params.set("Size", xml.len() as i64);
+    params.set("ModDate", Object::string_literal(pdf_date(&now)));
+    let mut file_dict = Dictionary::new();
+    file_dict.set("Type", "EmbeddedFile");
+    file_dict.set("Subtype", "text/xml");
+    file_dict.set("Params", params);
+    let file_id = doc.add_object(Stream::new(file_dict, xml.to_vec()));
+
+    let mut ef = Dictionary::new();
+    ef.set("F", file_id);
+    ef.set("UF", file_id);
+    let mut filespec = Dictionary::new();
+    filespec.set("Type", "Filespec");
+    filespec.set("F", Object::string_literal(XML_FILENAME));
+    filespec.set("UF", Object::string_literal(XML_FILENAME));
+    filespec.set("Desc", Object::string_literal("Factur-X invoice"));
+    filespec.set("AFRelationship", "Alternative");
+    filespec.set("EF", ef);
+    let filespec_id = doc.add_object(filespec);
+
+    // sRGB output intent, matching the DeviceRGB colours printpdf writes
+    let mut icc_dict = Dictionary::new();
+    icc_dict.set("N", 3);
+    let icc_id = doc.add_object(Stream::new(icc_dict, srgb_icc_profile()));
+    let mut intent = Dictionary::new();
+    intent.set("Type", "OutputIntent");
+    intent.set("S", "GTS_PDFA1");
+    intent.set("OutputConditionIdentifier", Object::string_literal("sRGB IEC61966-2.1"));
+    intent.set("Info", Object::string_literal("sRGB IEC61966-2.1"));
+    intent.set("DestOutputProfile", icc_id);
+
+    let mut metadata_dict = Dictionary::new();
+    metadata_dict.set("Type", "Metadata");
+    metadata_dict.set("Subtype", "XML");
+    let metadata = Stream::new(metadata_dict, xmp_metadata(title, &now).into_bytes()).with_compression(false);
+    let metadata_id = doc.add_object(metadata);
+
+    let mut names = Dictionary::new();
+    names.set("Names", vec![Object::string_literal(XML_FILENAME), Object::Reference(filespec_id)]);
+    let mut name_tree = Dictionary::new();
+    name_tree.set("EmbeddedFiles", names);
+
+    let catalog = doc.catalog_mut()?;
+    catalog.set("Names", name_tree);
+    catalog.set("AF", vec![Object::Reference(filespec_id)]);
+    catalog.set("OutputIntents", vec![Object::Dictionary(intent)]);
+    catalog.set("Metadata", metadata_id);
+    // PDF/A requires every optional content configuration to be named
+    if let Ok(Object::Dictionary(oc)) = catalog.get_mut(b"OCProperties") {
+        if let Ok(Object::Dictionary(default)) = oc.get_mut(b"D") {
+            default.set("Name", Object::string_literal("Default"));
+        }
+    }
+
+    // The document info dictionary would have to mirror the XMP; drop it
+    doc.trailer.remove(b"Info");
+
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// "D:20240131120000+01'00'"
+fn pdf_date(now: &chrono::DateTime<chrono::Local>) -> String {
+    let offset = now.format("%z").to_string();
+    format!("D:{}{}'{}'", now.format("%Y%m%d%H%M%S"), &offset[..3], &offset[3..])
+}
+
+/// XMP packet declaring PDF/A-3b and the Factur-X extension schema
+fn xmp_metadata(title: &str, now: &chrono::DateTime<chrono::Local>) -> String {
+    let date = now.format("%Y-%m-%dT%H:%M:%S%:z");
+    let property = |name: &str, description: &str| format!(
+        r#"<rdf:li rdf:parseType="Resource">
+                <pdfaProperty:name>{}</pdfaProperty:name>
+                <pdfaProperty:valueType>Text</pdfaProperty:valueType>
+                <pdfaProperty:category>external</pdfaProperty:category>
+                <pdfaProperty:description>{}</pdfaProperty:description>
+              </rdf:li>"#,
+        name, description
+    );
+    let properties = [
+        property("DocumentFileName", "The name of the embedded XML document"),
+        property("DocumentType", "The type of the hybrid document in capital letters, e.g. INVOICE or ORDER"),
+        property("Version", "The actual version of the standard applying to the embedded XML document"),
+        property("ConformanceLevel", "The conformance level of the embedded XML document"),
+    ].join("\n              ");
+    let title = title.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+
+    format!(r#"<?xpacket begin="{bom}" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+  <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+    <rdf:Description rdf:about="" xmlns:pdfaid="http://www.aiim.org/pdfa/ns/id/">
+      <pdfaid:part>3</pdfaid:part>
+      <pdfaid:conformance>B</pdfaid:conformance>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+      <dc:format>application/pdf</dc:format>
+      <dc:title><rdf:Alt><rdf:li xml:lang="x-default">{title}</rdf:li></rdf:Alt></dc:title>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/">
+      <xmp:CreateDate>{date}</xmp:CreateDate>
+      <xmp:ModifyDate>{date}</xmp:ModifyDate>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:fx="{fx}">
+      <fx:DocumentType>INVOICE</fx:DocumentType>
+      <fx:DocumentFileName>{file}</fx:DocumentFileName>
+      <fx:Version>1.0</fx:Version>
+      <fx:ConformanceLevel>EN 16931</fx:ConformanceLevel>
+    </rdf:Description>
+    <rdf:Description rdf:about=""
+        xmlns:pdfaExtension="http://www.aiim.org/pdfa/ns/extension/"
+        xmlns:pdfaSchema="http://www.aiim.org/pdfa/ns/schema#"
+        xmlns:pdfaProperty="http://www.aiim.org/pdfa/ns/property#">
+      <pdfaExtension:schemas>
+        <rdf:Bag>
+          <rdf:li rdf:parseType="Resource">
+            <pdfaSchema:schema>Factur-X PDFA Extension Schema</pdfaSchema:schema>
+            <pdfaSchema:namespaceURI>{fx}</pdfaSchema:namespaceURI>
+            <pdfaSchema:prefix>fx</pdfaSchema:prefix>
+            <pdfaSchema:property>
+              <rdf:Seq>
+              {properties}
+              </rdf:Seq>
+            </pdfaSchema:property>
+          </rdf:li>
+        </rdf:Bag>
+      </pdfaExtension:schemas>
+    </rdf:Description>
+  </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#,
+        bom = '\u{feff}',
+        title = title,
+        date = date,
+        fx = FX_NS,
+        file = XML_FILENAME,
+        properties = properties,
+    )
+}
+
+/// Minimal ICC v2 display profile for sRGB: D50-adapted primaries and the
+/// sRGB tone curve sampled at 1024 points
+fn srgb_icc_profile() -> Vec<u8> {
+    fn s15f16(v: f64) -> [u8; 4] {
+        ((v * 65536.0).round() as i32).to_be_bytes()
+    }
+    fn xyz(x: f64, y: f64, z: f64) -> Vec<u8> {
+        let mut tag = b"XYZ \0\0\0\0".to_vec();
+        for v in [x, y, z] {
+            tag.extend(s15f16(v));
+        }
+        tag
+    }
+
+    let description = "sRGB IEC61966-2.1";
+    let mut desc = b"desc\0\0\0\0".to_vec();
+    desc.extend((description.len() as u32 + 1).to_be_bytes());
+    desc.extend(description.as_bytes());
+    desc.push(0);
+    desc.extend([0u8; 4 + 4 + 2 + 1 + 67]); // empty Unicode and ScriptCode descriptions
+
+    let mut cprt = b"text\0\0\0\0".to_vec();
+    cprt.extend(b"No copyright, use freely\0");
+
+    let mut curve = b"curv\0\0\0\0".to_vec();
+    curve.extend(1024u32.to_be_bytes());
+    for i in 0..1024 {
+        let v = i as f64 / 1023.0;
+        let linear = if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) };
+        curve.extend(((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+
+    // (signature, data); the three TRC tags share one curve
+    let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"desc", desc),
+        (b"cprt", cprt),
+        (b"wtpt", xyz(0.9642, 1.0, 0.8249)),
+        (b"rXYZ", xyz(0.4361, 0.2225, 0.0139)),
+        (b"gXYZ", xyz(0.3851, 0.7169, 0.0971)),
+        (b"bXYZ", xyz(0.1431, 0.0606, 0.7141)),
+        (b"rTRC", curve),
+    ];
+    let table_len = 4 + 12 * (tags.len() + 2);
+    let mut table = ((tags.len() + 2) as u32).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    let mut curve_entry = (0u32, 0u32);
+    for (signature, tag) in &tags {
+        let offset = (128 + table_len + data.len()) as u32;
+        table.extend(*signature);
+        table.extend(offset.to_be_bytes());
+        table.extend((tag.len() as u32).to_be_bytes());
+        if *signature == b"rTRC" {
+            curve_entry = (offset, tag.len() as u32);
+        }
+        data.extend(tag);
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+    }
+    for signature in [b"gTRC", b"bTRC"] {
+        table.extend(signature);
+        table.extend(curve_entry.0.to_be_bytes());
+        table.extend(curve_entry.1.to_be_bytes());
+    }
+
+    let size = (128 + table.len() + data.len()) as u32;
+    let mut header = Vec::with_capacity(128);
+    header.extend(size.to_be_bytes());
+    header.extend([0u8; 4]); // preferred CMM
+    header.extend([0x02, 0x10, 0, 0]); // version 2.1
+    header.extend(b"mntrRGB XYZ ");
+    for v in [2024u16, 1, 1, 0, 0, 0] {
+        header.extend(v.to_be_bytes());
+    }
+    header.extend(b"acsp");
+    header.extend([0u8; 24]); // platform, flags, manufacturer, model, attributes
+    header.extend([0u8; 4]); // perceptual intent
+    header.extend(s15f16(0.9642));
+    header.extend(s15f16(1.0));
+    header.extend(s15f16(0.8249));
+    header.resize(128, 0);
+
+    [header, table, data].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::einvoice::tests::{invoice, seller};
+
+    #[test]
+    fn factur_x_needs_an_embedded_font() {
+        assert!(render_invoice(&invoice(Some((19.0, None))), &seller(), None).unwrap_err().contains("embedded font"));
+    }
+
+    #[test]
+    fn xml_is_attached_and_declared_pdfa3() {
+        let Some(font) = pdf::load_font(None) else {
+            eprintln!("no system TrueType font found; skipping");
+            return;
+        };
+        let invoice = invoice(Some((19.0, None)));
+        let bytes = render_invoice(&invoice, &seller(), Some(font)).unwrap();
+        assert!(bytes.starts_with(b"%PDF-1.7\n%\xc3\xa2"));
+        let doc = lopdf::Document::load_mem(&bytes).unwrap();
+        let catalog = doc.catalog().unwrap();
+
+        // /AF and the embedded files name tree point at the same file specification
+        let af = catalog.get(b"AF").and_then(Object::as_array).unwrap();
+        assert_eq!(af.len(), 1);
+        let filespec_id = af[0].as_reference().unwrap();
+        let names = catalog.get(b"Names").and_then(Object::as_dict).unwrap()
+            .get(b"EmbeddedFiles").and_then(Object::as_dict).unwrap()
+            .get(b"Names").and_then(Object::as_array).unwrap();
+        assert_eq!(names[0].as_str().unwrap(), XML_FILENAME.as_bytes());
+        assert_eq!(names[1].as_reference().unwrap(), filespec_id);
+
+        let filespec = doc.get_dictionary(filespec_id).unwrap();
+        assert_eq!(filespec.get(b"F").and_then(Object::as_str).unwrap(), XML_FILENAME.as_bytes());
+        assert_eq!(filespec.get(b"AFRelationship").and_then(Object::as_name_str).unwrap(), "Alternative");
+        let file_id = filespec.get(b"EF").and_then(Object::as_dict).unwrap()
+            .get(b"F").and_then(Object::as_reference).unwrap();
+        let mut file = doc.get_object(file_id).and_then(Object::as_stream).unwrap().clone();
+        assert_eq!(file.dict.get(b"Type").and_then(Object::as_name_str).unwrap(), "EmbeddedFile");
+        assert_eq!(file.dict.get(b"Subtype").and_then(Object::as_name_str).unwrap(), "text/xml");
+        file.decompress();
+        assert_eq!(String::from_utf8(file.content).unwrap(), einvoice::render_cii(&invoice, &seller()).unwrap());
+
+        // XMP declares PDF/A-3b and the Factur-X profile
+        let metadata_id = catalog.get(b"Metadata").and_then(Object::as_reference).unwrap();
+        let metadata = doc.get_object(metadata_id).and_then(Object::as_stream).unwrap();
+        let xmp = String::from_utf8(metadata.content.clone()).unwrap();
+        assert!(xmp.contains("<pdfaid:part>3</pdfaid:part>"));
+        assert!(xmp.contains("<pdfaid:conformance>B</pdfaid:conformance>"));
+        assert!(xmp.contains(&format!("<fx:DocumentFileName>{}</fx:DocumentFileName>", XML_FILENAME)));
+        assert!(xmp.contains("<fx:ConformanceLevel>EN 16931</fx:ConformanceLevel>"));
+        assert!(xmp.contains("Invoice INV-1"));
+
+        let intents = catalog.get(b"OutputIntents").and_then(Object::as_array).unwrap();
+        assert_eq!(intents[0].as_dict().unwrap().get(b"S").and_then(Object::as_name_str).unwrap(), "GTS_PDFA1");
+        assert!(doc.trailer.get(b"Info").is_err());
+    }
+}
@@ -0,0 +1,377 @@
+use crate::core::logic;
+use crate::core::models::*;
+use std::collections::HashMap;
+
+/// Account roots beancount accepts
+const BEANCOUNT_ROOTS: [&str; 5] = ["Assets", "Liabilities", "Equity", "Income", "Expenses"];
+
+/// One balanced journal transaction
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+    pub invoice_id: i64,
+    pub kind: JournalEntryKind,
+    pub date: String,
+    pub payee: String,
+    pub invoice_number: String,
+    pub credit_note: bool,
+    pub currency: String,
+    /// Account and amount; the amounts sum to zero
+    pub postings: Vec<(String, f64)>,
+}
+
+impl JournalEntry {
+    fn narration(&self) -> String {
+        let document = if self.credit_note { "Credit note" } else { "Invoice" };
+        match self.kind {
+            JournalEntryKind::Issued => format!("{} {}", document, self.invoice_number),
+            JournalEntryKind::Settled => format!("Payment of invoice {}", self.invoice_number),
+            JournalEntryKind::Voided => format!("{} {} voided", document, self.invoice_number),
+        }
+    }
+}
+
+/// Entries `invoice` still needs, given the kinds already exported for it.
+/// Drafts need none; a void invoice only needs reversing if its issue was
+/// exported. Issues are booked on the day the invoice left Draft, so a late
+/// issue never lands before what an earlier export already wrote. Credit
+/// notes are issued with the signs reversed and never settle.
+pub fn entries_due(invoice: &Invoice, exported: &[JournalEntryKind], settings: &AccountingSettings, currency: &str) -> Vec<JournalEntry> {
+    let entry = |kind: JournalEntryKind, date: &str, postings: Vec<(String, f64)>| JournalEntry {
+        invoice_id: invoice.id,
+        kind,
+        date: date.to_string(),
+        payee: invoice.client_name.clone(),
+        invoice_number: invoice.invoice_number.clone(),
+        credit_note: invoice.is_credit_note(),
+        currency: currency.to_string(),
+        postings: postings.into_iter().filter(|(_, amount)| *amount != 0.0).collect(),
+    };
+    let issued_date = invoice.invoice_date();
+    let mut entries = Vec::new();
+    match invoice.status {
+        InvoiceStatus::Draft => {}
+        InvoiceStatus::Void => {
+            if exported.contains(&JournalEntryKind::Issued) && !exported.contains(&JournalEntryKind::Voided) {
+                let date = invoice.voided_at.as_deref().and_then(|d| d.get(..10)).unwrap_or(issued_date);
+                let reversed = issued_postings(invoice, settings).into_iter().map(|(a, amount)| (a, -amount)).collect();
+                entries.push(entry(JournalEntryKind::Voided, date, reversed));
+            }
+        }
+        _ => {
+            if !exported.contains(&JournalEntryKind::Issued) {
+                entries.push(entry(JournalEntryKind::Issued, issued_date, issued_postings(invoice, settings)));
+            }
+            let settled = invoice.status == InvoiceStatus::Paid && !invoice.is_credit_note() && invoice.amount_paid > 0.0;
+            if settled && !exported.contains(&JournalEntryKind::Settled) {
+                let receivable = account_name(&settings.receivable_account, &invoice.client_name);
+                let date = invoice.paid_date.as_deref().unwrap_or(issued_date);
+                entries.push(entry(JournalEntryKind::Settled, date, vec![
+                    (settings.bank_account.clone(), invoice.amount_paid),
+                    (receivable, -invoice.amount_paid),
+                ]));
+            }
+        }
+    }
+    entries
+}
+
+/// Accounts a journal must open, as (date of first use, account)
+pub type AccountOpenings = Vec<(String, String)>;
+
+/// Entries due for `invoices` (each with its currency) in date order, and the
+/// accounts (date of first use, account) beancount must open for them beyond
+/// `opened`. `exported` holds the kinds already exported per invoice.
+pub fn pending_entries(
+    invoices: &[(&Invoice, String)],
+    exported: &HashMap<i64, Vec<JournalEntryKind>>,
+    opened: &[String],
+    settings: &AccountingSettings,
+    format: JournalFormat,
+) -> Result<(Vec<JournalEntry>, AccountOpenings), String> {
+    let mut entries = Vec::new();
+    for (invoice, currency) in invoices {
+        let done = exported.get(&invoice.id).map(Vec::as_slice).unwrap_or(&[]);
+        entries.extend(entries_due(invoice, done, settings, currency));
+    }
+    entries.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.invoice_number.cmp(&b.invoice_number)));
+
+    // Beancount needs accounts opened by their first use
+    let mut new_accounts: AccountOpenings = Vec::new();
+    for entry in &entries {
+        for (account, _) in &entry.postings {
+            check_account(account, format)?;
+            let needs_open = format == JournalFormat::Beancount
+                && !opened.contains(account)
+                && !new_accounts.iter().any(|(_, a)| a == account);
+            if needs_open {
+                new_accounts.push((entry.date.clone(), account.clone()));
+            }
+        }
+    }
+    Ok((entries, new_accounts))
+}
+
+/// Receivable and withholding against income and tax. Income is the total net
+/// of tax, so the postings balance to the cent.
+fn issued_postings(invoice: &Invoice, settings: &AccountingSettings) -> Vec<(String, f64)> {
+    let sign = if invoice.is_credit_note() { -1.0 } else { 1.0 };
+    let net = logic::round_currency(invoice.total + invoice.withholding_amount - invoice.tax_amount);
+    vec![
+        (account_name(&settings.receivable_account, &invoice.client_name), sign * invoice.total),
+        (settings.withholding_account.clone(), sign * invoice.withholding_amount),
+        (settings.income_account.clone(), -sign * net),
+        (settings.tax_account.clone(), -sign * invoice.tax_amount),
+    ]
+}
+
+/// `template` with `{client}` replaced by the client name as one account
+/// component: "acme ltd." -> "AcmeLtd"
+pub fn account_name(template: &str, client: &str) -> String {
+    let component: String = client
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().map(|c| c.to_uppercase().collect::<String>()).unwrap_or_default();
+            first + chars.as_str()
+        })
+        .collect();
+    let component = if component.is_empty() { "Unknown".to_string() } else { component };
+    template.replace("{client}", &component)
+}
+
+/// Whether `account` can be written in `format`
+pub fn check_account(account: &str, format: JournalFormat) -> Result<(), String> {
+    let parts: Vec<&str> = account.split(':').collect();
+    if parts.iter().any(|p| p.is_empty() || p.trim() != *p) || account.contains("  ") || account.contains('\t') {
+        return Err(format!("Invalid account name: {}", account));
+    }
+    if format == JournalFormat::Beancount {
+        if !BEANCOUNT_ROOTS.contains(&parts[0]) {
+            return Err(format!("Beancount accounts must start with {}: {}", BEANCOUNT_ROOTS.join(", "), account));
+        }
+        let valid_component = |part: &str| {
+            part.chars().next().is_some_and(|c| c.is_uppercase() || c.is_ascii_digit())
+                && part.chars().all(|c| c.is_alphanumeric() || c == '-')
+        };
+        if !parts.iter().all(|p| valid_component(p)) {
+            return Err(format!("Beancount account components must start with a capital letter or digit and contain only letters, digits and '-': {}", account));
+        }
+    }
+    Ok(())
+}
+
+/// Journal text for `entries`, preceded by open directives for `new_accounts`
+/// (date, account) in beancount. Starts with a comment naming the export date.
+pub fn render(entries: &[JournalEntry], new_accounts: &[(String, String)], format: JournalFormat, exported_on: &str) -> String {
+    let mut out = format!("; Invoices exported {}\n\n", exported_on);
+    if format == JournalFormat::Beancount && !new_accounts.is_empty() {
+        for (date, account) in new_accounts {
+            out.push_str(&format!("{} open {}\n", date, account));
+        }
+        out.push('\n');
+    }
+    for entry in entries {
+        match format {
+            JournalFormat::Ledger => render_ledger(&mut out, entry),
+            JournalFormat::Beancount => render_beancount(&mut out, entry),
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn render_ledger(out: &mut String, entry: &JournalEntry) {
+    let code: String = entry.invoice_number.chars().filter(|c| !matches!(c, '(' | ')')).collect();
+    out.push_str(&format!("{} * ({}) {}  ; {}\n", entry.date, code, ledger_text(&entry.payee), entry.narration()));
+    out.push_str(&format!("    ; invoice: {}\n", ledger_text(&entry.invoice_number)));
+    let width = entry.postings.iter().map(|(a, _)| a.chars().count()).max().unwrap_or(0);
+    for (account, amount) in &entry.postings {
+        out.push_str(&format!("    {:<width$}  {:>12} {}\n", account, format_amount(*amount), entry.currency, width = width));
+    }
+}
+
+fn render_beancount(out: &mut String, entry: &JournalEntry) {
+    out.push_str(&format!("{} * \"{}\" \"{}\"\n", entry.date, quoted(&entry.payee), quoted(&entry.narration())));
+    out.push_str(&format!("  invoice: \"{}\"\n", quoted(&entry.invoice_number)));
+    let width = entry.postings.iter().map(|(a, _)| a.chars().count()).max().unwrap_or(0);
+    for (account, amount) in &entry.postings {
+        out.push_str(&format!("  {:<width$}  {:>12} {}\n", account, format_amount(*amount), entry.currency, width = width));
+    }
+}
+
+fn format_amount(amount: f64) -> String {
+    let formatted = format!("{:.2}", amount);
+    if formatted == "-0.00" { "0.00".to_string() } else { formatted }
+}
+
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Payee or tag value; ';' would start a comment
+fn ledger_text(text: &str) -> String {
+    single_line(text).replace(';', ",")
+}
+
+/// Content of a beancount string literal
+fn quoted(text: &str) -> String {
+    single_line(text).replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invoice(id: i64, status: InvoiceStatus) -> Invoice {
+        Invoice {
+            id,
+            invoice_number: format!("INV-{}", id),
+            client_name: "Acme Ltd".to_string(),
+            client_email: None,
+            client_address: ClientAddress::default(),
+            created_date: "2024-03-01".to_string(),
+            issued_date: Some("2024-03-04".to_string()),
+            due_date: "2024-03-31".to_string(),
+            status,
+            subtotal: 120.0,
+            tax_rate: None,
+            tax_amount: 20.0,
+            total: 110.0,
+            notes: None,
+            discount: None,
+            discount_amount: 0.0,
+            withholding_amount: 10.0,
+            amount_paid: 0.0,
+            balance_due: 110.0,
+            overdue_at: None,
+            profile_id: None,
+            template_id: None,
+            credited_invoice_id: None,
+            credited_invoice_number: None,
+            amount_credited: 0.0,
+            voided_at: None,
+            paid_date: None,
+            line_grouping: LineGrouping::PerSession,
+            include_timesheet: false,
+            quote_id: None,
+            quote_number: None,
+            items: Vec::new(),
+            taxes: Vec::new(),
+            payments: Vec::new(),
+            timesheet: Vec::new(),
+        }
+    }
+
+    fn paid(id: i64) -> Invoice {
+        let mut invoice = invoice(id, InvoiceStatus::Paid);
+        invoice.amount_paid = 110.0;
+        invoice.balance_due = 0.0;
+        invoice.paid_date = Some("2024-03-20".to_string());
+        invoice
+    }
+
+    fn summary(entries: &[JournalEntry]) -> Vec<(i64, JournalEntryKind, &str)> {
+        entries.iter().map(|e| (e.invoice_id, e.kind, e.date.as_str())).collect()
+    }
+
+    #[test]
+    fn entries_follow_the_invoice_life() {
+        use JournalEntryKind::*;
+        let settings = AccountingSettings::default();
+        let due = |invoice: &Invoice, exported: &[JournalEntryKind]| entries_due(invoice, exported, &settings, "EUR");
+
+        assert!(due(&invoice(1, InvoiceStatus::Draft), &[]).is_empty());
+        let issued = due(&invoice(1, InvoiceStatus::Sent), &[]);
+        assert_eq!(summary(&issued), vec![(1, Issued, "2024-03-04")]);
+        assert_eq!(issued[0].postings, vec![
+            ("Assets:Receivable:AcmeLtd".to_string(), 110.0),
+            ("Assets:Tax:Withheld".to_string(), 10.0),
+            ("Income:Consulting".to_string(), -100.0),
+            ("Liabilities:Tax".to_string(), -20.0),
+        ]);
+
+        assert_eq!(summary(&due(&paid(1), &[])), vec![(1, Issued, "2024-03-04"), (1, Settled, "2024-03-20")]);
+        let settled = due(&paid(1), &[Issued]);
+        assert_eq!(summary(&settled), vec![(1, Settled, "2024-03-20")]);
+        assert_eq!(settled[0].postings, vec![
+            ("Assets:Bank".to_string(), 110.0),
+            ("Assets:Receivable:AcmeLtd".to_string(), -110.0),
+        ]);
+        assert!(due(&paid(1), &[Issued, Settled]).is_empty());
+
+        let mut void = invoice(1, InvoiceStatus::Void);
+        void.voided_at = Some("2024-03-10 09:30:00".to_string());
+        assert!(due(&void, &[]).is_empty());
+        let voided = due(&void, &[Issued]);
+        assert_eq!(summary(&voided), vec![(1, Voided, "2024-03-10")]);
+        assert!(voided[0].postings.iter().zip(&issued[0].postings).all(|(v, i)| v.0 == i.0 && v.1 == -i.1));
+        assert!(due(&void, &[Issued, Voided]).is_empty());
+
+        // Credit notes reverse the signs and never settle
+        let mut note = paid(2);
+        note.credited_invoice_id = Some(1);
+        let credited = due(&note, &[]);
+        assert_eq!(summary(&credited), vec![(2, Issued, "2024-03-04")]);
+        assert_eq!(credited[0].postings[0], ("Assets:Receivable:AcmeLtd".to_string(), -110.0));
+
+        // Invoices issued before the issue date was recorded fall back to their creation
+        let mut legacy = invoice(3, InvoiceStatus::Sent);
+        legacy.issued_date = None;
+        assert_eq!(summary(&due(&legacy, &[])), vec![(3, Issued, "2024-03-01")]);
+
+        for entry in issued.iter().chain(&settled).chain(&voided).chain(&credited) {
+            assert_eq!(logic::round_currency(entry.postings.iter().map(|(_, amount)| amount).sum()), 0.0);
+        }
+    }
+
+    #[test]
+    fn incremental_exports_add_only_new_entries_and_accounts() {
+        let settings = AccountingSettings::default();
+        let sent = invoice(1, InvoiceStatus::Sent);
+        let (entries, accounts) = pending_entries(&[(&sent, "EUR".to_string())], &HashMap::new(), &[], &settings, JournalFormat::Beancount).unwrap();
+        assert_eq!(entries.len(), 1);
+        let opened: Vec<String> = accounts.iter().map(|(_, account)| account.clone()).collect();
+        assert_eq!(opened.len(), 4);
+        assert!(accounts.iter().all(|(date, _)| date == "2024-03-04"));
+
+        // Once paid, the next export only settles it, opening the bank account
+        let exported = HashMap::from([(1, vec![JournalEntryKind::Issued])]);
+        let settled = paid(1);
+        let other = invoice(2, InvoiceStatus::Sent);
+        let booked = [(&settled, "EUR".to_string()), (&other, "EUR".to_string())];
+        let (entries, accounts) = pending_entries(&booked, &exported, &opened, &settings, JournalFormat::Beancount).unwrap();
+        assert_eq!(summary(&entries), vec![(2, JournalEntryKind::Issued, "2024-03-04"), (1, JournalEntryKind::Settled, "2024-03-20")]);
+        assert_eq!(accounts, vec![("2024-03-20".to_string(), "Assets:Bank".to_string())]);
+
+        // Ledger declares no accounts
+        let (_, accounts) = pending_entries(&booked, &HashMap::new(), &[], &settings, JournalFormat::Ledger).unwrap();
+        assert!(accounts.is_empty());
+
+        let bad = AccountingSettings { income_account: "income".to_string(), ..settings };
+        assert!(pending_entries(&booked, &HashMap::new(), &[], &bad, JournalFormat::Beancount).is_err());
+    }
+
+    #[test]
+    fn journals_render_in_both_formats() {
+        let mut invoice = paid(1);
+        invoice.client_name = "Acme; \"Ltd\"".to_string();
+        let entries = entries_due(&invoice, &[JournalEntryKind::Issued], &AccountingSettings::default(), "EUR");
+
+        let ledger = render(&entries, &[], JournalFormat::Ledger, "2024-04-01");
+        assert_eq!(ledger, "; Invoices exported 2024-04-01\n\n\
+            2024-03-20 * (INV-1) Acme, \"Ltd\"  ; Payment of invoice INV-1\n    \
+            ; invoice: INV-1\n    \
+            Assets:Bank                      110.00 EUR\n    \
+            Assets:Receivable:AcmeLtd       -110.00 EUR\n\n");
+
+        let accounts = vec![("2024-03-20".to_string(), "Assets:Bank".to_string())];
+        let beancount = render(&entries, &accounts, JournalFormat::Beancount, "2024-04-01");
+        assert_eq!(beancount, "; Invoices exported 2024-04-01\n\n\
+            2024-03-20 open Assets:Bank\n\n\
+            2024-03-20 * \"Acme; \\\"Ltd\\\"\" \"Payment of invoice INV-1\"\n  \
+            invoice: \"INV-1\"\n  \
+            Assets:Bank                      110.00 EUR\n  \
+            Assets:Receivable:AcmeLtd       -110.00 EUR\n\n");
+    }
+}
@@ -0,0 +1,32 @@
+pub mod einvoice;
+pub mod facturx;
+pub mod journal;
+pub mod pdf;
+pub mod template;
+
+/// 1234.5 -> "1,234.50"
+pub(crate) fn format_money(amount: f64) -> String {
+    let formatted = format!("{:.2}", amount.abs());
+    let (int_part, frac_part) = formatted.split_once('.').unwrap_or((&formatted, "00"));
+    let mut grouped = String::new();
+    for (i, c) in int_part.chars().enumerate() {
+        if i > 0 && (int_part.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    let sign = if amount < 0.0 && formatted != "0.00" { "-" } else { "" };
+    format!("{}{}.{}", sign, grouped, frac_part)
+}
+
+/// Line quantity with its unit: "2.5h", "3 pcs", "1"
+pub(crate) fn format_quantity(quantity: f64, unit: Option<&str>) -> String {
+    let qty = format!("{:.2}", quantity);
+    let qty = qty.trim_end_matches('0').trim_end_matches('.');
+    match unit {
+        Some("hours") => format!("{}h", qty),
+        Some("fixed") => qty.to_string(),
+        Some(unit) if !unit.is_empty() => format!("{} {}", qty, unit),
+        _ => qty.to_string(),
+    }
+}
@@ -0,0 +1,538 @@
+use crate::core::models::*;
+use super::{format_money, format_quantity};
+use owned_ttf_parser::{AsFaceRef, OwnedFace};
+use printpdf::*;
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+const ROW_HEIGHT: f32 = 5.5;
+/// Lowest baseline before breaking to a new page (leaves room for the footer)
+const BOTTOM: f32 = 25.0;
+const PT_TO_MM: f32 = 0.352_778;
+
+// Item table columns: description on the left, numbers right-aligned
+const DESCRIPTION_WIDTH: f32 = 95.0;
+const QTY_RIGHT: f32 = 135.0;
+const RATE_RIGHT: f32 = 162.0;
+const AMOUNT_RIGHT: f32 = PAGE_WIDTH - MARGIN;
+
+// Timesheet columns: date, session, hours
+const TIMESHEET_DESCRIPTION_X: f32 = MARGIN + 25.0;
+const TIMESHEET_DESCRIPTION_WIDTH: f32 = 125.0;
+
+/// Left edge of the Bill To column when a sender profile is printed
+const BILL_TO_X: f32 = 110.0;
+const PARTY_WIDTH: f32 = 80.0;
+const LOGO_MAX_HEIGHT: f32 = 18.0;
+const LOGO_MAX_WIDTH: f32 = 60.0;
+
+/// System fonts with wide Unicode coverage, tried in order when no font is configured
+const FALLBACK_FONTS: &[&str] = &[
+    "C:\\Windows\\Fonts\\arialuni.ttf",
+    "C:\\Windows\\Fonts\\segoeui.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+    "/Library/Fonts/Arial Unicode.ttf",
+    "/System/Library/Fonts/Supplemental/Arial Unicode.ttf",
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "/usr/share/fonts/truetype/noto/NotoSans-Regular.ttf",
+    "/usr/share/fonts/noto/NotoSans-Regular.ttf",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+];
+
+/// Read the configured TrueType font, or the first usable system font.
+/// Without one, rendering falls back to built-in Helvetica (Latin-1 only).
+pub fn load_font(configured: Option<&str>) -> Option<Vec<u8>> {
+    configured.into_iter()
+        .chain(FALLBACK_FONTS.iter().copied())
+        .filter_map(|path| std::fs::read(path).ok())
+        .find(|data| owned_ttf_parser::Face::parse(data, 0).is_ok())
+}
+
+/// Render an invoice to PDF bytes. Amounts are printed exactly as stored.
+/// The sender block, logo and bank details come from `profile`, if any.
+pub fn render_invoice(invoice: &Invoice, profile: Option<&BusinessProfile>, font_data: Option<Vec<u8>>) -> Result<Vec<u8>, String> {
+    let (title, document) = if invoice.is_credit_note() {
+        ("CREDIT NOTE", "Credit note")
+    } else {
+        ("INVOICE", "Invoice")
+    };
+    let mut w = PdfWriter::new(&format!("{} {}", document, invoice.invoice_number), font_data)?;
+
+    // Header, with the logo above the title
+    let header_top = w.y;
+    if let Some(path) = profile.and_then(|p| p.logo_path.as_deref()) {
+        w.logo(path)?;
+    }
+    w.text(title, 22.0, MARGIN, w.y);
+    let title_y = w.y;
+    w.y = header_top;
+    w.text_right(&format!("# {}", invoice.invoice_number), 11.0, AMOUNT_RIGHT, w.y);
+    w.y -= 7.0;
+    w.text_right(&format!("Date: {}", invoice.invoice_date()), 9.0, AMOUNT_RIGHT, w.y);
+    w.y -= 5.0;
+    match &invoice.credited_invoice_number {
+        Some(number) => w.text_right(&format!("Credits invoice: {}", number), 9.0, AMOUNT_RIGHT, w.y),
+        None => w.text_right(&format!("Due: {}", invoice.due_date), 9.0, AMOUNT_RIGHT, w.y),
+    }
+    w.y -= 5.0;
+    w.text_right(&format!("Status: {}", invoice.status.to_string()), 9.0, AMOUNT_RIGHT, w.y);
+    w.y = w.y.min(title_y);
+
+    // From / Bill To
+    w.y -= 10.0;
+    let parties_top = w.y;
+    let mut bottom = w.y;
+    let bill_to_x = match profile {
+        Some(profile) => {
+            let mut lines = vec![];
+            lines.extend(profile.address.iter().flat_map(|a| a.lines().map(str::to_string)));
+            let locality = [profile.postal_code.as_deref(), profile.city.as_deref(), profile.country_code.as_deref()]
+                .iter()
+                .flatten()
+                .copied()
+                .collect::<Vec<_>>()
+                .join(" ");
+            if !locality.is_empty() {
+                lines.push(locality);
+            }
+            lines.extend(profile.email.clone());
+            lines.extend(profile.phone.clone());
+            lines.extend(profile.tax_id.as_ref().map(|t| format!("Tax ID: {}", t)));
+            w.party("From", &profile.business_name, &lines, MARGIN);
+            bottom = w.y;
+            w.y = parties_top;
+            BILL_TO_X
+        }
+        None => MARGIN,
+    };
+    let client_lines = invoice.client_email.iter().cloned().collect::<Vec<_>>();
+    w.party("Bill To", &invoice.client_name, &client_lines, bill_to_x);
+    w.y = w.y.min(bottom);
+
+    // Items
+    w.y -= 12.0;
+    w.table_header();
+    for item in &invoice.items {
+        let lines = w.wrap(&item.description, 9.0, DESCRIPTION_WIDTH);
+        w.ensure_space(ROW_HEIGHT * lines.len() as f32, Some(PdfWriter::table_header));
+        w.text_right(&format_quantity(item.hours, item.unit.as_deref()), 9.0, QTY_RIGHT, w.y);
+        w.text_right(&format_money(item.rate), 9.0, RATE_RIGHT, w.y);
+        w.text_right(&format_money(item.amount), 9.0, AMOUNT_RIGHT, w.y);
+        for line in &lines {
+            w.text(line, 9.0, MARGIN, w.y);
+            w.y -= ROW_HEIGHT;
+        }
+        if let Some(discount) = &item.discount {
+            w.ensure_space(ROW_HEIGHT, Some(PdfWriter::table_header));
+            w.grey();
+            w.text(&format!("  Discount {}", format_discount(discount)), 8.0, MARGIN, w.y);
+            w.black();
+            w.y -= ROW_HEIGHT;
+        }
+    }
+    w.rule(w.y + ROW_HEIGHT - 1.5);
+
+    // Totals
+    let mut totals: Vec<(String, String)> = vec![("Subtotal".to_string(), format_money(invoice.subtotal))];
+    if invoice.discount_amount != 0.0 {
+        let label = match &invoice.discount {
+            Some(d) => format!("Discount {}", format_discount(d)),
+            None => "Discount".to_string(),
+        };
+        totals.push((label, format!("-{}", format_money(invoice.discount_amount))));
+    }
+    for tax in &invoice.taxes {
+        let amount = match tax.kind {
+            TaxKind::Withholding => format!("-{}", format_money(tax.amount)),
+            _ => format_money(tax.amount),
+        };
+        totals.push((tax_label(tax), amount));
+    }
+    w.ensure_space(ROW_HEIGHT * (totals.len() + 3) as f32, None);
+    w.y -= 3.0;
+    for (label, amount) in &totals {
+        w.grey();
+        w.text_right(label, 9.0, RATE_RIGHT, w.y);
+        w.black();
+        w.text_right(amount, 9.0, AMOUNT_RIGHT, w.y);
+        w.y -= ROW_HEIGHT;
+    }
+    w.y -= 2.0;
+    w.text_right("Total", 12.0, RATE_RIGHT, w.y);
+    w.text_right(&format_money(invoice.total), 12.0, AMOUNT_RIGHT, w.y);
+    if invoice.amount_paid > 0.0 || invoice.amount_credited > 0.0 {
+        w.y -= 1.0;
+        for (label, amount) in [("Paid", invoice.amount_paid), ("Credited", invoice.amount_credited)] {
+            if amount > 0.0 {
+                w.y -= ROW_HEIGHT;
+                w.grey();
+                w.text_right(label, 9.0, RATE_RIGHT, w.y);
+                w.black();
+                w.text_right(&format!("-{}", format_money(amount)), 9.0, AMOUNT_RIGHT, w.y);
+            }
+        }
+        w.y -= ROW_HEIGHT;
+        w.text_right("Balance due", 10.0, RATE_RIGHT, w.y);
+        w.text_right(&format_money(invoice.balance_due), 10.0, AMOUNT_RIGHT, w.y);
+    }
+
+    // Payment instructions
+    if let Some(bank_details) = profile.and_then(|p| p.bank_details.as_deref()) {
+        w.y -= 12.0;
+        w.ensure_space(ROW_HEIGHT * 2.0, None);
+        w.grey();
+        w.text("Payment details", 9.0, MARGIN, w.y);
+        w.black();
+        w.y -= ROW_HEIGHT;
+        w.paragraphs(bank_details);
+    }
+
+    // Notes and tax statements (e.g. reverse charge)
+    let notes = invoice.notes.iter()
+        .chain(invoice.taxes.iter().filter_map(|t| t.note.as_ref()))
+        .collect::<Vec<_>>();
+    if !notes.is_empty() {
+        w.y -= 12.0;
+        w.ensure_space(ROW_HEIGHT * 2.0, None);
+        w.grey();
+        w.text("Notes", 9.0, MARGIN, w.y);
+        w.black();
+        w.y -= ROW_HEIGHT;
+        for note in notes {
+            w.paragraphs(note);
+        }
+    }
+
+    // Per-session breakdown behind grouped lines
+    if !invoice.timesheet.is_empty() {
+        w.y -= 12.0;
+        w.ensure_space(ROW_HEIGHT * 4.0, None);
+        w.text("Timesheet", 12.0, MARGIN, w.y);
+        w.y -= ROW_HEIGHT + 2.0;
+        w.timesheet_header();
+        for entry in &invoice.timesheet {
+            let mut session = format!("{} - {}", entry.session_type.as_deref().unwrap_or("Work"), entry.project_name);
+            if let Some(description) = entry.description.as_deref().filter(|d| !d.trim().is_empty()) {
+                session = format!("{}: {}", session, description);
+            }
+            let lines = w.wrap(&session, 9.0, TIMESHEET_DESCRIPTION_WIDTH);
+            w.ensure_space(ROW_HEIGHT * lines.len() as f32, Some(PdfWriter::timesheet_header));
+            w.text(&entry.date, 9.0, MARGIN, w.y);
+            w.text_right(&format_quantity(entry.hours, None), 9.0, AMOUNT_RIGHT, w.y);
+            for line in &lines {
+                w.text(line, 9.0, TIMESHEET_DESCRIPTION_X, w.y);
+                w.y -= ROW_HEIGHT;
+            }
+        }
+        let total_hours: f64 = invoice.timesheet.iter().map(|e| e.hours).sum();
+        w.rule(w.y + ROW_HEIGHT - 1.5);
+        w.y -= 1.0;
+        w.text_right("Total hours", 9.0, RATE_RIGHT, w.y);
+        w.text_right(&format_quantity(total_hours, None), 9.0, AMOUNT_RIGHT, w.y);
+    }
+
+    w.finish()
+}
+
+/// Page state and text helpers over a printpdf document
+struct PdfWriter {
+    doc: PdfDocumentReference,
+    font: IndirectFontRef,
+    /// Glyph metrics of the embedded font; `None` means built-in Helvetica
+    face: Option<OwnedFace>,
+    layers: Vec<PdfLayerReference>,
+    /// Current baseline, in mm from the bottom of the page
+    y: f32,
+}
+
+impl PdfWriter {
+    fn new(title: &str, font_data: Option<Vec<u8>>) -> Result<Self, String> {
+        let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        let (font, face) = match font_data {
+            Some(data) => {
+                let font = doc.add_external_font(data.as_slice())
+                    .map_err(|e| format!("Failed to embed font: {}", e))?;
+                let face = OwnedFace::from_vec(data, 0)
+                    .map_err(|e| format!("Failed to read font: {}", e))?;
+                (font, Some(face))
+            }
+            None => {
+                let font = doc.add_builtin_font(BuiltinFont::Helvetica)
+                    .map_err(|e| format!("Failed to load font: {}", e))?;
+                (font, None)
+            }
+        };
+        let layer = doc.get_page(page).get_layer(layer);
+        Ok(PdfWriter { doc, font, face, layers: vec![layer], y: PAGE_HEIGHT - MARGIN })
+    }
+
+    fn layer(&self) -> &PdfLayerReference {
+        self.layers.last().expect("at least one page")
+    }
+
+    fn text(&self, text: &str, size: f32, x: f32, y: f32) {
+        self.layer().use_text(text, size, Mm(x), Mm(y), &self.font);
+    }
+
+    fn text_right(&self, text: &str, size: f32, right: f32, y: f32) {
+        self.text(text, size, right - self.width(text, size), y);
+    }
+
+    fn grey(&self) {
+        self.layer().set_fill_color(Color::Rgb(Rgb::new(0.45, 0.45, 0.45, None)));
+    }
+
+    fn black(&self) {
+        self.layer().set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+    }
+
+    fn rule(&self, y: f32) {
+        let layer = self.layer();
+        layer.set_outline_color(Color::Rgb(Rgb::new(0.8, 0.8, 0.8, None)));
+        layer.set_outline_thickness(0.5);
+        layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(MARGIN), Mm(y)), false),
+                (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(y)), false),
+            ],
+            is_closed: false,
+        });
+    }
+
+    /// Draw a logo at the current position, scaled to fit the logo box,
+    /// and move below it
+    fn logo(&mut self, path: &str) -> Result<(), String> {
+        let data = std::fs::read(path).map_err(|e| format!("Failed to read logo {}: {}", path, e))?;
+        let image = image_crate::load_from_memory(&data)
+            .map_err(|e| format!("Unsupported logo image {}: {}", path, e))?;
+        let (px_width, px_height) = (image.width() as f32, image.height() as f32);
+        if px_width == 0.0 || px_height == 0.0 {
+            return Ok(());
+        }
+        // 1px is 25.4 / dpi mm
+        let dpi = (px_height * 25.4 / LOGO_MAX_HEIGHT).max(px_width * 25.4 / LOGO_MAX_WIDTH);
+        let height = px_height * 25.4 / dpi;
+        let top = self.y + 6.0;
+        Image::from_dynamic_image(&image).add_to_layer(self.layer().clone(), ImageTransform {
+            translate_x: Some(Mm(MARGIN)),
+            translate_y: Some(Mm(top - height)),
+            dpi: Some(dpi),
+            ..Default::default()
+        });
+        self.y = top - height - 9.0;
+        Ok(())
+    }
+
+    /// Label, name and detail lines in a column starting at `x`
+    fn party(&mut self, label: &str, name: &str, lines: &[String], x: f32) {
+        self.grey();
+        self.text(label, 9.0, x, self.y);
+        self.black();
+        self.y -= 6.0;
+        for (i, name_line) in self.wrap(name, 12.0, PARTY_WIDTH).iter().enumerate() {
+            if i > 0 {
+                self.y -= 5.5;
+            }
+            self.text(name_line, 12.0, x, self.y);
+        }
+        for line in lines {
+            for wrapped in self.wrap(line, 9.0, PARTY_WIDTH) {
+                self.y -= 4.5;
+                self.text(&wrapped, 9.0, x, self.y);
+            }
+        }
+    }
+
+    /// Wrapped full-width text, one paragraph per line of `text`
+    fn paragraphs(&mut self, text: &str) {
+        for paragraph in text.lines() {
+            for line in self.wrap(paragraph, 9.0, PAGE_WIDTH - 2.0 * MARGIN) {
+                self.ensure_space(ROW_HEIGHT, None);
+                self.text(&line, 9.0, MARGIN, self.y);
+                self.y -= ROW_HEIGHT;
+            }
+        }
+    }
+
+    fn table_header(&mut self) {
+        self.grey();
+        self.text("Description", 9.0, MARGIN, self.y);
+        self.text_right("Qty", 9.0, QTY_RIGHT, self.y);
+        self.text_right("Rate", 9.0, RATE_RIGHT, self.y);
+        self.text_right("Amount", 9.0, AMOUNT_RIGHT, self.y);
+        self.black();
+        self.rule(self.y - 2.0);
+        self.y -= ROW_HEIGHT + 2.0;
+    }
+
+    fn timesheet_header(&mut self) {
+        self.grey();
+        self.text("Date", 9.0, MARGIN, self.y);
+        self.text("Session", 9.0, TIMESHEET_DESCRIPTION_X, self.y);
+        self.text_right("Hours", 9.0, AMOUNT_RIGHT, self.y);
+        self.black();
+        self.rule(self.y - 2.0);
+        self.y -= ROW_HEIGHT + 2.0;
+    }
+
+    /// Start a new page if `height` doesn't fit, repeating `header` if given
+    fn ensure_space(&mut self, height: f32, header: Option<fn(&mut PdfWriter)>) {
+        if self.y - height + ROW_HEIGHT >= BOTTOM {
+            return;
+        }
+        let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        self.layers.push(self.doc.get_page(page).get_layer(layer));
+        self.y = PAGE_HEIGHT - MARGIN;
+        if let Some(header) = header {
+            header(self);
+        }
+    }
+
+    /// Width of `text` in mm
+    fn width(&self, text: &str, size: f32) -> f32 {
+        let em: f32 = match &self.face {
+            Some(face) => {
+                let face = face.as_face_ref();
+                let units = face.units_per_em() as f32;
+                text.chars()
+                    .map(|c| {
+                        face.glyph_index(c)
+                            .and_then(|g| face.glyph_hor_advance(g))
+                            .unwrap_or(0) as f32 / units
+                    })
+                    .sum()
+            }
+            None => text.chars().map(helvetica_width).sum(),
+        };
+        em * size * PT_TO_MM
+    }
+
+    /// Greedy word wrap to `max_width` mm; overlong words are split
+    fn wrap(&self, text: &str, size: f32, max_width: f32) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        for word in text.split_whitespace() {
+            let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+            if self.width(&candidate, size) <= max_width {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                line.push(c);
+                if self.width(&line, size) > max_width && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(std::mem::take(&mut line));
+                    line.push(c);
+                }
+            }
+        }
+        if !line.is_empty() || lines.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// Add page numbers and serialize
+    fn finish(self) -> Result<Vec<u8>, String> {
+        let count = self.layers.len();
+        for (i, layer) in self.layers.iter().enumerate() {
+            let label = format!("Page {} of {}", i + 1, count);
+            let x = PAGE_WIDTH - MARGIN - self.width(&label, 8.0);
+            layer.set_fill_color(Color::Rgb(Rgb::new(0.6, 0.6, 0.6, None)));
+            layer.use_text(label, 8.0, Mm(x), Mm(12.0), &self.font);
+        }
+        self.doc.save_to_bytes().map_err(|e| format!("Failed to write PDF: {}", e))
+    }
+}
+
+/// Approximate Helvetica advance widths (in em) for the built-in font fallback
+fn helvetica_width(c: char) -> f32 {
+    match c {
+        ' ' | '.' | ',' | ':' | ';' | '!' | '|' | 'i' | 'j' | 'l' | 'I' | '\'' => 0.278,
+        '-' | '(' | ')' | 'f' | 't' | 'r' | '/' => 0.333,
+        'm' | 'M' | 'W' => 0.833,
+        'w' | '%' => 0.889,
+        'A'..='Z' => 0.667,
+        '#' | '$' | '0'..='9' | 'a'..='z' => 0.556,
+        _ => 0.556,
+    }
+}
+
+fn format_discount(discount: &Discount) -> String {
+    match discount.discount_type {
+        DiscountType::Percent => format!("({}%)", discount.value),
+        DiscountType::Fixed => format!("({})", format_money(discount.value)),
+    }
+}
+
+/// Rates are stored as percentages (20 = 20%)
+fn tax_label(tax: &InvoiceTax) -> String {
+    let suffix = match tax.kind {
+        TaxKind::Additive => "",
+        TaxKind::Inclusive => ", incl.",
+        TaxKind::Withholding => ", withheld",
+    };
+    format!("{} ({}%{})", tax.name, tax.rate, suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::einvoice::tests::{invoice, seller};
+    use printpdf::lopdf;
+
+    fn page_texts(pdf: &[u8]) -> Vec<String> {
+        let doc = lopdf::Document::load_mem(pdf).unwrap();
+        doc.get_pages().keys().map(|page| doc.extract_text(&[*page]).unwrap()).collect()
+    }
+
+    #[test]
+    fn long_item_tables_break_across_pages() {
+        let short = invoice(None);
+        let mut long = short.clone();
+        let line = short.items[0].clone();
+        long.items = (1..=90).map(|n| InvoiceItem { id: n, description: format!("Line {}", n), ..line.clone() }).collect();
+
+        let pages = page_texts(&render_invoice(&long, Some(&seller()), None).unwrap());
+        assert!(pages.len() >= 3, "{} pages", pages.len());
+        for (i, page) in pages.iter().enumerate() {
+            assert!(page.contains(&format!("Page {} of {}", i + 1, pages.len())));
+            assert!(page.contains("Description"), "no table header on page {}", i + 1);
+        }
+        assert!(pages[0].contains("Line 1\n") && pages.last().unwrap().contains("Line 90"));
+        assert!(pages.last().unwrap().contains("Total"));
+
+        assert_eq!(page_texts(&render_invoice(&short, Some(&seller()), None).unwrap()).len(), 1);
+    }
+
+    #[test]
+    fn embedded_font_renders_non_latin_names() {
+        let Some(font) = load_font(None) else {
+            eprintln!("no system TrueType font found; skipping");
+            return;
+        };
+        let face = owned_ttf_parser::Face::parse(&font, 0).unwrap();
+        let mut invoice = invoice(None);
+        invoice.client_name = "Ωμέγα Τεχνική ООО".to_string();
+        let expected: String = invoice.client_name.chars()
+            .map(|c| face.glyph_index(c).map(|g| format!("{:04X}", g.0)).unwrap_or_default())
+            .collect();
+        assert_eq!(expected.len(), invoice.client_name.chars().count() * 4, "font lacks a glyph");
+
+        let pdf = render_invoice(&invoice, Some(&seller()), Some(font.clone())).unwrap();
+        let doc = lopdf::Document::load_mem(&pdf).unwrap();
+        let embedded = doc.objects.values()
+            .filter_map(|o| o.as_dict().ok())
+            .filter_map(|d| d.get(b"FontFile2").ok())
+            .count();
+        assert_eq!(embedded, 1);
+        let page = *doc.get_pages().values().next().unwrap();
+        let content = String::from_utf8_lossy(&doc.get_page_content(page).unwrap()).to_uppercase();
+        assert!(content.contains(&format!("<{}> TJ", expected)), "client name not drawn with the embedded font");
+    }
+}
@@ -0,0 +1,90 @@
+use crate::core::models::*;
+use super::{format_money, format_quantity};
+use base64::Engine;
+use handlebars::{handlebars_helper, Handlebars};
+use serde_json::json;
+
+/// Template used when an invoice has none; users start their own from a copy of it
+pub const DEFAULT_TEMPLATE: &str = include_str!("templates/invoice.hbs");
+
+handlebars_helper!(money: |amount: f64| format_money(amount));
+handlebars_helper!(quantity: |qty: f64, unit: Json| format_quantity(qty, unit.as_str()));
+
+/// The built-in template, listed alongside stored ones with id 0
+pub fn builtin_template() -> InvoiceTemplate {
+    InvoiceTemplate {
+        id: 0,
+        name: "Default".to_string(),
+        body: DEFAULT_TEMPLATE.to_string(),
+        is_builtin: true,
+    }
+}
+
+/// Check that a template body parses
+pub fn validate_template(body: &str) -> Result<(), String> {
+    Handlebars::new()
+        .register_template_string("invoice", body)
+        .map_err(|e| format!("Invalid template: {}", e))
+}
+
+/// Render an invoice to HTML with a Handlebars template.
+///
+/// The template sees `invoice` and `profile` as serialized, plus `logo` (the
+/// profile's logo as a data URI) and `notes` (invoice notes followed by tax
+/// statements) and `timesheet_hours` (total of `invoice.timesheet`). Helpers: `{{money amount}}` and `{{quantity hours unit}}`.
+/// Values are HTML-escaped.
+pub fn render_invoice_html(invoice: &Invoice, profile: Option<&BusinessProfile>, body: &str) -> Result<String, String> {
+    let mut hb = Handlebars::new();
+    hb.register_helper("money", Box::new(money));
+    hb.register_helper("quantity", Box::new(quantity));
+
+    let notes = invoice.notes.iter()
+        .chain(invoice.taxes.iter().filter_map(|t| t.note.as_ref()))
+        .collect::<Vec<_>>();
+    let logo = match profile.and_then(|p| p.logo_path.as_deref()) {
+        Some(path) => Some(logo_data_uri(path)?),
+        None => None,
+    };
+    let context = json!({
+        "invoice": invoice,
+        "profile": profile,
+        "logo": logo,
+        "notes": notes,
+        "timesheet_hours": invoice.timesheet.iter().map(|e| e.hours).sum::<f64>(),
+    });
+
+    hb.render_template(body, &context)
+        .map_err(|e| format!("Failed to render template: {}", e))
+}
+
+/// Inline the logo so the HTML stays self-contained
+fn logo_data_uri(path: &str) -> Result<String, String> {
+    let data = std::fs::read(path).map_err(|e| format!("Failed to read logo {}: {}", path, e))?;
+    let extension = std::path::Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let mime = match extension.as_deref() {
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("svg") => "image/svg+xml",
+        _ => "image/png",
+    };
+    Ok(format!("data:{};base64,{}", mime, base64::engine::general_purpose::STANDARD.encode(data)))
+}
+
+/// Render an email subject or body template. Same context and helpers as
+/// `render_invoice_html`, plus `days_overdue` for reminders, but plain text:
+/// values are not escaped.
+pub fn render_email_text(template: &str, invoice: &Invoice, profile: Option<&BusinessProfile>, days_overdue: Option<i64>) -> Result<String, String> {
+    let mut hb = Handlebars::new();
+    hb.register_helper("money", Box::new(money));
+    hb.register_helper("quantity", Box::new(quantity));
+    hb.register_escape_fn(handlebars::no_escape);
+    let context = json!({
+        "invoice": invoice,
+        "profile": profile,
+        "days_overdue": days_overdue,
+    });
+    hb.render_template(template, &context)
+        .map_err(|e| format!("Failed to render email template: {}", e))
+}
@@ -0,0 +1,294 @@
+use crate::core::models::{BankCsvFormat, NewBankTransaction};
+use std::collections::HashMap;
+
+/// Read an OFX (1.x SGML or 2.x XML) or CSV bank statement. CSV needs the
+/// bank's column layout; OFX is recognised from its header.
+pub fn parse_statement(text: &str, csv_format: Option<&BankCsvFormat>) -> Result<Vec<NewBankTransaction>, String> {
+    let text = text.trim_start_matches('\u{feff}');
+    if is_ofx(text) {
+        return parse_ofx(text);
+    }
+    let format = csv_format.ok_or("Set up the CSV column layout for this bank first")?;
+    parse_csv(text, format)
+}
+
+fn is_ofx(text: &str) -> bool {
+    let head: String = text.chars().take(1024).collect::<String>().to_ascii_uppercase();
+    head.trim_start().starts_with("OFXHEADER") || head.contains("<OFX>")
+}
+
+// ========== OFX ==========
+
+/// Transactions of every `<STMTTRN>` in the file
+pub fn parse_ofx(text: &str) -> Result<Vec<NewBankTransaction>, String> {
+    let upper = text.to_ascii_uppercase();
+    let account = tag_value(text, &upper, "ACCTID").unwrap_or_default();
+    let starts: Vec<usize> = upper.match_indices("<STMTTRN>").map(|(i, _)| i).collect();
+    if starts.is_empty() && !upper.contains("<BANKTRANLIST>") {
+        return Err("No bank transactions found in the OFX file".to_string());
+    }
+
+    let mut transactions = Vec::new();
+    for (n, start) in starts.iter().enumerate() {
+        let end = starts.get(n + 1).copied().unwrap_or(text.len());
+        let end = upper[*start..end].find("</STMTTRN>").map(|i| start + i).unwrap_or(end);
+        let (block, block_upper) = (&text[*start..end], &upper[*start..end]);
+        let field = |tag: &str| tag_value(block, block_upper, tag);
+
+        let posted = field("DTPOSTED").ok_or("OFX transaction without DTPOSTED")?;
+        let date = ofx_date(&posted).ok_or_else(|| format!("Invalid OFX date: {}", posted))?;
+        let amount_text = field("TRNAMT").ok_or("OFX transaction without TRNAMT")?;
+        let amount = amount_text.replace(',', ".").parse::<f64>()
+            .map_err(|_| format!("Invalid OFX amount: {}", amount_text))?;
+        let name = field("NAME").or_else(|| field("PAYEE"));
+        let memo = field("MEMO");
+        let fitid = field("FITID").unwrap_or_else(|| format!("{}:{}:{}", date, amount, n));
+
+        transactions.push(NewBankTransaction {
+            external_id: format!("ofx:{}:{}", account, fitid),
+            date,
+            amount,
+            description: memo.or_else(|| name.clone()).unwrap_or_default(),
+            reference: field("REFNUM").or_else(|| field("CHECKNUM")),
+            counterparty: name,
+        });
+    }
+    Ok(transactions)
+}
+
+/// Value of the first `<TAG>` in an OFX block: the text up to the next tag or
+/// line end, as SGML OFX leaves elements unclosed. `upper` is `block` uppercased.
+fn tag_value(block: &str, upper: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let start = upper.find(&open)? + open.len();
+    let rest = &block[start..];
+    let end = rest.find(['<', '\r', '\n']).unwrap_or(rest.len());
+    let value = decode_entities(rest[..end].trim());
+    if value.is_empty() { None } else { Some(value) }
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// OFX dates start YYYYMMDD; time and time zone, if any, are ignored
+fn ofx_date(value: &str) -> Option<String> {
+    let digits = value.get(..8)?;
+    chrono::NaiveDate::parse_from_str(digits, "%Y%m%d")
+        .ok()
+        .map(|d| d.format("%Y-%m-%d").to_string())
+}
+
+// ========== CSV ==========
+
+/// Transactions of a CSV export laid out as `format` describes. Blank rows are
+/// skipped; any other row that can't be read fails the import with its line number.
+pub fn parse_csv(text: &str, format: &BankCsvFormat) -> Result<Vec<NewBankTransaction>, String> {
+    let records = csv_records(text, format.delimiter)?;
+    let first_row = format.skip_rows + usize::from(format.has_header);
+    // Identical lines on the same day are numbered so each gets its own id
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut transactions = Vec::new();
+
+    for (line, record) in records.iter().skip(first_row) {
+        if record.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+        let column = |index: usize| -> Result<&str, String> {
+            record.get(index)
+                .map(|f| f.trim())
+                .ok_or_else(|| format!("Line {}: no column {}", line, index + 1))
+        };
+        let optional = |index: Option<usize>| -> Option<String> {
+            index.and_then(|i| record.get(i))
+                .map(|f| f.trim().to_string())
+                .filter(|f| !f.is_empty())
+        };
+
+        let date_text = column(format.date_column)?;
+        let date = chrono::NaiveDate::parse_from_str(date_text, &format.date_format)
+            .map_err(|_| format!("Line {}: date {} doesn't match {}", line, date_text, format.date_format))?
+            .format("%Y-%m-%d")
+            .to_string();
+        let amount = match format.amount_column {
+            Some(index) => parse_amount(column(index)?, format.decimal_comma)
+                .ok_or_else(|| format!("Line {}: invalid amount", line))?,
+            None => {
+                let credit = optional(format.credit_column)
+                    .map(|c| parse_amount(&c, format.decimal_comma).ok_or_else(|| format!("Line {}: invalid credit", line)))
+                    .transpose()?
+                    .unwrap_or(0.0);
+                let debit = optional(format.debit_column)
+                    .map(|d| parse_amount(&d, format.decimal_comma).ok_or_else(|| format!("Line {}: invalid debit", line)))
+                    .transpose()?
+                    .unwrap_or(0.0);
+                credit.abs() - debit.abs()
+            }
+        };
+        let description = column(format.description_column)?.to_string();
+
+        let key = format!("{}:{:.2}:{}", date, amount, description);
+        let occurrence = seen.entry(key.clone()).or_insert(0);
+        *occurrence += 1;
+
+        transactions.push(NewBankTransaction {
+            external_id: format!("csv:{}:{}", key, occurrence),
+            date,
+            amount,
+            description,
+            reference: optional(format.reference_column),
+            counterparty: optional(format.counterparty_column),
+        });
+    }
+    Ok(transactions)
+}
+
+/// Records with the line each starts on. Handles quoted fields, doubled
+/// quotes and line breaks inside quotes.
+fn csv_records(text: &str, delimiter: char) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                in_quotes = true;
+            }
+            '\r' => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push((record_line, std::mem::take(&mut record)));
+                line += 1;
+                record_line = line;
+            }
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(format!("Line {}: unterminated quoted field", record_line));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((record_line, record));
+    }
+    Ok(records)
+}
+
+/// "1,234.50", "-12.00", "(12.00)", "€ 12,50" with `decimal_comma`
+fn parse_amount(text: &str, decimal_comma: bool) -> Option<f64> {
+    let negative = text.contains('-') || (text.starts_with('(') && text.ends_with(')'));
+    let digits: String = text.chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+        .collect();
+    let normalized = if decimal_comma {
+        digits.replace('.', "").replace(',', ".")
+    } else {
+        digits.replace(',', "")
+    };
+    let value = normalized.parse::<f64>().ok()?;
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn csv_format() -> BankCsvFormat {
+        BankCsvFormat {
+            delimiter: ';',
+            skip_rows: 1,
+            has_header: true,
+            date_column: 0,
+            date_format: "%d/%m/%Y".to_string(),
+            amount_column: None,
+            credit_column: Some(2),
+            debit_column: Some(3),
+            description_column: 1,
+            reference_column: Some(4),
+            counterparty_column: None,
+            decimal_comma: true,
+        }
+    }
+
+    #[test]
+    fn sgml_ofx_transactions_are_read() {
+        let ofx = "OFXHEADER:100\r\nDATA:OFXSGML\r\n\r\n<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS>\r\n\
+            <BANKACCTFROM><ACCTID>12345</BANKACCTFROM>\r\n<BANKTRANLIST>\r\n\
+            <STMTTRN><TRNTYPE>CREDIT<DTPOSTED>20240305120000[0:GMT]<TRNAMT>1250.00<FITID>A1\r\n\
+            <NAME>ACME &amp; CO<MEMO>INV-0042</STMTTRN>\r\n\
+            <STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20240306<TRNAMT>-9,99<FITID>A2<NAME>Hosting</STMTTRN>\r\n\
+            </BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>";
+        let transactions = parse_statement(ofx, None).unwrap();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].external_id, "ofx:12345:A1");
+        assert_eq!(transactions[0].date, "2024-03-05");
+        assert_eq!(transactions[0].amount, 1250.0);
+        assert_eq!(transactions[0].description, "INV-0042");
+        assert_eq!(transactions[0].counterparty.as_deref(), Some("ACME & CO"));
+        assert_eq!(transactions[1].amount, -9.99);
+        assert_eq!(transactions[1].description, "Hosting");
+    }
+
+    #[test]
+    fn csv_needs_a_layout() {
+        assert!(parse_statement("Date;Text\n", None).is_err());
+    }
+
+    #[test]
+    fn csv_rows_follow_the_layout() {
+        let csv = "Account 123\nDate;Text;Credit;Debit;Ref\n\
+            05/03/2024;\"Payment \"\"INV-0042\"\"\";1.250,00;;R1\n\
+            \n\
+            05/03/2024;Fee;;2,50;\n\
+            05/03/2024;Fee;;2,50;\n";
+        let transactions = parse_csv(csv, &csv_format()).unwrap();
+        assert_eq!(transactions.len(), 3);
+        assert_eq!(transactions[0].description, "Payment \"INV-0042\"");
+        assert_eq!(transactions[0].amount, 1250.0);
+        assert_eq!(transactions[0].reference.as_deref(), Some("R1"));
+        assert_eq!(transactions[1].amount, -2.5);
+        assert_ne!(transactions[1].external_id, transactions[2].external_id);
+    }
+
+    #[test]
+    fn csv_errors_name_the_line() {
+        let csv = "Account 123\nDate;Text;Credit;Debit;Ref\n2024-03-05;Fee;;2,50;\n";
+        assert_eq!(parse_csv(csv, &csv_format()).unwrap_err(), "Line 3: date 2024-03-05 doesn't match %d/%m/%Y");
+        assert!(parse_csv("a;\"open\n", &csv_format()).unwrap_err().contains("unterminated"));
+    }
+
+    #[test]
+    fn amounts_in_local_formats() {
+        assert_eq!(parse_amount("1,234.50", false), Some(1234.5));
+        assert_eq!(parse_amount("(12.00)", false), Some(-12.0));
+        assert_eq!(parse_amount("€ 12,50", true), Some(12.5));
+        assert_eq!(parse_amount("-1.234,56", true), Some(-1234.56));
+        assert_eq!(parse_amount("n/a", false), None);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::core::logic;
+use crate::core::models::{Invoice, PaymentReminder, Quote, ReminderChannel, RetainerBilling};
+use crate::mail;
+use crate::storage::db::Database;
+use chrono::Local;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+/// Emitted with the invoices (`Vec<Invoice>`) that just became overdue
+pub const INVOICES_OVERDUE_EVENT: &str = "invoices-overdue";
+
+/// Emitted with the quotes (`Vec<Quote>`) that just expired
+pub const QUOTES_EXPIRED_EVENT: &str = "quotes-expired";
+
+/// Emitted with the draft invoices (`Vec<Invoice>`) generated for retainers
+pub const RETAINER_INVOICES_EVENT: &str = "retainer-invoices-created";
+
+/// Emitted with the retainers that couldn't be billed (`Vec<RetainerBillingFailure>`)
+pub const RETAINER_BILLING_FAILED_EVENT: &str = "retainer-billing-failed";
+
+/// Emitted with the reminders (`Vec<PaymentReminder>`) handled in a run
+pub const PAYMENT_REMINDERS_EVENT: &str = "payment-reminders";
+
+/// Longest sleep between checks. The monotonic clock stops while the machine
+/// is suspended, so wake at least hourly to notice that the date changed.
+const MAX_SLEEP: Duration = Duration::from_secs(60 * 60);
+
+// ========== OVERDUE INVOICES ==========
+
+/// Check for overdue invoices at startup and again whenever the local date changes
+pub fn spawn_overdue_job(app: AppHandle) {
+    spawn_daily_job(app, |app, today| run_overdue_check(app, today).map(|_| ()));
+}
+
+/// Move unpaid invoices due before `today` to Overdue and notify the UI.
+/// Returns the invoices that changed.
+pub fn run_overdue_check(app: &AppHandle, today: &str) -> Result<Vec<Invoice>, String> {
+    let db = app.state::<Database>();
+    let invoices = db.mark_overdue_invoices(today)
+        .map_err(|e| format!("Failed to check overdue invoices: {}", e))?;
+    if !invoices.is_empty() {
+        let _ = app.emit_all(INVOICES_OVERDUE_EVENT, invoices.clone());
+    }
+    Ok(invoices)
+}
+
+// ========== QUOTES ==========
+
+/// Expire sent quotes at startup and again whenever the local date changes
+pub fn spawn_quote_expiry_job(app: AppHandle) {
+    spawn_daily_job(app, |app, today| run_quote_expiry(app, today).map(|_| ()));
+}
+
+/// Move Sent quotes valid until before `today` to Expired and notify the UI.
+/// Returns the quotes that changed.
+pub fn run_quote_expiry(app: &AppHandle, today: &str) -> Result<Vec<Quote>, String> {
+    let db = app.state::<Database>();
+    let quotes = db.mark_expired_quotes(today)
+        .map_err(|e| format!("Failed to check expired quotes: {}", e))?;
+    if !quotes.is_empty() {
+        let _ = app.emit_all(QUOTES_EXPIRED_EVENT, quotes.clone());
+    }
+    Ok(quotes)
+}
+
+// ========== RETAINERS ==========
+
+/// Bill retainer periods that have ended, at startup and on each new day.
+/// Retainers that failed are retried on the next wake-up.
+pub fn spawn_retainer_job(app: AppHandle) {
+    spawn_daily_job(app, |app, today| {
+        let billing = run_retainer_billing(app, today)?;
+        if billing.failures.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("{} retainer(s) could not be billed", billing.failures.len()))
+        }
+    });
+}
+
+/// Generate drafts for retainer periods ended before `today` and notify the
+/// UI of the new invoices and of any retainer that failed.
+pub fn run_retainer_billing(app: &AppHandle, today: &str) -> Result<RetainerBilling, String> {
+    let db = app.state::<Database>();
+    let billing = db.bill_due_retainers(today)
+        .map_err(|e| format!("Failed to bill retainers: {}", e))?;
+    if !billing.invoices.is_empty() {
+        let _ = app.emit_all(RETAINER_INVOICES_EVENT, billing.invoices.clone());
+    }
+    if !billing.failures.is_empty() {
+        let _ = app.emit_all(RETAINER_BILLING_FAILED_EVENT, billing.failures.clone());
+    }
+    Ok(billing)
+}
+
+// ========== PAYMENT REMINDERS ==========
+
+/// Send payment reminders at startup and on each new day. Reminders that
+/// fail are not logged, so the next day's run picks them up again.
+pub fn spawn_reminder_job(app: AppHandle) {
+    spawn_daily_job(app, |app, today| run_payment_reminders(app, today).map(|_| ()));
+}
+
+/// Send the reminders due on `today`, log them and notify the UI. Blocks
+/// while emails are sent. Returns what happened to each reminder.
+pub fn run_payment_reminders(app: &AppHandle, today: &str) -> Result<Vec<PaymentReminder>, String> {
+    let db = app.state::<Database>();
+    let due = db.get_due_reminders(today)
+        .map_err(|e| format!("Failed to check payment reminders: {}", e))?;
+    if due.is_empty() {
+        return Ok(Vec::new());
+    }
+    let smtp = db.get_smtp_settings()
+        .map_err(|e| format!("Failed: {}", e))?;
+    let credentials = db.get_smtp_credentials()
+        .map_err(|e| format!("Failed: {}", e))?;
+    let today_date = chrono::NaiveDate::parse_from_str(today, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date: {}", today))?;
+
+    let mut reminders = Vec::new();
+    for reminder in due {
+        let channel = reminder.schedule.channel;
+        let mut recipient = None;
+        let mut error = None;
+        if channel == ReminderChannel::Email {
+            match (&smtp, reminder.invoice.client_email.as_deref().map(str::trim).filter(|e| !e.is_empty())) {
+                (Some(smtp), Some(to)) => {
+                    recipient = Some(to.to_string());
+                    let days_overdue = chrono::NaiveDate::parse_from_str(&reminder.invoice.due_date, "%Y-%m-%d")
+                        .map(|due| (today_date - due).num_days().max(0))
+                        .unwrap_or(0);
+                    let sent = mail::invoice_mail(
+                        &db,
+                        &reminder.invoice,
+                        to,
+                        &reminder.schedule.subject_template,
+                        &reminder.schedule.body_template,
+                        Some(days_overdue),
+                    )
+                    .and_then(|message| mail::send(smtp, credentials.as_ref(), &message));
+                    if let Err(e) = sent {
+                        error = Some(e);
+                    }
+                }
+                (None, _) => error = Some("Set up an SMTP server in settings to email reminders".to_string()),
+                (_, None) => error = Some("The invoice has no client email".to_string()),
+            }
+        }
+        // Only reminders delivered on their schedule's channel count as sent
+        if error.is_none() {
+            db.record_invoice_reminder(&reminder, channel, recipient.as_deref())
+                .map_err(|e| format!("Failed to log reminder: {}", e))?;
+        }
+        reminders.push(PaymentReminder {
+            invoice: reminder.invoice,
+            offset_days: reminder.offset_days,
+            channel,
+            recipient,
+            error,
+        });
+    }
+    let _ = app.emit_all(PAYMENT_REMINDERS_EVENT, reminders.clone());
+    Ok(reminders)
+}
+
+/// Run `job` at startup and again whenever the local date changes. Jobs
+/// block (database, mail), so each run goes to the blocking pool and is
+/// awaited before the next check.
+fn spawn_daily_job(app: AppHandle, job: fn(&AppHandle, &str) -> Result<(), String>) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_run: Option<String> = None;
+        loop {
+            let today = logic::get_today();
+            if last_run.as_deref() != Some(today.as_str()) {
+                let (job_app, job_today) = (app.clone(), today.clone());
+                let result = tauri::async_runtime::spawn_blocking(move || job(&job_app, &job_today)).await;
+                // A failed run is retried on the next wake-up
+                if matches!(result, Ok(Ok(()))) {
+                    last_run = Some(today);
+                }
+            }
+            tokio::time::sleep(until_next_day().min(MAX_SLEEP)).await;
+        }
+    });
+}
+
+fn until_next_day() -> Duration {
+    let now = Local::now().naive_local();
+    let next = (now.date() + chrono::Duration::days(1))
+        .and_hms_opt(0, 0, 1)
+        .expect("valid time");
+    (next - now).to_std().unwrap_or(MAX_SLEEP)
+}
@@ -0,0 +1,470 @@
+use crate::core::models::{Invoice, SmtpCredentials, SmtpSecurity, SmtpSettings};
+use crate::export::{pdf, template};
+use crate::storage::db::Database;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use native_tls::{TlsConnector, TlsStream};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Connect, read and write timeout for the SMTP conversation
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Email with a single attachment
+pub struct OutgoingMail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+    pub attachment_name: String,
+    pub attachment_type: String,
+    pub attachment: Vec<u8>,
+}
+
+/// Basic sanity check of an email address; the server has the final word
+pub fn validate_address(address: &str) -> Result<(), String> {
+    let invalid = || Err(format!("Invalid email address: {}", address));
+    let (local, domain) = match address.rsplit_once('@') {
+        Some(parts) => parts,
+        None => return invalid(),
+    };
+    if local.is_empty() || (!domain.contains('.') && domain != "localhost") {
+        return invalid();
+    }
+    if address.chars().any(|c| c.is_whitespace() || c.is_control() || matches!(c, '<' | '>' | ',' | ';')) {
+        return invalid();
+    }
+    Ok(())
+}
+
+/// Attachment file name for an invoice number, safe for every mail client
+pub fn attachment_filename(invoice_number: &str) -> String {
+    let name: String = invoice_number.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .collect();
+    format!("{}.pdf", name)
+}
+
+/// Email to `to` with the invoice PDF attached and the subject and body
+/// rendered from templates (see `template::render_email_text`)
+pub fn invoice_mail(
+    db: &Database,
+    invoice: &Invoice,
+    to: &str,
+    subject_template: &str,
+    body_template: &str,
+    days_overdue: Option<i64>,
+) -> Result<OutgoingMail, String> {
+    let profile = db.get_invoice_profile(invoice).map_err(|e| format!("Failed: {}", e))?;
+    let font_path = db.get_setting("pdf_font_path").map_err(|e| format!("Failed: {}", e))?;
+    let attachment = pdf::render_invoice(invoice, profile.as_ref(), pdf::load_font(font_path.as_deref()))?;
+    Ok(OutgoingMail {
+        to: to.to_string(),
+        subject: template::render_email_text(subject_template, invoice, profile.as_ref(), days_overdue)?,
+        body: template::render_email_text(body_template, invoice, profile.as_ref(), days_overdue)?,
+        attachment_name: attachment_filename(&invoice.invoice_number),
+        attachment_type: "application/pdf".to_string(),
+        attachment,
+    })
+}
+
+/// Send `mail` through the configured server. Blocks until the server accepts
+/// or rejects the message, so call it off the async runtime.
+pub fn send(settings: &SmtpSettings, credentials: Option<&SmtpCredentials>, mail: &OutgoingMail) -> Result<(), String> {
+    validate_address(&settings.from_address)?;
+    validate_address(&mail.to)?;
+    if credentials.is_some() && settings.security == SmtpSecurity::None && !is_loopback(&settings.host) {
+        return Err("Refusing to send the SMTP password unencrypted; use STARTTLS or TLS".to_string());
+    }
+
+    let mut client = SmtpClient::connect(settings)?;
+    client.expect_reply(&[220])?;
+    let mut extensions = client.ehlo()?;
+
+    if settings.security == SmtpSecurity::StartTls {
+        if !extensions.iter().any(|e| e.eq_ignore_ascii_case("STARTTLS")) {
+            return Err("The SMTP server does not support STARTTLS".to_string());
+        }
+        client.command("STARTTLS", &[220])?;
+        client.start_tls(&settings.host)?;
+        extensions = client.ehlo()?;
+    }
+
+    if let Some(credentials) = credentials {
+        client.authenticate(&extensions, credentials)?;
+    }
+
+    client.command(&format!("MAIL FROM:<{}>", settings.from_address), &[250])?;
+    client.command(&format!("RCPT TO:<{}>", mail.to), &[250, 251])?;
+    client.command("DATA", &[354])?;
+    let message = build_message(settings, mail);
+    client.write_data(&message)?;
+    client.expect_reply(&[250])?;
+    // The message is accepted; a failed goodbye doesn't matter
+    let _ = client.command("QUIT", &[221]);
+    Ok(())
+}
+
+fn is_loopback(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost")
+        || host.trim_matches(|c| c == '[' || c == ']').parse::<IpAddr>().map(|ip| ip.is_loopback()).unwrap_or(false)
+}
+
+enum Transport {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+    /// Only while switching to TLS
+    Closed,
+}
+
+impl Read for Transport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Transport::Plain(stream) => stream.read(buf),
+            Transport::Tls(stream) => stream.read(buf),
+            Transport::Closed => Ok(0),
+        }
+    }
+}
+
+impl Write for Transport {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Transport::Plain(stream) => stream.write(buf),
+            Transport::Tls(stream) => stream.write(buf),
+            Transport::Closed => Err(std::io::ErrorKind::NotConnected.into()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Transport::Plain(stream) => stream.flush(),
+            Transport::Tls(stream) => stream.flush(),
+            Transport::Closed => Ok(()),
+        }
+    }
+}
+
+struct SmtpClient {
+    stream: BufReader<Transport>,
+}
+
+impl SmtpClient {
+    fn connect(settings: &SmtpSettings) -> Result<Self, String> {
+        let host = settings.host.trim();
+        let addresses = (host, settings.port).to_socket_addrs()
+            .map_err(|e| format!("Failed to resolve {}: {}", host, e))?;
+        let mut last_error = None;
+        let mut tcp = None;
+        for address in addresses {
+            match TcpStream::connect_timeout(&address, SMTP_TIMEOUT) {
+                Ok(stream) => {
+                    tcp = Some(stream);
+                    break;
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        let tcp = tcp.ok_or_else(|| match last_error {
+            Some(e) => format!("Failed to connect to {}:{}: {}", host, settings.port, e),
+            None => format!("No address found for {}", host),
+        })?;
+        tcp.set_read_timeout(Some(SMTP_TIMEOUT)).map_err(|e| e.to_string())?;
+        tcp.set_write_timeout(Some(SMTP_TIMEOUT)).map_err(|e| e.to_string())?;
+
+        let transport = match settings.security {
+            SmtpSecurity::Tls => Transport::Tls(Box::new(tls_handshake(host, tcp)?)),
+            SmtpSecurity::None | SmtpSecurity::StartTls => Transport::Plain(tcp),
+        };
+        Ok(SmtpClient { stream: BufReader::new(transport) })
+    }
+
+    fn start_tls(&mut self, host: &str) -> Result<(), String> {
+        // Anything read past the 220 came unencrypted and would pass as a
+        // reply from the secured session (STARTTLS response injection)
+        if !self.stream.buffer().is_empty() {
+            return Err("The SMTP server sent unexpected data before STARTTLS".to_string());
+        }
+        let transport = std::mem::replace(self.stream.get_mut(), Transport::Closed);
+        let tcp = match transport {
+            Transport::Plain(tcp) => tcp,
+            _ => return Err("Connection is already encrypted".to_string()),
+        };
+        *self.stream.get_mut() = Transport::Tls(Box::new(tls_handshake(host.trim(), tcp)?));
+        Ok(())
+    }
+
+    /// Greet the server; returns the extensions it announced
+    fn ehlo(&mut self) -> Result<Vec<String>, String> {
+        let name = hostname::get().ok()
+            .and_then(|h| h.into_string().ok())
+            .filter(|h| !h.is_empty() && h.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.'))
+            .unwrap_or_else(|| "localhost".to_string());
+        let reply = self.command(&format!("EHLO {}", name), &[250])?;
+        Ok(reply.into_iter().skip(1).collect())
+    }
+
+    fn authenticate(&mut self, extensions: &[String], credentials: &SmtpCredentials) -> Result<(), String> {
+        let mechanisms: Vec<String> = extensions.iter()
+            .filter_map(|e| e.strip_prefix("AUTH ").or_else(|| e.strip_prefix("AUTH=")))
+            .flat_map(|m| m.split_whitespace().map(|s| s.to_ascii_uppercase()))
+            .collect();
+        if mechanisms.iter().any(|m| m == "PLAIN") {
+            let token = BASE64.encode(format!("\0{}\0{}", credentials.username, credentials.password));
+            self.command(&format!("AUTH PLAIN {}", token), &[235])
+                .map_err(|e| format!("SMTP login failed: {}", e))?;
+        } else if mechanisms.iter().any(|m| m == "LOGIN") {
+            self.command("AUTH LOGIN", &[334])?;
+            self.command(&BASE64.encode(&credentials.username), &[334])
+                .map_err(|e| format!("SMTP login failed: {}", e))?;
+            self.command(&BASE64.encode(&credentials.password), &[235])
+                .map_err(|e| format!("SMTP login failed: {}", e))?;
+        } else {
+            return Err("The SMTP server offers no supported login method (PLAIN or LOGIN)".to_string());
+        }
+        Ok(())
+    }
+
+    /// Send one command line and check the reply code; returns the reply lines
+    fn command(&mut self, line: &str, expected: &[u16]) -> Result<Vec<String>, String> {
+        let transport = self.stream.get_mut();
+        transport.write_all(line.as_bytes())
+            .and_then(|_| transport.write_all(b"\r\n"))
+            .and_then(|_| transport.flush())
+            .map_err(|e| format!("SMTP connection lost: {}", e))?;
+        self.expect_reply(expected)
+    }
+
+    /// Send the message body, dot-stuffed and terminated by a lone `.`
+    fn write_data(&mut self, message: &str) -> Result<(), String> {
+        let mut data = String::with_capacity(message.len() + 64);
+        for line in message.split("\r\n") {
+            if line.starts_with('.') {
+                data.push('.');
+            }
+            data.push_str(line);
+            data.push_str("\r\n");
+        }
+        data.push_str(".\r\n");
+        let transport = self.stream.get_mut();
+        transport.write_all(data.as_bytes())
+            .and_then(|_| transport.flush())
+            .map_err(|e| format!("SMTP connection lost: {}", e))
+    }
+
+    /// Read a possibly multi-line reply (`250-...` lines, then `250 ...`)
+    fn expect_reply(&mut self, expected: &[u16]) -> Result<Vec<String>, String> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            let read = self.stream.read_line(&mut line)
+                .map_err(|e| format!("SMTP connection lost: {}", e))?;
+            if read == 0 {
+                return Err("SMTP server closed the connection".to_string());
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            let code = line.get(..3).and_then(|c| c.parse::<u16>().ok())
+                .ok_or_else(|| format!("Unexpected SMTP reply: {}", line))?;
+            let last = line.as_bytes().get(3) != Some(&b'-');
+            lines.push(line.get(4..).unwrap_or("").to_string());
+            if last {
+                if expected.contains(&code) {
+                    return Ok(lines);
+                }
+                return Err(format!("SMTP server replied {} {}", code, lines.join(" ")));
+            }
+        }
+    }
+}
+
+fn tls_handshake(host: &str, tcp: TcpStream) -> Result<TlsStream<TcpStream>, String> {
+    let connector = TlsConnector::new().map_err(|e| format!("Failed to set up TLS: {}", e))?;
+    connector.connect(host, tcp).map_err(|e| format!("TLS handshake with {} failed: {}", host, e))
+}
+
+/// MIME message: a text part and the attachment, both base64 encoded
+fn build_message(settings: &SmtpSettings, mail: &OutgoingMail) -> String {
+    let boundary = format!("=_{}", uuid::Uuid::new_v4().simple());
+    let domain = settings.from_address.rsplit_once('@').map(|(_, d)| d).unwrap_or("localhost");
+    let from = match settings.from_name.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+        Some(name) => format!("{} <{}>", encode_display_name(name), settings.from_address),
+        None => format!("<{}>", settings.from_address),
+    };
+    let body = mail.body.replace("\r\n", "\n").replace('\n', "\r\n");
+    let attachment_name = header_value(&mail.attachment_name).replace('"', "");
+
+    let mut message = String::new();
+    message.push_str(&format!("From: {}\r\n", from));
+    message.push_str(&format!("To: <{}>\r\n", mail.to));
+    message.push_str(&format!("Subject: {}\r\n", encode_word(&header_value(&mail.subject))));
+    message.push_str(&format!("Date: {}\r\n", chrono::Local::now().to_rfc2822()));
+    message.push_str(&format!("Message-ID: <{}@{}>\r\n", uuid::Uuid::new_v4(), domain));
+    message.push_str("MIME-Version: 1.0\r\n");
+    message.push_str(&format!("Content-Type: multipart/mixed; boundary=\"{}\"\r\n\r\n", boundary));
+
+    message.push_str(&format!("--{}\r\n", boundary));
+    message.push_str("Content-Type: text/plain; charset=utf-8\r\n");
+    message.push_str("Content-Transfer-Encoding: base64\r\n\r\n");
+    message.push_str(&wrap_base64(body.as_bytes()));
+
+    message.push_str(&format!("--{}\r\n", boundary));
+    message.push_str(&format!("Content-Type: {}; name=\"{}\"\r\n", mail.attachment_type, attachment_name));
+    message.push_str(&format!("Content-Disposition: attachment; filename=\"{}\"\r\n", attachment_name));
+    message.push_str("Content-Transfer-Encoding: base64\r\n\r\n");
+    message.push_str(&wrap_base64(&mail.attachment));
+
+    message.push_str(&format!("--{}--\r\n", boundary));
+    message
+}
+
+/// Header text on a single line, so a template can't inject headers
+fn header_value(text: &str) -> String {
+    text.replace(['\r', '\n'], " ").trim().to_string()
+}
+
+/// RFC 2047 encoded word for non-ASCII header text
+fn encode_word(text: &str) -> String {
+    if text.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+        text.to_string()
+    } else {
+        format!("=?utf-8?B?{}?=", BASE64.encode(text))
+    }
+}
+
+fn encode_display_name(name: &str) -> String {
+    let name = header_value(name);
+    if name.is_ascii() {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        encode_word(&name)
+    }
+}
+
+/// Base64 in 76-character lines, each ending in CRLF
+fn wrap_base64(data: &[u8]) -> String {
+    let encoded = BASE64.encode(data);
+    let mut wrapped = String::with_capacity(encoded.len() + encoded.len() / 38 + 2);
+    for chunk in encoded.as_bytes().chunks(76) {
+        // Base64 output is ASCII
+        wrapped.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+        wrapped.push_str("\r\n");
+    }
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    /// Local SMTP stand-in: answers each command with the next reply in
+    /// `replies` (after the greeting, which is `replies[0]`) and returns
+    /// everything the client sent.
+    fn fake_server(replies: &'static [&'static str]) -> (u16, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut received = String::new();
+            let mut in_data = false;
+            writer.write_all(replies[0].as_bytes()).unwrap();
+            for reply in &replies[1..] {
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                        return received;
+                    }
+                    received.push_str(&line);
+                    if !in_data || line == ".\r\n" {
+                        break;
+                    }
+                }
+                in_data = reply.starts_with("354");
+                writer.write_all(reply.as_bytes()).unwrap();
+            }
+            received
+        });
+        (port, handle)
+    }
+
+    fn settings(port: u16, security: SmtpSecurity) -> SmtpSettings {
+        SmtpSettings {
+            host: "127.0.0.1".to_string(),
+            port,
+            security,
+            from_address: "me@example.com".to_string(),
+            ..SmtpSettings::default()
+        }
+    }
+
+    fn mail() -> OutgoingMail {
+        OutgoingMail {
+            to: "client@example.com".to_string(),
+            subject: "Invoice INV-0001".to_string(),
+            body: "Hello".to_string(),
+            attachment_name: "INV-0001.pdf".to_string(),
+            attachment_type: "application/pdf".to_string(),
+            attachment: b"%PDF-1.4".to_vec(),
+        }
+    }
+
+    #[test]
+    fn sends_through_a_plain_local_server() {
+        let (port, server) = fake_server(&[
+            "220 test ready\r\n",
+            "250-test\r\n250 AUTH PLAIN\r\n",
+            "235 ok\r\n",
+            "250 ok\r\n",
+            "250 ok\r\n",
+            "354 go ahead\r\n",
+            "250 queued\r\n",
+            "221 bye\r\n",
+        ]);
+        let credentials = SmtpCredentials { username: "me".to_string(), password: "secret".to_string() };
+        send(&settings(port, SmtpSecurity::None), Some(&credentials), &mail()).unwrap();
+
+        let received = server.join().unwrap();
+        assert!(received.contains(&format!("AUTH PLAIN {}\r\n", BASE64.encode("\0me\0secret"))));
+        assert!(received.contains("MAIL FROM:<me@example.com>\r\n"));
+        assert!(received.contains("RCPT TO:<client@example.com>\r\n"));
+        assert!(received.contains("Subject: Invoice INV-0001\r\n"));
+        assert!(received.contains(&wrap_base64(b"%PDF-1.4")));
+        assert!(received.ends_with("\r\n.\r\nQUIT\r\n"));
+    }
+
+    #[test]
+    fn rejected_recipient_is_an_error() {
+        let (port, server) = fake_server(&[
+            "220 test ready\r\n",
+            "250 test\r\n",
+            "250 ok\r\n",
+            "550 no such user\r\n",
+        ]);
+        let error = send(&settings(port, SmtpSecurity::None), None, &mail()).unwrap_err();
+        assert!(error.contains("550"), "{}", error);
+        assert!(!server.join().unwrap().contains("DATA"));
+    }
+
+    #[test]
+    fn replies_injected_before_starttls_abort() {
+        let (port, server) = fake_server(&[
+            "220 test ready\r\n",
+            "250-test\r\n250 STARTTLS\r\n",
+            "220 go ahead\r\n250-injected\r\n250 AUTH PLAIN\r\n",
+        ]);
+        let credentials = SmtpCredentials { username: "me".to_string(), password: "secret".to_string() };
+        let error = send(&settings(port, SmtpSecurity::StartTls), Some(&credentials), &mail()).unwrap_err();
+        assert!(error.contains("before STARTTLS"), "{}", error);
+        assert!(!server.join().unwrap().contains("AUTH"));
+    }
+
+    #[test]
+    fn plain_password_refused_to_remote_servers() {
+        let credentials = SmtpCredentials { username: "me".to_string(), password: "secret".to_string() };
+        let mut remote = settings(25, SmtpSecurity::None);
+        remote.host = "mail.example.com".to_string();
+        assert!(send(&remote, Some(&credentials), &mail()).is_err());
+    }
+}
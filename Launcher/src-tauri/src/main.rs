@@ -3,6 +3,10 @@
 mod core;
 mod storage;
 mod commands;
+mod export;
+mod import;
+mod jobs;
+mod mail;
 
 use storage::db::Database;
 use std::path::PathBuf;
@@ -18,6 +22,13 @@ fn main() {
 
     tauri::Builder::default()
         .manage(database)
+        .setup(|app| {
+            jobs::spawn_overdue_job(app.handle());
+            jobs::spawn_quote_expiry_job(app.handle());
+            jobs::spawn_retainer_job(app.handle());
+            jobs::spawn_reminder_job(app.handle());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             // Session type commands
             commands::get_all_session_types,
@@ -28,6 +39,7 @@ fn main() {
             commands::get_all_sessions,
             commands::get_sessions_by_date,
             commands::get_sessions_by_type_id,
+            commands::query_sessions,
             commands::add_session,
             commands::update_session,
             commands::delete_session,
@@ -64,9 +76,95 @@ fn main() {
             commands::get_all_invoices,
             commands::get_invoice,
             commands::create_invoice,
+            commands::preview_invoice_from_criteria,
+            commands::create_invoice_from_criteria,
+            commands::get_all_tax_rates,
+            commands::add_tax_rate,
+            commands::update_tax_rate,
+            commands::delete_tax_rate,
+            commands::get_invoice_numbering,
+            commands::set_invoice_numbering,
+            commands::preview_invoice_number,
             commands::update_invoice_status,
             commands::delete_invoice,
+            commands::get_credit_notes,
+            commands::create_credit_note,
+            commands::void_invoice,
+            commands::update_draft_invoice,
+            commands::add_sessions_to_invoice,
+            commands::add_invoice_item,
+            commands::update_invoice_item,
+            commands::remove_invoice_item,
+            commands::record_payment,
+            commands::delete_payment,
+            commands::check_overdue_invoices,
+            commands::render_invoice_pdf,
+            commands::render_invoice_html,
+            commands::export_invoice_ubl,
+            commands::export_invoice_facturx,
+            commands::get_smtp_settings,
+            commands::set_smtp_settings,
+            commands::get_invoice_history,
+            commands::send_invoice_email,
+            commands::get_all_business_profiles,
+            commands::add_business_profile,
+            commands::update_business_profile,
+            commands::delete_business_profile,
+            commands::get_all_invoice_templates,
+            commands::add_invoice_template,
+            commands::update_invoice_template,
+            commands::delete_invoice_template,
+            commands::duplicate_invoice_template,
             commands::get_uninvoiced_sessions,
+            // Quote commands
+            commands::get_all_quotes,
+            commands::get_quote,
+            commands::create_quote,
+            commands::update_draft_quote,
+            commands::add_quote_item,
+            commands::update_quote_item,
+            commands::remove_quote_item,
+            commands::update_quote_status,
+            commands::delete_quote,
+            commands::convert_quote_to_invoice,
+            commands::check_expired_quotes,
+            // Retainer commands
+            commands::get_all_retainers,
+            commands::add_retainer,
+            commands::update_retainer,
+            commands::delete_retainer,
+            commands::get_retainer_usage,
+            commands::bill_due_retainers,
+            // Payment reminder commands
+            commands::get_reminder_schedules,
+            commands::add_reminder_schedule,
+            commands::update_reminder_schedule,
+            commands::delete_reminder_schedule,
+            commands::get_due_reminders,
+            commands::get_invoice_reminders,
+            commands::send_due_reminders,
+            // Receivables commands
+            commands::get_receivables_report,
+            commands::get_client_payment_stats,
+            // Bank reconciliation commands
+            commands::import_bank_statement,
+            commands::get_bank_csv_format,
+            commands::get_bank_transactions,
+            commands::suggest_bank_matches,
+            commands::confirm_bank_match,
+            commands::ignore_bank_transaction,
+            commands::delete_bank_transaction,
+            // Accounting export commands
+            commands::get_accounting_settings,
+            commands::set_accounting_settings,
+            commands::export_journal,
+            // Expense commands
+            commands::get_expenses,
+            commands::add_expense,
+            commands::update_expense,
+            commands::delete_expense,
+            commands::get_expense_categories,
+            commands::get_net_income,
             // Habit commands
             commands::get_all_habits,
             commands::add_habit,
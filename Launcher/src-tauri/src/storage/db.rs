@@ -1,20 +1,162 @@
-use rusqlite::{Connection, Result as SqlResult, params};
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult, params, params_from_iter};
+use rusqlite::types::Value;
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::{Mutex, MutexGuard};
 use crate::core::models::*;
-use chrono::Datelike;
+use crate::core::logic;
+use super::pool::{self, PooledConnection, ReadPool};
+use super::secrets::SecretStore;
 
+/// SQL expression for a session's pay (alias `s`), mirrors `WorkSession::calculate_pay`
+const SESSION_PAY_SQL: &str =
+    "CASE s.pay_type WHEN 'Hourly' THEN COALESCE(s.hourly_rate, 0) * s.hours WHEN 'Fixed' THEN COALESCE(s.fixed_amount, 0) ELSE 0 END";
+
+/// Column list read by `invoice_from_row`
+const INVOICE_COLUMNS: &str =
+    "id, invoice_number, client_name, client_email, created_date, due_date, status,
+     subtotal, tax_rate, tax_amount, total, notes, discount_type, discount_value, discount_amount,
+     withholding_amount,
+     (SELECT COALESCE(SUM(p.amount), 0) FROM invoice_payments p WHERE p.invoice_id = invoices.id),
+     overdue_at, profile_id, template_id, credited_invoice_id,
+     (SELECT o.invoice_number FROM invoices o WHERE o.id = invoices.credited_invoice_id),
+     (SELECT COALESCE(SUM(c.total), 0) FROM invoices c WHERE c.credited_invoice_id = invoices.id AND c.status <> 'Void'),
+     voided_at, line_grouping, include_timesheet,
+     (SELECT q.id FROM quotes q WHERE q.invoice_id = invoices.id),
+     (SELECT q.quote_number FROM quotes q WHERE q.invoice_id = invoices.id),
+     paid_date, client_address, client_city, client_postal_code, client_country_code, client_tax_id,
+     issued_date";
+
+/// Date an invoice's balance was cleared: its latest payment
+const LAST_PAYMENT_DATE_SQL: &str =
+    "(SELECT MAX(p.payment_date) FROM invoice_payments p WHERE p.invoice_id = invoices.id)";
+
+/// Sessions on a line item that hasn't been released by a credit note or void,
+/// and sessions on a retainer's projects dated in a period already billed:
+/// the fee covers them even when they were entered after the period closed
+const INVOICED_SESSIONS_SQL: &str =
+    "SELECT l.session_id FROM invoice_item_sessions l
+     JOIN invoice_items ii ON ii.id = l.item_id
+     WHERE ii.session_released = 0
+     UNION
+     SELECT rs.id FROM sessions rs
+     JOIN retainers r ON EXISTS (SELECT 1 FROM json_each(r.project_names) p WHERE p.value = rs.project_name)
+     JOIN retainer_periods rp ON rp.retainer_id = r.id AND rs.date BETWEEN rp.period_start AND rp.period_end
+     WHERE EXISTS (SELECT 1 FROM invoice_items ri WHERE ri.invoice_id = rp.invoice_id AND ri.session_released = 0)";
+
+/// Invoice lines split across the session types they bill, in proportion to
+/// hours: (invoice_id, session_type_id, amount). Lines without sessions have
+/// no type; credit note lines take the types of the line they credit.
+const INVOICE_LINE_TYPES_SQL: &str =
+    "SELECT ii.invoice_id, t.session_type_id, ii.amount * COALESCE(t.hours / t.item_hours, 1) AS amount
+     FROM invoice_items ii
+     LEFT JOIN (SELECT l.item_id, s.session_type_id, SUM(s.hours) AS hours,
+                       SUM(SUM(s.hours)) OVER (PARTITION BY l.item_id) AS item_hours
+                FROM invoice_item_sessions l JOIN sessions s ON s.id = l.session_id
+                GROUP BY l.item_id, s.session_type_id) t
+       ON t.item_id = COALESCE(ii.credited_item_id, ii.id)";
+
+/// Income on `basis` as rows of (date, session_type_id, amount). Invoiced and
+/// received amounts exclude tax, additive or inclusive, and include withholding:
+/// a payment counts for the share of the invoice total that is net income.
+fn income_sql(basis: ReportingBasis) -> String {
+    match basis {
+        ReportingBasis::Earned => "SELECT date, session_type_id, pay AS amount FROM daily_rollups".to_string(),
+        ReportingBasis::Invoiced => format!(
+            "SELECT COALESCE(i.issued_date, i.created_date) AS date, l.session_type_id,
+                    l.amount / i.subtotal * (i.total + i.withholding_amount - i.tax_amount)
+                        * (CASE WHEN i.credited_invoice_id IS NULL THEN 1 ELSE -1 END) AS amount
+             FROM invoices i JOIN ({}) l ON l.invoice_id = i.id
+             WHERE i.status NOT IN ('Draft', 'Void') AND i.subtotal <> 0",
+            INVOICE_LINE_TYPES_SQL
+        ),
+        // Invoices marked Paid before payments were recorded count in full on their paid date
+        ReportingBasis::Received => format!(
+            "SELECT p.payment_date AS date, l.session_type_id,
+                    l.amount / i.subtotal * (i.total + i.withholding_amount - i.tax_amount) * p.amount / i.total AS amount
+             FROM invoice_payments p
+             JOIN invoices i ON i.id = p.invoice_id
+             JOIN ({0}) l ON l.invoice_id = i.id
+             WHERE i.credited_invoice_id IS NULL AND i.subtotal <> 0 AND i.total > 0
+             UNION ALL
+             SELECT COALESCE(i.paid_date, i.created_date), l.session_type_id,
+                    l.amount / i.subtotal * (i.total + i.withholding_amount - i.tax_amount)
+             FROM invoices i JOIN ({0}) l ON l.invoice_id = i.id
+             WHERE i.status = 'Paid' AND i.credited_invoice_id IS NULL AND i.subtotal <> 0
+               AND NOT EXISTS (SELECT 1 FROM invoice_payments p WHERE p.invoice_id = i.id)",
+            INVOICE_LINE_TYPES_SQL
+        ),
+    }
+}
+
+/// Column list read by `business_profile_from_row`
+const BUSINESS_PROFILE_COLUMNS: &str =
+    "id, name, business_name, address, email, phone, tax_id, bank_details, logo_path, is_default,
+     city, postal_code, country_code, currency";
+
+/// Column list read by `quote_from_row`
+const QUOTE_COLUMNS: &str =
+    "id, quote_number, client_name, client_email, created_date, valid_until, status,
+     subtotal, discount_type, discount_value, discount_amount, tax_amount, withholding_amount, total,
+     notes, profile_id, invoice_id,
+     (SELECT i.invoice_number FROM invoices i WHERE i.id = quotes.invoice_id)";
+
+/// Column list read by `retainer_from_row`
+const RETAINER_COLUMNS: &str =
+    "id, client_name, client_email, project_names, period, fee, included_hours, overage_rate,
+     rollover, rollover_cap, start_date, end_date, payment_terms_days, profile_id, tax_rate_ids, active";
+
+/// Column list read by `reminder_schedule_from_row`
+const REMINDER_SCHEDULE_COLUMNS: &str =
+    "id, client_name, offsets, channel, subject_template, body_template, active";
+
+/// Column list read by `bank_transaction_from_row`
+const BANK_TRANSACTION_COLUMNS: &str =
+    "id, external_id, date, amount, description, reference, counterparty, ignored,
+     (SELECT COALESCE(SUM(p.amount), 0) FROM invoice_payments p WHERE p.bank_transaction_id = bank_transactions.id),
+     (SELECT json_group_array(i.invoice_number) FROM invoice_payments p JOIN invoices i ON i.id = p.invoice_id
+      WHERE p.bank_transaction_id = bank_transactions.id),
+     imported_at";
+
+/// Column list read by `expense_from_row`
+const EXPENSE_COLUMNS: &str =
+    "id, date, amount, description, category, is_business, client_name, project_name,
+     recurrence, end_date, receipt_paths, notes, created_at";
+
+/// Bump when the rollup definition changes, so existing databases rebuild
+/// `daily_rollups` once on their next start
+const DAILY_ROLLUPS_VERSION: &str = "1";
+
+/// Number of read-only connections kept open alongside the single writer
+const READ_POOL_SIZE: usize = 4;
+
+/// SQLite store in WAL mode: one writer connection behind a mutex and a small
+/// pool of read-only connections, so long reads don't block other commands.
 pub struct Database {
-    conn: Mutex<Connection>,
+    write_conn: Mutex<Connection>,
+    read_pool: ReadPool,
+    secrets: SecretStore,
 }
 
 impl Database {
     pub fn new(db_path: PathBuf) -> SqlResult<Self> {
-        let conn = Connection::open(db_path)?;
+        let conn = pool::open_writer(&db_path)?;
         conn.execute_batch(include_str!("schema.sql"))?;
         Self::run_migrations(&conn)?;
+        Self::rebuild_daily_rollups_if_stale(&conn)?;
         Self::seed_default_session_types(&conn)?;
-        Ok(Database { conn: Mutex::new(conn) })
+        let read_pool = ReadPool::open(&db_path, READ_POOL_SIZE)?;
+        let secrets = SecretStore::new(db_path.with_file_name("secrets.key"));
+        Ok(Database { write_conn: Mutex::new(conn), read_pool, secrets })
+    }
+
+    /// Check out a read-only connection. Never hold it across an `.await`.
+    fn reader(&self) -> PooledConnection<'_> {
+        self.read_pool.get()
+    }
+
+    /// Lock the writer connection. Never hold it across an `.await`.
+    fn writer(&self) -> MutexGuard<'_, Connection> {
+        pool::lock_writer(&self.write_conn)
     }
 
     fn run_migrations(conn: &Connection) -> SqlResult<()> {
@@ -35,9 +177,194 @@ impl Database {
                 return Ok(()); // Old schema exists but no session_types table - skip migration
             }
         }
+
+        // Invoice line kinds and discounts
+        Self::add_column_if_missing(conn, "invoices", "discount_type", "TEXT")?;
+        Self::add_column_if_missing(conn, "invoices", "discount_value", "REAL")?;
+        Self::add_column_if_missing(conn, "invoices", "discount_amount", "REAL NOT NULL DEFAULT 0")?;
+        Self::add_column_if_missing(conn, "invoice_items", "kind", "TEXT NOT NULL DEFAULT 'Session'")?;
+        Self::add_column_if_missing(conn, "invoice_items", "unit", "TEXT")?;
+        Self::add_column_if_missing(conn, "invoice_items", "discount_type", "TEXT")?;
+        Self::add_column_if_missing(conn, "invoice_items", "discount_value", "REAL")?;
+        Self::add_column_if_missing(conn, "invoice_items", "taxable", "INTEGER NOT NULL DEFAULT 1")?;
+
+        // Multiple taxes per invoice
+        Self::add_column_if_missing(conn, "invoices", "withholding_amount", "REAL NOT NULL DEFAULT 0")?;
+        Self::add_column_if_missing(conn, "invoice_items", "tax_category", "TEXT")?;
+
+        // Credit notes: added before the rebuild, whose triggers refer to them
+        Self::add_column_if_missing(conn, "invoice_items", "credited_item_id", "INTEGER REFERENCES invoice_items(id) ON DELETE SET NULL")?;
+        Self::add_column_if_missing(conn, "invoice_items", "session_released", "INTEGER NOT NULL DEFAULT 0")?;
+
+        // New invoice statuses (also adds credited_invoice_id and voided_at)
+        Self::rebuild_invoices_table_if_outdated(conn)?;
+        Self::add_column_if_missing(conn, "invoices", "overdue_at", "TEXT")?;
+
+        // Sender profiles and templates
+        Self::add_column_if_missing(conn, "invoices", "profile_id", "INTEGER REFERENCES business_profiles(id) ON DELETE SET NULL")?;
+        Self::add_column_if_missing(conn, "invoices", "template_id", "INTEGER REFERENCES invoice_templates(id) ON DELETE SET NULL")?;
+
+        // Line grouping and timesheets; links backfilled from single-session lines
+        Self::add_column_if_missing(conn, "invoices", "line_grouping", "TEXT NOT NULL DEFAULT 'PerSession'")?;
+        Self::add_column_if_missing(conn, "invoices", "include_timesheet", "INTEGER NOT NULL DEFAULT 0")?;
+        conn.execute_batch(
+            "INSERT INTO invoice_item_sessions (item_id, session_id)
+             SELECT id, session_id FROM invoice_items
+             WHERE session_id IS NOT NULL AND id NOT IN (SELECT item_id FROM invoice_item_sessions)"
+        )?;
+
+        // Structured seller address for e-invoices
+        Self::add_column_if_missing(conn, "business_profiles", "city", "TEXT")?;
+        Self::add_column_if_missing(conn, "business_profiles", "postal_code", "TEXT")?;
+        Self::add_column_if_missing(conn, "business_profiles", "country_code", "TEXT")?;
+        Self::add_column_if_missing(conn, "business_profiles", "currency", "TEXT")?;
+
+        // Payments reconciled from bank statements
+        Self::add_column_if_missing(conn, "invoice_payments", "bank_transaction_id", "INTEGER REFERENCES bank_transactions(id) ON DELETE SET NULL")?;
+
+        // Paid date for receivables; backfilled from the last payment
+        Self::add_column_if_missing(conn, "invoices", "paid_date", "TEXT")?;
+        conn.execute_batch(&format!(
+            "UPDATE invoices SET paid_date = {} WHERE status = 'Paid' AND paid_date IS NULL",
+            LAST_PAYMENT_DATE_SQL
+        ))?;
+
+        // Seller frozen on issue; issued invoices get their current profile
+        Self::add_column_if_missing(conn, "invoices", "seller_snapshot", "TEXT")?;
+        // Buyer address for e-invoices
+        Self::add_column_if_missing(conn, "invoices", "client_address", "TEXT")?;
+        Self::add_column_if_missing(conn, "invoices", "client_city", "TEXT")?;
+        Self::add_column_if_missing(conn, "invoices", "client_postal_code", "TEXT")?;
+        Self::add_column_if_missing(conn, "invoices", "client_country_code", "TEXT")?;
+        Self::add_column_if_missing(conn, "invoices", "client_tax_id", "TEXT")?;
+        // Invoices issued before the issue date was recorded count as issued
+        // on the day they were created
+        Self::add_column_if_missing(conn, "invoices", "issued_date", "TEXT")?;
+        conn.execute_batch("UPDATE invoices SET issued_date = created_date WHERE status <> 'Draft' AND issued_date IS NULL")?;
+        Self::recreate_trigger_if_outdated(conn, "trg_invoices_lock_content")?;
+        let unfrozen: Vec<(i64, Option<i64>)> = conn.prepare(
+            "SELECT id, profile_id FROM invoices WHERE status <> 'Draft' AND seller_snapshot IS NULL"
+        )?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        for (id, profile_id) in unfrozen {
+            let snapshot = seller_snapshot(conn, profile_id)?;
+            conn.execute("UPDATE invoices SET seller_snapshot = ? WHERE id = ?", params![snapshot, id])?;
+        }
         Ok(())
     }
 
+    /// `CREATE TRIGGER IF NOT EXISTS` keeps an outdated trigger, so one whose
+    /// stored definition differs from schema.sql is dropped and recreated.
+    fn recreate_trigger_if_outdated(conn: &Connection, name: &str) -> SqlResult<()> {
+        let schema = include_str!("schema.sql");
+        let start = schema.find(&format!("CREATE TRIGGER IF NOT EXISTS {} ", name)).expect("trigger in schema.sql");
+        let end = start + schema[start..].find("\nEND;").expect("end of trigger") + 4;
+        let create = &schema[start..end];
+        let current: Option<String> = conn.query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'trigger' AND name = ?",
+            params![name],
+            |row| row.get(0),
+        ).optional()?;
+        if current.as_deref() == Some(create.replacen(" IF NOT EXISTS", "", 1).as_str()) {
+            return Ok(());
+        }
+        conn.execute_batch(&format!("DROP TRIGGER IF EXISTS {}; {};", name, create))
+    }
+
+    /// SQLite can't alter a CHECK constraint, so an `invoices` table whose status
+    /// column differs from schema.sql is rebuilt from the current definition.
+    /// Triggers referencing it are dropped and recreated from schema.sql, and
+    /// foreign keys are off meanwhile so the old table's children survive.
+    fn rebuild_invoices_table_if_outdated(conn: &Connection) -> SqlResult<()> {
+        let schema = include_str!("schema.sql");
+        let start = schema.find("CREATE TABLE IF NOT EXISTS invoices (").expect("invoices table in schema.sql");
+        let end = start + schema[start..].find("\n);").expect("end of invoices table") + 3;
+        let create = &schema[start..end];
+        let status_column = create.lines()
+            .map(|l| l.trim().trim_end_matches(','))
+            .find(|l| l.starts_with("status "))
+            .expect("invoices.status in schema.sql");
+        
+        let current: String = conn.query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'invoices'",
+            [],
+            |row| row.get(0)
+        )?;
+        if current.contains(status_column) {
+            return Ok(());
+        }
+        
+        let columns: Vec<String> = conn.prepare("SELECT name FROM pragma_table_info('invoices')")?
+            .query_map([], |row| row.get(0))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        let triggers: Vec<String> = conn.prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'trigger' AND sql LIKE '%invoices%'"
+        )?
+            .query_map([], |row| row.get(0))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        
+        conn.pragma_update(None, "foreign_keys", "OFF")?;
+        let tx = conn.unchecked_transaction()?;
+        for trigger in &triggers {
+            tx.execute_batch(&format!("DROP TRIGGER IF EXISTS \"{}\"", trigger))?;
+        }
+        tx.execute_batch(&create.replacen("IF NOT EXISTS invoices", "invoices_new", 1))?;
+        let new_columns: Vec<String> = tx.prepare("SELECT name FROM pragma_table_info('invoices_new')")?
+            .query_map([], |row| row.get(0))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        let shared = columns.iter()
+            .filter(|c| new_columns.contains(c))
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(", ");
+        tx.execute_batch(&format!(
+            "INSERT INTO invoices_new ({0}) SELECT {0} FROM invoices;
+             DROP TABLE invoices;
+             ALTER TABLE invoices_new RENAME TO invoices;",
+            shared
+        ))?;
+        tx.execute_batch(schema)?;
+        tx.commit()?;
+        conn.pragma_update(None, "foreign_keys", "ON")
+    }
+
+    fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> SqlResult<()> {
+        let exists = conn
+            .prepare(&format!("SELECT {} FROM {} LIMIT 1", column, table))
+            .is_ok();
+        if !exists {
+            conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))?;
+        }
+        Ok(())
+    }
+
+    /// Backfill `daily_rollups` for databases created before the rollup
+    /// triggers existed. The triggers keep it in step afterwards, so the full
+    /// scan runs once per `DAILY_ROLLUPS_VERSION`, recorded in `settings`.
+    fn rebuild_daily_rollups_if_stale(conn: &Connection) -> SqlResult<()> {
+        let built: Option<String> = conn.query_row(
+            "SELECT value FROM settings WHERE key = 'daily_rollups_version'",
+            [],
+            |row| row.get(0),
+        ).optional()?;
+        if built.as_deref() == Some(DAILY_ROLLUPS_VERSION) {
+            return Ok(());
+        }
+        conn.execute_batch(&format!(
+            "BEGIN;
+             DELETE FROM daily_rollups;
+             INSERT INTO daily_rollups (date, session_type_id, project_name, hours, pay, session_count)
+             SELECT s.date, s.session_type_id, s.project_name, SUM(s.hours), SUM({}), COUNT(*)
+             FROM sessions s
+             GROUP BY s.date, s.session_type_id, s.project_name;
+             INSERT OR REPLACE INTO settings (key, value, updated_at)
+             VALUES ('daily_rollups_version', '{}', datetime('now'));
+             COMMIT;",
+            SESSION_PAY_SQL, DAILY_ROLLUPS_VERSION
+        ))
+    }
+
     fn seed_default_session_types(conn: &Connection) -> SqlResult<()> {
         let count: i64 = conn.query_row("SELECT COUNT(*) FROM session_types", [], |row| row.get(0))?;
         if count == 0 {
@@ -56,7 +383,7 @@ impl Database {
     // ========== SESSION TYPE OPERATIONS ==========
 
     pub fn get_all_session_types(&self) -> SqlResult<Vec<SessionType>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         let mut stmt = conn.prepare("SELECT id, name, color, hourly_rate FROM session_types ORDER BY name")?;
         let types = stmt.query_map([], |row| {
             Ok(SessionType {
@@ -70,7 +397,7 @@ impl Database {
     }
 
     pub fn add_session_type(&self, session_type: NewSessionType) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.writer();
         conn.execute(
             "INSERT INTO session_types (name, color, hourly_rate) VALUES (?, ?, ?)",
             params![session_type.name, session_type.color, session_type.hourly_rate],
@@ -79,7 +406,7 @@ impl Database {
     }
 
     pub fn update_session_type(&self, session_type: &SessionType) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.writer();
         conn.execute(
             "UPDATE session_types SET name = ?, color = ?, hourly_rate = ? WHERE id = ?",
             params![session_type.name, session_type.color, session_type.hourly_rate, session_type.id],
@@ -88,7 +415,7 @@ impl Database {
     }
 
     pub fn delete_session_type(&self, id: i64) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.writer();
         conn.execute("DELETE FROM session_types WHERE id = ?", params![id])?;
         Ok(())
     }
@@ -96,7 +423,7 @@ impl Database {
     // ========== SESSION OPERATIONS ==========
 
     pub fn get_all_sessions(&self) -> SqlResult<Vec<WorkSession>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         let mut stmt = conn.prepare(
             "SELECT s.id, s.session_type_id, st.name, s.date, s.project_name, s.hours, s.description,
                     s.pay_type, s.hourly_rate, s.fixed_amount
@@ -124,7 +451,7 @@ impl Database {
     }
 
     pub fn get_sessions_by_date(&self, date: &str) -> SqlResult<Vec<WorkSession>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         let mut stmt = conn.prepare(
             "SELECT s.id, s.session_type_id, st.name, s.date, s.project_name, s.hours, s.description,
                     s.pay_type, s.hourly_rate, s.fixed_amount
@@ -152,7 +479,7 @@ impl Database {
     }
 
     pub fn get_sessions_by_type_id(&self, session_type_id: i64) -> SqlResult<Vec<WorkSession>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         let mut stmt = conn.prepare(
             "SELECT s.id, s.session_type_id, st.name, s.date, s.project_name, s.hours, s.description,
                     s.pay_type, s.hourly_rate, s.fixed_amount
@@ -180,8 +507,9 @@ impl Database {
     }
 
     pub fn add_session(&self, session: NewSession) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        tx.execute(
             "INSERT INTO sessions (session_type_id, date, project_name, hours, description, pay_type, hourly_rate, fixed_amount)
              VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
             params![
@@ -195,14 +523,16 @@ impl Database {
                 session.fixed_amount,
             ],
         )?;
-        let id = conn.last_insert_rowid();
-        self.update_project_cache_internal(&conn, &session.project_name, session.session_type_id, &session.date)?;
+        let id = tx.last_insert_rowid();
+        self.update_project_cache_internal(&tx, &session.project_name, session.session_type_id, &session.date)?;
+        tx.commit()?;
         Ok(id)
     }
 
     pub fn update_session(&self, session: &WorkSession) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        tx.execute(
             "UPDATE sessions SET session_type_id = ?, date = ?, project_name = ?, hours = ?,
              description = ?, pay_type = ?, hourly_rate = ?, fixed_amount = ? WHERE id = ?",
             params![
@@ -217,63 +547,149 @@ impl Database {
                 session.id,
             ],
         )?;
-        self.update_project_cache_internal(&conn, &session.project_name, session.session_type_id, &session.date)?;
+        self.update_project_cache_internal(&tx, &session.project_name, session.session_type_id, &session.date)?;
+        tx.commit()?;
         Ok(())
     }
 
+    /// Lines billing the session on draft invoices are rebuilt from their
+    /// other sessions and the drafts' totals recalculated. Sessions on issued
+    /// invoices are refused by the lock triggers; see `get_session_invoice_number`.
     pub fn delete_session(&self, id: i64) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM sessions WHERE id = ?", params![id])?;
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let draft_lines: Vec<(i64, i64, LineGrouping)> = tx.prepare(
+            "SELECT ii.id, ii.invoice_id, i.line_grouping
+             FROM invoice_item_sessions l
+             JOIN invoice_items ii ON ii.id = l.item_id
+             JOIN invoices i ON i.id = ii.invoice_id
+             WHERE l.session_id = ? AND i.status = 'Draft'"
+        )?
+            .query_map(params![id], |row| {
+                let grouping = LineGrouping::from_string(&row.get::<_, String>(2)?).unwrap_or_default();
+                Ok((row.get(0)?, row.get(1)?, grouping))
+            })?
+            .collect::<SqlResult<Vec<_>>>()?;
+        for (item_id, invoice_id, grouping) in &draft_lines {
+            let others: Vec<i64> = tx.prepare("SELECT session_id FROM invoice_item_sessions WHERE item_id = ? AND session_id <> ?")?
+                .query_map(params![item_id, id], |row| row.get(0))?
+                .collect::<SqlResult<Vec<_>>>()?;
+            tx.execute("DELETE FROM invoice_items WHERE id = ?", params![item_id])?;
+            self.insert_session_items_internal(&tx, *invoice_id, &others, *grouping)?;
+        }
+        tx.execute("DELETE FROM sessions WHERE id = ?", params![id])?;
+        for (_, invoice_id, _) in &draft_lines {
+            self.recalculate_invoice_totals_internal(&tx, *invoice_id)?;
+        }
+        tx.commit()?;
         Ok(())
     }
 
-    // ========== PAY SUMMARY ==========
-
-    pub fn get_pay_summary(&self) -> SqlResult<PaySummary> {
-        let conn = self.conn.lock().unwrap();
-        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
-        let month_start = chrono::Local::now().format("%Y-%m-01").to_string();
-        let year_start = chrono::Local::now().format("%Y-01-01").to_string();
-
-        let calculate_pay = |sessions: &[WorkSession]| -> f64 {
-            sessions.iter().map(|s| s.calculate_pay()).sum()
-        };
+    /// Number of an issued (non-Draft) invoice with a line for the session,
+    /// released or not. Such sessions are part of the invoice's record.
+    pub fn get_session_invoice_number(&self, id: i64) -> SqlResult<Option<String>> {
+        let conn = self.reader();
+        let result = conn.query_row(
+            "SELECT i.invoice_number
+             FROM invoice_item_sessions l
+             JOIN invoice_items ii ON ii.id = l.item_id
+             JOIN invoices i ON i.id = ii.invoice_id
+             WHERE l.session_id = ? AND i.status <> 'Draft'
+             ORDER BY ii.session_released, i.id DESC
+             LIMIT 1",
+            params![id],
+            |row| row.get(0)
+        );
+        match result {
+            Ok(number) => Ok(Some(number)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
 
-        // Today's pay
-        let today_sessions = self.get_sessions_by_date_internal(&conn, &today)?;
-        let today_pay = calculate_pay(&today_sessions);
+    // ========== SESSION QUERIES ==========
 
-        // This month's pay
-        let month_sessions = self.get_sessions_in_range_internal(&conn, &month_start, &today)?;
-        let month_pay = calculate_pay(&month_sessions);
+    /// Filtered, sorted, cursor-paginated session listing. Totals cover the
+    /// whole filtered set, not just the returned page.
+    pub fn query_sessions(&self, query: &SessionQuery) -> SqlResult<SessionPage> {
+        let conn = self.reader();
+        let (mut conditions, mut values) = Self::session_filter_sql(&query.filter);
 
-        // This year's pay
-        let year_sessions = self.get_sessions_in_range_internal(&conn, &year_start, &today)?;
-        let year_pay = calculate_pay(&year_sessions);
+        let where_sql = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let (total_count, total_hours, total_pay) = conn.query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(SUM(s.hours), 0), COALESCE(SUM({}), 0)
+                 FROM sessions s
+                 LEFT JOIN session_types st ON s.session_type_id = st.id
+                 {}",
+                SESSION_PAY_SQL, where_sql
+            ),
+            params_from_iter(values.iter()),
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?, row.get::<_, f64>(2)?)),
+        )?;
 
-        // All time pay
-        let all_sessions = self.get_all_sessions_internal(&conn)?;
-        let all_time_pay = calculate_pay(&all_sessions);
+        let sort_key = query.sort_by.unwrap_or(SessionSortKey::Date);
+        let direction = query.sort_direction.unwrap_or(SortDirection::Desc);
+        let sort_expr = match sort_key {
+            SessionSortKey::Date => "s.date",
+            SessionSortKey::Hours => "s.hours",
+            SessionSortKey::Pay => SESSION_PAY_SQL,
+            SessionSortKey::Project => "s.project_name",
+            SessionSortKey::SessionType => "COALESCE(st.name, '')",
+        };
+        let (cmp, order) = match direction {
+            SortDirection::Asc => (">", "ASC"),
+            SortDirection::Desc => ("<", "DESC"),
+        };
 
-        Ok(PaySummary {
-            today: today_pay,
-            this_month: month_pay,
-            this_year: year_pay,
-            all_time: all_time_pay,
-        })
-    }
+        // Keyset cursor: "<sort value>|<session id>" of the last row returned
+        if let Some(cursor) = &query.cursor {
+            let (value, id) = cursor.rsplit_once('|')
+                .and_then(|(v, id)| id.parse::<i64>().ok().map(|id| (v, id)))
+                .ok_or_else(|| invalid_input(format!("Invalid cursor: {}", cursor)))?;
+            let value = match sort_key {
+                SessionSortKey::Hours | SessionSortKey::Pay => {
+                    Value::Real(value.parse::<f64>().map_err(|_| invalid_input(format!("Invalid cursor: {}", cursor)))?)
+                }
+                _ => Value::Text(value.to_string()),
+            };
+            conditions.push(format!(
+                "({expr} {cmp} ? OR ({expr} = ? AND s.id {cmp} ?))",
+                expr = sort_expr,
+                cmp = cmp
+            ));
+            values.push(value.clone());
+            values.push(value);
+            values.push(Value::Integer(id));
+        }
 
-    fn get_sessions_by_date_internal(&self, conn: &Connection, date: &str) -> SqlResult<Vec<WorkSession>> {
-        let mut stmt = conn.prepare(
+        let limit = query.limit.unwrap_or(50).clamp(1, 500) as usize;
+        let where_sql = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let mut stmt = conn.prepare(&format!(
             "SELECT s.id, s.session_type_id, st.name, s.date, s.project_name, s.hours, s.description,
-                    s.pay_type, s.hourly_rate, s.fixed_amount
+                    s.pay_type, s.hourly_rate, s.fixed_amount, {expr}
              FROM sessions s
              LEFT JOIN session_types st ON s.session_type_id = st.id
-             WHERE s.date = ?"
-        )?;
-        let sessions = stmt.query_map([date], |row| {
+             {where_sql}
+             ORDER BY {expr} {order}, s.id {order}
+             LIMIT {limit}",
+            expr = sort_expr,
+            where_sql = where_sql,
+            order = order,
+            limit = limit + 1
+        ))?;
+
+        let mut rows: Vec<(WorkSession, Value)> = stmt.query_map(params_from_iter(values.iter()), |row| {
             let pay_type_str: Option<String> = row.get(7)?;
-            Ok(WorkSession {
+            Ok((WorkSession {
                 id: row.get(0)?,
                 session_type_id: row.get(1)?,
                 session_type_name: row.get(2)?,
@@ -284,20 +700,53 @@ impl Database {
                 pay_type: pay_type_str.and_then(|s| PayType::from_string(&s).ok()),
                 hourly_rate: row.get(8)?,
                 fixed_amount: row.get(9)?,
-            })
+            }, row.get(10)?))
         })?.collect::<SqlResult<Vec<_>>>()?;
-        Ok(sessions)
+
+        let next_cursor = if rows.len() > limit {
+            rows.truncate(limit);
+            rows.last().map(|(session, value)| {
+                let value = match value {
+                    Value::Integer(i) => i.to_string(),
+                    Value::Real(f) => f.to_string(),
+                    Value::Text(s) => s.clone(),
+                    _ => String::new(),
+                };
+                format!("{}|{}", value, session.id)
+            })
+        } else {
+            None
+        };
+
+        Ok(SessionPage {
+            sessions: rows.into_iter().map(|(session, _)| session).collect(),
+            next_cursor,
+            total_count,
+            total_hours,
+            total_pay,
+        })
     }
 
-    fn get_sessions_in_range_internal(&self, conn: &Connection, start: &str, end: &str) -> SqlResult<Vec<WorkSession>> {
-        let mut stmt = conn.prepare(
+    /// Every session matching the filter, oldest first
+    pub fn find_sessions(&self, filter: &SessionFilter) -> SqlResult<Vec<WorkSession>> {
+        let conn = self.reader();
+        let (conditions, values) = Self::session_filter_sql(filter);
+        let where_sql = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let mut stmt = conn.prepare(&format!(
             "SELECT s.id, s.session_type_id, st.name, s.date, s.project_name, s.hours, s.description,
                     s.pay_type, s.hourly_rate, s.fixed_amount
              FROM sessions s
              LEFT JOIN session_types st ON s.session_type_id = st.id
-             WHERE s.date >= ? AND s.date <= ?"
-        )?;
-        let sessions = stmt.query_map([start, end], |row| {
+             {}
+             ORDER BY s.date, s.id",
+            where_sql
+        ))?;
+
+        let sessions = stmt.query_map(params_from_iter(values.iter()), |row| {
             let pay_type_str: Option<String> = row.get(7)?;
             Ok(WorkSession {
                 id: row.get(0)?,
@@ -312,38 +761,194 @@ impl Database {
                 fixed_amount: row.get(9)?,
             })
         })?.collect::<SqlResult<Vec<_>>>()?;
+
         Ok(sessions)
     }
 
-    fn get_all_sessions_internal(&self, conn: &Connection) -> SqlResult<Vec<WorkSession>> {
+    /// Build WHERE conditions and bound values for a session filter.
+    /// Expects `sessions s` joined with `session_types st`.
+    fn session_filter_sql(filter: &SessionFilter) -> (Vec<String>, Vec<Value>) {
+        let mut conditions: Vec<String> = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+
+        let placeholders = |n: usize| vec!["?"; n].join(", ");
+
+        if let Some(from) = &filter.date_from {
+            conditions.push("s.date >= ?".to_string());
+            values.push(Value::Text(from.clone()));
+        }
+        if let Some(to) = &filter.date_to {
+            conditions.push("s.date <= ?".to_string());
+            values.push(Value::Text(to.clone()));
+        }
+        if !filter.session_type_ids.is_empty() {
+            conditions.push(format!("s.session_type_id IN ({})", placeholders(filter.session_type_ids.len())));
+            values.extend(filter.session_type_ids.iter().map(|id| Value::Integer(*id)));
+        }
+        if !filter.project_names.is_empty() {
+            conditions.push(format!("s.project_name IN ({})", placeholders(filter.project_names.len())));
+            values.extend(filter.project_names.iter().map(|p| Value::Text(p.clone())));
+        }
+        if !filter.pay_types.is_empty() {
+            // Sessions without a pay type are treated as 'None'
+            conditions.push(format!("COALESCE(s.pay_type, 'None') IN ({})", placeholders(filter.pay_types.len())));
+            values.extend(filter.pay_types.iter().map(|t| Value::Text(t.to_string())));
+        }
+        match filter.invoiced {
+            Some(true) => conditions.push(format!("s.id IN ({})", INVOICED_SESSIONS_SQL)),
+            Some(false) => conditions.push(format!("s.id NOT IN ({})", INVOICED_SESSIONS_SQL)),
+            None => {}
+        }
+        if let Some(min) = filter.min_hours {
+            conditions.push("s.hours >= ?".to_string());
+            values.push(Value::Real(min));
+        }
+        if let Some(max) = filter.max_hours {
+            conditions.push("s.hours <= ?".to_string());
+            values.push(Value::Real(max));
+        }
+        if let Some(text) = filter.text.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+            let pattern = format!(
+                "%{}%",
+                text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+            );
+            conditions.push(
+                "(s.project_name LIKE ? ESCAPE '\\' OR s.description LIKE ? ESCAPE '\\' OR st.name LIKE ? ESCAPE '\\')".to_string()
+            );
+            values.push(Value::Text(pattern.clone()));
+            values.push(Value::Text(pattern.clone()));
+            values.push(Value::Text(pattern));
+        }
+
+        (conditions, values)
+    }
+
+    // ========== PAY SUMMARY ==========
+
+    pub fn get_pay_summary(&self, basis: ReportingBasis) -> SqlResult<PaySummary> {
+        let conn = self.reader();
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let month_start = chrono::Local::now().format("%Y-%m-01").to_string();
+        let year_start = chrono::Local::now().format("%Y-01-01").to_string();
+        let pipeline = self.get_income_pipeline_internal(&conn)?;
+
+        conn.query_row(
+            &format!(
+                "SELECT COALESCE(SUM(CASE WHEN date = ?1 THEN amount END), 0),
+                        COALESCE(SUM(CASE WHEN date >= ?2 AND date <= ?1 THEN amount END), 0),
+                        COALESCE(SUM(CASE WHEN date >= ?3 AND date <= ?1 THEN amount END), 0),
+                        COALESCE(SUM(amount), 0)
+                 FROM ({})",
+                income_sql(basis)
+            ),
+            params![today, month_start, year_start],
+            |row| {
+                Ok(PaySummary {
+                    today: logic::round_currency(row.get(0)?),
+                    this_month: logic::round_currency(row.get(1)?),
+                    this_year: logic::round_currency(row.get(2)?),
+                    all_time: logic::round_currency(row.get(3)?),
+                    basis,
+                    pipeline,
+                })
+            }
+        )
+    }
+
+    /// Income on `basis` in a date range (inclusive)
+    pub fn get_income_total(&self, basis: ReportingBasis, start: &str, end: &str) -> SqlResult<f64> {
+        let conn = self.reader();
+        let total: f64 = conn.query_row(
+            &format!("SELECT COALESCE(SUM(amount), 0) FROM ({}) WHERE date >= ? AND date <= ?", income_sql(basis)),
+            [start, end],
+            |row| row.get(0)
+        )?;
+        Ok(logic::round_currency(total))
+    }
+
+    pub fn get_income_pipeline(&self) -> SqlResult<IncomePipeline> {
+        let conn = self.reader();
+        self.get_income_pipeline_internal(&conn)
+    }
+
+    fn get_income_pipeline_internal(&self, conn: &Connection) -> SqlResult<IncomePipeline> {
+        let (unbilled, unbilled_hours): (f64, f64) = conn.query_row(
+            &format!(
+                "SELECT COALESCE(SUM({}), 0), COALESCE(SUM(s.hours), 0) FROM sessions s
+                 WHERE s.id NOT IN (SELECT l.session_id FROM invoice_item_sessions l
+                                    JOIN invoice_items ii ON ii.id = l.item_id
+                                    JOIN invoices i ON i.id = ii.invoice_id
+                                    WHERE ii.session_released = 0 AND i.status <> 'Draft')",
+                SESSION_PAY_SQL
+            ),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?))
+        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM invoices
+             WHERE status IN ('Sent', 'PartiallyPaid', 'Overdue') AND credited_invoice_id IS NULL",
+            INVOICE_COLUMNS
+        ))?;
+        let invoiced_unpaid: f64 = stmt.query_map([], invoice_from_row)?
+            .map(|invoice| invoice.map(|i| i.balance_due))
+            .sum::<SqlResult<f64>>()?;
+        Ok(IncomePipeline {
+            unbilled: logic::round_currency(unbilled),
+            unbilled_hours,
+            invoiced_unpaid: logic::round_currency(invoiced_unpaid),
+        })
+    }
+
+    /// Per-day hours and pay in a date range (inclusive), ordered by date
+    pub fn get_daily_totals(&self, start: &str, end: &str) -> SqlResult<Vec<DailyHours>> {
+        let conn = self.reader();
         let mut stmt = conn.prepare(
-            "SELECT s.id, s.session_type_id, st.name, s.date, s.project_name, s.hours, s.description,
-                    s.pay_type, s.hourly_rate, s.fixed_amount
-             FROM sessions s
-             LEFT JOIN session_types st ON s.session_type_id = st.id"
+            "SELECT date, SUM(hours), SUM(pay) FROM daily_rollups
+             WHERE date >= ? AND date <= ?
+             GROUP BY date ORDER BY date"
         )?;
-        let sessions = stmt.query_map([], |row| {
-            let pay_type_str: Option<String> = row.get(7)?;
-            Ok(WorkSession {
-                id: row.get(0)?,
-                session_type_id: row.get(1)?,
-                session_type_name: row.get(2)?,
-                date: row.get(3)?,
-                project_name: row.get(4)?,
-                hours: row.get(5)?,
-                description: row.get(6)?,
-                pay_type: pay_type_str.and_then(|s| PayType::from_string(&s).ok()),
-                hourly_rate: row.get(8)?,
-                fixed_amount: row.get(9)?,
+        let days = stmt.query_map([start, end], |row| {
+            Ok(DailyHours {
+                date: row.get(0)?,
+                hours: row.get(1)?,
+                pay: row.get(2)?,
             })
         })?.collect::<SqlResult<Vec<_>>>()?;
-        Ok(sessions)
+        Ok(days)
+    }
+
+    /// All distinct dates with at least one session, ascending
+    pub fn get_session_dates(&self) -> SqlResult<Vec<String>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare("SELECT DISTINCT date FROM daily_rollups ORDER BY date")?;
+        let dates = stmt.query_map([], |row| row.get(0))?.collect::<SqlResult<Vec<_>>>()?;
+        Ok(dates)
+    }
+
+    /// Longest single session in a date range (inclusive)
+    pub fn get_longest_session(&self, start: &str, end: &str) -> SqlResult<f64> {
+        let conn = self.reader();
+        conn.query_row(
+            "SELECT COALESCE(MAX(hours), 0) FROM sessions WHERE date >= ? AND date <= ?",
+            [start, end],
+            |row| row.get(0)
+        )
+    }
+
+    /// Count sessions shorter than `max_hours` in a date range (inclusive)
+    pub fn count_short_sessions(&self, start: &str, end: &str, max_hours: f64) -> SqlResult<i64> {
+        let conn = self.reader();
+        conn.query_row(
+            "SELECT COUNT(*) FROM sessions WHERE date >= ? AND date <= ? AND hours < ?",
+            params![start, end, max_hours],
+            |row| row.get(0)
+        )
     }
 
     // ========== GOAL OPERATIONS ==========
 
     pub fn get_all_goals(&self) -> SqlResult<Vec<FinancialGoal>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         let mut stmt = conn.prepare(
             "SELECT id, goal_type, name, target_amount, current_amount, created_date, target_date
              FROM goals ORDER BY created_date DESC"
@@ -365,7 +970,7 @@ impl Database {
     }
 
     pub fn add_goal(&self, goal: NewGoal) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.writer();
         conn.execute(
             "INSERT INTO goals (goal_type, name, target_amount, current_amount, created_date, target_date)
              VALUES (?, ?, ?, ?, ?, ?)",
@@ -375,7 +980,7 @@ impl Database {
     }
 
     pub fn update_goal(&self, goal: &FinancialGoal) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.writer();
         conn.execute(
             "UPDATE goals SET goal_type = ?, name = ?, target_amount = ?, current_amount = ?,
              created_date = ?, target_date = ? WHERE id = ?",
@@ -385,13 +990,13 @@ impl Database {
     }
 
     pub fn add_contribution(&self, goal_id: i64, amount: f64) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.writer();
         conn.execute("UPDATE goals SET current_amount = current_amount + ? WHERE id = ?", params![amount, goal_id])?;
         Ok(())
     }
 
     pub fn delete_goal(&self, id: i64) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.writer();
         conn.execute("DELETE FROM goals WHERE id = ?", params![id])?;
         Ok(())
     }
@@ -399,7 +1004,7 @@ impl Database {
     // ========== PROJECT CACHE ==========
 
     pub fn get_projects_by_type_id(&self, session_type_id: i64) -> SqlResult<Vec<String>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         let mut stmt = conn.prepare(
             "SELECT DISTINCT project_name FROM projects WHERE session_type_id = ?
              ORDER BY use_count DESC, last_used DESC LIMIT 20"
@@ -424,42 +1029,35 @@ impl Database {
 
     // ========== ANALYTICS ==========
 
-    pub fn get_analytics(&self, range_start: &str, range_end: &str) -> SqlResult<AnalyticsData> {
-        let conn = self.conn.lock().unwrap();
-
-        // Get sessions in range with session type info
-        let mut stmt = conn.prepare(
-            "SELECT s.id, s.session_type_id, st.name, st.color, s.date, s.project_name, s.hours, 
-                    s.description, s.pay_type, s.hourly_rate, s.fixed_amount
-             FROM sessions s
-             LEFT JOIN session_types st ON s.session_type_id = st.id
-             WHERE s.date >= ? AND s.date <= ?
-             ORDER BY s.date"
-        )?;
-
-        let sessions: Vec<(WorkSession, String)> = stmt.query_map([range_start, range_end], |row| {
-            let pay_type_str: Option<String> = row.get(8)?;
-            let color: String = row.get::<_, Option<String>>(3)?.unwrap_or("#6366F1".to_string());
-            Ok((WorkSession {
-                id: row.get(0)?,
-                session_type_id: row.get(1)?,
-                session_type_name: row.get(2)?,
-                date: row.get(4)?,
-                project_name: row.get(5)?,
-                hours: row.get(6)?,
-                description: row.get(7)?,
-                pay_type: pay_type_str.and_then(|s| PayType::from_string(&s).ok()),
-                hourly_rate: row.get(9)?,
-                fixed_amount: row.get(10)?,
-            }, color))
-        })?.collect::<SqlResult<Vec<_>>>()?;
+    /// Hours always count when worked; pay is counted on `basis`
+    pub fn get_analytics(&self, range_start: &str, range_end: &str, basis: ReportingBasis) -> SqlResult<AnalyticsData> {
+        let conn = self.reader();
+        // Work from the rollups alongside income, so days and types with only one of them still show
+        let activity = format!(
+            "SELECT date, session_type_id, hours, session_count, 0 AS amount FROM daily_rollups
+             UNION ALL
+             SELECT date, session_type_id, 0, 0, amount FROM ({})",
+            income_sql(basis)
+        );
 
         // Summary calculations
-        let total_hours: f64 = sessions.iter().map(|(s, _)| s.hours).sum();
-        let total_sessions = sessions.len();
+        let (total_hours, total_sessions, total_pay): (f64, i64, f64) = conn.query_row(
+            &format!(
+                "SELECT COALESCE(SUM(hours), 0), COALESCE(SUM(session_count), 0), COALESCE(SUM(amount), 0)
+                 FROM ({}) WHERE date >= ? AND date <= ?",
+                activity
+            ),
+            [range_start, range_end],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        )?;
+        let total_pay = logic::round_currency(total_pay);
+        let total_sessions = total_sessions as usize;
         let avg_session_length = if total_sessions > 0 { total_hours / total_sessions as f64 } else { 0.0 };
-        let longest_session = sessions.iter().map(|(s, _)| s.hours).fold(0.0_f64, f64::max);
-        let total_pay: f64 = sessions.iter().map(|(s, _)| s.calculate_pay()).sum();
+        let longest_session: f64 = conn.query_row(
+            "SELECT COALESCE(MAX(hours), 0) FROM sessions WHERE date >= ? AND date <= ?",
+            [range_start, range_end],
+            |row| row.get(0)
+        )?;
 
         let summary = AnalyticsSummary {
             total_hours,
@@ -470,44 +1068,58 @@ impl Database {
         };
 
         // Daily hours aggregation
-        let mut daily_map: std::collections::HashMap<String, (f64, f64)> = std::collections::HashMap::new();
-        for (session, _) in &sessions {
-            let entry = daily_map.entry(session.date.clone()).or_insert((0.0, 0.0));
-            entry.0 += session.hours;
-            entry.1 += session.calculate_pay();
-        }
-        let mut daily_hours: Vec<DailyHours> = daily_map.into_iter()
-            .map(|(date, (hours, pay))| DailyHours { date, hours, pay })
-            .collect();
-        daily_hours.sort_by(|a, b| a.date.cmp(&b.date));
-
-        // Category breakdown
-        let mut category_map: std::collections::HashMap<String, (String, f64, usize, f64)> = std::collections::HashMap::new();
-        for (session, color) in &sessions {
-            let category_name = session.session_type_name.clone().unwrap_or("Unknown".to_string());
-            let entry = category_map.entry(category_name).or_insert((color.clone(), 0.0, 0, 0.0));
-            entry.1 += session.hours;
-            entry.2 += 1;
-            entry.3 += session.calculate_pay();
-        }
-        let category_breakdown: Vec<CategoryBreakdown> = category_map.into_iter()
-            .map(|(category, (color, hours, sessions, pay))| CategoryBreakdown {
-                category,
-                color,
-                hours,
-                sessions,
-                pay,
+        let mut stmt = conn.prepare(&format!(
+            "SELECT date, SUM(hours), SUM(amount) FROM ({})
+             WHERE date >= ? AND date <= ?
+             GROUP BY date ORDER BY date",
+            activity
+        ))?;
+        let daily_hours: Vec<DailyHours> = stmt.query_map([range_start, range_end], |row| {
+            Ok(DailyHours {
+                date: row.get(0)?,
+                hours: row.get(1)?,
+                pay: logic::round_currency(row.get(2)?),
             })
-            .collect();
+        })?.collect::<SqlResult<Vec<_>>>()?;
+
+        // Category breakdown; invoice lines without sessions are "Other"
+        let mut stmt = conn.prepare(&format!(
+            "SELECT CASE WHEN r.session_type_id IS NULL THEN 'Other' ELSE COALESCE(st.name, 'Unknown') END AS category,
+                    COALESCE(MAX(st.color), '#6366F1'),
+                    SUM(r.hours), SUM(r.session_count), SUM(r.amount)
+             FROM ({}) r
+             LEFT JOIN session_types st ON r.session_type_id = st.id
+             WHERE r.date >= ? AND r.date <= ?
+             GROUP BY category",
+            activity
+        ))?;
+        let category_breakdown: Vec<CategoryBreakdown> = stmt.query_map([range_start, range_end], |row| {
+            Ok(CategoryBreakdown {
+                category: row.get(0)?,
+                color: row.get(1)?,
+                hours: row.get(2)?,
+                sessions: row.get::<_, i64>(3)? as usize,
+                pay: logic::round_currency(row.get(4)?),
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
 
-        // Weekday breakdown
+        // Weekday breakdown (strftime('%w') is 0 = Sunday)
         let mut weekday_counts: [f64; 7] = [0.0; 7];
         let mut weekday_sessions: [usize; 7] = [0; 7];
-        for (session, _) in &sessions {
-            if let Ok(date) = chrono::NaiveDate::parse_from_str(&session.date, "%Y-%m-%d") {
-                let weekday = date.weekday().num_days_from_sunday() as usize;
-                weekday_counts[weekday] += session.hours;
-                weekday_sessions[weekday] += 1;
+        let mut stmt = conn.prepare(
+            "SELECT CAST(strftime('%w', date) AS INTEGER) AS weekday, SUM(hours), SUM(session_count)
+             FROM daily_rollups
+             WHERE date >= ? AND date <= ? AND strftime('%w', date) IS NOT NULL
+             GROUP BY weekday"
+        )?;
+        let rows = stmt.query_map([range_start, range_end], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?, row.get::<_, i64>(2)?))
+        })?;
+        for row in rows {
+            let (weekday, hours, sessions) = row?;
+            if let Some(i) = usize::try_from(weekday).ok().filter(|i| *i < 7) {
+                weekday_counts[i] = hours;
+                weekday_sessions[i] = sessions as usize;
             }
         }
         let weekday_names = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
@@ -520,6 +1132,7 @@ impl Database {
             .collect();
 
         Ok(AnalyticsData {
+            basis,
             summary,
             daily_hours,
             category_breakdown,
@@ -534,6 +1147,8 @@ pub struct PaySummary {
     pub this_month: f64,
     pub this_year: f64,
     pub all_time: f64,
+    pub basis: ReportingBasis,
+    pub pipeline: IncomePipeline,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -570,6 +1185,7 @@ pub struct WeekdayBreakdown {
 
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct AnalyticsData {
+    pub basis: ReportingBasis,
     pub summary: AnalyticsSummary,
     pub daily_hours: Vec<DailyHours>,
     pub category_breakdown: Vec<CategoryBreakdown>,
@@ -581,7 +1197,7 @@ pub struct AnalyticsData {
 impl Database {
     /// Get all unlocked achievement IDs
     pub fn get_unlocked_achievements(&self) -> SqlResult<Vec<(String, String)>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         let mut stmt = conn.prepare("SELECT id, unlocked_at FROM achievements")?;
         let achievements = stmt.query_map([], |row| {
             Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
@@ -591,7 +1207,7 @@ impl Database {
 
     /// Unlock an achievement
     pub fn unlock_achievement(&self, achievement_id: &str) -> SqlResult<bool> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.writer();
         let result = conn.execute(
             "INSERT OR IGNORE INTO achievements (id) VALUES (?)",
             params![achievement_id],
@@ -601,7 +1217,7 @@ impl Database {
 
     /// Log an app event (for achievement tracking)
     pub fn log_app_event(&self, event_type: &str, event_data: Option<&str>) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.writer();
         conn.execute(
             "INSERT INTO app_events (event_type, event_data) VALUES (?, ?)",
             params![event_type, event_data],
@@ -611,7 +1227,7 @@ impl Database {
 
     /// Count distinct days with events of a certain type
     pub fn count_event_days(&self, event_type: &str) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         let count: i64 = conn.query_row(
             "SELECT COUNT(DISTINCT date(created_at)) FROM app_events WHERE event_type = ?",
             params![event_type],
@@ -622,7 +1238,7 @@ impl Database {
 
     /// Count distinct event data values for a type
     pub fn count_distinct_event_data(&self, event_type: &str) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         let count: i64 = conn.query_row(
             "SELECT COUNT(DISTINCT event_data) FROM app_events WHERE event_type = ? AND event_data IS NOT NULL",
             params![event_type],
@@ -633,7 +1249,7 @@ impl Database {
 
     /// Check if two event types occurred on the same day
     pub fn events_same_day(&self, event1: &str, event2: &str) -> SqlResult<bool> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         let count: i64 = conn.query_row(
             "SELECT COUNT(*) FROM (
                 SELECT DISTINCT date(created_at) as d FROM app_events WHERE event_type = ?
@@ -650,14 +1266,14 @@ impl Database {
 
     /// Count total sessions
     pub fn count_total_sessions(&self) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         let count: i64 = conn.query_row("SELECT COUNT(*) FROM sessions", [], |row| row.get(0))?;
         Ok(count)
     }
 
     /// Count distinct days with sessions
     pub fn count_distinct_session_days(&self) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         let count: i64 = conn.query_row(
             "SELECT COUNT(DISTINCT date) FROM sessions",
             [],
@@ -668,7 +1284,7 @@ impl Database {
 
     /// Count distinct weeks with sessions
     pub fn count_distinct_session_weeks(&self) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         let count: i64 = conn.query_row(
             "SELECT COUNT(DISTINCT strftime('%Y-%W', date)) FROM sessions",
             [],
@@ -679,7 +1295,7 @@ impl Database {
 
     /// Get total hours tracked
     pub fn get_total_hours(&self) -> SqlResult<f64> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         let hours: f64 = conn.query_row(
             "SELECT COALESCE(SUM(hours), 0) FROM sessions",
             [],
@@ -690,7 +1306,7 @@ impl Database {
 
     /// Check if user has any paid sessions
     pub fn has_paid_session(&self) -> SqlResult<bool> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         let count: i64 = conn.query_row(
             "SELECT COUNT(*) FROM sessions WHERE pay_type IN ('Hourly', 'Fixed') AND (hourly_rate > 0 OR fixed_amount > 0)",
             [],
@@ -701,7 +1317,7 @@ impl Database {
 
     /// Check for sustainable week (avg < 8h/day for 7 consecutive days with sessions)
     pub fn has_sustainable_week(&self) -> SqlResult<bool> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         // Get daily totals ordered by date
         let mut stmt = conn.prepare(
             "SELECT date, SUM(hours) as daily_hours FROM sessions 
@@ -727,7 +1343,7 @@ impl Database {
 
     /// Check for paced week (no session > 6h in past 7 days with sessions)
     pub fn has_paced_week(&self) -> SqlResult<bool> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         // Need at least 7 distinct days of sessions
         let day_count: i64 = conn.query_row(
             "SELECT COUNT(DISTINCT date) FROM sessions",
@@ -755,7 +1371,7 @@ impl Database {
 
     /// Check for human weekend (< 3h total on a Sat+Sun)
     pub fn has_human_weekend(&self) -> SqlResult<bool> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
         // Find weekends (Sat=6, Sun=0 in strftime)
         let count: i64 = conn.query_row(
             "SELECT COUNT(*) FROM (
@@ -774,88 +1390,171 @@ impl Database {
     // ========== INVOICE OPERATIONS ==========
 
     pub fn get_all_invoices(&self) -> SqlResult<Vec<Invoice>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, invoice_number, client_name, client_email, created_date, due_date, 
-                    status, subtotal, tax_rate, tax_amount, total, notes
-             FROM invoices ORDER BY created_date DESC"
-        )?;
+        let conn = self.reader();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM invoices ORDER BY created_date DESC",
+            INVOICE_COLUMNS
+        ))?;
         
-        let invoices: Vec<Invoice> = stmt.query_map([], |row| {
-            let id: i64 = row.get(0)?;
-            let status_str: String = row.get(6)?;
-            Ok(Invoice {
-                id,
-                invoice_number: row.get(1)?,
-                client_name: row.get(2)?,
-                client_email: row.get(3)?,
-                created_date: row.get(4)?,
-                due_date: row.get(5)?,
-                status: InvoiceStatus::from_string(&status_str).unwrap_or(InvoiceStatus::Draft),
-                subtotal: row.get(7)?,
-                tax_rate: row.get(8)?,
-                tax_amount: row.get(9)?,
-                total: row.get(10)?,
-                notes: row.get(11)?,
-                items: Vec::new(), // Will be populated below
-            })
-        })?.collect::<SqlResult<Vec<_>>>()?;
+        let invoices: Vec<Invoice> = stmt.query_map([], invoice_from_row)?
+            .collect::<SqlResult<Vec<_>>>()?;
         
-        // Get items for each invoice
+        // Get items and taxes for each invoice
         let mut result = invoices;
         for invoice in &mut result {
-            invoice.items = self.get_invoice_items_internal(&conn, invoice.id)?;
+            self.load_invoice_lines_internal(&conn, invoice)?;
         }
         
         Ok(result)
     }
 
     pub fn get_invoice(&self, id: i64) -> SqlResult<Invoice> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader();
+        self.get_invoice_internal(&conn, id)
+    }
+
+    fn get_invoice_internal(&self, conn: &Connection, id: i64) -> SqlResult<Invoice> {
         let mut invoice: Invoice = conn.query_row(
-            "SELECT id, invoice_number, client_name, client_email, created_date, due_date, 
-                    status, subtotal, tax_rate, tax_amount, total, notes
-             FROM invoices WHERE id = ?",
+            &format!("SELECT {} FROM invoices WHERE id = ?", INVOICE_COLUMNS),
             params![id],
-            |row| {
-                let status_str: String = row.get(6)?;
-                Ok(Invoice {
-                    id: row.get(0)?,
-                    invoice_number: row.get(1)?,
-                    client_name: row.get(2)?,
-                    client_email: row.get(3)?,
-                    created_date: row.get(4)?,
-                    due_date: row.get(5)?,
-                    status: InvoiceStatus::from_string(&status_str).unwrap_or(InvoiceStatus::Draft),
-                    subtotal: row.get(7)?,
-                    tax_rate: row.get(8)?,
-                    tax_amount: row.get(9)?,
-                    total: row.get(10)?,
-                    notes: row.get(11)?,
-                    items: Vec::new(),
-                })
-            }
+            invoice_from_row,
         )?;
         
-        invoice.items = self.get_invoice_items_internal(&conn, id)?;
+        self.load_invoice_lines_internal(conn, &mut invoice)?;
         Ok(invoice)
     }
 
+    fn load_invoice_lines_internal(&self, conn: &Connection, invoice: &mut Invoice) -> SqlResult<()> {
+        invoice.items = self.get_invoice_items_internal(conn, invoice.id)?;
+        invoice.taxes = self.get_invoice_taxes_internal(conn, invoice.id)?;
+        invoice.payments = self.get_invoice_payments_internal(conn, invoice.id)?;
+        if invoice.include_timesheet {
+            invoice.timesheet = self.get_invoice_timesheet_internal(conn, invoice.id)?;
+        }
+        
+        // Invoices from before named taxes only have `tax_rate`
+        if invoice.taxes.is_empty() {
+            if let Some(rate) = invoice.tax_rate.filter(|r| *r != 0.0) {
+                invoice.taxes.push(InvoiceTax {
+                    id: 0,
+                    invoice_id: invoice.id,
+                    tax_rate_id: None,
+                    name: "Tax".to_string(),
+                    rate,
+                    kind: TaxKind::Additive,
+                    category: None,
+                    note: None,
+                    base: invoice.subtotal - invoice.discount_amount,
+                    amount: invoice.tax_amount,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn get_invoice_timesheet_internal(&self, conn: &Connection, invoice_id: i64) -> SqlResult<Vec<TimesheetEntry>> {
+        let mut stmt = conn.prepare(
+            "SELECT s.id, s.date, st.name, COALESCE(s.project_name, 'Unnamed Session'), s.description, s.hours
+             FROM invoice_item_sessions l
+             JOIN invoice_items ii ON ii.id = l.item_id
+             JOIN sessions s ON s.id = l.session_id
+             LEFT JOIN session_types st ON s.session_type_id = st.id
+             WHERE ii.invoice_id = ?
+             ORDER BY s.date, s.id"
+        )?;
+        
+        let entries = stmt.query_map(params![invoice_id], |row| {
+            Ok(TimesheetEntry {
+                session_id: row.get(0)?,
+                date: row.get(1)?,
+                session_type: row.get(2)?,
+                project_name: row.get(3)?,
+                description: row.get(4)?,
+                hours: row.get(5)?,
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+        
+        Ok(entries)
+    }
+
+    fn get_invoice_payments_internal(&self, conn: &Connection, invoice_id: i64) -> SqlResult<Vec<InvoicePayment>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, invoice_id, payment_date, amount, method, reference, notes, bank_transaction_id
+             FROM invoice_payments WHERE invoice_id = ? ORDER BY payment_date, id"
+        )?;
+        
+        let payments = stmt.query_map(params![invoice_id], |row| {
+            Ok(InvoicePayment {
+                id: row.get(0)?,
+                invoice_id: row.get(1)?,
+                payment_date: row.get(2)?,
+                amount: row.get(3)?,
+                method: row.get(4)?,
+                reference: row.get(5)?,
+                notes: row.get(6)?,
+                bank_transaction_id: row.get(7)?,
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+        
+        Ok(payments)
+    }
+
+    fn get_invoice_taxes_internal(&self, conn: &Connection, invoice_id: i64) -> SqlResult<Vec<InvoiceTax>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, invoice_id, tax_rate_id, name, rate, kind, category, note, base, amount
+             FROM invoice_taxes WHERE invoice_id = ? ORDER BY id"
+        )?;
+        
+        let taxes = stmt.query_map(params![invoice_id], |row| {
+            let kind: String = row.get(5)?;
+            Ok(InvoiceTax {
+                id: row.get(0)?,
+                invoice_id: row.get(1)?,
+                tax_rate_id: row.get(2)?,
+                name: row.get(3)?,
+                rate: row.get(4)?,
+                kind: TaxKind::from_string(&kind).unwrap_or(TaxKind::Additive),
+                category: row.get(6)?,
+                note: row.get(7)?,
+                base: row.get(8)?,
+                amount: row.get(9)?,
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+        
+        Ok(taxes)
+    }
+
     fn get_invoice_items_internal(&self, conn: &Connection, invoice_id: i64) -> SqlResult<Vec<InvoiceItem>> {
         let mut stmt = conn.prepare(
-            "SELECT id, invoice_id, session_id, description, hours, rate, amount
-             FROM invoice_items WHERE invoice_id = ?"
+            "SELECT id, invoice_id, session_id, kind, description, hours, unit, rate,
+                    discount_type, discount_value, taxable, tax_category, amount,
+                    credited_item_id, session_released,
+                    (SELECT GROUP_CONCAT(l.session_id) FROM invoice_item_sessions l WHERE l.item_id = invoice_items.id)
+             FROM invoice_items WHERE invoice_id = ? ORDER BY id"
         )?;
         
         let items = stmt.query_map(params![invoice_id], |row| {
+            let kind: String = row.get(3)?;
+            let session_ids: Option<String> = row.get(15)?;
             Ok(InvoiceItem {
                 id: row.get(0)?,
                 invoice_id: row.get(1)?,
                 session_id: row.get(2)?,
-                description: row.get(3)?,
-                hours: row.get(4)?,
-                rate: row.get(5)?,
-                amount: row.get(6)?,
+                session_ids: session_ids.iter()
+                    .flat_map(|ids| ids.split(','))
+                    .filter_map(|id| id.parse().ok())
+                    .collect(),
+                kind: InvoiceItemKind::from_string(&kind).unwrap_or(InvoiceItemKind::Session),
+                description: row.get(4)?,
+                hours: row.get(5)?,
+                unit: row.get(6)?,
+                rate: row.get(7)?,
+                discount: discount_from_columns(row.get(8)?, row.get(9)?),
+                taxable: row.get(10)?,
+                tax_category: row.get(11)?,
+                amount: row.get(12)?,
+                credited_item_id: row.get(13)?,
+                session_released: row.get(14)?,
             })
         })?.collect::<SqlResult<Vec<_>>>()?;
         
@@ -863,550 +1562,4610 @@ impl Database {
     }
 
     pub fn create_invoice(&self, invoice: NewInvoice) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
-        
-        // Generate invoice number
-        let count: i64 = conn.query_row("SELECT COUNT(*) FROM invoices", [], |row| row.get(0))?;
-        let invoice_number = format!("INV-{:04}", count + 1);
-        let created_date = chrono::Local::now().format("%Y-%m-%d").to_string();
-        
-        // Get sessions to calculate totals
-        let session_ids_str = invoice.session_ids.iter()
-            .map(|id| id.to_string())
-            .collect::<Vec<_>>()
-            .join(",");
-        
-        let sessions: Vec<(i64, String, f64, Option<f64>, Option<f64>, Option<String>, Option<String>)> = if !invoice.session_ids.is_empty() {
-            let mut stmt = conn.prepare(&format!(
-                "SELECT s.id, s.project_name, s.hours, s.hourly_rate, s.fixed_amount, s.pay_type, st.name
-                 FROM sessions s
-                 LEFT JOIN session_types st ON s.session_type_id = st.id
-                 WHERE s.id IN ({})", session_ids_str
-            ))?;
-            let result: Vec<_> = stmt.query_map([], |row| {
-                Ok((
-                    row.get::<_, i64>(0)?,
-                    row.get::<_, String>(1)?,
-                    row.get::<_, f64>(2)?,
-                    row.get::<_, Option<f64>>(3)?,
-                    row.get::<_, Option<f64>>(4)?,
-                    row.get::<_, Option<String>>(5)?,
-                    row.get::<_, Option<String>>(6)?,
-                ))
-            })?.collect::<SqlResult<Vec<_>>>()?;
-            result
-        } else {
-            Vec::new()
-        };
-        
-        // Calculate subtotal
-        let subtotal: f64 = sessions.iter().map(|(_, _, hours, hourly_rate, fixed_amount, pay_type, _)| {
-            match pay_type.as_deref() {
-                Some("Hourly") => hourly_rate.unwrap_or(0.0) * hours,
-                Some("Fixed") => fixed_amount.unwrap_or(0.0),
-                _ => 0.0,
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let invoice_id = self.create_invoice_internal(&tx, invoice)?;
+        tx.commit()?;
+        Ok(invoice_id)
+    }
+
+    /// The invoice `create_invoice` would produce, computed in a transaction
+    /// that is rolled back
+    pub fn preview_invoice(&self, invoice: NewInvoice) -> SqlResult<Invoice> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let invoice_id = self.create_invoice_internal(&tx, invoice)?;
+        self.get_invoice_internal(&tx, invoice_id)
+    }
+
+    /// Client name and email of the latest invoice billing each of these
+    /// projects, one entry per distinct client. Projects never billed are skipped.
+    pub fn get_last_clients_for_projects(&self, project_names: &[String]) -> SqlResult<Vec<(String, Option<String>)>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(
+            "SELECT i.client_name, i.client_email FROM invoices i
+             WHERE i.credited_invoice_id IS NULL
+               AND EXISTS (
+                   SELECT 1 FROM invoice_items ii
+                   JOIN invoice_item_sessions l ON l.item_id = ii.id
+                   JOIN sessions s ON s.id = l.session_id
+                   WHERE ii.invoice_id = i.id AND s.project_name = ?
+               )
+             ORDER BY i.created_date DESC, i.id DESC
+             LIMIT 1"
+        )?;
+        let mut clients: Vec<(String, Option<String>)> = Vec::new();
+        for project_name in project_names {
+            let result = stmt.query_row(params![project_name], |row| Ok((row.get(0)?, row.get(1)?)));
+            match result {
+                Ok(client) => {
+                    if !clients.iter().any(|(name, _)| *name == client.0) {
+                        clients.push(client);
+                    }
+                }
+                Err(rusqlite::Error::QueryReturnedNoRows) => {}
+                Err(e) => return Err(e),
             }
-        }).sum();
-        
-        let tax_amount = subtotal * invoice.tax_rate.unwrap_or(0.0) / 100.0;
-        let total = subtotal + tax_amount;
+        }
+        Ok(clients)
+    }
+
+    /// Like `create_invoice`, but fails unless every session in `session_ids`
+    /// is still uninvoiced and ends up on the invoice
+    pub fn create_invoice_for_sessions(&self, invoice: NewInvoice) -> SqlResult<Invoice> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let expected = invoice.session_ids.len() as i64;
+        let invoice_id = self.create_invoice_internal(&tx, invoice)?;
+        let billed: i64 = tx.query_row(
+            "SELECT COUNT(DISTINCT l.session_id) FROM invoice_item_sessions l
+             JOIN invoice_items ii ON ii.id = l.item_id
+             WHERE ii.invoice_id = ?",
+            params![invoice_id],
+            |row| row.get(0),
+        )?;
+        if billed != expected {
+            return Err(invalid_input("Some of the sessions have been invoiced in the meantime".to_string()));
+        }
+        let invoice = self.get_invoice_internal(&tx, invoice_id)?;
+        tx.commit()?;
+        Ok(invoice)
+    }
+
+    fn create_invoice_internal(&self, conn: &Connection, invoice: NewInvoice) -> SqlResult<i64> {
+        // Assign invoice number (rolled back with the invoice on failure)
+        let created_date = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let invoice_number = self.next_invoice_number_internal(conn, &invoice.client_name, &created_date)?;
+        let (discount_type, discount_value) = discount_to_columns(invoice.discount.as_ref());
+        let profile_id = match invoice.profile_id {
+            Some(id) => Some(id),
+            None => self.get_default_business_profile_internal(conn)?.map(|p| p.id),
+        };
         
-        // Insert invoice
+        // Insert invoice; totals are filled in once the line items exist
         conn.execute(
             "INSERT INTO invoices (invoice_number, client_name, client_email, created_date, due_date, 
-                                   status, subtotal, tax_rate, tax_amount, total, notes)
-             VALUES (?, ?, ?, ?, ?, 'Draft', ?, ?, ?, ?, ?)",
+                                   status, subtotal, tax_rate, tax_amount, total, notes,
+                                   discount_type, discount_value, profile_id, template_id,
+                                   line_grouping, include_timesheet)
+             VALUES (?, ?, ?, ?, ?, 'Draft', 0, ?, 0, 0, ?, ?, ?, ?, ?, ?, ?)",
             params![
                 invoice_number,
                 invoice.client_name,
                 invoice.client_email,
                 created_date,
                 invoice.due_date,
-                subtotal,
                 invoice.tax_rate,
-                tax_amount,
-                total,
-                invoice.notes
+                invoice.notes,
+                discount_type,
+                discount_value,
+                profile_id,
+                invoice.template_id,
+                invoice.line_grouping.to_string(),
+                invoice.include_timesheet
             ],
         )?;
         
         let invoice_id = conn.last_insert_rowid();
-        
-        // Insert line items
-        for (session_id, project_name, hours, hourly_rate, fixed_amount, pay_type, session_type) in sessions {
-            let rate = hourly_rate.unwrap_or(0.0);
-            let amount = match pay_type.as_deref() {
-                Some("Hourly") => rate * hours,
-                Some("Fixed") => fixed_amount.unwrap_or(0.0),
-                _ => 0.0,
-            };
-            let description = format!("{} - {}", session_type.unwrap_or("Work".to_string()), project_name);
-            
-            conn.execute(
-                "INSERT INTO invoice_items (invoice_id, session_id, description, hours, rate, amount)
-                 VALUES (?, ?, ?, ?, ?, ?)",
-                params![invoice_id, session_id, description, hours, rate, amount],
-            )?;
+        let client_address = if invoice.client_address.is_empty() {
+            self.get_last_client_address_internal(conn, &invoice.client_name)?
+        } else {
+            invoice.client_address
+        };
+        self.set_client_address_internal(conn, invoice_id, &client_address)?;
+        self.insert_session_items_internal(conn, invoice_id, &invoice.session_ids, invoice.line_grouping)?;
+        for item in &invoice.items {
+            self.insert_invoice_item_internal(conn, invoice_id, item)?;
         }
-        
+        self.set_invoice_taxes_internal(conn, invoice_id, invoice.tax_rate, &invoice.tax_rate_ids)?;
+        self.recalculate_invoice_totals_internal(conn, invoice_id)?;
         Ok(invoice_id)
     }
 
-    pub fn update_invoice_status(&self, id: i64, status: InvoiceStatus) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "UPDATE invoices SET status = ? WHERE id = ?",
-            params![status.to_string(), id],
-        )?;
-        Ok(())
+    /// Address on the client's latest invoice that has one
+    fn get_last_client_address_internal(&self, conn: &Connection, client_name: &str) -> SqlResult<ClientAddress> {
+        let result = conn.query_row(
+            "SELECT client_address, client_city, client_postal_code, client_country_code, client_tax_id
+             FROM invoices
+             WHERE client_name = ? AND COALESCE(client_address, client_city, client_postal_code,
+                                                client_country_code, client_tax_id) IS NOT NULL
+             ORDER BY created_date DESC, id DESC
+             LIMIT 1",
+            params![client_name.trim()],
+            |row| Ok(ClientAddress {
+                address: row.get(0)?,
+                city: row.get(1)?,
+                postal_code: row.get(2)?,
+                country_code: row.get(3)?,
+                tax_id: row.get(4)?,
+            })
+        );
+        match result {
+            Ok(address) => Ok(address),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(ClientAddress::default()),
+            Err(e) => Err(e),
+        }
     }
 
-    pub fn delete_invoice(&self, id: i64) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM invoice_items WHERE invoice_id = ?", params![id])?;
-        conn.execute("DELETE FROM invoices WHERE id = ?", params![id])?;
+    fn set_client_address_internal(&self, conn: &Connection, invoice_id: i64, address: &ClientAddress) -> SqlResult<()> {
+        conn.execute(
+            "UPDATE invoices SET client_address = ?, client_city = ?, client_postal_code = ?,
+                                 client_country_code = ?, client_tax_id = ?
+             WHERE id = ?",
+            params![
+                address.address,
+                address.city,
+                address.postal_code,
+                address.country_code.as_deref().map(|c| c.trim().to_uppercase()),
+                address.tax_id,
+                invoice_id
+            ],
+        )?;
         Ok(())
     }
 
-    pub fn get_uninvoiced_sessions(&self) -> SqlResult<Vec<WorkSession>> {
-        let conn = self.conn.lock().unwrap();
-        // Get all sessions that haven't been invoiced yet
-        let mut stmt = conn.prepare(
-            "SELECT s.id, s.session_type_id, st.name as session_type_name, s.date, 
-                    COALESCE(s.project_name, 'Unnamed Session') as project_name, 
-                    s.hours, s.description,
-                    s.pay_type, 
-                    COALESCE(s.hourly_rate, st.hourly_rate) as hourly_rate, 
-                    s.fixed_amount
+    /// Add line items for sessions, grouped as asked. Sessions already on an
+    /// invoice are skipped. Returns the number of sessions added.
+    fn insert_session_items_internal(&self, conn: &Connection, invoice_id: i64, session_ids: &[i64], grouping: LineGrouping) -> SqlResult<usize> {
+        if session_ids.is_empty() {
+            return Ok(0);
+        }
+        let mut stmt = conn.prepare(&format!(
+            "SELECT s.id, s.date, s.project_name, s.hours, s.hourly_rate, s.fixed_amount, s.pay_type, st.name
              FROM sessions s
              LEFT JOIN session_types st ON s.session_type_id = st.id
-             WHERE s.id NOT IN (SELECT DISTINCT session_id FROM invoice_items WHERE session_id IS NOT NULL)
-             ORDER BY s.date DESC"
-        )?;
-        
-        let sessions = stmt.query_map([], |row| {
-            let pay_type_str: Option<String> = row.get(7)?;
-            Ok(WorkSession {
+             WHERE s.id IN ({})
+               AND s.id NOT IN ({})
+             ORDER BY s.date, s.id",
+            vec!["?"; session_ids.len()].join(", "),
+            INVOICED_SESSIONS_SQL
+        ))?;
+        let sessions = stmt.query_map(params_from_iter(session_ids.iter()), |row| {
+            Ok(logic::BillableSession {
                 id: row.get(0)?,
-                session_type_id: row.get(1)?,
-                session_type_name: row.get(2)?,
-                date: row.get(3)?,
-                project_name: row.get(4)?,
-                hours: row.get(5)?,
-                description: row.get(6)?,
-                pay_type: pay_type_str.and_then(|s| PayType::from_string(&s).ok()),
-                hourly_rate: row.get(8)?,
-                fixed_amount: row.get(9)?,
+                date: row.get(1)?,
+                project_name: row.get(2)?,
+                hours: row.get(3)?,
+                hourly_rate: row.get(4)?,
+                fixed_amount: row.get(5)?,
+                pay_type: row.get(6)?,
+                session_type: row.get(7)?,
             })
         })?.collect::<SqlResult<Vec<_>>>()?;
         
-        Ok(sessions)
-    }
-
-    // ========== HABIT OPERATIONS ==========
-
-    pub fn get_all_habits(&self) -> SqlResult<Vec<Habit>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT h.id, h.name, h.description, h.trigger_type, h.trigger_value, h.reward_description, h.is_active,
-                    COALESCE((SELECT COUNT(*) FROM habit_logs WHERE habit_id = h.id), 0) as total_completions
-             FROM habits h ORDER BY h.name"
-        )?;
-        
-        let habits: Vec<Habit> = stmt.query_map([], |row| {
-            let id: i64 = row.get(0)?;
-            let trigger_type: String = row.get(3)?;
-            let trigger_value: f64 = row.get(4)?;
-            let total_completions: i32 = row.get(7)?;
-            
-            // Convert trigger_value to minutes if it's an hours-based trigger
-            let trigger_minutes = if trigger_type == "after_hours" {
-                Some((trigger_value * 60.0) as i32)
-            } else {
-                None
+        for line in logic::group_session_lines(&sessions, grouping) {
+            let session_id = match line.session_ids.as_slice() {
+                [only] => Some(*only),
+                _ => None,
             };
-            
-            Ok(Habit {
-                id,
-                name: row.get(1)?,
-                description: row.get(2)?,
-                trigger_type,
-                trigger_value,
-                reward_description: row.get(5)?,
-                is_active: row.get::<_, i32>(6)? == 1,
-                current_streak: 0, // Will be computed below
-                best_streak: 0,    // Will be computed below
-                total_completions,
-                target_streak: 7,  // Default target
-                trigger_minutes,
-            })
-        })?.collect::<SqlResult<Vec<_>>>()?;
-        
-        // Compute streaks for each habit
-        let mut result = habits;
-        for habit in &mut result {
-            let (current, best) = self.compute_habit_streaks(&conn, habit.id)?;
-            habit.current_streak = current;
-            habit.best_streak = best;
+            conn.execute(
+                "INSERT INTO invoice_items (invoice_id, session_id, kind, description, hours, unit, rate, amount)
+                 VALUES (?, ?, 'Session', ?, ?, ?, ?, ?)",
+                params![invoice_id, session_id, line.description, line.quantity, line.unit, line.rate, line.amount],
+            )?;
+            let item_id = conn.last_insert_rowid();
+            for session_id in &line.session_ids {
+                conn.execute(
+                    "INSERT INTO invoice_item_sessions (item_id, session_id) VALUES (?, ?)",
+                    params![item_id, session_id],
+                )?;
+            }
         }
-        
-        Ok(result)
+        Ok(sessions.len())
     }
-    
-    fn compute_habit_streaks(&self, conn: &Connection, habit_id: i64) -> SqlResult<(i32, i32)> {
-        // Get all completion dates for this habit, ordered by date
-        let mut stmt = conn.prepare(
-            "SELECT DISTINCT date(completed_at) as completion_date 
-             FROM habit_logs WHERE habit_id = ? 
-             ORDER BY completion_date DESC"
+
+    fn insert_invoice_item_internal(&self, conn: &Connection, invoice_id: i64, item: &NewInvoiceItem) -> SqlResult<i64> {
+        let (discount_type, discount_value) = discount_to_columns(item.discount.as_ref());
+        let amount = logic::line_amount(item.hours, item.rate, item.discount.as_ref());
+        conn.execute(
+            "INSERT INTO invoice_items (invoice_id, session_id, kind, description, hours, unit, rate,
+                                        discount_type, discount_value, taxable, tax_category, amount)
+             VALUES (?, NULL, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                invoice_id,
+                item.kind.to_string(),
+                item.description,
+                item.hours,
+                item.unit,
+                item.rate,
+                discount_type,
+                discount_value,
+                item.taxable,
+                item.tax_category,
+                amount
+            ],
         )?;
-        
-        let dates: Vec<String> = stmt.query_map(params![habit_id], |row| {
-            row.get(0)
-        })?.collect::<SqlResult<Vec<_>>>()?;
-        
-        if dates.is_empty() {
-            return Ok((0, 0));
-        }
-        
-        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
-        let yesterday = (chrono::Local::now() - chrono::Duration::days(1)).format("%Y-%m-%d").to_string();
-        
-        // Calculate current streak
-        let mut current_streak = 0;
-        let mut check_date = chrono::Local::now().date_naive();
-        
-        // Start from today or yesterday
-        if dates.contains(&today) || dates.contains(&yesterday) {
-            for date_str in &dates {
-                let check_str = check_date.format("%Y-%m-%d").to_string();
-                if date_str == &check_str {
-                    current_streak += 1;
-                    check_date = check_date.pred_opt().unwrap_or(check_date);
-                } else if date_str < &check_str {
-                    // Check if this date is the next consecutive day
-                    let prev_str = check_date.pred_opt().unwrap_or(check_date).format("%Y-%m-%d").to_string();
-                    if date_str == &prev_str {
-                        current_streak += 1;
-                        check_date = check_date.pred_opt().unwrap_or(check_date).pred_opt().unwrap_or(check_date);
-                    } else {
-                        break;
-                    }
-                }
-            }
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Replace the taxes applied to an invoice: the ad-hoc `tax_rate` (if any)
+    /// followed by snapshots of the named rates
+    fn set_invoice_taxes_internal(&self, conn: &Connection, invoice_id: i64, tax_rate: Option<f64>, tax_rate_ids: &[i64]) -> SqlResult<()> {
+        conn.execute("DELETE FROM invoice_taxes WHERE invoice_id = ?", params![invoice_id])?;
+        if let Some(rate) = tax_rate.filter(|r| *r != 0.0) {
+            conn.execute(
+                "INSERT INTO invoice_taxes (invoice_id, name, rate, kind) VALUES (?, 'Tax', ?, 'Additive')",
+                params![invoice_id, rate],
+            )?;
         }
-        
-        // Calculate best streak
-        let mut best_streak = 0;
-        let mut streak = 1;
-        let mut sorted_dates = dates.clone();
-        sorted_dates.sort();
-        
-        for i in 1..sorted_dates.len() {
-            if let (Ok(prev), Ok(curr)) = (
-                chrono::NaiveDate::parse_from_str(&sorted_dates[i-1], "%Y-%m-%d"),
-                chrono::NaiveDate::parse_from_str(&sorted_dates[i], "%Y-%m-%d"),
-            ) {
-                if (curr - prev).num_days() == 1 {
-                    streak += 1;
-                } else {
-                    best_streak = best_streak.max(streak);
-                    streak = 1;
-                }
+        for tax_rate_id in tax_rate_ids {
+            let inserted = conn.execute(
+                "INSERT INTO invoice_taxes (invoice_id, tax_rate_id, name, rate, kind, category, note)
+                 SELECT ?, id, name, rate, kind, category, note FROM tax_rates WHERE id = ?",
+                params![invoice_id, tax_rate_id],
+            )?;
+            if inserted == 0 {
+                return Err(invalid_input(format!("Tax rate {} not found", tax_rate_id)));
             }
         }
-        best_streak = best_streak.max(streak);
-        
-        Ok((current_streak, best_streak))
+        Ok(())
     }
 
-    pub fn add_habit(&self, habit: Habit) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
+    /// Recompute subtotal, discount, taxes and total from the invoice's line items
+    fn recalculate_invoice_totals_internal(&self, conn: &Connection, invoice_id: i64) -> SqlResult<()> {
+        let mut invoice = self.get_invoice_internal(conn, invoice_id)?;
+        let totals = logic::calculate_invoice_totals(&invoice.items, invoice.discount.as_ref(), &mut invoice.taxes);
+        
+        conn.execute("DELETE FROM invoice_taxes WHERE invoice_id = ?", params![invoice_id])?;
+        for tax in &invoice.taxes {
+            conn.execute(
+                "INSERT INTO invoice_taxes (invoice_id, tax_rate_id, name, rate, kind, category, note, base, amount)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    invoice_id,
+                    tax.tax_rate_id,
+                    tax.name,
+                    tax.rate,
+                    tax.kind.to_string(),
+                    tax.category,
+                    tax.note,
+                    tax.base,
+                    tax.amount
+                ],
+            )?;
+        }
         conn.execute(
-            "INSERT INTO habits (name, description, trigger_type, trigger_value, reward_description, is_active)
-             VALUES (?, ?, ?, ?, ?, ?)",
+            "UPDATE invoices SET subtotal = ?, discount_amount = ?, tax_amount = ?, withholding_amount = ?, total = ?
+             WHERE id = ?",
             params![
-                habit.name,
-                habit.description,
-                habit.trigger_type,
-                habit.trigger_value,
-                habit.reward_description,
-                if habit.is_active { 1 } else { 0 }
+                totals.subtotal,
+                totals.discount_amount,
+                totals.tax_amount,
+                totals.withholding_amount,
+                totals.total,
+                invoice_id
             ],
         )?;
-        Ok(conn.last_insert_rowid())
+        Ok(())
     }
 
-    pub fn update_habit(&self, habit: &Habit) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "UPDATE habits SET name = ?, description = ?, trigger_type = ?, trigger_value = ?, 
-             reward_description = ?, is_active = ? WHERE id = ?",
+    // ========== DRAFT EDITING ==========
+    // Invoices past Draft are locked by triggers in schema.sql.
+
+    pub fn update_draft_invoice(&self, id: i64, update: &InvoiceDraftUpdate) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let (discount_type, discount_value) = discount_to_columns(update.discount.as_ref());
+        tx.execute(
+            "UPDATE invoices SET client_name = ?, client_email = ?, due_date = ?, tax_rate = ?, notes = ?,
+                                 discount_type = ?, discount_value = ?, profile_id = ?, template_id = ?,
+                                 include_timesheet = ?
+             WHERE id = ?",
             params![
-                habit.name,
-                habit.description,
-                habit.trigger_type,
-                habit.trigger_value,
-                habit.reward_description,
-                if habit.is_active { 1 } else { 0 },
-                habit.id
+                update.client_name,
+                update.client_email,
+                update.due_date,
+                update.tax_rate,
+                update.notes,
+                discount_type,
+                discount_value,
+                update.profile_id,
+                update.template_id,
+                update.include_timesheet,
+                id
             ],
         )?;
+        self.set_client_address_internal(&tx, id, &update.client_address)?;
+        self.set_invoice_taxes_internal(&tx, id, update.tax_rate, &update.tax_rate_ids)?;
+        self.recalculate_invoice_totals_internal(&tx, id)?;
+        tx.commit()?;
         Ok(())
     }
 
-    pub fn delete_habit(&self, id: i64) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM habit_logs WHERE habit_id = ?", params![id])?;
-        conn.execute("DELETE FROM habits WHERE id = ?", params![id])?;
+    /// Added sessions are grouped among themselves with the invoice's
+    /// grouping, not merged into existing lines. Returns the number of
+    /// sessions actually added.
+    pub fn add_sessions_to_invoice(&self, invoice_id: i64, session_ids: &[i64]) -> SqlResult<usize> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let grouping = self.get_invoice_internal(&tx, invoice_id)?.line_grouping;
+        let added = self.insert_session_items_internal(&tx, invoice_id, session_ids, grouping)?;
+        self.recalculate_invoice_totals_internal(&tx, invoice_id)?;
+        tx.commit()?;
+        Ok(added)
+    }
+
+    /// Add a manual or expense line; returns the new item's id
+    pub fn add_invoice_item(&self, invoice_id: i64, item: &NewInvoiceItem) -> SqlResult<i64> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let item_id = self.insert_invoice_item_internal(&tx, invoice_id, item)?;
+        self.recalculate_invoice_totals_internal(&tx, invoice_id)?;
+        tx.commit()?;
+        Ok(item_id)
+    }
+
+    pub fn update_invoice_item(&self, invoice_id: i64, item: &InvoiceItemUpdate) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let (discount_type, discount_value) = discount_to_columns(item.discount.as_ref());
+        let amount = logic::line_amount(item.hours, item.rate, item.discount.as_ref());
+        tx.execute(
+            "UPDATE invoice_items SET description = ?, hours = ?, unit = ?, rate = ?,
+                                      discount_type = ?, discount_value = ?, taxable = ?, tax_category = ?,
+                                      amount = ?
+             WHERE id = ? AND invoice_id = ?",
+            params![
+                item.description,
+                item.hours,
+                item.unit,
+                item.rate,
+                discount_type,
+                discount_value,
+                item.taxable,
+                item.tax_category,
+                amount,
+                item.id,
+                invoice_id
+            ],
+        )?;
+        self.recalculate_invoice_totals_internal(&tx, invoice_id)?;
+        tx.commit()?;
         Ok(())
     }
 
-    pub fn log_habit_completion(&self, habit_id: i64, notes: Option<String>) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "INSERT INTO habit_logs (habit_id, notes) VALUES (?, ?)",
-            params![habit_id, notes],
+    /// Remove a line item; its sessions become uninvoiced again
+    pub fn remove_invoice_item(&self, invoice_id: i64, item_id: i64) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM invoice_items WHERE id = ? AND invoice_id = ?",
+            params![item_id, invoice_id],
         )?;
-        Ok(conn.last_insert_rowid())
+        self.recalculate_invoice_totals_internal(&tx, invoice_id)?;
+        tx.commit()?;
+        Ok(())
     }
 
-    pub fn get_habit_logs_for_date(&self, date: &str) -> SqlResult<Vec<HabitLog>> {
-        let conn = self.conn.lock().unwrap();
+    // ========== TAX RATES ==========
+
+    pub fn get_all_tax_rates(&self) -> SqlResult<Vec<TaxRate>> {
+        let conn = self.reader();
         let mut stmt = conn.prepare(
-            "SELECT id, habit_id, completed_at, notes
-             FROM habit_logs WHERE date(completed_at) = ?"
+            "SELECT id, name, rate, kind, category, note FROM tax_rates ORDER BY name"
         )?;
         
-        let logs = stmt.query_map(params![date], |row| {
-            Ok(HabitLog {
+        let rates = stmt.query_map([], |row| {
+            let kind: String = row.get(3)?;
+            Ok(TaxRate {
                 id: row.get(0)?,
-                habit_id: row.get(1)?,
-                completed_at: row.get(2)?,
-                notes: row.get(3)?,
+                name: row.get(1)?,
+                rate: row.get(2)?,
+                kind: TaxKind::from_string(&kind).unwrap_or(TaxKind::Additive),
+                category: row.get(4)?,
+                note: row.get(5)?,
             })
         })?.collect::<SqlResult<Vec<_>>>()?;
         
-        Ok(logs)
+        Ok(rates)
     }
 
-    // ========== AUTO-TRACKING OPERATIONS ==========
-
-    pub fn get_pending_suggestions(&self) -> SqlResult<Vec<ActivitySuggestion>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, date, app_name, window_title, suggested_project, suggested_session_type_id,
-                    duration_minutes, start_time, end_time, status
-             FROM activity_suggestions WHERE status = 'pending' ORDER BY date DESC, start_time DESC"
+    pub fn add_tax_rate(&self, tax_rate: &NewTaxRate) -> SqlResult<i64> {
+        let conn = self.writer();
+        conn.execute(
+            "INSERT INTO tax_rates (name, rate, kind, category, note) VALUES (?, ?, ?, ?, ?)",
+            params![
+                tax_rate.name.trim(),
+                tax_rate.rate,
+                tax_rate.kind.to_string(),
+                tax_rate.category,
+                tax_rate.note
+            ],
         )?;
-        
-        let suggestions = stmt.query_map([], |row| {
-            Ok(ActivitySuggestion {
-                id: row.get(0)?,
-                date: row.get(1)?,
-                app_name: row.get(2)?,
-                window_title: row.get(3)?,
-                suggested_project: row.get(4)?,
-                suggested_session_type_id: row.get(5)?,
-                duration_minutes: row.get(6)?,
-                start_time: row.get(7)?,
-                end_time: row.get(8)?,
-                status: row.get(9)?,
-            })
-        })?.collect::<SqlResult<Vec<_>>>()?;
-        
-        Ok(suggestions)
+        Ok(conn.last_insert_rowid())
     }
 
-    pub fn accept_suggestion(&self, suggestion_id: i64, session_type_id: i64, project_name: &str) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
-        
-        // Get suggestion
-        let suggestion: ActivitySuggestion = conn.query_row(
-            "SELECT id, date, app_name, window_title, suggested_project, suggested_session_type_id,
-                    duration_minutes, start_time, end_time, status
-             FROM activity_suggestions WHERE id = ?",
-            params![suggestion_id],
-            |row| {
-                Ok(ActivitySuggestion {
-                    id: row.get(0)?,
-                    date: row.get(1)?,
-                    app_name: row.get(2)?,
-                    window_title: row.get(3)?,
-                    suggested_project: row.get(4)?,
-                    suggested_session_type_id: row.get(5)?,
-                    duration_minutes: row.get(6)?,
-                    start_time: row.get(7)?,
-                    end_time: row.get(8)?,
-                    status: row.get(9)?,
-                })
-            }
-        )?;
-        
-        // Create session from suggestion
-        let hours = suggestion.duration_minutes / 60.0;
+    /// Invoices keep the snapshot they were created with
+    pub fn update_tax_rate(&self, id: i64, tax_rate: &NewTaxRate) -> SqlResult<()> {
+        let conn = self.writer();
         conn.execute(
-            "INSERT INTO sessions (session_type_id, date, project_name, hours, description, pay_type)
-             VALUES (?, ?, ?, ?, ?, 'None')",
+            "UPDATE tax_rates SET name = ?, rate = ?, kind = ?, category = ?, note = ? WHERE id = ?",
             params![
-                session_type_id,
-                suggestion.date,
-                project_name,
-                hours,
-                format!("Auto-tracked: {} - {}", suggestion.app_name, suggestion.window_title)
+                tax_rate.name.trim(),
+                tax_rate.rate,
+                tax_rate.kind.to_string(),
+                tax_rate.category,
+                tax_rate.note,
+                id
             ],
         )?;
-        
-        let session_id = conn.last_insert_rowid();
-        
-        // Update suggestion status
-        conn.execute(
-            "UPDATE activity_suggestions SET status = 'accepted' WHERE id = ?",
-            params![suggestion_id],
-        )?;
-        
-        Ok(session_id)
+        Ok(())
     }
 
-    pub fn dismiss_suggestion(&self, suggestion_id: i64) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "UPDATE activity_suggestions SET status = 'dismissed' WHERE id = ?",
-            params![suggestion_id],
-        )?;
+    pub fn delete_tax_rate(&self, id: i64) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute("DELETE FROM tax_rates WHERE id = ?", params![id])?;
         Ok(())
     }
 
-    pub fn get_tracking_rules(&self) -> SqlResult<Vec<TrackingRule>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, app_pattern, title_pattern, project_name, session_type_id, is_active
-             FROM tracking_rules ORDER BY app_pattern"
-        )?;
-        
-        let rules = stmt.query_map([], |row| {
-            Ok(TrackingRule {
-                id: row.get(0)?,
-                app_pattern: row.get(1)?,
-                title_pattern: row.get(2)?,
-                project_name: row.get(3)?,
-                session_type_id: row.get(4)?,
-                is_active: row.get::<_, i32>(5)? == 1,
-            })
-        })?.collect::<SqlResult<Vec<_>>>()?;
-        
-        Ok(rules)
+    // ========== BUSINESS PROFILES ==========
+
+    pub fn get_all_business_profiles(&self) -> SqlResult<Vec<BusinessProfile>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM business_profiles ORDER BY is_default DESC, name",
+            BUSINESS_PROFILE_COLUMNS
+        ))?;
+        let profiles = stmt.query_map([], business_profile_from_row)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(profiles)
     }
 
-    pub fn add_tracking_rule(&self, rule: TrackingRule) -> SqlResult<i64> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "INSERT INTO tracking_rules (app_pattern, title_pattern, project_name, session_type_id, is_active)
-             VALUES (?, ?, ?, ?, ?)",
-            params![
-                rule.app_pattern,
-                rule.title_pattern,
-                rule.project_name,
-                rule.session_type_id,
-                if rule.is_active { 1 } else { 0 }
-            ],
-        )?;
-        Ok(conn.last_insert_rowid())
+    pub fn get_business_profile(&self, id: i64) -> SqlResult<BusinessProfile> {
+        let conn = self.reader();
+        conn.query_row(
+            &format!("SELECT {} FROM business_profiles WHERE id = ?", BUSINESS_PROFILE_COLUMNS),
+            params![id],
+            business_profile_from_row,
+        )
     }
 
-    pub fn delete_tracking_rule(&self, id: i64) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM tracking_rules WHERE id = ?", params![id])?;
-        Ok(())
+    /// The seller frozen when the invoice was issued; for drafts the
+    /// invoice's own profile, else the current default
+    pub fn get_invoice_profile(&self, invoice: &Invoice) -> SqlResult<Option<BusinessProfile>> {
+        let snapshot: Option<String> = {
+            let conn = self.reader();
+            conn.query_row("SELECT seller_snapshot FROM invoices WHERE id = ?", params![invoice.id], |row| row.get(0))?
+        };
+        if let Some(json) = snapshot {
+            return serde_json::from_str(&json)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)));
+        }
+        match invoice.profile_id {
+            Some(id) => self.get_business_profile(id).map(Some),
+            None => self.get_default_business_profile(),
+        }
     }
 
-    // ========== LICENSE ==========
+    pub fn get_default_business_profile(&self) -> SqlResult<Option<BusinessProfile>> {
+        let conn = self.reader();
+        self.get_default_business_profile_internal(&conn)
+    }
 
-    pub fn get_license(&self) -> SqlResult<License> {
-        let conn = self.conn.lock().unwrap();
+    fn get_default_business_profile_internal(&self, conn: &Connection) -> SqlResult<Option<BusinessProfile>> {
         let result = conn.query_row(
-            "SELECT tier, license_key, activated_at, expires_at FROM license WHERE id = 1",
+            &format!("SELECT {} FROM business_profiles WHERE is_default = 1", BUSINESS_PROFILE_COLUMNS),
             [],
-            |row| {
-                let tier_str: String = row.get(0)?;
-                Ok(License {
-                    tier: Tier::from_string(&tier_str),
-                    license_key: row.get(1)?,
-                    activated_at: row.get(2)?,
-                    expires_at: row.get(3)?,
-                })
-            }
+            business_profile_from_row,
         );
-
         match result {
-            Ok(license) => Ok(license),
-            Err(_) => Ok(License::default()),
+            Ok(profile) => Ok(Some(profile)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
         }
     }
 
-    pub fn save_license(&self, license: &License) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "INSERT OR REPLACE INTO license (id, tier, license_key, activated_at, expires_at, updated_at)
-             VALUES (1, ?, ?, ?, ?, datetime('now'))",
+    /// The first profile always becomes the default
+    pub fn add_business_profile(&self, profile: &NewBusinessProfile) -> SqlResult<i64> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let has_default = self.get_default_business_profile_internal(&tx)?.is_some();
+        let is_default = profile.is_default || !has_default;
+        if is_default {
+            tx.execute("UPDATE business_profiles SET is_default = 0 WHERE is_default = 1", [])?;
+        }
+        tx.execute(
+            "INSERT INTO business_profiles (name, business_name, address, email, phone, tax_id, bank_details, logo_path, is_default,
+                                            city, postal_code, country_code, currency)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
             params![
-                license.tier.to_string(),
-                license.license_key,
-                license.activated_at,
-                license.expires_at,
+                profile.name.trim(),
+                profile.business_name.trim(),
+                profile.address,
+                profile.email,
+                profile.phone,
+                profile.tax_id,
+                profile.bank_details,
+                profile.logo_path,
+                is_default,
+                profile.city,
+                profile.postal_code,
+                profile.country_code,
+                profile.currency
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Unsetting `is_default` is ignored: pick another default instead
+    pub fn update_business_profile(&self, id: i64, profile: &NewBusinessProfile) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        if profile.is_default {
+            tx.execute("UPDATE business_profiles SET is_default = 0 WHERE is_default = 1 AND id <> ?", params![id])?;
+        }
+        tx.execute(
+            "UPDATE business_profiles SET name = ?, business_name = ?, address = ?, email = ?, phone = ?,
+                                          tax_id = ?, bank_details = ?, logo_path = ?,
+                                          is_default = MAX(is_default, ?),
+                                          city = ?, postal_code = ?, country_code = ?, currency = ?
+             WHERE id = ?",
+            params![
+                profile.name.trim(),
+                profile.business_name.trim(),
+                profile.address,
+                profile.email,
+                profile.phone,
+                profile.tax_id,
+                profile.bank_details,
+                profile.logo_path,
+                profile.is_default,
+                profile.city,
+                profile.postal_code,
+                profile.country_code,
+                profile.currency,
+                id
             ],
         )?;
+        tx.commit()?;
         Ok(())
     }
 
-    pub fn get_session_type_count(&self) -> SqlResult<u32> {
-        let conn = self.conn.lock().unwrap();
-        let count: u32 = conn.query_row(
-            "SELECT COUNT(*) FROM session_types",
-            [],
-            |row| row.get(0)
+    /// Invoices using the profile fall back to the default; if the default is
+    /// deleted, the oldest remaining profile takes over.
+    pub fn delete_business_profile(&self, id: i64) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM business_profiles WHERE id = ?", params![id])?;
+        if self.get_default_business_profile_internal(&tx)?.is_none() {
+            tx.execute(
+                "UPDATE business_profiles SET is_default = 1
+                 WHERE id = (SELECT MIN(id) FROM business_profiles)",
+                [],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // ========== INVOICE TEMPLATES ==========
+
+    /// Stored templates only; the built-in template is added by the caller
+    pub fn get_all_invoice_templates(&self) -> SqlResult<Vec<InvoiceTemplate>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare("SELECT id, name, body FROM invoice_templates ORDER BY name")?;
+        let templates = stmt.query_map([], invoice_template_from_row)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(templates)
+    }
+
+    pub fn get_invoice_template(&self, id: i64) -> SqlResult<InvoiceTemplate> {
+        let conn = self.reader();
+        conn.query_row(
+            "SELECT id, name, body FROM invoice_templates WHERE id = ?",
+            params![id],
+            invoice_template_from_row,
+        )
+    }
+
+    pub fn add_invoice_template(&self, template: &NewInvoiceTemplate) -> SqlResult<i64> {
+        let conn = self.writer();
+        conn.execute(
+            "INSERT INTO invoice_templates (name, body) VALUES (?, ?)",
+            params![template.name.trim(), template.body],
         )?;
-        Ok(count)
+        Ok(conn.last_insert_rowid())
     }
 
-    pub fn get_goal_count(&self) -> SqlResult<u32> {
-        let conn = self.conn.lock().unwrap();
-        let count: u32 = conn.query_row(
-            "SELECT COUNT(*) FROM goals",
-            [],
-            |row| row.get(0)
+    pub fn update_invoice_template(&self, id: i64, template: &NewInvoiceTemplate) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute(
+            "UPDATE invoice_templates SET name = ?, body = ? WHERE id = ?",
+            params![template.name.trim(), template.body, id],
         )?;
-        Ok(count)
+        Ok(())
     }
 
-    // ========== SETTINGS ==========
+    /// Drafts using the template fall back to the built-in one. Issued
+    /// invoices keep rendering with their template, so it can't be deleted.
+    pub fn delete_invoice_template(&self, id: i64) -> SqlResult<()> {
+        let conn = self.writer();
+        let issued: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM invoices WHERE template_id = ? AND status <> 'Draft'",
+            params![id],
+            |row| row.get(0),
+        )?;
+        if issued > 0 {
+            return Err(invalid_input(format!("This template is used by {} issued invoice(s)", issued)));
+        }
+        conn.execute("DELETE FROM invoice_templates WHERE id = ?", params![id])?;
+        Ok(())
+    }
 
-    pub fn get_setting(&self, key: &str) -> SqlResult<Option<String>> {
-        let conn = self.conn.lock().unwrap();
-        let result = conn.query_row(
-            "SELECT value FROM settings WHERE key = ?",
-            params![key],
+    // ========== INVOICE NUMBERING ==========
+
+    pub fn get_invoice_numbering(&self) -> SqlResult<InvoiceNumbering> {
+        let conn = self.reader();
+        self.get_invoice_numbering_internal(&conn)
+    }
+
+    pub fn set_invoice_numbering(&self, numbering: &InvoiceNumbering) -> SqlResult<()> {
+        let conn = self.writer();
+        let json = serde_json::to_string(numbering)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value, updated_at) VALUES ('invoice_numbering', ?, datetime('now'))",
+            params![json],
+        )?;
+        Ok(())
+    }
+
+    /// Number the next invoice for this client would get, without consuming it
+    pub fn preview_invoice_number(&self, client_name: &str) -> SqlResult<String> {
+        let conn = self.reader();
+        let numbering = self.get_invoice_numbering_internal(&conn)?;
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let next: i64 = conn.query_row(
+            "SELECT COALESCE((SELECT next_value FROM number_sequences WHERE scope = ?), 1)",
+            params![numbering.sequence_scope(&today)],
             |row| row.get(0)
+        )?;
+        logic::format_invoice_number(&numbering.pattern, numbering.prefix_for(client_name), &today, next)
+            .map_err(invalid_input)
+    }
+
+    fn get_invoice_numbering_internal(&self, conn: &Connection) -> SqlResult<InvoiceNumbering> {
+        let result = conn.query_row(
+            "SELECT value FROM settings WHERE key = 'invoice_numbering'",
+            [],
+            |row| row.get::<_, String>(0)
         );
         match result {
-            Ok(value) => Ok(Some(value)),
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(InvoiceNumbering::default()),
             Err(e) => Err(e),
         }
     }
 
-    pub fn set_setting(&self, key: &str, value: &str) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
+    /// Take the next value of a number sequence. Call inside the transaction
+    /// that uses the value so a rollback also returns it.
+    fn next_sequence_value_internal(&self, conn: &Connection, scope: &str) -> SqlResult<i64> {
         conn.execute(
-            "INSERT OR REPLACE INTO settings (key, value, updated_at) VALUES (?, ?, datetime('now'))",
-            params![key, value],
+            "INSERT OR IGNORE INTO number_sequences (scope, next_value) VALUES (?, 1)",
+            params![scope],
         )?;
+        conn.query_row(
+            "UPDATE number_sequences SET next_value = next_value + 1 WHERE scope = ? RETURNING next_value - 1",
+            params![scope],
+            |row| row.get(0)
+        )
+    }
+
+    fn next_invoice_number_internal(&self, conn: &Connection, client_name: &str, date: &str) -> SqlResult<String> {
+        let numbering = self.get_invoice_numbering_internal(conn)?;
+        let scope = numbering.sequence_scope(date);
+        self.next_document_number_internal(conn, &numbering.pattern, &scope, numbering.prefix_for(client_name), date)
+    }
+
+    fn next_credit_note_number_internal(&self, conn: &Connection, client_name: &str, date: &str) -> SqlResult<String> {
+        let numbering = self.get_invoice_numbering_internal(conn)?;
+        let scope = numbering.credit_note_scope(date);
+        self.next_document_number_internal(conn, &numbering.credit_note_pattern, &scope, numbering.prefix_for(client_name), date)
+    }
+
+    /// Credit notes share the invoices table, so both kinds of number are
+    /// unique across it; quote numbers are kept apart from them too
+    fn next_document_number_internal(&self, conn: &Connection, pattern: &str, scope: &str, prefix: &str, date: &str) -> SqlResult<String> {
+        loop {
+            let seq = self.next_sequence_value_internal(conn, scope)?;
+            let number = logic::format_invoice_number(pattern, prefix, date, seq)
+                .map_err(invalid_input)?;
+            // Skip numbers already taken, e.g. by invoices created before the sequence existed
+            let taken: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM invoices WHERE invoice_number = ?1)
+                     OR EXISTS(SELECT 1 FROM quotes WHERE quote_number = ?1)",
+                params![number],
+                |row| row.get(0)
+            )?;
+            if !taken {
+                return Ok(number);
+            }
+        }
+    }
+
+    /// Manual status changes: a draft can be issued (Sent), and an open
+    /// invoice marked Paid, which records a payment for the outstanding
+    /// balance dated today so the ledger always adds up. Every other status
+    /// follows from payments, credit notes and due dates.
+    pub fn update_invoice_status(&self, id: i64, status: InvoiceStatus) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let invoice = self.get_invoice_internal(&tx, id)?;
+        match (invoice.status, status) {
+            (InvoiceStatus::Draft, InvoiceStatus::Sent) => self.issue_invoice_internal(&tx, id)?,
+            (InvoiceStatus::Draft | InvoiceStatus::Sent | InvoiceStatus::PartiallyPaid | InvoiceStatus::Overdue, InvoiceStatus::Paid) => {
+                if invoice.status == InvoiceStatus::Draft {
+                    self.issue_invoice_internal(&tx, id)?;
+                }
+                if invoice.balance_due > 0.0 {
+                    tx.execute(
+                        "INSERT INTO invoice_payments (invoice_id, payment_date, amount, notes)
+                         VALUES (?, ?, ?, 'Marked as paid')",
+                        params![id, logic::get_today(), invoice.balance_due],
+                    )?;
+                }
+                self.sync_payment_status_internal(&tx, id)?;
+            }
+            (from, to) => return Err(invalid_input(format!("An invoice can't be moved from {} to {}", from.to_string(), to.to_string()))),
+        }
+        tx.commit()?;
         Ok(())
     }
 
-    pub fn delete_setting(&self, key: &str) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM settings WHERE key = ?", params![key])?;
+    /// Move a draft to Sent, freezing its seller; other invoices are left
+    /// as they are. Returns the invoice.
+    pub fn issue_invoice(&self, id: i64) -> SqlResult<Invoice> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        self.issue_invoice_internal(&tx, id)?;
+        let invoice = self.get_invoice_internal(&tx, id)?;
+        tx.commit()?;
+        Ok(invoice)
+    }
+
+    /// The invoice as `issue_invoice` would leave it, without issuing it: what
+    /// an email renders before the send is known to have succeeded
+    pub fn preview_issued_invoice(&self, id: i64) -> SqlResult<Invoice> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        self.issue_invoice_internal(&tx, id)?;
+        // Dropping the transaction rolls the issue back
+        self.get_invoice_internal(&tx, id)
+    }
+
+    /// Move a draft to Sent, dated today
+    fn issue_invoice_internal(&self, conn: &Connection, id: i64) -> SqlResult<()> {
+        let profile_id: Option<i64> = conn.query_row("SELECT profile_id FROM invoices WHERE id = ?", params![id], |row| row.get(0))?;
+        let snapshot = seller_snapshot(conn, profile_id)?;
+        conn.execute(
+            "UPDATE invoices SET status = 'Sent', issued_date = ?, seller_snapshot = COALESCE(seller_snapshot, ?)
+             WHERE id = ? AND status = 'Draft'",
+            params![logic::get_today(), snapshot, id],
+        )?;
+        Ok(())
+    }
+
+    // ========== PAYMENTS ==========
+
+    /// Record a payment and move the invoice to PartiallyPaid or Paid
+    pub fn record_payment(&self, invoice_id: i64, payment: &NewInvoicePayment) -> SqlResult<i64> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let invoice = self.get_invoice_internal(&tx, invoice_id)?;
+        if invoice.status == InvoiceStatus::Draft {
+            return Err(invalid_input("Send the invoice before recording payments".to_string()));
+        }
+        if invoice.status == InvoiceStatus::Void || invoice.is_credit_note() {
+            return Err(invalid_input(format!("Nothing is owed on {}", invoice.invoice_number)));
+        }
+        if logic::round_currency(payment.amount - invoice.balance_due) > 0.0 {
+            return Err(invalid_input(format!(
+                "Payment of {:.2} exceeds the balance due of {:.2}",
+                payment.amount, invoice.balance_due
+            )));
+        }
+        tx.execute(
+            "INSERT INTO invoice_payments (invoice_id, payment_date, amount, method, reference, notes)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                invoice_id,
+                payment.payment_date,
+                payment.amount,
+                payment.method,
+                payment.reference,
+                payment.notes
+            ],
+        )?;
+        let payment_id = tx.last_insert_rowid();
+        self.sync_payment_status_internal(&tx, invoice_id)?;
+        tx.commit()?;
+        Ok(payment_id)
+    }
+
+    pub fn delete_payment(&self, invoice_id: i64, payment_id: i64) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM invoice_payments WHERE id = ? AND invoice_id = ?",
+            params![payment_id, invoice_id],
+        )?;
+        self.sync_payment_status_internal(&tx, invoice_id)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Move Sent and PartiallyPaid invoices due before `today` to Overdue;
+    /// returns the invoices moved
+    pub fn mark_overdue_invoices(&self, today: &str) -> SqlResult<Vec<Invoice>> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let ids = tx.prepare(
+            "UPDATE invoices SET status = 'Overdue', overdue_at = datetime('now')
+             WHERE status IN ('Sent', 'PartiallyPaid') AND due_date < ? AND credited_invoice_id IS NULL
+             RETURNING id"
+        )?
+            .query_map(params![today], |row| row.get::<_, i64>(0))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        let invoices = ids.iter()
+            .map(|id| self.get_invoice_internal(&tx, *id))
+            .collect::<SqlResult<Vec<_>>>()?;
+        tx.commit()?;
+        Ok(invoices)
+    }
+
+    fn sync_payment_status_internal(&self, conn: &Connection, invoice_id: i64) -> SqlResult<()> {
+        let invoice = self.get_invoice_internal(conn, invoice_id)?;
+        let status = logic::status_after_payments(invoice.status, invoice.total, invoice.amount_paid, invoice.amount_credited);
+        if status != invoice.status {
+            conn.execute(
+                &format!(
+                    "UPDATE invoices SET status = ?1,
+                                         voided_at = CASE WHEN ?1 = 'Void' THEN datetime('now') ELSE voided_at END,
+                                         paid_date = CASE WHEN ?1 = 'Paid' THEN {} END
+                     WHERE id = ?2",
+                    LAST_PAYMENT_DATE_SQL
+                ),
+                params![status.to_string(), invoice_id],
+            )?;
+        }
         Ok(())
     }
+
+    // ========== CREDIT NOTES ==========
+    // Credit notes are rows of `invoices` with `credited_invoice_id` set and
+    // positive amounts; the sum of the non-void ones reduces the balance due.
+
+    /// Credit notes issued against an invoice, oldest first
+    pub fn get_credit_notes(&self, invoice_id: i64) -> SqlResult<Vec<Invoice>> {
+        let conn = self.reader();
+        self.get_credit_notes_internal(&conn, invoice_id)
+    }
+
+    fn get_credit_notes_internal(&self, conn: &Connection, invoice_id: i64) -> SqlResult<Vec<Invoice>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM invoices WHERE credited_invoice_id = ? ORDER BY id",
+            INVOICE_COLUMNS
+        ))?;
+        let mut notes = stmt.query_map(params![invoice_id], invoice_from_row)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        for note in &mut notes {
+            self.load_invoice_lines_internal(conn, note)?;
+        }
+        Ok(notes)
+    }
+
+    /// Issue a credit note for the given quantities of an invoice's lines,
+    /// each at most what `logic::creditable_quantities` leaves; no lines
+    /// credits everything that remains. The note copies the credited lines,
+    /// the invoice's taxes and a matching share of its discount, and is
+    /// issued (Sent) straight away.
+    pub fn create_credit_note(&self, invoice_id: i64, lines: &[CreditedLine], notes: Option<&str>, release_sessions: bool) -> SqlResult<i64> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let original = self.get_invoice_internal(&tx, invoice_id)?;
+        if original.is_credit_note() {
+            return Err(invalid_input("A credit note can't be credited; void it instead".to_string()));
+        }
+        match original.status {
+            InvoiceStatus::Draft => return Err(invalid_input("Drafts can be edited or deleted instead of credited".to_string())),
+            InvoiceStatus::Void => return Err(invalid_input(format!("Invoice {} is void", original.invoice_number))),
+            _ => {}
+        }
+        
+        // Checked here so that a concurrent note can't credit a line twice
+        let remaining = logic::creditable_quantities(&original, &self.get_credit_notes_internal(&tx, invoice_id)?);
+        let lines = if lines.is_empty() {
+            original.items.iter()
+                .filter_map(|item| remaining.get(&item.id).filter(|q| **q > 0.0).map(|q| CreditedLine { item_id: item.id, quantity: *q }))
+                .collect::<Vec<_>>()
+        } else {
+            for line in lines {
+                let available = remaining.get(&line.item_id)
+                    .ok_or_else(|| invalid_input(format!("Line {} is not on invoice {}", line.item_id, original.invoice_number)))?;
+                if line.quantity > *available + 1e-9 {
+                    return Err(invalid_input(format!("Only {} of line {} is left to credit", available, line.item_id)));
+                }
+            }
+            lines.to_vec()
+        };
+        if lines.is_empty() {
+            return Err(invalid_input(format!("Invoice {} has been credited in full", original.invoice_number)));
+        }
+        
+        let created_date = logic::get_today();
+        let number = self.next_credit_note_number_internal(&tx, &original.client_name, &created_date)?;
+        
+        // Each credited line with the share of it being credited
+        let credited = lines.iter()
+            .filter_map(|line| {
+                let item = original.items.iter().find(|i| i.id == line.item_id)?;
+                let share = if item.hours > 0.0 { (line.quantity / item.hours).min(1.0) } else { 1.0 };
+                Some((item, line.quantity, share))
+            })
+            .collect::<Vec<_>>();
+        
+        // A fixed invoice discount is credited in proportion to the lines
+        let credited_subtotal: f64 = credited.iter().map(|(item, _, share)| item.amount * share).sum();
+        let discount = original.discount.as_ref().map(|d| match d.discount_type {
+            DiscountType::Percent => *d,
+            DiscountType::Fixed => Discount {
+                discount_type: DiscountType::Fixed,
+                value: if original.subtotal > 0.0 {
+                    logic::round_currency(original.discount_amount * credited_subtotal / original.subtotal)
+                } else {
+                    0.0
+                },
+            },
+        });
+        let (discount_type, discount_value) = discount_to_columns(discount.as_ref());
+        
+        tx.execute(
+            "INSERT INTO invoices (invoice_number, client_name, client_email, created_date, due_date,
+                                   status, subtotal, tax_rate, tax_amount, total, notes,
+                                   discount_type, discount_value, profile_id, template_id, credited_invoice_id)
+             VALUES (?, ?, ?, ?, ?, 'Draft', 0, ?, 0, 0, ?, ?, ?, ?, ?, ?)",
+            params![
+                number,
+                original.client_name,
+                original.client_email,
+                created_date,
+                created_date,
+                original.tax_rate,
+                notes,
+                discount_type,
+                discount_value,
+                original.profile_id,
+                original.template_id,
+                invoice_id
+            ],
+        )?;
+        let note_id = tx.last_insert_rowid();
+        self.set_client_address_internal(&tx, note_id, &original.client_address)?;
+        
+        for (item, quantity, share) in &credited {
+            // A fixed line discount is credited in proportion to the quantity
+            let line_discount = item.discount.as_ref().map(|d| match d.discount_type {
+                DiscountType::Percent => *d,
+                DiscountType::Fixed => Discount { discount_type: DiscountType::Fixed, value: d.value * share },
+            });
+            let (discount_type, discount_value) = discount_to_columns(line_discount.as_ref());
+            tx.execute(
+                "INSERT INTO invoice_items (invoice_id, session_id, kind, description, hours, unit, rate,
+                                            discount_type, discount_value, taxable, tax_category, amount,
+                                            credited_item_id)
+                 VALUES (?, NULL, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    note_id,
+                    item.kind.to_string(),
+                    item.description,
+                    quantity,
+                    item.unit,
+                    item.rate,
+                    discount_type,
+                    discount_value,
+                    item.taxable,
+                    item.tax_category,
+                    logic::round_currency(item.amount * share),
+                    item.id
+                ],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO invoice_taxes (invoice_id, tax_rate_id, name, rate, kind, category, note)
+             SELECT ?, tax_rate_id, name, rate, kind, category, note FROM invoice_taxes WHERE invoice_id = ? ORDER BY id",
+            params![note_id, invoice_id],
+        )?;
+        self.recalculate_invoice_totals_internal(&tx, note_id)?;
+        
+        // Only sessions credited in full become billable again
+        if release_sessions {
+            let credit_notes = self.get_credit_notes_internal(&tx, invoice_id)?;
+            let remaining = logic::creditable_quantities(&original, &credit_notes);
+            for (item, _, _) in &credited {
+                if !item.session_ids.is_empty() && remaining.get(&item.id).copied() == Some(0.0) {
+                    tx.execute("UPDATE invoice_items SET session_released = 1 WHERE id = ?", params![item.id])?;
+                }
+            }
+        }
+        
+        // The note keeps the seller printed on the invoice it corrects
+        tx.execute(
+            "UPDATE invoices SET seller_snapshot = (SELECT seller_snapshot FROM invoices WHERE id = ?) WHERE id = ?",
+            params![invoice_id, note_id],
+        )?;
+        self.issue_invoice_internal(&tx, note_id)?;
+        self.sync_payment_status_internal(&tx, invoice_id)?;
+        tx.commit()?;
+        Ok(note_id)
+    }
+
+    /// Void an invoice or credit note, keeping the record. Invoices with
+    /// payments or open credit notes can't be voided. With `release_sessions`
+    /// its sessions become billable again.
+    pub fn void_invoice(&self, id: i64, release_sessions: bool) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let invoice = self.get_invoice_internal(&tx, id)?;
+        match invoice.status {
+            InvoiceStatus::Draft => return Err(invalid_input("Drafts can be deleted instead of voided".to_string())),
+            InvoiceStatus::Void => return Err(invalid_input(format!("{} is already void", invoice.invoice_number))),
+            _ => {}
+        }
+        if invoice.amount_paid > 0.0 {
+            return Err(invalid_input(format!(
+                "Invoice {} has payments recorded; issue a credit note instead",
+                invoice.invoice_number
+            )));
+        }
+        // Voiding both would reverse the credited amount twice
+        let open_note: Option<String> = tx.query_row(
+            "SELECT invoice_number FROM invoices
+             WHERE credited_invoice_id = ? AND status <> 'Void' ORDER BY id LIMIT 1",
+            params![id],
+            |row| row.get(0)
+        ).optional()?;
+        if let Some(note_number) = open_note {
+            return Err(invalid_input(format!(
+                "Invoice {} has credit note {}; void the credit note first",
+                invoice.invoice_number, note_number
+            )));
+        }
+        tx.execute(
+            "UPDATE invoices SET status = 'Void', voided_at = datetime('now') WHERE id = ?",
+            params![id],
+        )?;
+        if release_sessions {
+            tx.execute(
+                "UPDATE invoice_items SET session_released = 1
+                 WHERE invoice_id = ? AND id IN (SELECT item_id FROM invoice_item_sessions)",
+                params![id],
+            )?;
+        }
+        // A void credit note no longer reduces what its invoice owes, nor
+        // releases the sessions of the lines it credited
+        if let Some(original_id) = invoice.credited_invoice_id {
+            let credited_item_ids = invoice.items.iter()
+                .filter_map(|item| item.credited_item_id)
+                .collect::<Vec<_>>();
+            if !credited_item_ids.is_empty() {
+                let placeholders = vec!["?"; credited_item_ids.len()].join(", ");
+                let rebilled: i64 = tx.query_row(
+                    &format!(
+                        "SELECT COUNT(*) FROM invoice_item_sessions l
+                         JOIN invoice_items ii ON ii.id = l.item_id
+                         WHERE ii.id IN ({}) AND ii.session_released = 1
+                           AND l.session_id IN ({})",
+                        placeholders, INVOICED_SESSIONS_SQL
+                    ),
+                    params_from_iter(credited_item_ids.iter()),
+                    |row| row.get(0),
+                )?;
+                if rebilled > 0 {
+                    return Err(invalid_input(format!(
+                        "Sessions credited by {} have been billed again; void or credit that invoice first",
+                        invoice.invoice_number
+                    )));
+                }
+                tx.execute(
+                    &format!("UPDATE invoice_items SET session_released = 0 WHERE id IN ({})", placeholders),
+                    params_from_iter(credited_item_ids.iter()),
+                )?;
+            }
+            self.sync_payment_status_internal(&tx, original_id)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Only drafts can be deleted; issued invoices are voided or credited
+    pub fn delete_invoice(&self, id: i64) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let status: String = tx.query_row("SELECT status FROM invoices WHERE id = ?", params![id], |row| row.get(0))?;
+        if status != InvoiceStatus::Draft.to_string() {
+            return Err(invalid_input(format!("A {} invoice can't be deleted", status)));
+        }
+        // A retainer draft gives its period back, so the next run bills it
+        // again. Later periods carried hours over from it, so only the
+        // retainer's latest period can be handed back.
+        let period: Option<(i64, String)> = tx.query_row(
+            "SELECT retainer_id, period_start FROM retainer_periods WHERE invoice_id = ?",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?))
+        ).optional()?;
+        if let Some((retainer_id, period_start)) = period {
+            let later: bool = tx.query_row(
+                "SELECT EXISTS(SELECT 1 FROM retainer_periods WHERE retainer_id = ? AND period_start > ?)",
+                params![retainer_id, period_start],
+                |row| row.get(0)
+            )?;
+            if later {
+                return Err(invalid_input(
+                    "Only the draft of a retainer's latest period can be deleted".to_string()
+                ));
+            }
+            tx.execute(
+                "DELETE FROM retainer_periods WHERE retainer_id = ? AND period_start = ?",
+                params![retainer_id, period_start],
+            )?;
+        }
+        // Invoice row first: the item lock triggers ignore items of a deleted invoice
+        tx.execute("DELETE FROM invoices WHERE id = ?", params![id])?;
+        tx.execute("DELETE FROM invoice_items WHERE invoice_id = ?", params![id])?;
+        tx.execute("DELETE FROM invoice_taxes WHERE invoice_id = ?", params![id])?;
+        tx.execute("DELETE FROM invoice_payments WHERE invoice_id = ?", params![id])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_uninvoiced_sessions(&self) -> SqlResult<Vec<WorkSession>> {
+        let conn = self.reader();
+        // Get all sessions that haven't been invoiced yet
+        let mut stmt = conn.prepare(&format!(
+            "SELECT s.id, s.session_type_id, st.name as session_type_name, s.date, 
+                    COALESCE(s.project_name, 'Unnamed Session') as project_name, 
+                    s.hours, s.description,
+                    s.pay_type, 
+                    COALESCE(s.hourly_rate, st.hourly_rate) as hourly_rate, 
+                    s.fixed_amount
+             FROM sessions s
+             LEFT JOIN session_types st ON s.session_type_id = st.id
+             WHERE s.id NOT IN ({})
+             ORDER BY s.date DESC",
+            INVOICED_SESSIONS_SQL
+        ))?;
+        
+        let sessions = stmt.query_map([], |row| {
+            let pay_type_str: Option<String> = row.get(7)?;
+            Ok(WorkSession {
+                id: row.get(0)?,
+                session_type_id: row.get(1)?,
+                session_type_name: row.get(2)?,
+                date: row.get(3)?,
+                project_name: row.get(4)?,
+                hours: row.get(5)?,
+                description: row.get(6)?,
+                pay_type: pay_type_str.and_then(|s| PayType::from_string(&s).ok()),
+                hourly_rate: row.get(8)?,
+                fixed_amount: row.get(9)?,
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+        
+        Ok(sessions)
+    }
+
+    // ========== QUOTES ==========
+    // Quotes past Draft are locked by triggers in schema.sql.
+
+    pub fn get_all_quotes(&self) -> SqlResult<Vec<Quote>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM quotes ORDER BY created_date DESC, id DESC",
+            QUOTE_COLUMNS
+        ))?;
+        let mut quotes = stmt.query_map([], quote_from_row)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        for quote in &mut quotes {
+            self.load_quote_lines_internal(&conn, quote)?;
+        }
+        Ok(quotes)
+    }
+
+    pub fn get_quote(&self, id: i64) -> SqlResult<Quote> {
+        let conn = self.reader();
+        self.get_quote_internal(&conn, id)
+    }
+
+    fn get_quote_internal(&self, conn: &Connection, id: i64) -> SqlResult<Quote> {
+        let mut quote = conn.query_row(
+            &format!("SELECT {} FROM quotes WHERE id = ?", QUOTE_COLUMNS),
+            params![id],
+            quote_from_row,
+        )?;
+        self.load_quote_lines_internal(conn, &mut quote)?;
+        Ok(quote)
+    }
+
+    fn load_quote_lines_internal(&self, conn: &Connection, quote: &mut Quote) -> SqlResult<()> {
+        let mut stmt = conn.prepare(
+            "SELECT id, quote_id, kind, description, hours, unit, rate, discount_type, discount_value,
+                    taxable, tax_category, amount
+             FROM quote_items WHERE quote_id = ? ORDER BY id"
+        )?;
+        quote.items = stmt.query_map(params![quote.id], |row| {
+            let kind: String = row.get(2)?;
+            Ok(InvoiceItem {
+                id: row.get(0)?,
+                invoice_id: row.get(1)?,
+                session_id: None,
+                session_ids: Vec::new(),
+                kind: InvoiceItemKind::from_string(&kind).unwrap_or(InvoiceItemKind::Manual),
+                description: row.get(3)?,
+                hours: row.get(4)?,
+                unit: row.get(5)?,
+                rate: row.get(6)?,
+                discount: discount_from_columns(row.get(7)?, row.get(8)?),
+                taxable: row.get(9)?,
+                tax_category: row.get(10)?,
+                amount: row.get(11)?,
+                credited_item_id: None,
+                session_released: false,
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, quote_id, tax_rate_id, name, rate, kind, category, note, base, amount
+             FROM quote_taxes WHERE quote_id = ? ORDER BY id"
+        )?;
+        quote.taxes = stmt.query_map(params![quote.id], |row| {
+            let kind: String = row.get(5)?;
+            Ok(InvoiceTax {
+                id: row.get(0)?,
+                invoice_id: row.get(1)?,
+                tax_rate_id: row.get(2)?,
+                name: row.get(3)?,
+                rate: row.get(4)?,
+                kind: TaxKind::from_string(&kind).unwrap_or(TaxKind::Additive),
+                category: row.get(6)?,
+                note: row.get(7)?,
+                base: row.get(8)?,
+                amount: row.get(9)?,
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+        Ok(())
+    }
+
+    pub fn create_quote(&self, quote: &NewQuote) -> SqlResult<i64> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let created_date = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let quote_number = self.next_quote_number_internal(&tx, &quote.client_name, &created_date)?;
+        let (discount_type, discount_value) = discount_to_columns(quote.discount.as_ref());
+        let profile_id = match quote.profile_id {
+            Some(id) => Some(id),
+            None => self.get_default_business_profile_internal(&tx)?.map(|p| p.id),
+        };
+        tx.execute(
+            "INSERT INTO quotes (quote_number, client_name, client_email, created_date, valid_until, notes,
+                                 discount_type, discount_value, profile_id)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                quote_number,
+                quote.client_name.trim(),
+                quote.client_email,
+                created_date,
+                quote.valid_until,
+                quote.notes,
+                discount_type,
+                discount_value,
+                profile_id
+            ],
+        )?;
+        let quote_id = tx.last_insert_rowid();
+        for item in &quote.items {
+            self.insert_quote_item_internal(&tx, quote_id, item)?;
+        }
+        self.set_quote_taxes_internal(&tx, quote_id, &quote.tax_rate_ids)?;
+        self.recalculate_quote_totals_internal(&tx, quote_id)?;
+        tx.commit()?;
+        Ok(quote_id)
+    }
+
+    pub fn update_draft_quote(&self, id: i64, update: &QuoteDraftUpdate) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let (discount_type, discount_value) = discount_to_columns(update.discount.as_ref());
+        tx.execute(
+            "UPDATE quotes SET client_name = ?, client_email = ?, valid_until = ?, notes = ?,
+                               discount_type = ?, discount_value = ?, profile_id = ?
+             WHERE id = ?",
+            params![
+                update.client_name.trim(),
+                update.client_email,
+                update.valid_until,
+                update.notes,
+                discount_type,
+                discount_value,
+                update.profile_id,
+                id
+            ],
+        )?;
+        self.set_quote_taxes_internal(&tx, id, &update.tax_rate_ids)?;
+        self.recalculate_quote_totals_internal(&tx, id)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn add_quote_item(&self, quote_id: i64, item: &NewInvoiceItem) -> SqlResult<i64> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let item_id = self.insert_quote_item_internal(&tx, quote_id, item)?;
+        self.recalculate_quote_totals_internal(&tx, quote_id)?;
+        tx.commit()?;
+        Ok(item_id)
+    }
+
+    pub fn update_quote_item(&self, quote_id: i64, item: &InvoiceItemUpdate) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let (discount_type, discount_value) = discount_to_columns(item.discount.as_ref());
+        let amount = logic::line_amount(item.hours, item.rate, item.discount.as_ref());
+        tx.execute(
+            "UPDATE quote_items SET description = ?, hours = ?, unit = ?, rate = ?,
+                                    discount_type = ?, discount_value = ?, taxable = ?, tax_category = ?,
+                                    amount = ?
+             WHERE id = ? AND quote_id = ?",
+            params![
+                item.description,
+                item.hours,
+                item.unit,
+                item.rate,
+                discount_type,
+                discount_value,
+                item.taxable,
+                item.tax_category,
+                amount,
+                item.id,
+                quote_id
+            ],
+        )?;
+        self.recalculate_quote_totals_internal(&tx, quote_id)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn remove_quote_item(&self, quote_id: i64, item_id: i64) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM quote_items WHERE id = ? AND quote_id = ?",
+            params![item_id, quote_id],
+        )?;
+        self.recalculate_quote_totals_internal(&tx, quote_id)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn update_quote_status(&self, id: i64, status: QuoteStatus) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute(
+            "UPDATE quotes SET status = ? WHERE id = ?",
+            params![status.to_string(), id],
+        )?;
+        Ok(())
+    }
+
+    /// Only Draft quotes can be deleted; sent ones stay on record
+    pub fn delete_quote(&self, id: i64) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let quote = self.get_quote_internal(&tx, id)?;
+        if quote.status != QuoteStatus::Draft {
+            return Err(invalid_input(format!("Quote {} is {} and can't be deleted", quote.quote_number, quote.status)));
+        }
+        // The item and tax lock triggers ignore lines of a deleted quote
+        tx.execute("DELETE FROM quotes WHERE id = ? AND status = 'Draft'", params![id])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Move Sent quotes whose validity ended before `today` to Expired.
+    /// Returns the quotes that changed.
+    pub fn mark_expired_quotes(&self, today: &str) -> SqlResult<Vec<Quote>> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let ids = tx.prepare(
+            "UPDATE quotes SET status = 'Expired' WHERE status = 'Sent' AND valid_until < ? RETURNING id"
+        )?
+            .query_map(params![today], |row| row.get::<_, i64>(0))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        let quotes = ids.iter()
+            .map(|id| self.get_quote_internal(&tx, *id))
+            .collect::<SqlResult<Vec<_>>>()?;
+        tx.commit()?;
+        Ok(quotes)
+    }
+
+    /// Create a draft invoice with the quote's lines, discount and taxes,
+    /// mark the quote Accepted and link the two. Only Sent, Expired or
+    /// Accepted quotes not converted yet qualify. Returns the invoice id.
+    pub fn convert_quote_to_invoice(&self, id: i64, due_date: &str) -> SqlResult<i64> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let quote = self.get_quote_internal(&tx, id)?;
+        if let Some(number) = &quote.invoice_number {
+            return Err(invalid_input(format!("Quote {} was already converted into invoice {}", quote.quote_number, number)));
+        }
+        if quote.status != QuoteStatus::Accepted && !quote.status.can_become(QuoteStatus::Accepted) {
+            return Err(invalid_input(format!(
+                "Quote {} is {}; only sent or accepted quotes can be invoiced",
+                quote.quote_number,
+                quote.status
+            )));
+        }
+        let items = quote.items.iter()
+            .map(|item| NewInvoiceItem {
+                kind: item.kind,
+                description: item.description.clone(),
+                hours: item.hours,
+                unit: item.unit.clone(),
+                rate: item.rate,
+                discount: item.discount,
+                taxable: item.taxable,
+                tax_category: item.tax_category.clone(),
+            })
+            .collect();
+        let invoice_id = self.create_invoice_internal(&tx, NewInvoice {
+            client_name: quote.client_name.clone(),
+            client_email: quote.client_email.clone(),
+            client_address: ClientAddress::default(),
+            due_date: due_date.to_string(),
+            tax_rate: None,
+            notes: quote.notes.clone(),
+            session_ids: Vec::new(),
+            items,
+            discount: quote.discount,
+            tax_rate_ids: Vec::new(),
+            profile_id: quote.profile_id,
+            template_id: None,
+            line_grouping: LineGrouping::default(),
+            include_timesheet: false,
+        })?;
+        // The quoted rates, even if the tax rates have changed since
+        tx.execute(
+            "INSERT INTO invoice_taxes (invoice_id, tax_rate_id, name, rate, kind, category, note)
+             SELECT ?, tax_rate_id, name, rate, kind, category, note FROM quote_taxes WHERE quote_id = ? ORDER BY id",
+            params![invoice_id, id],
+        )?;
+        self.recalculate_invoice_totals_internal(&tx, invoice_id)?;
+        let linked = tx.execute(
+            "UPDATE quotes SET status = 'Accepted', invoice_id = ?
+             WHERE id = ? AND invoice_id IS NULL AND status IN ('Sent', 'Expired', 'Accepted')",
+            params![invoice_id, id],
+        )?;
+        if linked != 1 {
+            return Err(invalid_input(format!("Quote {} was converted in the meantime", quote.quote_number)));
+        }
+        tx.commit()?;
+        Ok(invoice_id)
+    }
+
+    fn next_quote_number_internal(&self, conn: &Connection, client_name: &str, date: &str) -> SqlResult<String> {
+        let numbering = self.get_invoice_numbering_internal(conn)?;
+        let scope = numbering.quote_scope(date);
+        self.next_document_number_internal(conn, &numbering.quote_pattern, &scope, numbering.prefix_for(client_name), date)
+    }
+
+    fn insert_quote_item_internal(&self, conn: &Connection, quote_id: i64, item: &NewInvoiceItem) -> SqlResult<i64> {
+        let (discount_type, discount_value) = discount_to_columns(item.discount.as_ref());
+        let amount = logic::line_amount(item.hours, item.rate, item.discount.as_ref());
+        conn.execute(
+            "INSERT INTO quote_items (quote_id, kind, description, hours, unit, rate,
+                                      discount_type, discount_value, taxable, tax_category, amount)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                quote_id,
+                item.kind.to_string(),
+                item.description,
+                item.hours,
+                item.unit,
+                item.rate,
+                discount_type,
+                discount_value,
+                item.taxable,
+                item.tax_category,
+                amount
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Replace the taxes on a quote with snapshots of the named rates
+    fn set_quote_taxes_internal(&self, conn: &Connection, quote_id: i64, tax_rate_ids: &[i64]) -> SqlResult<()> {
+        conn.execute("DELETE FROM quote_taxes WHERE quote_id = ?", params![quote_id])?;
+        for tax_rate_id in tax_rate_ids {
+            let inserted = conn.execute(
+                "INSERT INTO quote_taxes (quote_id, tax_rate_id, name, rate, kind, category, note)
+                 SELECT ?, id, name, rate, kind, category, note FROM tax_rates WHERE id = ?",
+                params![quote_id, tax_rate_id],
+            )?;
+            if inserted == 0 {
+                return Err(invalid_input(format!("Tax rate {} not found", tax_rate_id)));
+            }
+        }
+        Ok(())
+    }
+
+    fn recalculate_quote_totals_internal(&self, conn: &Connection, quote_id: i64) -> SqlResult<()> {
+        let mut quote = self.get_quote_internal(conn, quote_id)?;
+        let totals = logic::calculate_invoice_totals(&quote.items, quote.discount.as_ref(), &mut quote.taxes);
+        for tax in &quote.taxes {
+            conn.execute(
+                "UPDATE quote_taxes SET base = ?, amount = ? WHERE id = ?",
+                params![tax.base, tax.amount, tax.id],
+            )?;
+        }
+        conn.execute(
+            "UPDATE quotes SET subtotal = ?, discount_amount = ?, tax_amount = ?, withholding_amount = ?, total = ?
+             WHERE id = ?",
+            params![
+                totals.subtotal,
+                totals.discount_amount,
+                totals.tax_amount,
+                totals.withholding_amount,
+                totals.total,
+                quote_id
+            ],
+        )?;
+        Ok(())
+    }
+
+    // ========== RETAINERS ==========
+
+    pub fn get_all_retainers(&self) -> SqlResult<Vec<Retainer>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM retainers ORDER BY active DESC, client_name",
+            RETAINER_COLUMNS
+        ))?;
+        let retainers = stmt.query_map([], retainer_from_row)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(retainers)
+    }
+
+    pub fn get_retainer(&self, id: i64) -> SqlResult<Retainer> {
+        let conn = self.reader();
+        self.get_retainer_internal(&conn, id)
+    }
+
+    fn get_retainer_internal(&self, conn: &Connection, id: i64) -> SqlResult<Retainer> {
+        conn.query_row(
+            &format!("SELECT {} FROM retainers WHERE id = ?", RETAINER_COLUMNS),
+            params![id],
+            retainer_from_row,
+        )
+    }
+
+    pub fn add_retainer(&self, retainer: &NewRetainer) -> SqlResult<i64> {
+        let conn = self.writer();
+        let (project_names, tax_rate_ids) = retainer_json_columns(retainer)?;
+        conn.execute(
+            "INSERT INTO retainers (client_name, client_email, project_names, period, fee, included_hours, overage_rate,
+                                    rollover, rollover_cap, start_date, end_date, payment_terms_days, profile_id,
+                                    tax_rate_ids, active)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                retainer.client_name.trim(),
+                retainer.client_email,
+                project_names,
+                retainer.period.to_string(),
+                retainer.fee,
+                retainer.included_hours,
+                retainer.overage_rate,
+                retainer.rollover.to_string(),
+                retainer.rollover_cap,
+                retainer.start_date,
+                retainer.end_date,
+                retainer.payment_terms_days,
+                retainer.profile_id,
+                tax_rate_ids,
+                retainer.active
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Billed periods are kept as they were; changes apply from the next one
+    pub fn update_retainer(&self, id: i64, retainer: &NewRetainer) -> SqlResult<()> {
+        let conn = self.writer();
+        let (project_names, tax_rate_ids) = retainer_json_columns(retainer)?;
+        conn.execute(
+            "UPDATE retainers SET client_name = ?, client_email = ?, project_names = ?, period = ?, fee = ?,
+                                  included_hours = ?, overage_rate = ?, rollover = ?, rollover_cap = ?,
+                                  start_date = ?, end_date = ?, payment_terms_days = ?, profile_id = ?,
+                                  tax_rate_ids = ?, active = ?
+             WHERE id = ?",
+            params![
+                retainer.client_name.trim(),
+                retainer.client_email,
+                project_names,
+                retainer.period.to_string(),
+                retainer.fee,
+                retainer.included_hours,
+                retainer.overage_rate,
+                retainer.rollover.to_string(),
+                retainer.rollover_cap,
+                retainer.start_date,
+                retainer.end_date,
+                retainer.payment_terms_days,
+                retainer.profile_id,
+                tax_rate_ids,
+                retainer.active,
+                id
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Invoices already generated for the retainer are kept
+    pub fn delete_retainer(&self, id: i64) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute("DELETE FROM retainers WHERE id = ?", params![id])?;
+        Ok(())
+    }
+
+    /// Billed periods of a retainer, oldest first, followed by the first
+    /// unbilled one if it has started (`is_current`, counted up to now)
+    pub fn get_retainer_usage(&self, id: i64, today: &str) -> SqlResult<Vec<RetainerUsage>> {
+        let conn = self.reader();
+        let retainer = self.get_retainer_internal(&conn, id)?;
+        let mut stmt = conn.prepare(
+            "SELECT p.retainer_id, p.period_start, p.period_end, p.included_hours, p.rollover_in, p.hours_used,
+                    p.overage_hours, p.rollover_out, p.invoice_id, i.invoice_number
+             FROM retainer_periods p
+             LEFT JOIN invoices i ON i.id = p.invoice_id
+             WHERE p.retainer_id = ?
+             ORDER BY p.period_start"
+        )?;
+        let mut usage = stmt.query_map(params![id], |row| {
+            Ok(RetainerUsage {
+                retainer_id: row.get(0)?,
+                period_start: row.get(1)?,
+                period_end: row.get(2)?,
+                included_hours: row.get(3)?,
+                rollover_in: row.get(4)?,
+                hours_used: row.get(5)?,
+                overage_hours: row.get(6)?,
+                rollover_out: row.get(7)?,
+                invoice_id: row.get(8)?,
+                invoice_number: row.get(9)?,
+                is_current: false,
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+
+        if let Some((start, end, rollover_in)) = self.next_retainer_period_internal(&conn, &retainer)? {
+            if start <= parse_date(today)? {
+                let (period_start, period_end) = (format_date(start), format_date(end));
+                let sessions = self.retainer_sessions_internal(&conn, &retainer, &period_start, &period_end)?;
+                let hours_used = logic::round_quantity(sessions.iter().map(|(_, hours)| hours).sum());
+                let (overage_hours, rollover_out) = logic::retainer_usage(&retainer, rollover_in, hours_used);
+                usage.push(RetainerUsage {
+                    retainer_id: id,
+                    period_start,
+                    period_end,
+                    included_hours: retainer.included_hours,
+                    rollover_in,
+                    hours_used,
+                    overage_hours,
+                    rollover_out,
+                    invoice_id: None,
+                    invoice_number: None,
+                    is_current: true,
+                });
+            }
+        }
+        Ok(usage)
+    }
+
+    /// Generate a draft invoice for every period of an active retainer that
+    /// ended before `today`, catching up on periods missed while the app was
+    /// closed. Each retainer is billed in its own transaction; one that fails
+    /// is reported and left for the next run.
+    pub fn bill_due_retainers(&self, today: &str) -> SqlResult<RetainerBilling> {
+        let today = parse_date(today)?;
+        let mut conn = self.writer();
+        let retainers = {
+            let mut stmt = conn.prepare(&format!("SELECT {} FROM retainers WHERE active = 1", RETAINER_COLUMNS))?;
+            let retainers = stmt.query_map([], retainer_from_row)?
+                .collect::<SqlResult<Vec<_>>>()?;
+            retainers
+        };
+
+        let mut billing = RetainerBilling::default();
+        for retainer in &retainers {
+            let tx = conn.transaction()?;
+            match self.bill_retainer_internal(&tx, retainer, today) {
+                Ok(invoices) => {
+                    tx.commit()?;
+                    billing.invoices.extend(invoices);
+                }
+                Err(e) => billing.failures.push(RetainerBillingFailure {
+                    retainer_id: retainer.id,
+                    client_name: retainer.client_name.clone(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+        Ok(billing)
+    }
+
+    /// Bill the retainer's periods that ended before `today`
+    fn bill_retainer_internal(&self, conn: &Connection, retainer: &Retainer, today: chrono::NaiveDate) -> SqlResult<Vec<Invoice>> {
+        let mut invoices = Vec::new();
+        while let Some((start, end, rollover_in)) = self.next_retainer_period_internal(conn, retainer)? {
+            if end >= today {
+                break;
+            }
+            let invoice_id = self.bill_retainer_period_internal(conn, retainer, start, end, rollover_in, today)?;
+            invoices.push(self.get_invoice_internal(conn, invoice_id)?);
+        }
+        Ok(invoices)
+    }
+
+    /// First unbilled period of a retainer and the hours carried into it, or
+    /// `None` once the retainer's end date has been billed
+    fn next_retainer_period_internal(&self, conn: &Connection, retainer: &Retainer) -> SqlResult<Option<(chrono::NaiveDate, chrono::NaiveDate, f64)>> {
+        let anchor = parse_date(&retainer.start_date)?;
+        let last = conn.query_row(
+            "SELECT period_end, rollover_out FROM retainer_periods
+             WHERE retainer_id = ? ORDER BY period_start DESC LIMIT 1",
+            params![retainer.id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+        );
+        let (start, rollover_in) = match last {
+            Ok((last_end, rollover)) => {
+                let next = parse_date(&last_end)?.succ_opt().unwrap_or(chrono::NaiveDate::MAX);
+                (next.max(anchor), rollover)
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => (anchor, 0.0),
+            Err(e) => return Err(e),
+        };
+
+        let mut end = logic::retainer_period_end(anchor, retainer.period, start);
+        if let Some(end_date) = &retainer.end_date {
+            let end_date = parse_date(end_date)?;
+            if start > end_date {
+                return Ok(None);
+            }
+            end = end.min(end_date);
+        }
+        Ok(Some((start, end, rollover_in)))
+    }
+
+    /// Uninvoiced sessions (id, hours) on the retainer's projects in a period
+    fn retainer_sessions_internal(&self, conn: &Connection, retainer: &Retainer, start: &str, end: &str) -> SqlResult<Vec<(i64, f64)>> {
+        if retainer.project_names.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut stmt = conn.prepare(&format!(
+            "SELECT s.id, s.hours FROM sessions s
+             WHERE s.project_name IN ({})
+               AND s.date BETWEEN ? AND ?
+               AND s.id NOT IN ({})
+             ORDER BY s.date, s.id",
+            vec!["?"; retainer.project_names.len()].join(", "),
+            INVOICED_SESSIONS_SQL
+        ))?;
+        let mut values: Vec<Value> = retainer.project_names.iter()
+            .map(|p| Value::Text(p.clone()))
+            .collect();
+        values.push(Value::Text(start.to_string()));
+        values.push(Value::Text(end.to_string()));
+        let sessions = stmt.query_map(params_from_iter(values), |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(sessions)
+    }
+
+    /// Draft invoice for one closed period: the fee, plus hours beyond those
+    /// included at the overage rate. The period's sessions are linked to the
+    /// fee line, so they count as invoiced and show on the timesheet.
+    fn bill_retainer_period_internal(
+        &self,
+        conn: &Connection,
+        retainer: &Retainer,
+        start: chrono::NaiveDate,
+        end: chrono::NaiveDate,
+        rollover_in: f64,
+        today: chrono::NaiveDate,
+    ) -> SqlResult<i64> {
+        let (period_start, period_end) = (format_date(start), format_date(end));
+        let sessions = self.retainer_sessions_internal(conn, retainer, &period_start, &period_end)?;
+        let hours_used = logic::round_quantity(sessions.iter().map(|(_, hours)| hours).sum());
+        let (overage_hours, rollover_out) = logic::retainer_usage(retainer, rollover_in, hours_used);
+        let available = logic::round_quantity(retainer.included_hours + rollover_in);
+
+        let mut items = vec![NewInvoiceItem {
+            kind: InvoiceItemKind::Manual,
+            description: format!("{} retainer, {} to {}", retainer.period, period_start, period_end),
+            hours: 1.0,
+            unit: None,
+            rate: retainer.fee,
+            discount: None,
+            taxable: true,
+            tax_category: None,
+        }];
+        if overage_hours > 0.0 {
+            items.push(NewInvoiceItem {
+                kind: InvoiceItemKind::Manual,
+                description: format!("Hours beyond the {} included", available),
+                hours: overage_hours,
+                unit: Some("hours".to_string()),
+                rate: retainer.overage_rate,
+                discount: None,
+                taxable: true,
+                tax_category: None,
+            });
+        }
+
+        // Rates deleted since the retainer was set up are skipped rather than
+        // failing every later period
+        let mut tax_rate_ids = Vec::new();
+        for tax_rate_id in &retainer.tax_rate_ids {
+            let exists: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM tax_rates WHERE id = ?)",
+                params![tax_rate_id],
+                |row| row.get(0)
+            )?;
+            if exists {
+                tax_rate_ids.push(*tax_rate_id);
+            }
+        }
+
+        let invoice_id = self.create_invoice_internal(conn, NewInvoice {
+            client_name: retainer.client_name.clone(),
+            client_email: retainer.client_email.clone(),
+            client_address: ClientAddress::default(),
+            due_date: format_date(today + chrono::Duration::days(retainer.payment_terms_days)),
+            tax_rate: None,
+            notes: Some(format!("{} of {} hours used, {} to {}", hours_used, available, period_start, period_end)),
+            session_ids: Vec::new(),
+            items,
+            discount: None,
+            tax_rate_ids,
+            profile_id: retainer.profile_id,
+            template_id: None,
+            line_grouping: LineGrouping::default(),
+            include_timesheet: true,
+        })?;
+
+        let fee_item_id: i64 = conn.query_row(
+            "SELECT MIN(id) FROM invoice_items WHERE invoice_id = ?",
+            params![invoice_id],
+            |row| row.get(0)
+        )?;
+        for (session_id, _) in &sessions {
+            conn.execute(
+                "INSERT INTO invoice_item_sessions (item_id, session_id) VALUES (?, ?)",
+                params![fee_item_id, session_id],
+            )?;
+        }
+        conn.execute(
+            "INSERT INTO retainer_periods (retainer_id, period_start, period_end, included_hours, rollover_in,
+                                           hours_used, overage_hours, rollover_out, invoice_id)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                retainer.id,
+                period_start,
+                period_end,
+                retainer.included_hours,
+                rollover_in,
+                hours_used,
+                overage_hours,
+                rollover_out,
+                invoice_id
+            ],
+        )?;
+        Ok(invoice_id)
+    }
+
+    // ========== EMAIL ==========
+
+    /// SMTP settings; the password is never returned, `has_password` says if one is stored
+    pub fn get_smtp_settings(&self) -> SqlResult<Option<SmtpSettings>> {
+        let conn = self.reader();
+        let result = conn.query_row(
+            "SELECT value FROM settings WHERE key = 'smtp_settings'",
+            [],
+            |row| row.get::<_, String>(0)
+        );
+        let mut settings: SmtpSettings = match result {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))?,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let credentials = self.get_smtp_credentials_internal(&conn)?;
+        settings.username = credentials.as_ref().map(|c| c.username.clone());
+        settings.has_password = credentials.map(|c| !c.password.is_empty()).unwrap_or(false);
+        settings.password = None;
+        Ok(Some(settings))
+    }
+
+    /// Decrypted SMTP login, if one is stored
+    pub fn get_smtp_credentials(&self) -> SqlResult<Option<SmtpCredentials>> {
+        let conn = self.reader();
+        self.get_smtp_credentials_internal(&conn)
+    }
+
+    /// Save SMTP settings. The login goes to the encrypted store; without a new
+    /// password the stored one is kept, and without a username the login is removed.
+    pub fn set_smtp_settings(&self, settings: &SmtpSettings) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let username = settings.username.as_deref().map(str::trim).filter(|u| !u.is_empty());
+        let credentials = match username {
+            Some(username) => {
+                let password = match settings.password.as_deref() {
+                    Some(password) if !password.is_empty() => password.to_string(),
+                    _ => self.get_smtp_credentials_internal(&tx)?.map(|c| c.password).unwrap_or_default(),
+                };
+                Some(SmtpCredentials { username: username.to_string(), password })
+            }
+            None => None,
+        };
+        match credentials {
+            Some(credentials) => {
+                let json = serde_json::to_string(&credentials)
+                    .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+                let sealed = self.secrets.seal(&json).map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+                tx.execute(
+                    "INSERT OR REPLACE INTO settings (key, value, updated_at) VALUES ('smtp_credentials', ?, datetime('now'))",
+                    params![sealed],
+                )?;
+            }
+            None => {
+                tx.execute("DELETE FROM settings WHERE key = 'smtp_credentials'", [])?;
+            }
+        }
+
+        let stored = SmtpSettings { username: None, password: None, has_password: false, ..settings.clone() };
+        let json = serde_json::to_string(&stored)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        tx.execute(
+            "INSERT OR REPLACE INTO settings (key, value, updated_at) VALUES ('smtp_settings', ?, datetime('now'))",
+            params![json],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_smtp_credentials_internal(&self, conn: &Connection) -> SqlResult<Option<SmtpCredentials>> {
+        let result = conn.query_row(
+            "SELECT value FROM settings WHERE key = 'smtp_credentials'",
+            [],
+            |row| row.get::<_, String>(0)
+        );
+        let sealed = match result {
+            Ok(sealed) => sealed,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let json = self.secrets.open(&sealed)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, e.into()))?;
+        serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))
+    }
+
+    /// Issue a draft and log that it was emailed, in one transaction. Called
+    /// once the mail is sent, so a failed send leaves the draft as it was.
+    pub fn record_invoice_email(&self, invoice_id: i64, recipient: &str, subject: &str) -> SqlResult<Invoice> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        self.issue_invoice_internal(&tx, invoice_id)?;
+        tx.execute(
+            "INSERT INTO invoice_history (invoice_id, event, recipient, details) VALUES (?, ?, ?, ?)",
+            params![invoice_id, InvoiceEvent::EmailSent.to_string(), recipient, subject],
+        )?;
+        let invoice = self.get_invoice_internal(&tx, invoice_id)?;
+        tx.commit()?;
+        Ok(invoice)
+    }
+
+    /// Invoice history, newest first
+    pub fn get_invoice_history(&self, invoice_id: i64) -> SqlResult<Vec<InvoiceHistoryEntry>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(
+            "SELECT id, invoice_id, event, recipient, details, created_at
+             FROM invoice_history WHERE invoice_id = ? ORDER BY created_at DESC, id DESC"
+        )?;
+        let entries = stmt.query_map(params![invoice_id], |row| {
+            let event: String = row.get(2)?;
+            Ok(InvoiceHistoryEntry {
+                id: row.get(0)?,
+                invoice_id: row.get(1)?,
+                event: InvoiceEvent::from_string(&event).unwrap_or(InvoiceEvent::EmailSent),
+                recipient: row.get(3)?,
+                details: row.get(4)?,
+                created_at: row.get(5)?,
+            })
+        })?;
+        entries.collect()
+    }
+
+    // ========== PAYMENT REMINDERS ==========
+
+    pub fn get_reminder_schedules(&self) -> SqlResult<Vec<ReminderSchedule>> {
+        let conn = self.reader();
+        self.get_reminder_schedules_internal(&conn)
+    }
+
+    fn get_reminder_schedules_internal(&self, conn: &Connection) -> SqlResult<Vec<ReminderSchedule>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM reminder_schedules ORDER BY client_name IS NOT NULL, client_name",
+            REMINDER_SCHEDULE_COLUMNS
+        ))?;
+        let schedules = stmt.query_map([], reminder_schedule_from_row)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(schedules)
+    }
+
+    pub fn add_reminder_schedule(&self, schedule: &NewReminderSchedule) -> SqlResult<i64> {
+        let conn = self.writer();
+        conn.execute(
+            "INSERT INTO reminder_schedules (client_name, offsets, channel, subject_template, body_template, active)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                schedule.client_name.as_deref().map(str::trim),
+                reminder_offsets_json(&schedule.offsets)?,
+                schedule.channel.to_string(),
+                schedule.subject_template,
+                schedule.body_template,
+                schedule.active
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Reminders already sent stay logged; the new steps apply from the next run
+    pub fn update_reminder_schedule(&self, id: i64, schedule: &NewReminderSchedule) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute(
+            "UPDATE reminder_schedules SET client_name = ?, offsets = ?, channel = ?, subject_template = ?,
+                                           body_template = ?, active = ?
+             WHERE id = ?",
+            params![
+                schedule.client_name.as_deref().map(str::trim),
+                reminder_offsets_json(&schedule.offsets)?,
+                schedule.channel.to_string(),
+                schedule.subject_template,
+                schedule.body_template,
+                schedule.active,
+                id
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_reminder_schedule(&self, id: i64) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute("DELETE FROM reminder_schedules WHERE id = ?", params![id])?;
+        Ok(())
+    }
+
+    /// Unpaid invoices that reached a reminder step by `today` they haven't been
+    /// reminded of. Drafts, void invoices and credit notes are never chased.
+    pub fn get_due_reminders(&self, today: &str) -> SqlResult<Vec<DueReminder>> {
+        let today = parse_date(today)?;
+        let conn = self.reader();
+        let schedules = self.get_reminder_schedules_internal(&conn)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM invoices
+             WHERE status IN ('Sent', 'PartiallyPaid', 'Overdue') AND credited_invoice_id IS NULL
+             ORDER BY due_date, id",
+            INVOICE_COLUMNS
+        ))?;
+        let invoices = stmt.query_map([], invoice_from_row)?
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        let mut due = Vec::new();
+        for mut invoice in invoices {
+            if invoice.balance_due <= 0.0 {
+                continue;
+            }
+            let schedule = schedules.iter()
+                .find(|s| s.client_name.as_deref() == Some(invoice.client_name.as_str()))
+                .or_else(|| schedules.iter().find(|s| s.client_name.is_none()));
+            let schedule = match schedule {
+                Some(schedule) if schedule.active => schedule,
+                _ => continue,
+            };
+            let due_date = match chrono::NaiveDate::parse_from_str(&invoice.due_date, "%Y-%m-%d") {
+                Ok(date) => date,
+                Err(_) => continue,
+            };
+            let last_sent: Option<i64> = conn.query_row(
+                "SELECT MAX(offset_days) FROM invoice_reminders WHERE invoice_id = ?",
+                params![invoice.id],
+                |row| row.get(0)
+            )?;
+            if let Some(offset_days) = logic::due_reminder_offset(&schedule.offsets, due_date, today, last_sent) {
+                self.load_invoice_lines_internal(&conn, &mut invoice)?;
+                due.push(DueReminder { invoice, schedule: schedule.clone(), offset_days });
+            }
+        }
+        Ok(due)
+    }
+
+    /// Log a reminder as sent; a step already logged for the invoice is kept as is
+    pub fn record_invoice_reminder(&self, reminder: &DueReminder, channel: ReminderChannel, recipient: Option<&str>) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute(
+            "INSERT OR IGNORE INTO invoice_reminders (invoice_id, schedule_id, offset_days, channel, recipient)
+             VALUES (?, ?, ?, ?, ?)",
+            params![
+                reminder.invoice.id,
+                reminder.schedule.id,
+                reminder.offset_days,
+                channel.to_string(),
+                recipient
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Reminders sent for an invoice, oldest first
+    pub fn get_invoice_reminders(&self, invoice_id: i64) -> SqlResult<Vec<InvoiceReminder>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(
+            "SELECT id, invoice_id, schedule_id, offset_days, channel, recipient, sent_at
+             FROM invoice_reminders WHERE invoice_id = ? ORDER BY offset_days"
+        )?;
+        let reminders = stmt.query_map(params![invoice_id], |row| {
+            let channel: String = row.get(4)?;
+            Ok(InvoiceReminder {
+                id: row.get(0)?,
+                invoice_id: row.get(1)?,
+                schedule_id: row.get(2)?,
+                offset_days: row.get(3)?,
+                channel: ReminderChannel::from_string(&channel).unwrap_or(ReminderChannel::Notification),
+                recipient: row.get(5)?,
+                sent_at: row.get(6)?,
+            })
+        })?;
+        reminders.collect()
+    }
+
+    // ========== RECEIVABLES ==========
+
+    /// Aging of everything still owed on `today`, by client
+    pub fn get_receivables_report(&self, today: &str) -> SqlResult<ReceivablesReport> {
+        let today = parse_date(today)?;
+        let conn = self.reader();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM invoices
+             WHERE status IN ('Sent', 'PartiallyPaid', 'Overdue') AND credited_invoice_id IS NULL",
+            INVOICE_COLUMNS
+        ))?;
+        let invoices = stmt.query_map([], invoice_from_row)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(logic::receivables_report(&invoices, today))
+    }
+
+    /// Average days-to-pay and on-time rate per client
+    pub fn get_client_payment_stats(&self) -> SqlResult<Vec<ClientPaymentStats>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM invoices
+             WHERE status = 'Paid' AND paid_date IS NOT NULL AND credited_invoice_id IS NULL",
+            INVOICE_COLUMNS
+        ))?;
+        let invoices = stmt.query_map([], invoice_from_row)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(logic::client_payment_stats(&invoices))
+    }
+
+    // ========== BANK RECONCILIATION ==========
+
+    /// Store incoming transactions from a statement. Outgoing ones and lines
+    /// imported before (same external id) are skipped.
+    pub fn import_bank_transactions(&self, transactions: &[NewBankTransaction]) -> SqlResult<BankImportResult> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let mut result = BankImportResult { imported: 0, duplicates: 0, skipped_outgoing: 0 };
+        for transaction in transactions {
+            if transaction.amount <= 0.0 {
+                result.skipped_outgoing += 1;
+                continue;
+            }
+            let inserted = tx.execute(
+                "INSERT OR IGNORE INTO bank_transactions (external_id, date, amount, description, reference, counterparty)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+                params![
+                    transaction.external_id,
+                    transaction.date,
+                    logic::round_currency(transaction.amount),
+                    transaction.description,
+                    transaction.reference,
+                    transaction.counterparty
+                ],
+            )?;
+            if inserted == 0 {
+                result.duplicates += 1;
+            } else {
+                result.imported += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(result)
+    }
+
+    /// Imported transactions, newest first. With `unreconciled_only`, just the
+    /// ones not ignored and not fully recorded as payments.
+    pub fn get_bank_transactions(&self, unreconciled_only: bool) -> SqlResult<Vec<BankTransaction>> {
+        let conn = self.reader();
+        self.get_bank_transactions_internal(&conn, unreconciled_only)
+    }
+
+    fn get_bank_transactions_internal(&self, conn: &Connection, unreconciled_only: bool) -> SqlResult<Vec<BankTransaction>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM bank_transactions ORDER BY date DESC, id DESC",
+            BANK_TRANSACTION_COLUMNS
+        ))?;
+        let transactions = stmt.query_map([], bank_transaction_from_row)?
+            .collect::<SqlResult<Vec<BankTransaction>>>()?;
+        Ok(transactions.into_iter()
+            .filter(|t| !unreconciled_only || (!t.ignored && t.unallocated() > 0.0))
+            .collect())
+    }
+
+    pub fn get_bank_transaction(&self, id: i64) -> SqlResult<BankTransaction> {
+        let conn = self.reader();
+        self.get_bank_transaction_internal(&conn, id)
+    }
+
+    fn get_bank_transaction_internal(&self, conn: &Connection, id: i64) -> SqlResult<BankTransaction> {
+        conn.query_row(
+            &format!("SELECT {} FROM bank_transactions WHERE id = ?", BANK_TRANSACTION_COLUMNS),
+            params![id],
+            bank_transaction_from_row,
+        )
+    }
+
+    /// Open invoices each unreconciled transaction may be paying, best first,
+    /// at most three per transaction
+    pub fn suggest_bank_matches(&self) -> SqlResult<Vec<MatchSuggestion>> {
+        let conn = self.reader();
+        let transactions = self.get_bank_transactions_internal(&conn, true)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM invoices
+             WHERE status IN ('Sent', 'PartiallyPaid', 'Overdue') AND credited_invoice_id IS NULL",
+            INVOICE_COLUMNS
+        ))?;
+        let invoices = stmt.query_map([], invoice_from_row)?
+            .collect::<SqlResult<Vec<_>>>()?;
+
+        let mut suggestions = Vec::new();
+        for transaction in &transactions {
+            let mut matches: Vec<MatchSuggestion> = invoices.iter()
+                .filter_map(|invoice| logic::match_bank_transaction(transaction, invoice))
+                .collect();
+            matches.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.invoice_id.cmp(&b.invoice_id)));
+            suggestions.extend(matches.into_iter().take(3));
+        }
+        Ok(suggestions)
+    }
+
+    /// Record `amount` of a transaction as a payment of the invoice, dated
+    /// the day the money arrived. `amount` defaults to as much as both the
+    /// transaction and the invoice's balance allow, and can exceed neither.
+    /// Returns the payment id.
+    pub fn confirm_bank_match(&self, transaction_id: i64, invoice_id: i64, amount: Option<f64>) -> SqlResult<i64> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let transaction = self.get_bank_transaction_internal(&tx, transaction_id)?;
+        let invoice = self.get_invoice_internal(&tx, invoice_id)?;
+        if invoice.is_credit_note()
+            || matches!(invoice.status, InvoiceStatus::Draft | InvoiceStatus::Void)
+            || invoice.balance_due <= 0.0
+        {
+            return Err(invalid_input(format!("Invoice {} is not awaiting payment", invoice.invoice_number)));
+        }
+        let available = transaction.unallocated();
+        let amount = logic::round_currency(amount.unwrap_or_else(|| available.min(invoice.balance_due)));
+        if !amount.is_finite() || amount <= 0.0 {
+            return Err(invalid_input("Nothing left on this transaction to record".to_string()));
+        }
+        if amount > available {
+            return Err(invalid_input(format!("Only {:.2} of this transaction is left to record", available)));
+        }
+        if logic::round_currency(amount - invoice.balance_due) > 0.0 {
+            return Err(invalid_input(format!(
+                "Only {:.2} is left to pay on invoice {}",
+                invoice.balance_due,
+                invoice.invoice_number
+            )));
+        }
+        let reference = transaction.reference.unwrap_or(transaction.description);
+        tx.execute(
+            "INSERT INTO invoice_payments (invoice_id, payment_date, amount, method, reference, bank_transaction_id)
+             VALUES (?, ?, ?, 'Bank transfer', ?, ?)",
+            params![invoice_id, transaction.date, amount, reference, transaction_id],
+        )?;
+        let payment_id = tx.last_insert_rowid();
+        self.sync_payment_status_internal(&tx, invoice_id)?;
+        tx.commit()?;
+        Ok(payment_id)
+    }
+
+    /// Mark a transaction as not a client payment, or undo that
+    pub fn set_bank_transaction_ignored(&self, id: i64, ignored: bool) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute(
+            "UPDATE bank_transactions SET ignored = ? WHERE id = ?",
+            params![ignored, id],
+        )?;
+        Ok(())
+    }
+
+    /// Payments recorded from the transaction are kept
+    pub fn delete_bank_transaction(&self, id: i64) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute("DELETE FROM bank_transactions WHERE id = ?", params![id])?;
+        Ok(())
+    }
+
+    // ========== ACCOUNTING EXPORT ==========
+
+    /// Saved account names, or the defaults
+    pub fn get_accounting_settings(&self) -> SqlResult<AccountingSettings> {
+        let conn = self.reader();
+        let result = conn.query_row(
+            "SELECT value FROM settings WHERE key = 'accounting_settings'",
+            [],
+            |row| row.get::<_, String>(0)
+        );
+        match result {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(AccountingSettings::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn set_accounting_settings(&self, settings: &AccountingSettings) -> SqlResult<()> {
+        let json = serde_json::to_string(settings)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let conn = self.writer();
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value, updated_at) VALUES ('accounting_settings', ?, datetime('now'))",
+            params![json],
+        )?;
+        Ok(())
+    }
+
+    /// Entries already exported in `format`, as (invoice_id, kind)
+    pub fn get_journal_exports(&self, format: JournalFormat) -> SqlResult<Vec<(i64, JournalEntryKind)>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare("SELECT invoice_id, kind FROM journal_exports WHERE format = ?")?;
+        let exports = stmt.query_map([format.to_string()], |row| {
+            let kind = JournalEntryKind::from_string(&row.get::<_, String>(1)?)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, e.into()))?;
+            Ok((row.get(0)?, kind))
+        })?.collect::<SqlResult<Vec<_>>>()?;
+        Ok(exports)
+    }
+
+    /// Accounts already opened in `format`
+    pub fn get_journal_accounts(&self, format: JournalFormat) -> SqlResult<Vec<String>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare("SELECT account FROM journal_accounts WHERE format = ? ORDER BY account")?;
+        let accounts = stmt.query_map([format.to_string()], |row| row.get(0))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(accounts)
+    }
+
+    /// Remember what an export wrote. A full export (`replace`) forgets
+    /// earlier exports in that format first.
+    pub fn record_journal_export(&self, format: JournalFormat, entries: &[(i64, JournalEntryKind)], accounts: &[String], replace: bool) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let format = format.to_string();
+        if replace {
+            tx.execute("DELETE FROM journal_exports WHERE format = ?", params![format])?;
+            tx.execute("DELETE FROM journal_accounts WHERE format = ?", params![format])?;
+        }
+        for (invoice_id, kind) in entries {
+            tx.execute(
+                "INSERT OR REPLACE INTO journal_exports (invoice_id, format, kind) VALUES (?, ?, ?)",
+                params![invoice_id, format, kind.to_string()],
+            )?;
+        }
+        for account in accounts {
+            tx.execute(
+                "INSERT OR IGNORE INTO journal_accounts (format, account) VALUES (?, ?)",
+                params![format, account],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // ========== EXPENSES ==========
+
+    pub fn get_all_expenses(&self) -> SqlResult<Vec<Expense>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM expenses ORDER BY date DESC, id DESC",
+            EXPENSE_COLUMNS
+        ))?;
+        let expenses = stmt.query_map([], expense_from_row)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(expenses)
+    }
+
+    /// Expenses with a due date from `start` to `end`: one-off expenses dated
+    /// in the range and recurring ones running during it
+    pub fn get_expenses_between(&self, start: &str, end: &str) -> SqlResult<Vec<Expense>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM expenses
+             WHERE date <= ?2
+               AND (date >= ?1 OR (recurrence <> 'None' AND (end_date IS NULL OR end_date >= ?1)))
+             ORDER BY date",
+            EXPENSE_COLUMNS
+        ))?;
+        let expenses = stmt.query_map([start, end], expense_from_row)?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(expenses)
+    }
+
+    pub fn get_expense(&self, id: i64) -> SqlResult<Expense> {
+        let conn = self.reader();
+        conn.query_row(
+            &format!("SELECT {} FROM expenses WHERE id = ?", EXPENSE_COLUMNS),
+            [id],
+            expense_from_row
+        )
+    }
+
+    pub fn add_expense(&self, expense: &NewExpense) -> SqlResult<i64> {
+        let conn = self.writer();
+        conn.execute(
+            "INSERT INTO expenses (date, amount, description, category, is_business, client_name, project_name,
+                                   recurrence, end_date, receipt_paths, notes)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                expense.date,
+                expense.amount,
+                expense.description.trim(),
+                expense.category.trim(),
+                expense.is_business,
+                expense.client_name,
+                expense.project_name,
+                expense.recurrence.to_string(),
+                expense.end_date,
+                receipt_paths_json(&expense.receipt_paths)?,
+                expense.notes
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn update_expense(&self, id: i64, expense: &NewExpense) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute(
+            "UPDATE expenses SET date = ?, amount = ?, description = ?, category = ?, is_business = ?,
+                                 client_name = ?, project_name = ?, recurrence = ?, end_date = ?,
+                                 receipt_paths = ?, notes = ?
+             WHERE id = ?",
+            params![
+                expense.date,
+                expense.amount,
+                expense.description.trim(),
+                expense.category.trim(),
+                expense.is_business,
+                expense.client_name,
+                expense.project_name,
+                expense.recurrence.to_string(),
+                expense.end_date,
+                receipt_paths_json(&expense.receipt_paths)?,
+                expense.notes,
+                id
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_expense(&self, id: i64) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute("DELETE FROM expenses WHERE id = ?", params![id])?;
+        Ok(())
+    }
+
+    /// Categories in use, most used first
+    pub fn get_expense_categories(&self) -> SqlResult<Vec<String>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(
+            "SELECT category FROM expenses GROUP BY category ORDER BY COUNT(*) DESC, category"
+        )?;
+        let categories = stmt.query_map([], |row| row.get(0))?.collect::<SqlResult<Vec<_>>>()?;
+        Ok(categories)
+    }
+
+    /// Income on `basis` per month (YYYY-MM) from `start` to `end`
+    pub fn get_income_by_month(&self, basis: ReportingBasis, start: &str, end: &str) -> SqlResult<Vec<(String, f64)>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT substr(date, 1, 7) AS month, SUM(amount) FROM ({})
+             WHERE date >= ? AND date <= ?
+             GROUP BY month ORDER BY month",
+            income_sql(basis)
+        ))?;
+        let months = stmt.query_map([start, end], |row| Ok((row.get(0)?, logic::round_currency(row.get(1)?))))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        Ok(months)
+    }
+
+    // ========== HABIT OPERATIONS ==========
+
+    pub fn get_all_habits(&self) -> SqlResult<Vec<Habit>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(
+            "SELECT h.id, h.name, h.description, h.trigger_type, h.trigger_value, h.reward_description, h.is_active,
+                    COALESCE((SELECT COUNT(*) FROM habit_logs WHERE habit_id = h.id), 0) as total_completions
+             FROM habits h ORDER BY h.name"
+        )?;
+        
+        let habits: Vec<Habit> = stmt.query_map([], |row| {
+            let id: i64 = row.get(0)?;
+            let trigger_type: String = row.get(3)?;
+            let trigger_value: f64 = row.get(4)?;
+            let total_completions: i32 = row.get(7)?;
+            
+            // Convert trigger_value to minutes if it's an hours-based trigger
+            let trigger_minutes = if trigger_type == "after_hours" {
+                Some((trigger_value * 60.0) as i32)
+            } else {
+                None
+            };
+            
+            Ok(Habit {
+                id,
+                name: row.get(1)?,
+                description: row.get(2)?,
+                trigger_type,
+                trigger_value,
+                reward_description: row.get(5)?,
+                is_active: row.get::<_, i32>(6)? == 1,
+                current_streak: 0, // Will be computed below
+                best_streak: 0,    // Will be computed below
+                total_completions,
+                target_streak: 7,  // Default target
+                trigger_minutes,
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+        
+        // Compute streaks for each habit
+        let mut result = habits;
+        for habit in &mut result {
+            let (current, best) = self.compute_habit_streaks(&conn, habit.id)?;
+            habit.current_streak = current;
+            habit.best_streak = best;
+        }
+        
+        Ok(result)
+    }
+    
+    fn compute_habit_streaks(&self, conn: &Connection, habit_id: i64) -> SqlResult<(i32, i32)> {
+        // Get all completion dates for this habit, ordered by date
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT date(completed_at) as completion_date 
+             FROM habit_logs WHERE habit_id = ? 
+             ORDER BY completion_date DESC"
+        )?;
+        
+        let dates: Vec<String> = stmt.query_map(params![habit_id], |row| {
+            row.get(0)
+        })?.collect::<SqlResult<Vec<_>>>()?;
+        
+        if dates.is_empty() {
+            return Ok((0, 0));
+        }
+        
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let yesterday = (chrono::Local::now() - chrono::Duration::days(1)).format("%Y-%m-%d").to_string();
+        
+        // Calculate current streak
+        let mut current_streak = 0;
+        let mut check_date = chrono::Local::now().date_naive();
+        
+        // Start from today or yesterday
+        if dates.contains(&today) || dates.contains(&yesterday) {
+            for date_str in &dates {
+                let check_str = check_date.format("%Y-%m-%d").to_string();
+                if date_str == &check_str {
+                    current_streak += 1;
+                    check_date = check_date.pred_opt().unwrap_or(check_date);
+                } else if date_str < &check_str {
+                    // Check if this date is the next consecutive day
+                    let prev_str = check_date.pred_opt().unwrap_or(check_date).format("%Y-%m-%d").to_string();
+                    if date_str == &prev_str {
+                        current_streak += 1;
+                        check_date = check_date.pred_opt().unwrap_or(check_date).pred_opt().unwrap_or(check_date);
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+        
+        // Calculate best streak
+        let mut best_streak = 0;
+        let mut streak = 1;
+        let mut sorted_dates = dates.clone();
+        sorted_dates.sort();
+        
+        for i in 1..sorted_dates.len() {
+            if let (Ok(prev), Ok(curr)) = (
+                chrono::NaiveDate::parse_from_str(&sorted_dates[i-1], "%Y-%m-%d"),
+                chrono::NaiveDate::parse_from_str(&sorted_dates[i], "%Y-%m-%d"),
+            ) {
+                if (curr - prev).num_days() == 1 {
+                    streak += 1;
+                } else {
+                    best_streak = best_streak.max(streak);
+                    streak = 1;
+                }
+            }
+        }
+        best_streak = best_streak.max(streak);
+        
+        Ok((current_streak, best_streak))
+    }
+
+    pub fn add_habit(&self, habit: Habit) -> SqlResult<i64> {
+        let conn = self.writer();
+        conn.execute(
+            "INSERT INTO habits (name, description, trigger_type, trigger_value, reward_description, is_active)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                habit.name,
+                habit.description,
+                habit.trigger_type,
+                habit.trigger_value,
+                habit.reward_description,
+                if habit.is_active { 1 } else { 0 }
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn update_habit(&self, habit: &Habit) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute(
+            "UPDATE habits SET name = ?, description = ?, trigger_type = ?, trigger_value = ?, 
+             reward_description = ?, is_active = ? WHERE id = ?",
+            params![
+                habit.name,
+                habit.description,
+                habit.trigger_type,
+                habit.trigger_value,
+                habit.reward_description,
+                if habit.is_active { 1 } else { 0 },
+                habit.id
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_habit(&self, id: i64) -> SqlResult<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM habit_logs WHERE habit_id = ?", params![id])?;
+        tx.execute("DELETE FROM habits WHERE id = ?", params![id])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn log_habit_completion(&self, habit_id: i64, notes: Option<String>) -> SqlResult<i64> {
+        let conn = self.writer();
+        conn.execute(
+            "INSERT INTO habit_logs (habit_id, notes) VALUES (?, ?)",
+            params![habit_id, notes],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn get_habit_logs_for_date(&self, date: &str) -> SqlResult<Vec<HabitLog>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(
+            "SELECT id, habit_id, completed_at, notes
+             FROM habit_logs WHERE date(completed_at) = ?"
+        )?;
+        
+        let logs = stmt.query_map(params![date], |row| {
+            Ok(HabitLog {
+                id: row.get(0)?,
+                habit_id: row.get(1)?,
+                completed_at: row.get(2)?,
+                notes: row.get(3)?,
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+        
+        Ok(logs)
+    }
+
+    // ========== AUTO-TRACKING OPERATIONS ==========
+
+    pub fn get_pending_suggestions(&self) -> SqlResult<Vec<ActivitySuggestion>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(
+            "SELECT id, date, app_name, window_title, suggested_project, suggested_session_type_id,
+                    duration_minutes, start_time, end_time, status
+             FROM activity_suggestions WHERE status = 'pending' ORDER BY date DESC, start_time DESC"
+        )?;
+        
+        let suggestions = stmt.query_map([], |row| {
+            Ok(ActivitySuggestion {
+                id: row.get(0)?,
+                date: row.get(1)?,
+                app_name: row.get(2)?,
+                window_title: row.get(3)?,
+                suggested_project: row.get(4)?,
+                suggested_session_type_id: row.get(5)?,
+                duration_minutes: row.get(6)?,
+                start_time: row.get(7)?,
+                end_time: row.get(8)?,
+                status: row.get(9)?,
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+        
+        Ok(suggestions)
+    }
+
+    pub fn accept_suggestion(&self, suggestion_id: i64, session_type_id: i64, project_name: &str) -> SqlResult<i64> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        
+        // Get suggestion; one already accepted or dismissed returns no rows
+        let suggestion: ActivitySuggestion = tx.query_row(
+            "SELECT id, date, app_name, window_title, suggested_project, suggested_session_type_id,
+                    duration_minutes, start_time, end_time, status
+             FROM activity_suggestions WHERE id = ? AND status = 'pending'",
+            params![suggestion_id],
+            |row| {
+                Ok(ActivitySuggestion {
+                    id: row.get(0)?,
+                    date: row.get(1)?,
+                    app_name: row.get(2)?,
+                    window_title: row.get(3)?,
+                    suggested_project: row.get(4)?,
+                    suggested_session_type_id: row.get(5)?,
+                    duration_minutes: row.get(6)?,
+                    start_time: row.get(7)?,
+                    end_time: row.get(8)?,
+                    status: row.get(9)?,
+                })
+            }
+        )?;
+        
+        // Create session from suggestion
+        let hours = suggestion.duration_minutes / 60.0;
+        tx.execute(
+            "INSERT INTO sessions (session_type_id, date, project_name, hours, description, pay_type)
+             VALUES (?, ?, ?, ?, ?, 'None')",
+            params![
+                session_type_id,
+                suggestion.date,
+                project_name,
+                hours,
+                format!("Auto-tracked: {} - {}", suggestion.app_name, suggestion.window_title)
+            ],
+        )?;
+        
+        let session_id = tx.last_insert_rowid();
+        
+        // Update suggestion status
+        tx.execute(
+            "UPDATE activity_suggestions SET status = 'accepted' WHERE id = ?",
+            params![suggestion_id],
+        )?;
+        
+        tx.commit()?;
+        Ok(session_id)
+    }
+
+    pub fn dismiss_suggestion(&self, suggestion_id: i64) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute(
+            "UPDATE activity_suggestions SET status = 'dismissed' WHERE id = ?",
+            params![suggestion_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_tracking_rules(&self) -> SqlResult<Vec<TrackingRule>> {
+        let conn = self.reader();
+        let mut stmt = conn.prepare(
+            "SELECT id, app_pattern, title_pattern, project_name, session_type_id, is_active
+             FROM tracking_rules ORDER BY app_pattern"
+        )?;
+        
+        let rules = stmt.query_map([], |row| {
+            Ok(TrackingRule {
+                id: row.get(0)?,
+                app_pattern: row.get(1)?,
+                title_pattern: row.get(2)?,
+                project_name: row.get(3)?,
+                session_type_id: row.get(4)?,
+                is_active: row.get::<_, i32>(5)? == 1,
+            })
+        })?.collect::<SqlResult<Vec<_>>>()?;
+        
+        Ok(rules)
+    }
+
+    pub fn add_tracking_rule(&self, rule: TrackingRule) -> SqlResult<i64> {
+        let conn = self.writer();
+        conn.execute(
+            "INSERT INTO tracking_rules (app_pattern, title_pattern, project_name, session_type_id, is_active)
+             VALUES (?, ?, ?, ?, ?)",
+            params![
+                rule.app_pattern,
+                rule.title_pattern,
+                rule.project_name,
+                rule.session_type_id,
+                if rule.is_active { 1 } else { 0 }
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn delete_tracking_rule(&self, id: i64) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute("DELETE FROM tracking_rules WHERE id = ?", params![id])?;
+        Ok(())
+    }
+
+    // ========== LICENSE ==========
+
+    pub fn get_license(&self) -> SqlResult<License> {
+        let conn = self.reader();
+        let result = conn.query_row(
+            "SELECT tier, license_key, activated_at, expires_at FROM license WHERE id = 1",
+            [],
+            |row| {
+                let tier_str: String = row.get(0)?;
+                Ok(License {
+                    tier: Tier::from_string(&tier_str),
+                    license_key: row.get(1)?,
+                    activated_at: row.get(2)?,
+                    expires_at: row.get(3)?,
+                })
+            }
+        );
+
+        match result {
+            Ok(license) => Ok(license),
+            Err(_) => Ok(License::default()),
+        }
+    }
+
+    pub fn save_license(&self, license: &License) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute(
+            "INSERT OR REPLACE INTO license (id, tier, license_key, activated_at, expires_at, updated_at)
+             VALUES (1, ?, ?, ?, ?, datetime('now'))",
+            params![
+                license.tier.to_string(),
+                license.license_key,
+                license.activated_at,
+                license.expires_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_session_type_count(&self) -> SqlResult<u32> {
+        let conn = self.reader();
+        let count: u32 = conn.query_row(
+            "SELECT COUNT(*) FROM session_types",
+            [],
+            |row| row.get(0)
+        )?;
+        Ok(count)
+    }
+
+    pub fn get_goal_count(&self) -> SqlResult<u32> {
+        let conn = self.reader();
+        let count: u32 = conn.query_row(
+            "SELECT COUNT(*) FROM goals",
+            [],
+            |row| row.get(0)
+        )?;
+        Ok(count)
+    }
+
+    // ========== SETTINGS ==========
+
+    pub fn get_setting(&self, key: &str) -> SqlResult<Option<String>> {
+        let conn = self.reader();
+        let result = conn.query_row(
+            "SELECT value FROM settings WHERE key = ?",
+            params![key],
+            |row| row.get(0)
+        );
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value, updated_at) VALUES (?, ?, datetime('now'))",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_setting(&self, key: &str) -> SqlResult<()> {
+        let conn = self.writer();
+        conn.execute("DELETE FROM settings WHERE key = ?", params![key])?;
+        Ok(())
+    }
+}
+
+/// Map a row selected with `INVOICE_COLUMNS`; items are loaded separately
+fn invoice_from_row(row: &rusqlite::Row) -> SqlResult<Invoice> {
+    let status_str: String = row.get(6)?;
+    let status = InvoiceStatus::from_string(&status_str).unwrap_or(InvoiceStatus::Draft);
+    let total: f64 = row.get(10)?;
+    let amount_paid: f64 = row.get(16)?;
+    let credited_invoice_id: Option<i64> = row.get(20)?;
+    let amount_credited: f64 = row.get(22)?;
+    let line_grouping: String = row.get(24)?;
+    // Invoices marked Paid before payments were recorded have no ledger entries.
+    // Nothing is owed on void invoices or on credit notes.
+    let balance_due = if status == InvoiceStatus::Paid || status == InvoiceStatus::Void || credited_invoice_id.is_some() {
+        0.0
+    } else {
+        logic::round_currency(total - amount_paid - amount_credited)
+    };
+    Ok(Invoice {
+        id: row.get(0)?,
+        invoice_number: row.get(1)?,
+        client_name: row.get(2)?,
+        client_email: row.get(3)?,
+        client_address: ClientAddress {
+            address: row.get(29)?,
+            city: row.get(30)?,
+            postal_code: row.get(31)?,
+            country_code: row.get(32)?,
+            tax_id: row.get(33)?,
+        },
+        created_date: row.get(4)?,
+        issued_date: row.get(34)?,
+        due_date: row.get(5)?,
+        status,
+        subtotal: row.get(7)?,
+        tax_rate: row.get(8)?,
+        tax_amount: row.get(9)?,
+        total,
+        notes: row.get(11)?,
+        discount: discount_from_columns(row.get(12)?, row.get(13)?),
+        discount_amount: row.get(14)?,
+        withholding_amount: row.get(15)?,
+        amount_paid,
+        balance_due,
+        overdue_at: row.get(17)?,
+        profile_id: row.get(18)?,
+        template_id: row.get(19)?,
+        credited_invoice_id,
+        credited_invoice_number: row.get(21)?,
+        amount_credited,
+        voided_at: row.get(23)?,
+        paid_date: row.get(28)?,
+        line_grouping: LineGrouping::from_string(&line_grouping).unwrap_or_default(),
+        include_timesheet: row.get(25)?,
+        quote_id: row.get(26)?,
+        quote_number: row.get(27)?,
+        items: Vec::new(),
+        taxes: Vec::new(),
+        payments: Vec::new(),
+        timesheet: Vec::new(),
+    })
+}
+
+fn business_profile_from_row(row: &rusqlite::Row) -> SqlResult<BusinessProfile> {
+    Ok(BusinessProfile {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        business_name: row.get(2)?,
+        address: row.get(3)?,
+        email: row.get(4)?,
+        phone: row.get(5)?,
+        tax_id: row.get(6)?,
+        bank_details: row.get(7)?,
+        logo_path: row.get(8)?,
+        is_default: row.get(9)?,
+        city: row.get(10)?,
+        postal_code: row.get(11)?,
+        country_code: row.get(12)?,
+        currency: row.get(13)?,
+    })
+}
+
+fn invoice_template_from_row(row: &rusqlite::Row) -> SqlResult<InvoiceTemplate> {
+    Ok(InvoiceTemplate {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        body: row.get(2)?,
+        is_builtin: false,
+    })
+}
+
+fn quote_from_row(row: &rusqlite::Row) -> SqlResult<Quote> {
+    let status: String = row.get(6)?;
+    Ok(Quote {
+        id: row.get(0)?,
+        quote_number: row.get(1)?,
+        client_name: row.get(2)?,
+        client_email: row.get(3)?,
+        created_date: row.get(4)?,
+        valid_until: row.get(5)?,
+        status: QuoteStatus::from_string(&status).unwrap_or(QuoteStatus::Draft),
+        subtotal: row.get(7)?,
+        discount: discount_from_columns(row.get(8)?, row.get(9)?),
+        discount_amount: row.get(10)?,
+        tax_amount: row.get(11)?,
+        withholding_amount: row.get(12)?,
+        total: row.get(13)?,
+        notes: row.get(14)?,
+        profile_id: row.get(15)?,
+        invoice_id: row.get(16)?,
+        invoice_number: row.get(17)?,
+        items: Vec::new(),
+        taxes: Vec::new(),
+    })
+}
+
+fn retainer_from_row(row: &rusqlite::Row) -> SqlResult<Retainer> {
+    let project_names: String = row.get(3)?;
+    let period: String = row.get(4)?;
+    let rollover: String = row.get(8)?;
+    let tax_rate_ids: String = row.get(14)?;
+    Ok(Retainer {
+        id: row.get(0)?,
+        client_name: row.get(1)?,
+        client_email: row.get(2)?,
+        project_names: serde_json::from_str(&project_names)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e)))?,
+        period: RetainerPeriod::from_string(&period).unwrap_or(RetainerPeriod::Monthly),
+        fee: row.get(5)?,
+        included_hours: row.get(6)?,
+        overage_rate: row.get(7)?,
+        rollover: RolloverPolicy::from_string(&rollover).unwrap_or(RolloverPolicy::Expire),
+        rollover_cap: row.get(9)?,
+        start_date: row.get(10)?,
+        end_date: row.get(11)?,
+        payment_terms_days: row.get(12)?,
+        profile_id: row.get(13)?,
+        tax_rate_ids: serde_json::from_str(&tax_rate_ids)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(14, rusqlite::types::Type::Text, Box::new(e)))?,
+        active: row.get(15)?,
+    })
+}
+
+/// Project names (trimmed, blanks dropped) and tax rate ids as stored JSON
+fn retainer_json_columns(retainer: &NewRetainer) -> SqlResult<(String, String)> {
+    let project_names: Vec<&str> = retainer.project_names.iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .collect();
+    let to_json = |e: serde_json::Error| rusqlite::Error::ToSqlConversionFailure(Box::new(e));
+    Ok((
+        serde_json::to_string(&project_names).map_err(to_json)?,
+        serde_json::to_string(&retainer.tax_rate_ids).map_err(to_json)?,
+    ))
+}
+
+fn bank_transaction_from_row(row: &rusqlite::Row) -> SqlResult<BankTransaction> {
+    let invoice_numbers: String = row.get(9)?;
+    Ok(BankTransaction {
+        id: row.get(0)?,
+        external_id: row.get(1)?,
+        date: row.get(2)?,
+        amount: row.get(3)?,
+        description: row.get(4)?,
+        reference: row.get(5)?,
+        counterparty: row.get(6)?,
+        ignored: row.get(7)?,
+        allocated: logic::round_currency(row.get(8)?),
+        invoice_numbers: serde_json::from_str(&invoice_numbers).unwrap_or_default(),
+        imported_at: row.get(10)?,
+    })
+}
+
+fn reminder_schedule_from_row(row: &rusqlite::Row) -> SqlResult<ReminderSchedule> {
+    let offsets: String = row.get(2)?;
+    let channel: String = row.get(3)?;
+    Ok(ReminderSchedule {
+        id: row.get(0)?,
+        client_name: row.get(1)?,
+        offsets: serde_json::from_str(&offsets).unwrap_or_default(),
+        channel: ReminderChannel::from_string(&channel).unwrap_or(ReminderChannel::Notification),
+        subject_template: row.get(4)?,
+        body_template: row.get(5)?,
+        active: row.get(6)?,
+    })
+}
+
+/// Reminder offsets as stored JSON: ascending, without duplicates
+fn expense_from_row(row: &rusqlite::Row) -> SqlResult<Expense> {
+    let recurrence: String = row.get(8)?;
+    let receipt_paths: String = row.get(10)?;
+    Ok(Expense {
+        id: row.get(0)?,
+        date: row.get(1)?,
+        amount: row.get(2)?,
+        description: row.get(3)?,
+        category: row.get(4)?,
+        is_business: row.get(5)?,
+        client_name: row.get(6)?,
+        project_name: row.get(7)?,
+        recurrence: ExpenseRecurrence::from_string(&recurrence).unwrap_or_default(),
+        end_date: row.get(9)?,
+        receipt_paths: serde_json::from_str(&receipt_paths).unwrap_or_default(),
+        notes: row.get(11)?,
+        created_at: row.get(12)?,
+    })
+}
+
+fn reminder_offsets_json(offsets: &[i64]) -> SqlResult<String> {
+    let mut offsets = offsets.to_vec();
+    offsets.sort_unstable();
+    offsets.dedup();
+    serde_json::to_string(&offsets).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+fn receipt_paths_json(paths: &[String]) -> SqlResult<String> {
+    let paths: Vec<&str> = paths.iter().map(|p| p.trim()).collect();
+    serde_json::to_string(&paths).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+fn parse_date(date: &str) -> SqlResult<chrono::NaiveDate> {
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| invalid_input(format!("Invalid date: {}", date)))
+}
+
+/// The business profile an invoice is issued under, as stored in `seller_snapshot`
+fn seller_snapshot(conn: &Connection, profile_id: Option<i64>) -> SqlResult<Option<String>> {
+    let profile = match profile_id {
+        Some(id) => conn.query_row(
+            &format!("SELECT {} FROM business_profiles WHERE id = ?", BUSINESS_PROFILE_COLUMNS),
+            params![id],
+            business_profile_from_row,
+        ).optional()?,
+        None => None,
+    };
+    let profile = match profile {
+        Some(profile) => Some(profile),
+        None => conn.query_row(
+            &format!("SELECT {} FROM business_profiles WHERE is_default = 1", BUSINESS_PROFILE_COLUMNS),
+            [],
+            business_profile_from_row,
+        ).optional()?,
+    };
+    profile
+        .map(|p| serde_json::to_string(&p).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e))))
+        .transpose()
+}
+
+/// Error for a value that can't be stored, shown to the user as `message`
+fn invalid_input(message: String) -> rusqlite::Error {
+    rusqlite::Error::ToSqlConversionFailure(message.into())
+}
+
+fn format_date(date: chrono::NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+fn discount_from_columns(discount_type: Option<String>, value: Option<f64>) -> Option<Discount> {
+    let discount_type = DiscountType::from_string(&discount_type?).ok()?;
+    Some(Discount { discount_type, value: value.unwrap_or(0.0) })
+}
+
+fn discount_to_columns(discount: Option<&Discount>) -> (Option<String>, Option<f64>) {
+    match discount {
+        Some(d) => (Some(d.discount_type.to_string()), Some(d.value)),
+        None => (None, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Path of a temporary database, removed with its WAL files on drop
+    struct TempPath(PathBuf);
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut path = self.0.clone().into_os_string();
+                path.push(suffix);
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    fn temp_path() -> TempPath {
+        TempPath(std::env::temp_dir().join(format!("chrono-test-{}.db", uuid::Uuid::new_v4())))
+    }
+
+    /// Database on a temporary file; `db` drops first, so the file is closed
+    /// before it is removed
+    struct TestDb {
+        db: Database,
+        _path: TempPath,
+    }
+
+    impl std::ops::Deref for TestDb {
+        type Target = Database;
+
+        fn deref(&self) -> &Database {
+            &self.db
+        }
+    }
+
+    fn test_db() -> TestDb {
+        let path = temp_path();
+        TestDb { db: Database::new(path.0.clone()).unwrap(), _path: path }
+    }
+
+    fn add_hourly_session(db: &Database, date: &str, project: &str, hours: f64, rate: f64) -> i64 {
+        db.add_session(NewSession {
+            session_type_id: 1,
+            date: date.to_string(),
+            project_name: project.to_string(),
+            hours,
+            description: None,
+            pay_type: Some(PayType::Hourly),
+            hourly_rate: Some(rate),
+            fixed_amount: None,
+        }).unwrap()
+    }
+
+    fn rollup_totals(db: &Database) -> (i64, f64, f64) {
+        db.reader().query_row(
+            "SELECT SUM(session_count), SUM(hours), SUM(pay) FROM daily_rollups",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        ).unwrap()
+    }
+
+    fn session_query(sort_by: SessionSortKey, sort_direction: SortDirection, limit: u32) -> SessionQuery {
+        SessionQuery {
+            filter: SessionFilter::default(),
+            sort_by: Some(sort_by),
+            sort_direction: Some(sort_direction),
+            cursor: None,
+            limit: Some(limit),
+        }
+    }
+
+    fn new_invoice(client: &str, session_ids: Vec<i64>, items: Vec<NewInvoiceItem>) -> NewInvoice {
+        NewInvoice {
+            client_name: client.to_string(),
+            client_email: None,
+            client_address: ClientAddress::default(),
+            due_date: "2099-01-31".to_string(),
+            tax_rate: None,
+            notes: None,
+            session_ids,
+            items,
+            discount: None,
+            tax_rate_ids: Vec::new(),
+            profile_id: None,
+            template_id: None,
+            line_grouping: LineGrouping::PerSession,
+            include_timesheet: false,
+        }
+    }
+
+    fn manual_item(description: &str, quantity: f64, price: f64) -> NewInvoiceItem {
+        NewInvoiceItem {
+            kind: InvoiceItemKind::Manual,
+            description: description.to_string(),
+            hours: quantity,
+            unit: None,
+            rate: price,
+            discount: None,
+            taxable: true,
+            tax_category: None,
+        }
+    }
+
+    fn add_tax(db: &Database, name: &str, rate: f64, kind: TaxKind) -> i64 {
+        db.add_tax_rate(&NewTaxRate { name: name.to_string(), rate, kind, category: None, note: None }).unwrap()
+    }
+
+    fn count(db: &Database, sql: &str) -> i64 {
+        db.reader().query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    /// Makes every statement of `kind` on `table` fail, as a disk or constraint error would
+    fn inject_fault(db: &Database, kind: &str, table: &str) {
+        db.writer().execute_batch(&format!(
+            "CREATE TRIGGER injected_fault BEFORE {} ON {} BEGIN SELECT RAISE(ABORT, 'injected fault'); END;",
+            kind, table
+        )).unwrap();
+    }
+
+    fn clear_fault(db: &Database) {
+        db.writer().execute_batch("DROP TRIGGER injected_fault").unwrap();
+    }
+
+    fn add_suggestion(db: &Database) -> i64 {
+        let conn = db.writer();
+        conn.execute(
+            "INSERT INTO activity_suggestions (date, app_name, window_title, duration_minutes, start_time, end_time)
+             VALUES ('2024-03-01', 'Editor', 'main.rs', 90, '09:00', '10:30')",
+            [],
+        ).unwrap();
+        conn.last_insert_rowid()
+    }
+
+    /// Ids of every page of `query`, following the cursor
+    fn page_through(db: &Database, mut query: SessionQuery) -> Vec<i64> {
+        let mut ids = Vec::new();
+        loop {
+            let page = db.query_sessions(&query).unwrap();
+            assert!(page.sessions.len() <= query.limit.unwrap() as usize);
+            ids.extend(page.sessions.iter().map(|s| s.id));
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => return ids,
+            }
+        }
+    }
+
+    #[test]
+    fn session_pages_return_every_row_once_in_order() {
+        let db = test_db();
+        // Few distinct values, so most rows tie on the sort key
+        for i in 0..23 {
+            let project = ["Acme", "Beta", "Core"][i % 3];
+            let date = format!("2024-03-0{}", 1 + i % 4);
+            add_hourly_session(&db, &date, project, [1.0, 2.5][i % 2], [40.0, 50.0][i % 5 / 3]);
+        }
+        let sort_keys = [
+            SessionSortKey::Date,
+            SessionSortKey::Hours,
+            SessionSortKey::Pay,
+            SessionSortKey::Project,
+            SessionSortKey::SessionType,
+        ];
+        for sort_by in sort_keys {
+            for direction in [SortDirection::Asc, SortDirection::Desc] {
+                let all = db.query_sessions(&session_query(sort_by, direction, 500)).unwrap();
+                assert_eq!(all.total_count, 23);
+                assert!(all.next_cursor.is_none());
+                let expected: Vec<i64> = all.sessions.iter().map(|s| s.id).collect();
+                for limit in [1, 4, 7] {
+                    let paged = page_through(&db, session_query(sort_by, direction, limit));
+                    assert_eq!(paged, expected, "{:?} {:?} by {}", sort_by, direction, limit);
+                }
+            }
+        }
+
+        let by_hours = db.query_sessions(&session_query(SessionSortKey::Hours, SortDirection::Asc, 500)).unwrap();
+        assert!(by_hours.sessions.windows(2).all(|w| (w[0].hours, w[0].id) < (w[1].hours, w[1].id)));
+    }
+
+    #[test]
+    fn session_filters_combine_and_total_the_whole_set() {
+        let db = test_db();
+        add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let match_b = add_hourly_session(&db, "2024-03-05", "Acme", 3.0, 50.0);
+        let match_c = add_hourly_session(&db, "2024-03-09", "Acme", 4.0, 50.0);
+        add_hourly_session(&db, "2024-03-05", "Beta", 3.0, 50.0);
+        add_hourly_session(&db, "2024-03-20", "Acme", 3.0, 50.0);
+        db.writer().execute("UPDATE sessions SET description = 'Design review' WHERE id = ?", params![match_c]).unwrap();
+
+        let mut query = session_query(SessionSortKey::Date, SortDirection::Asc, 1);
+        query.filter = SessionFilter {
+            date_from: Some("2024-03-02".to_string()),
+            date_to: Some("2024-03-10".to_string()),
+            project_names: vec!["Acme".to_string()],
+            pay_types: vec![PayType::Hourly],
+            min_hours: Some(3.0),
+            ..SessionFilter::default()
+        };
+        let page = db.query_sessions(&query).unwrap();
+        assert_eq!((page.total_count, page.total_hours, page.total_pay), (2, 7.0, 350.0));
+        assert_eq!(page_through(&db, query.clone()), vec![match_b, match_c]);
+
+        query.filter.text = Some("review".to_string());
+        assert_eq!(page_through(&db, query.clone()), vec![match_c]);
+        query.filter.text = Some("%".to_string());
+        assert!(page_through(&db, query).is_empty());
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        let db = test_db();
+        add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        for (sort_by, cursor) in [
+            (SessionSortKey::Date, "2024-03-01"),
+            (SessionSortKey::Date, "2024-03-01|x"),
+            (SessionSortKey::Hours, "two|1"),
+        ] {
+            let mut query = session_query(sort_by, SortDirection::Asc, 10);
+            query.cursor = Some(cursor.to_string());
+            let error = db.query_sessions(&query).unwrap_err();
+            assert!(error.to_string().contains("Invalid cursor"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn rollups_follow_session_changes() {
+        let db = test_db();
+        let first = add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let second = add_hourly_session(&db, "2024-03-01", "Acme", 3.0, 50.0);
+        add_hourly_session(&db, "2024-03-02", "Beta", 1.0, 40.0);
+        assert_eq!(rollup_totals(&db), (3, 6.0, 290.0));
+
+        db.writer().execute("UPDATE sessions SET hours = 4, date = '2024-03-05' WHERE id = ?", params![first]).unwrap();
+        assert_eq!(rollup_totals(&db), (3, 8.0, 390.0));
+        db.delete_session(second).unwrap();
+        assert_eq!(rollup_totals(&db), (2, 5.0, 240.0));
+        let days: i64 = db.reader().query_row("SELECT COUNT(*) FROM daily_rollups", [], |row| row.get(0)).unwrap();
+        assert_eq!(days, 2);
+    }
+
+    #[test]
+    fn rollups_rebuilt_once_for_older_databases() {
+        let path = temp_path();
+        let db = Database::new(path.0.clone()).unwrap();
+        add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        add_hourly_session(&db, "2024-03-02", "Acme", 3.0, 50.0);
+        assert_eq!(rollup_totals(&db), (2, 5.0, 250.0));
+        // As left by a version without rollups
+        db.writer().execute_batch(
+            "DELETE FROM daily_rollups; DELETE FROM settings WHERE key = 'daily_rollups_version'"
+        ).unwrap();
+        drop(db);
+
+        let db = Database::new(path.0.clone()).unwrap();
+        assert_eq!(rollup_totals(&db), (2, 5.0, 250.0));
+        // Once built, the triggers maintain them and startup skips the scan
+        db.writer().execute_batch("UPDATE daily_rollups SET hours = hours + 1").unwrap();
+        drop(db);
+        let db = Database::new(path.0.clone()).unwrap();
+        assert_eq!(rollup_totals(&db), (2, 7.0, 250.0));
+    }
+
+    /// Timings on a synthetic 100k-session database:
+    /// `cargo test --release rollup_benchmark -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn rollup_benchmark_100k_sessions() {
+        let path = temp_path();
+        let db = Database::new(path.0.clone()).unwrap();
+        {
+            let mut conn = db.writer();
+            let tx = conn.transaction().unwrap();
+            let start = chrono::NaiveDate::from_ymd_opt(2016, 1, 1).unwrap();
+            for i in 0..100_000i64 {
+                let date = start + chrono::Duration::days(i % 3650);
+                tx.execute(
+                    "INSERT INTO sessions (session_type_id, date, project_name, hours, pay_type, hourly_rate)
+                     VALUES (?, ?, ?, ?, 'Hourly', 30)",
+                    params![1 + i % 2, date.format("%Y-%m-%d").to_string(), format!("Project {}", i % 25), 0.5 + (i % 16) as f64 * 0.5],
+                ).unwrap();
+            }
+            tx.execute("DELETE FROM settings WHERE key = 'daily_rollups_version'", []).unwrap();
+            tx.commit().unwrap();
+        }
+        drop(db);
+
+        let timed = |label: &str, f: &dyn Fn()| {
+            let started = Instant::now();
+            f();
+            println!("{:<28} {:?}", label, started.elapsed());
+        };
+        let started = Instant::now();
+        drop(Database::new(path.0.clone()).unwrap());
+        println!("{:<28} {:?}", "first open (rollup rebuild)", started.elapsed());
+        let started = Instant::now();
+        let db = Database::new(path.0.clone()).unwrap();
+        println!("{:<28} {:?}", "open", started.elapsed());
+        for basis in [ReportingBasis::Earned, ReportingBasis::Invoiced] {
+            timed(&format!("pay summary ({:?})", basis), &|| { db.get_pay_summary(basis).unwrap(); });
+        }
+        timed("analytics, 10 years", &|| { db.get_analytics("2016-01-01", "2025-12-31", ReportingBasis::Earned).unwrap(); });
+        timed("analytics, 1 year", &|| { db.get_analytics("2025-01-01", "2025-12-31", ReportingBasis::Earned).unwrap(); });
+        timed("daily totals, 1 year", &|| { db.get_daily_totals("2025-01-01", "2025-12-31").unwrap(); });
+        assert_eq!(rollup_totals(&db).0, 100_000);
+    }
+
+    #[test]
+    fn reads_see_committed_data_while_a_write_is_open() {
+        let db = test_db();
+        add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let mut conn = db.writer();
+        let tx = conn.transaction().unwrap();
+        tx.execute("DELETE FROM sessions", []).unwrap();
+        // The open write neither blocks nor leaks into reads
+        let page = db.query_sessions(&session_query(SessionSortKey::Date, SortDirection::Asc, 10)).unwrap();
+        assert_eq!(page.total_count, 1);
+        tx.commit().unwrap();
+        drop(conn);
+        let page = db.query_sessions(&session_query(SessionSortKey::Date, SortDirection::Asc, 10)).unwrap();
+        assert_eq!(page.total_count, 0);
+    }
+
+    #[test]
+    fn writer_rolls_back_after_a_panicked_holder() {
+        let db = test_db();
+        add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        std::thread::scope(|scope| {
+            let result = scope.spawn(|| {
+                let conn = db.writer();
+                conn.execute_batch("BEGIN; DELETE FROM sessions;").unwrap();
+                panic!("holder failed mid-transaction");
+            }).join();
+            assert!(result.is_err());
+        });
+        assert!(db.writer().is_autocommit());
+        add_hourly_session(&db, "2024-03-02", "Acme", 1.0, 50.0);
+        let page = db.query_sessions(&session_query(SessionSortKey::Date, SortDirection::Asc, 10)).unwrap();
+        assert_eq!(page.total_count, 2);
+    }
+
+    #[test]
+    fn failed_invoice_insert_leaves_no_invoice_and_no_number() {
+        let db = test_db();
+        let session = add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let sequences = "SELECT COALESCE(SUM(next_value), 0) FROM number_sequences";
+        let before = count(&db, sequences);
+
+        inject_fault(&db, "INSERT", "invoice_items");
+        assert!(db.create_invoice(new_invoice("Acme", vec![session], Vec::new())).is_err());
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM invoices"), 0);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM invoice_items"), 0);
+        assert_eq!(count(&db, sequences), before);
+
+        // The number that would have been burnt goes to the next invoice
+        clear_fault(&db);
+        let expected = db.preview_invoice_number("Acme").unwrap();
+        let id = db.create_invoice(new_invoice("Acme", vec![session], Vec::new())).unwrap();
+        let invoice = db.get_invoice(id).unwrap();
+        assert_eq!(invoice.invoice_number, expected);
+        assert_eq!(invoice.items.len(), 1);
+    }
+
+    #[test]
+    fn failed_draft_delete_keeps_invoice_and_lines() {
+        let db = test_db();
+        let session = add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let id = db.create_invoice(new_invoice("Acme", vec![session], Vec::new())).unwrap();
+
+        inject_fault(&db, "DELETE", "invoices");
+        assert!(db.delete_invoice(id).is_err());
+        assert_eq!(db.get_invoice(id).unwrap().items.len(), 1);
+    }
+
+    #[test]
+    fn failed_suggestion_accept_creates_no_session() {
+        let db = test_db();
+        let suggestion = add_suggestion(&db);
+
+        inject_fault(&db, "UPDATE", "activity_suggestions");
+        assert!(db.accept_suggestion(suggestion, 1, "Acme").is_err());
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM sessions"), 0);
+        assert_eq!(db.get_pending_suggestions().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn suggestion_accepted_only_once() {
+        let db = test_db();
+        let suggestion = add_suggestion(&db);
+        db.accept_suggestion(suggestion, 1, "Acme").unwrap();
+        assert!(matches!(db.accept_suggestion(suggestion, 1, "Acme"), Err(rusqlite::Error::QueryReturnedNoRows)));
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM sessions"), 1);
+    }
+
+    #[test]
+    fn numbering_errors_carry_their_message() {
+        let db = test_db();
+        let numbering = InvoiceNumbering { pattern: "INV-{seq:x}".to_string(), ..InvoiceNumbering::default() };
+        db.set_invoice_numbering(&numbering).unwrap();
+        let error = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 100.0)])).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown token {seq:x} in pattern");
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM invoices"), 0);
+    }
+
+    #[test]
+    fn corrupt_numbering_settings_are_an_error() {
+        let db = test_db();
+        db.writer().execute("INSERT INTO settings (key, value) VALUES ('invoice_numbering', '{\"pattern\": 3}')", []).unwrap();
+        assert!(db.get_invoice_numbering().is_err());
+        assert!(db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).is_err());
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM invoices"), 0);
+    }
+
+    #[test]
+    fn deleting_a_session_rebuilds_its_draft_line() {
+        let db = test_db();
+        let a = add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let b = add_hourly_session(&db, "2024-03-02", "Acme", 3.0, 50.0);
+        let mut new = new_invoice("Acme", vec![a, b], Vec::new());
+        new.line_grouping = LineGrouping::PerProject;
+        let id = db.create_invoice(new).unwrap();
+        assert_eq!(db.get_invoice(id).unwrap().total, 250.0);
+
+        assert_eq!(db.get_session_invoice_number(a).unwrap(), None);
+        db.delete_session(a).unwrap();
+        let invoice = db.get_invoice(id).unwrap();
+        assert_eq!(invoice.items.len(), 1);
+        assert_eq!(invoice.items[0].session_ids, vec![b]);
+        assert_eq!(invoice.items[0].hours, 3.0);
+        assert_eq!(invoice.total, 150.0);
+
+        db.delete_session(b).unwrap();
+        let invoice = db.get_invoice(id).unwrap();
+        assert!(invoice.items.is_empty());
+        assert_eq!(invoice.total, 0.0);
+    }
+
+    #[test]
+    fn sessions_on_issued_invoices_are_kept() {
+        let db = test_db();
+        let a = add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let id = db.create_invoice(new_invoice("Acme", vec![a], Vec::new())).unwrap();
+        db.update_invoice_status(id, InvoiceStatus::Sent).unwrap();
+
+        let number = db.get_invoice(id).unwrap().invoice_number;
+        assert_eq!(db.get_session_invoice_number(a).unwrap(), Some(number));
+        assert!(db.delete_session(a).is_err());
+        assert_eq!(db.get_invoice(id).unwrap().items[0].session_ids, vec![a]);
+    }
+
+    #[test]
+    fn failed_project_cache_update_keeps_the_session() {
+        let db = test_db();
+        add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let mut session = db.query_sessions(&session_query(SessionSortKey::Date, SortDirection::Asc, 1)).unwrap().sessions.remove(0);
+
+        inject_fault(&db, "INSERT", "projects");
+        session.project_name = "Beta".to_string();
+        assert!(db.update_session(&session).is_err());
+        let stored = db.query_sessions(&session_query(SessionSortKey::Date, SortDirection::Asc, 1)).unwrap().sessions.remove(0);
+        assert_eq!(stored.project_name, "Acme");
+    }
+
+    #[test]
+    fn invoice_numbers_follow_the_sequence_and_client_prefix() {
+        let db = test_db();
+        let mut numbering = InvoiceNumbering::default();
+        numbering.client_prefixes.insert("Acme".to_string(), "AC".to_string());
+        db.set_invoice_numbering(&numbering).unwrap();
+
+        assert_eq!(db.preview_invoice_number("Beta").unwrap(), "INV-0001");
+        let first = db.create_invoice(new_invoice("Beta", Vec::new(), Vec::new())).unwrap();
+        let second = db.create_invoice(new_invoice("Acme", Vec::new(), Vec::new())).unwrap();
+        assert_eq!(db.get_invoice(first).unwrap().invoice_number, "INV-0001");
+        assert_eq!(db.get_invoice(second).unwrap().invoice_number, "AC-INV-0002");
+        // Deleting an invoice does not hand its number out again
+        db.delete_invoice(second).unwrap();
+        assert_eq!(db.preview_invoice_number("Beta").unwrap(), "INV-0003");
+    }
+
+    #[test]
+    fn draft_edits_recompute_the_totals() {
+        let db = test_db();
+        let first = add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let second = add_hourly_session(&db, "2024-03-02", "Acme", 1.0, 50.0);
+        let id = db.create_invoice(new_invoice("Acme", vec![first], Vec::new())).unwrap();
+
+        assert_eq!(db.add_sessions_to_invoice(id, &[first, second]).unwrap(), 1);
+        assert_eq!(db.get_invoice(id).unwrap().total, 150.0);
+
+        let item = db.get_invoice(id).unwrap().items.remove(0);
+        db.update_invoice_item(id, &InvoiceItemUpdate {
+            id: item.id,
+            description: "Design".to_string(),
+            hours: 3.0,
+            unit: None,
+            rate: 60.0,
+            discount: None,
+            taxable: true,
+            tax_category: None,
+        }).unwrap();
+        assert_eq!(db.get_invoice(id).unwrap().total, 230.0);
+
+        db.remove_invoice_item(id, item.id).unwrap();
+        let invoice = db.get_invoice(id).unwrap();
+        assert_eq!((invoice.items.len(), invoice.total), (1, 50.0));
+        // The removed session can be billed again
+        assert_eq!(db.add_sessions_to_invoice(id, &[first]).unwrap(), 1);
+    }
+
+    #[test]
+    fn sent_invoices_are_locked() {
+        let db = test_db();
+        let session = add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let id = db.create_invoice(new_invoice("Acme", vec![session], Vec::new())).unwrap();
+        db.update_invoice_status(id, InvoiceStatus::Sent).unwrap();
+
+        let update = InvoiceDraftUpdate {
+            client_name: "Beta".to_string(),
+            client_email: None,
+            client_address: ClientAddress::default(),
+            due_date: "2099-02-28".to_string(),
+            tax_rate: None,
+            notes: None,
+            discount: None,
+            tax_rate_ids: Vec::new(),
+            profile_id: None,
+            template_id: None,
+            include_timesheet: false,
+        };
+        assert!(db.update_draft_invoice(id, &update).is_err());
+        let item = db.get_invoice(id).unwrap().items.remove(0);
+        assert!(db.remove_invoice_item(id, item.id).is_err());
+        assert!(db.update_invoice_status(id, InvoiceStatus::Draft).is_err());
+        let invoice = db.get_invoice(id).unwrap();
+        assert_eq!((invoice.client_name.as_str(), invoice.items.len()), ("Acme", 1));
+    }
+
+    #[test]
+    fn new_invoices_reuse_the_clients_last_address() {
+        let db = test_db();
+        let mut first = new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)]);
+        first.client_address = ClientAddress {
+            country_code: Some("FR".to_string()),
+            tax_id: Some("FR12345678901".to_string()),
+            ..ClientAddress::default()
+        };
+        db.create_invoice(first).unwrap();
+
+        let id = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        let address = db.get_invoice(id).unwrap().client_address;
+        assert_eq!((address.country_code.as_deref(), address.tax_id.as_deref()), (Some("FR"), Some("FR12345678901")));
+        let other = db.create_invoice(new_invoice("Beta", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        assert!(db.get_invoice(other).unwrap().client_address.is_empty());
+    }
+
+    #[test]
+    fn manual_lines_and_discounts_make_up_the_totals() {
+        let db = test_db();
+        let session = add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let mut design = manual_item("Design", 1.0, 80.0);
+        design.discount = Some(Discount { discount_type: DiscountType::Percent, value: 10.0 });
+        let mut travel = manual_item("Train ticket", 1.0, 30.0);
+        travel.kind = InvoiceItemKind::Expense;
+        travel.taxable = false;
+        let mut invoice = new_invoice("Acme", vec![session], vec![design, travel]);
+        invoice.discount = Some(Discount { discount_type: DiscountType::Fixed, value: 20.0 });
+        invoice.tax_rate = Some(10.0);
+
+        let id = db.create_invoice(invoice).unwrap();
+        let invoice = db.get_invoice(id).unwrap();
+        let amounts: Vec<f64> = invoice.items.iter().map(|i| i.amount).collect();
+        assert_eq!(amounts, vec![100.0, 72.0, 30.0]);
+        // The expense is not taxed, and the discount reduces the taxed base pro rata
+        assert_eq!((invoice.subtotal, invoice.discount_amount), (202.0, 20.0));
+        assert_eq!((invoice.tax_amount, invoice.total), (15.5, 197.5));
+    }
+
+    #[test]
+    fn invoice_taxes_are_copied_from_the_rates() {
+        let db = test_db();
+        let vat = add_tax(&db, "VAT", 20.0, TaxKind::Additive);
+        let withholding = add_tax(&db, "IRPF", 15.0, TaxKind::Withholding);
+        let mut invoice = new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 2.0, 100.0)]);
+        invoice.tax_rate_ids = vec![vat, withholding];
+        let id = db.create_invoice(invoice).unwrap();
+
+        let invoice = db.get_invoice(id).unwrap();
+        let taxes: Vec<(&str, f64)> = invoice.taxes.iter().map(|t| (t.name.as_str(), t.amount)).collect();
+        assert_eq!(taxes, vec![("VAT", 40.0), ("IRPF", 30.0)]);
+        assert_eq!((invoice.tax_amount, invoice.withholding_amount, invoice.total), (40.0, 30.0, 210.0));
+
+        // Later rate changes leave existing invoices alone
+        db.update_tax_rate(vat, &NewTaxRate { name: "VAT".to_string(), rate: 21.0, kind: TaxKind::Additive, category: None, note: None }).unwrap();
+        assert_eq!(db.get_invoice(id).unwrap().taxes[0].rate, 20.0);
+
+        let mut invoice = new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 2.0, 100.0)]);
+        invoice.tax_rate_ids = vec![999];
+        assert_eq!(db.create_invoice(invoice).unwrap_err().to_string(), "Tax rate 999 not found");
+    }
+
+    #[test]
+    fn issuing_only_moves_drafts() {
+        let db = test_db();
+        let id = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        assert_eq!(db.issue_invoice(id).unwrap().status, InvoiceStatus::Sent);
+        db.update_invoice_status(id, InvoiceStatus::Paid).unwrap();
+        assert_eq!(db.issue_invoice(id).unwrap().status, InvoiceStatus::Paid);
+        assert_eq!(db.record_invoice_email(id, "bill@acme.test", "Invoice").unwrap().status, InvoiceStatus::Paid);
+    }
+
+    #[test]
+    fn issue_date_is_recorded() {
+        let db = test_db();
+        let id = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        db.writer().execute("UPDATE invoices SET created_date = '2024-01-01' WHERE id = ?", params![id]).unwrap();
+        assert_eq!(db.get_invoice(id).unwrap().issued_date, None);
+
+        let invoice = db.issue_invoice(id).unwrap();
+        assert_eq!(invoice.issued_date, Some(logic::get_today()));
+        assert_eq!(invoice.created_date, "2024-01-01");
+        assert!(db.writer().execute("UPDATE invoices SET issued_date = '2024-01-01' WHERE id = ?", params![id]).is_err());
+    }
+
+    #[test]
+    fn emailed_drafts_are_issued_when_the_email_is_recorded() {
+        let db = test_db();
+        let id = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        let preview = db.preview_issued_invoice(id).unwrap();
+        assert_eq!((preview.status, preview.issued_date.clone()), (InvoiceStatus::Sent, Some(logic::get_today())));
+        assert_eq!(db.get_invoice(id).unwrap().status, InvoiceStatus::Draft);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM invoices WHERE seller_snapshot IS NOT NULL"), 0);
+
+        let sent = db.record_invoice_email(id, "bill@acme.test", "Invoice").unwrap();
+        assert_eq!((sent.status, sent.issued_date), (InvoiceStatus::Sent, preview.issued_date));
+        assert_eq!(db.get_invoice_history(id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn corrupt_smtp_settings_are_an_error() {
+        let db = test_db();
+        assert!(db.get_smtp_settings().unwrap().is_none());
+        db.writer().execute("INSERT INTO settings (key, value) VALUES ('smtp_settings', 'host=mail')", []).unwrap();
+        assert!(db.get_smtp_settings().is_err());
+    }
+
+    #[test]
+    fn bank_matches_never_overpay() {
+        let db = test_db();
+        let id = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        db.update_invoice_status(id, InvoiceStatus::Sent).unwrap();
+        db.import_bank_transactions(&[NewBankTransaction {
+            external_id: "tx-1".to_string(),
+            date: "2024-04-05".to_string(),
+            amount: 500.0,
+            description: "Acme".to_string(),
+            reference: None,
+            counterparty: None,
+        }]).unwrap();
+        let transaction_id = db.get_bank_transactions(true).unwrap()[0].id;
+
+        assert!(db.confirm_bank_match(transaction_id, id, Some(200.0)).is_err());
+        db.confirm_bank_match(transaction_id, id, None).unwrap();
+        assert_eq!(db.get_invoice(id).unwrap().status, InvoiceStatus::Paid);
+        assert!(db.confirm_bank_match(transaction_id, id, None).is_err());
+        assert_eq!(db.get_bank_transaction(transaction_id).unwrap().unallocated(), 350.0);
+    }
+
+    fn payment(amount: f64) -> NewInvoicePayment {
+        NewInvoicePayment {
+            payment_date: "2024-03-01".to_string(),
+            amount,
+            method: None,
+            reference: None,
+            notes: None,
+        }
+    }
+
+    #[test]
+    fn payments_move_the_status_and_balance() {
+        let db = test_db();
+        let id = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 2.0, 100.0)])).unwrap();
+        db.update_invoice_status(id, InvoiceStatus::Sent).unwrap();
+
+        db.record_payment(id, &payment(50.0)).unwrap();
+        let invoice = db.get_invoice(id).unwrap();
+        assert_eq!((invoice.status, invoice.amount_paid, invoice.balance_due), (InvoiceStatus::PartiallyPaid, 50.0, 150.0));
+
+        let rest = db.record_payment(id, &payment(150.0)).unwrap();
+        let invoice = db.get_invoice(id).unwrap();
+        assert_eq!((invoice.status, invoice.balance_due, invoice.payments.len()), (InvoiceStatus::Paid, 0.0, 2));
+
+        db.delete_payment(id, rest).unwrap();
+        assert_eq!(db.get_invoice(id).unwrap().status, InvoiceStatus::PartiallyPaid);
+    }
+
+    #[test]
+    fn paid_invoices_stay_paid() {
+        let db = test_db();
+        let id = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        db.update_invoice_status(id, InvoiceStatus::Paid).unwrap();
+        let invoice = db.get_invoice(id).unwrap();
+        assert_eq!(invoice.status, InvoiceStatus::Paid);
+        assert_eq!((invoice.amount_paid, invoice.balance_due), (150.0, 0.0));
+        assert!(invoice.paid_date.is_some());
+
+        for status in [InvoiceStatus::Sent, InvoiceStatus::PartiallyPaid, InvoiceStatus::Overdue, InvoiceStatus::Draft] {
+            assert!(db.update_invoice_status(id, status).is_err());
+        }
+        assert_eq!(db.get_invoice(id).unwrap().status, InvoiceStatus::Paid);
+    }
+
+    #[test]
+    fn marking_paid_settles_the_remaining_balance() {
+        let db = test_db();
+        let id = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        db.update_invoice_status(id, InvoiceStatus::Sent).unwrap();
+        db.record_payment(id, &NewInvoicePayment {
+            payment_date: "2024-03-01".to_string(),
+            amount: 100.0,
+            method: None,
+            reference: None,
+            notes: None,
+        }).unwrap();
+        assert_eq!(db.get_invoice(id).unwrap().status, InvoiceStatus::PartiallyPaid);
+        assert!(db.update_invoice_status(id, InvoiceStatus::Sent).is_err());
+
+        db.update_invoice_status(id, InvoiceStatus::Paid).unwrap();
+        let invoice = db.get_invoice(id).unwrap();
+        assert_eq!(invoice.status, InvoiceStatus::Paid);
+        assert_eq!(invoice.payments.iter().map(|p| p.amount).collect::<Vec<_>>(), vec![100.0, 50.0]);
+    }
+
+    #[test]
+    fn issued_invoices_keep_their_seller() {
+        let db = test_db();
+        let profile = db.add_business_profile(&business_profile("Old Name GmbH")).unwrap();
+        let id = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        db.update_invoice_status(id, InvoiceStatus::Sent).unwrap();
+
+        db.update_business_profile(profile, &business_profile("New Name GmbH")).unwrap();
+        let invoice = db.get_invoice(id).unwrap();
+        assert_eq!(db.get_invoice_profile(&invoice).unwrap().unwrap().business_name, "Old Name GmbH");
+
+        db.delete_business_profile(profile).unwrap();
+        let invoice = db.get_invoice(id).unwrap();
+        assert_eq!(invoice.profile_id, None);
+        assert_eq!(db.get_invoice_profile(&invoice).unwrap().unwrap().business_name, "Old Name GmbH");
+        assert!(db.writer().execute("UPDATE invoices SET seller_snapshot = NULL WHERE id = ?", params![id]).is_err());
+        assert!(db.writer().execute("UPDATE invoices SET withholding_amount = 10 WHERE id = ?", params![id]).is_err());
+    }
+
+    #[test]
+    fn outdated_lock_trigger_is_replaced() {
+        let path = temp_path();
+        let db = Database::new(path.0.clone()).unwrap();
+        let id = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        db.update_invoice_status(id, InvoiceStatus::Sent).unwrap();
+        db.writer().execute_batch(
+            "DROP TRIGGER trg_invoices_lock_content;
+             CREATE TRIGGER trg_invoices_lock_content BEFORE UPDATE OF total ON invoices
+             WHEN OLD.status <> 'Draft'
+             BEGIN
+                 SELECT RAISE(ABORT, 'Invoice is locked: only Draft invoices can be edited');
+             END;"
+        ).unwrap();
+        assert!(db.writer().execute("UPDATE invoices SET discount_amount = 5 WHERE id = ?", params![id]).is_ok());
+        drop(db);
+
+        let db = Database::new(path.0.clone()).unwrap();
+        assert!(db.writer().execute("UPDATE invoices SET discount_amount = 0 WHERE id = ?", params![id]).is_err());
+    }
+
+    #[test]
+    fn payments_need_an_issued_invoice_and_fit_its_balance() {
+        let db = test_db();
+        let id = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        assert!(db.record_payment(id, &payment(50.0)).is_err());
+
+        db.update_invoice_status(id, InvoiceStatus::Sent).unwrap();
+        let error = db.record_payment(id, &payment(150.01)).unwrap_err();
+        assert!(error.to_string().contains("exceeds the balance due of 150.00"), "{}", error);
+        db.record_payment(id, &payment(100.0)).unwrap();
+        assert!(db.record_payment(id, &payment(60.0)).is_err());
+        db.record_payment(id, &payment(50.0)).unwrap();
+        assert_eq!(db.get_invoice(id).unwrap().status, InvoiceStatus::Paid);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM invoice_payments"), 2);
+
+        let other = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        db.update_invoice_status(other, InvoiceStatus::Sent).unwrap();
+        let other_item = db.get_invoice(other).unwrap().items[0].id;
+        let note = db.create_credit_note(other, &[CreditedLine { item_id: other_item, quantity: 1.0 }], None, false).unwrap();
+        assert!(db.record_payment(note, &payment(10.0)).is_err());
+        assert_eq!(db.get_invoice(other).unwrap().status, InvoiceStatus::Void);
+        assert!(db.record_payment(other, &payment(10.0)).is_err());
+    }
+
+    fn issued_invoice_due(db: &Database, due_date: &str) -> i64 {
+        let mut invoice = new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)]);
+        invoice.due_date = due_date.to_string();
+        let id = db.create_invoice(invoice).unwrap();
+        db.update_invoice_status(id, InvoiceStatus::Sent).unwrap();
+        id
+    }
+
+    #[test]
+    fn overdue_check_moves_unpaid_invoices_past_due() {
+        let db = test_db();
+        let sent = issued_invoice_due(&db, "2024-03-01");
+        let partly_paid = issued_invoice_due(&db, "2024-03-01");
+        db.record_payment(partly_paid, &payment(50.0)).unwrap();
+        let paid = issued_invoice_due(&db, "2024-03-01");
+        db.record_payment(paid, &payment(150.0)).unwrap();
+        let not_due = issued_invoice_due(&db, "2024-03-02");
+        let credited = issued_invoice_due(&db, "2024-03-01");
+        let item_id = db.get_invoice(credited).unwrap().items[0].id;
+        let note = db.create_credit_note(credited, &[CreditedLine { item_id, quantity: 0.5 }], None, false).unwrap();
+        let mut draft = new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)]);
+        draft.due_date = "2024-03-01".to_string();
+        let draft = db.create_invoice(draft).unwrap();
+
+        let moved = db.mark_overdue_invoices("2024-03-02").unwrap();
+        assert_eq!(moved.iter().map(|i| i.id).collect::<Vec<_>>(), vec![sent, partly_paid, credited]);
+        assert!(moved.iter().all(|i| i.status == InvoiceStatus::Overdue && i.overdue_at.is_some()));
+        assert_eq!(db.get_invoice(note).unwrap().status, InvoiceStatus::Sent);
+        assert_eq!(db.get_invoice(paid).unwrap().status, InvoiceStatus::Paid);
+        assert_eq!(db.get_invoice(not_due).unwrap().status, InvoiceStatus::Sent);
+        assert!(db.mark_overdue_invoices("2024-03-02").unwrap().is_empty());
+        let moved = db.mark_overdue_invoices("2099-01-01").unwrap();
+        assert_eq!(moved.iter().map(|i| i.id).collect::<Vec<_>>(), vec![not_due]);
+        assert_eq!(db.get_invoice(draft).unwrap().status, InvoiceStatus::Draft);
+
+        db.record_payment(sent, &payment(50.0)).unwrap();
+        assert_eq!(db.get_invoice(sent).unwrap().status, InvoiceStatus::Overdue);
+        db.record_payment(sent, &payment(100.0)).unwrap();
+        assert_eq!(db.get_invoice(sent).unwrap().status, InvoiceStatus::Paid);
+    }
+
+    #[test]
+    fn invoice_pdf_prints_the_stored_totals() {
+        use crate::export::{format_money, pdf};
+        let db = test_db();
+        let mut invoice = new_invoice("Acme", Vec::new(), vec![
+            manual_item("Design", 3.0, 333.33),
+            NewInvoiceItem { discount: Some(Discount { discount_type: DiscountType::Percent, value: 15.0 }), ..manual_item("Build", 7.5, 95.0) },
+        ]);
+        invoice.discount = Some(Discount { discount_type: DiscountType::Fixed, value: 50.0 });
+        invoice.tax_rate_ids = vec![
+            add_tax(&db, "VAT", 21.0, TaxKind::Additive),
+            add_tax(&db, "IRPF", 15.0, TaxKind::Withholding),
+        ];
+        let id = db.create_invoice(invoice).unwrap();
+        db.update_invoice_status(id, InvoiceStatus::Sent).unwrap();
+        db.record_payment(id, &payment(500.0)).unwrap();
+        let invoice = db.get_invoice(id).unwrap();
+
+        let bytes = pdf::render_invoice(&invoice, None, None).unwrap();
+        let doc = printpdf::lopdf::Document::load_mem(&bytes).unwrap();
+        let text = doc.extract_text(&doc.get_pages().keys().copied().collect::<Vec<_>>()).unwrap();
+        let mut expected = vec![
+            format_money(invoice.subtotal),
+            format!("-{}", format_money(invoice.discount_amount)),
+            format_money(invoice.total),
+            format!("-{}", format_money(invoice.amount_paid)),
+            format_money(invoice.balance_due),
+        ];
+        expected.extend(invoice.items.iter().map(|item| format_money(item.amount)));
+        expected.extend(invoice.taxes.iter().map(|tax| match tax.kind {
+            TaxKind::Withholding => format!("-{}", format_money(tax.amount)),
+            _ => format_money(tax.amount),
+        }));
+        for amount in expected {
+            assert!(text.contains(&amount), "{} missing from\n{}", amount, text);
+        }
+    }
+
+
+    fn business_profile(business_name: &str) -> NewBusinessProfile {
+        NewBusinessProfile {
+            name: "Main".to_string(),
+            business_name: business_name.to_string(),
+            address: None,
+            city: None,
+            postal_code: None,
+            country_code: None,
+            email: None,
+            phone: None,
+            tax_id: Some("DE123456789".to_string()),
+            bank_details: None,
+            logo_path: None,
+            currency: None,
+            is_default: false,
+        }
+    }
+
+    #[test]
+    fn one_business_profile_is_always_the_default() {
+        let db = test_db();
+        let first = db.add_business_profile(&business_profile("First GmbH")).unwrap();
+        assert_eq!(db.get_default_business_profile().unwrap().unwrap().id, first);
+
+        let second = db.add_business_profile(&NewBusinessProfile { is_default: true, ..business_profile("Second GmbH") }).unwrap();
+        let third = db.add_business_profile(&business_profile("Third GmbH")).unwrap();
+        assert_eq!(db.get_default_business_profile().unwrap().unwrap().id, second);
+        assert_eq!(db.get_all_business_profiles().unwrap().iter().filter(|p| p.is_default).count(), 1);
+
+        db.update_business_profile(second, &business_profile("Second GmbH")).unwrap();
+        assert_eq!(db.get_default_business_profile().unwrap().unwrap().id, second);
+
+        db.delete_business_profile(second).unwrap();
+        assert_eq!(db.get_default_business_profile().unwrap().unwrap().id, first);
+        db.delete_business_profile(first).unwrap();
+        assert_eq!(db.get_default_business_profile().unwrap().unwrap().id, third);
+    }
+
+
+    #[test]
+    fn credit_notes_settle_and_voiding_restores_the_balance() {
+        let db = test_db();
+        let id = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 2.0, 150.0)])).unwrap();
+        db.update_invoice_status(id, InvoiceStatus::Sent).unwrap();
+        let item_id = db.get_invoice(id).unwrap().items[0].id;
+
+        let partial = db.create_credit_note(id, &[CreditedLine { item_id, quantity: 1.0 }], None, false).unwrap();
+        let note = db.get_invoice(partial).unwrap();
+        assert!(note.is_credit_note());
+        assert_eq!(note.status, InvoiceStatus::Sent);
+        let invoice = db.get_invoice(id).unwrap();
+        assert_eq!((invoice.amount_credited, invoice.balance_due, invoice.status), (150.0, 150.0, InvoiceStatus::Sent));
+
+        db.void_invoice(partial, false).unwrap();
+        let invoice = db.get_invoice(id).unwrap();
+        assert_eq!(db.get_invoice(partial).unwrap().status, InvoiceStatus::Void);
+        assert_eq!((invoice.amount_credited, invoice.balance_due), (0.0, 300.0));
+
+        db.create_credit_note(id, &[CreditedLine { item_id, quantity: 2.0 }], None, false).unwrap();
+        assert_eq!(db.get_invoice(id).unwrap().status, InvoiceStatus::Void);
+        assert_eq!(db.get_credit_notes(id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn credit_notes_never_exceed_what_is_left() {
+        let db = test_db();
+        let id = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 2.0, 150.0)])).unwrap();
+        db.update_invoice_status(id, InvoiceStatus::Sent).unwrap();
+        let item_id = db.get_invoice(id).unwrap().items[0].id;
+        db.create_credit_note(id, &[CreditedLine { item_id, quantity: 1.5 }], None, false).unwrap();
+
+        assert!(db.create_credit_note(id, &[CreditedLine { item_id, quantity: 1.0 }], None, false).is_err());
+        let rest = db.create_credit_note(id, &[], None, false).unwrap();
+        assert_eq!(db.get_invoice(rest).unwrap().items[0].hours, 0.5);
+        assert!(db.create_credit_note(id, &[], None, false).is_err());
+        assert_eq!(db.get_credit_notes(id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn only_unpaid_uncredited_invoices_can_be_voided() {
+        let db = test_db();
+        let draft = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        assert!(db.void_invoice(draft, false).is_err());
+
+        let paid = issued_invoice_due(&db, "2099-01-31");
+        db.record_payment(paid, &payment(10.0)).unwrap();
+        assert!(db.void_invoice(paid, false).is_err());
+
+        let credited = issued_invoice_due(&db, "2099-01-31");
+        let item_id = db.get_invoice(credited).unwrap().items[0].id;
+        let note = db.create_credit_note(credited, &[CreditedLine { item_id, quantity: 0.5 }], None, false).unwrap();
+        let error = db.void_invoice(credited, false).unwrap_err();
+        assert!(error.to_string().contains("void the credit note first"), "{}", error);
+
+        db.void_invoice(note, false).unwrap();
+        assert!(db.void_invoice(note, false).is_err());
+        db.void_invoice(credited, false).unwrap();
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM invoices WHERE status = 'Void'"), 2);
+        assert_eq!(db.get_invoice(draft).unwrap().status, InvoiceStatus::Draft);
+    }
+
+    #[test]
+    fn only_drafts_can_be_deleted() {
+        let db = test_db();
+        let a = add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let id = db.create_invoice(new_invoice("Acme", vec![a], Vec::new())).unwrap();
+        db.update_invoice_status(id, InvoiceStatus::Sent).unwrap();
+        let item_id = db.get_invoice(id).unwrap().items[0].id;
+        let note = db.create_credit_note(id, &[CreditedLine { item_id, quantity: 1.0 }], None, false).unwrap();
+
+        assert!(db.delete_invoice(id).is_err());
+        assert!(db.delete_invoice(note).is_err());
+        assert!(db.writer().execute("DELETE FROM invoices WHERE id = ?", params![id]).is_err());
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM invoice_items"), 2);
+
+        let draft = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        db.delete_invoice(draft).unwrap();
+        assert!(db.get_invoice(draft).is_err());
+    }
+
+    #[test]
+    fn voiding_a_credit_note_bills_its_sessions_again() {
+        let db = test_db();
+        let a = add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let b = add_hourly_session(&db, "2024-03-02", "Acme", 2.0, 50.0);
+        let id = db.create_invoice(new_invoice("Acme", vec![a, b], Vec::new())).unwrap();
+        db.update_invoice_status(id, InvoiceStatus::Sent).unwrap();
+        let item_id = db.get_invoice(id).unwrap().items[0].id;
+        let note = db.create_credit_note(id, &[CreditedLine { item_id, quantity: 2.0 }], None, true).unwrap();
+        assert_eq!(db.get_invoice(id).unwrap().balance_due, 100.0);
+        assert_eq!(db.get_uninvoiced_sessions().unwrap().iter().map(|s| s.id).collect::<Vec<_>>(), vec![a]);
+
+        db.void_invoice(note, false).unwrap();
+        assert_eq!(db.get_invoice(id).unwrap().balance_due, 200.0);
+        assert!(db.get_uninvoiced_sessions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn credit_note_not_voided_once_its_sessions_are_billed_again() {
+        let db = test_db();
+        let a = add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let id = db.create_invoice(new_invoice("Acme", vec![a], Vec::new())).unwrap();
+        db.update_invoice_status(id, InvoiceStatus::Sent).unwrap();
+        let item_id = db.get_invoice(id).unwrap().items[0].id;
+        let note = db.create_credit_note(id, &[CreditedLine { item_id, quantity: 2.0 }], None, true).unwrap();
+        let rebill = db.create_invoice(new_invoice("Acme", vec![a], Vec::new())).unwrap();
+
+        assert!(db.void_invoice(note, false).is_err());
+        assert_eq!(db.get_invoice(note).unwrap().status, InvoiceStatus::Sent);
+        assert_eq!(db.get_invoice(rebill).unwrap().items[0].session_ids, vec![a]);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM invoice_items WHERE session_released = 1"), 1);
+    }
+
+    #[test]
+    fn preview_matches_the_created_invoice_and_leaves_nothing_behind() {
+        let db = test_db();
+        let a = add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let b = add_hourly_session(&db, "2024-03-02", "Acme", 1.5, 50.0);
+        let expected_number = db.preview_invoice_number("Acme").unwrap();
+
+        let preview = db.preview_invoice(new_invoice("Acme", vec![a, b], Vec::new())).unwrap();
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM invoices"), 0);
+        assert_eq!(db.get_uninvoiced_sessions().unwrap().len(), 2);
+        assert_eq!(db.preview_invoice_number("Acme").unwrap(), expected_number);
+
+        let id = db.create_invoice(new_invoice("Acme", vec![a, b], Vec::new())).unwrap();
+        let created = db.get_invoice(id).unwrap();
+        assert_eq!(created.invoice_number, preview.invoice_number);
+        assert_eq!((created.subtotal, created.total), (preview.subtotal, preview.total));
+        assert_eq!(created.items.len(), preview.items.len());
+
+        let clients = db.get_last_clients_for_projects(&["Acme".to_string()]).unwrap();
+        assert_eq!(clients, vec![("Acme".to_string(), None)]);
+        assert!(db.get_last_clients_for_projects(&["Beta".to_string()]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn projects_resolve_to_each_of_their_clients() {
+        let db = test_db();
+        let a = add_hourly_session(&db, "2024-03-01", "Alpha", 2.0, 50.0);
+        let b = add_hourly_session(&db, "2024-03-01", "Beta", 2.0, 50.0);
+        db.create_invoice(new_invoice("Acme", vec![a], Vec::new())).unwrap();
+        db.create_invoice(new_invoice("Globex", vec![b], Vec::new())).unwrap();
+
+        let projects = ["Alpha".to_string(), "Beta".to_string(), "Gamma".to_string()];
+        let names = db.get_last_clients_for_projects(&projects).unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Acme", "Globex"]);
+    }
+
+    #[test]
+    fn invoice_for_sessions_fails_when_one_was_billed_meanwhile() {
+        let db = test_db();
+        let a = add_hourly_session(&db, "2024-03-01", "Acme", 2.0, 50.0);
+        let b = add_hourly_session(&db, "2024-03-02", "Acme", 2.0, 50.0);
+        db.create_invoice(new_invoice("Acme", vec![b], Vec::new())).unwrap();
+
+        assert!(db.create_invoice_for_sessions(new_invoice("Acme", vec![a, b], Vec::new())).is_err());
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM invoices"), 1);
+        let invoice = db.create_invoice_for_sessions(new_invoice("Acme", vec![a], Vec::new())).unwrap();
+        assert_eq!(invoice.items[0].session_ids, vec![a]);
+    }
+
+    fn monthly_retainer(client: &str, project: &str) -> NewRetainer {
+        NewRetainer {
+            client_name: client.to_string(),
+            client_email: None,
+            project_names: vec![project.to_string()],
+            period: RetainerPeriod::Monthly,
+            fee: 1000.0,
+            included_hours: 10.0,
+            overage_rate: 120.0,
+            rollover: RolloverPolicy::Expire,
+            rollover_cap: None,
+            start_date: "2024-01-01".to_string(),
+            end_date: None,
+            payment_terms_days: 14,
+            profile_id: None,
+            tax_rate_ids: Vec::new(),
+            active: true,
+        }
+    }
+
+    #[test]
+    fn retainers_bill_each_closed_period_once() {
+        let db = test_db();
+        let retainer = db.add_retainer(&monthly_retainer("Acme", "Acme")).unwrap();
+        add_hourly_session(&db, "2024-01-10", "Acme", 12.0, 50.0);
+        add_hourly_session(&db, "2024-03-05", "Acme", 1.0, 50.0);
+
+        let invoices = db.bill_due_retainers("2024-03-15").unwrap().invoices;
+        assert_eq!(invoices.len(), 2);
+        let january: Vec<(f64, f64)> = invoices[0].items.iter().map(|i| (i.hours, i.amount)).collect();
+        assert_eq!(january, vec![(1.0, 1000.0), (2.0, 240.0)]);
+        assert_eq!(invoices[1].total, 1000.0);
+        assert!(db.get_uninvoiced_sessions().unwrap().iter().all(|s| s.date.starts_with("2024-03")));
+
+        assert!(db.bill_due_retainers("2024-03-15").unwrap().invoices.is_empty());
+        let usage = db.get_retainer_usage(retainer, "2024-03-15").unwrap();
+        assert_eq!(usage.iter().map(|u| u.period_start.as_str()).collect::<Vec<_>>(), vec!["2024-01-01", "2024-02-01", "2024-03-01"]);
+    }
+
+    #[test]
+    fn late_sessions_in_billed_retainer_periods_are_covered() {
+        let db = test_db();
+        db.add_retainer(&monthly_retainer("Acme", "Acme")).unwrap();
+        let billing = db.bill_due_retainers("2024-02-15").unwrap();
+        assert_eq!(billing.invoices.len(), 1);
+
+        let late = add_hourly_session(&db, "2024-01-20", "Acme", 3.0, 50.0);
+        let current = add_hourly_session(&db, "2024-02-10", "Acme", 2.0, 50.0);
+        let uninvoiced = db.get_uninvoiced_sessions().unwrap().iter().map(|s| s.id).collect::<Vec<_>>();
+        assert_eq!(uninvoiced, vec![current]);
+        let id = db.create_invoice(new_invoice("Acme", vec![late, current], Vec::new())).unwrap();
+        assert_eq!(db.get_invoice(id).unwrap().items.iter().flat_map(|i| i.session_ids.clone()).collect::<Vec<_>>(), vec![current]);
+    }
+
+    #[test]
+    fn failing_retainer_does_not_hold_back_the_others() {
+        let db = test_db();
+        let broken = db.add_retainer(&monthly_retainer("Broken", "Broken")).unwrap();
+        db.add_retainer(&monthly_retainer("Acme", "Acme")).unwrap();
+        db.writer().execute_batch(&format!(
+            "CREATE TRIGGER injected_fault BEFORE INSERT ON retainer_periods WHEN NEW.retainer_id = {}
+             BEGIN SELECT RAISE(ABORT, 'injected fault'); END;",
+            broken
+        )).unwrap();
+
+        let billing = db.bill_due_retainers("2024-02-15").unwrap();
+        assert_eq!(billing.invoices.iter().map(|i| i.client_name.as_str()).collect::<Vec<_>>(), vec!["Acme"]);
+        assert_eq!(billing.failures.len(), 1);
+        assert_eq!(billing.failures[0].retainer_id, broken);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM invoices"), 1);
+
+        clear_fault(&db);
+        let billing = db.bill_due_retainers("2024-02-15").unwrap();
+        assert_eq!(billing.invoices.iter().map(|i| i.client_name.as_str()).collect::<Vec<_>>(), vec!["Broken"]);
+        assert!(billing.failures.is_empty());
+    }
+
+
+
+    #[test]
+    fn deleted_retainer_draft_is_billed_again() {
+        let db = test_db();
+        let retainer = db.add_retainer(&monthly_retainer("Acme", "Acme")).unwrap();
+        add_hourly_session(&db, "2024-02-10", "Acme", 12.0, 50.0);
+        let billing = db.bill_due_retainers("2024-03-15").unwrap();
+        let (january, february) = (billing.invoices[0].id, billing.invoices[1].id);
+
+        let error = db.delete_invoice(january).unwrap_err();
+        assert!(error.to_string().contains("latest period"), "{}", error);
+        db.delete_invoice(february).unwrap();
+        assert_eq!(db.get_retainer_usage(retainer, "2024-03-15").unwrap().len(), 2);
+
+        let billing = db.bill_due_retainers("2024-03-15").unwrap();
+        assert_eq!(billing.invoices.len(), 1);
+        let rebilled = &billing.invoices[0];
+        assert_eq!(rebilled.items.len(), 2);
+        assert_eq!(rebilled.items[1].hours, 2.0);
+        let usage = db.get_retainer_usage(retainer, "2024-03-15").unwrap();
+        assert_eq!(usage[1].period_start, "2024-02-01");
+        assert_eq!(usage[1].invoice_id, Some(rebilled.id));
+    }
+
+    #[test]
+    fn corrupt_retainer_json_is_an_error() {
+        let db = test_db();
+        let id = db.add_retainer(&monthly_retainer("Acme", "Acme")).unwrap();
+        db.writer().execute("UPDATE retainers SET project_names = 'Acme' WHERE id = ?", params![id]).unwrap();
+        assert!(db.get_retainer(id).is_err());
+        assert!(db.bill_due_retainers("2024-02-15").is_err());
+    }
+
+    fn new_quote(client: &str) -> NewQuote {
+        NewQuote {
+            client_name: client.to_string(),
+            client_email: None,
+            valid_until: "2099-01-31".to_string(),
+            notes: None,
+            items: vec![manual_item("Setup", 1.0, 150.0)],
+            discount: None,
+            tax_rate_ids: Vec::new(),
+            profile_id: None,
+        }
+    }
+
+    #[test]
+    fn accepted_quotes_become_invoices_with_their_lines_and_taxes() {
+        let db = test_db();
+        let vat = db.add_tax_rate(&NewTaxRate { name: "VAT".to_string(), rate: 20.0, kind: TaxKind::Additive, category: None, note: None }).unwrap();
+        let mut quote = new_quote("Acme");
+        quote.items.push(manual_item("Build", 4.0, 90.0));
+        quote.discount = Some(Discount { discount_type: DiscountType::Fixed, value: 10.0 });
+        quote.tax_rate_ids = vec![vat];
+        let id = db.create_quote(&quote).unwrap();
+        db.update_quote_status(id, QuoteStatus::Sent).unwrap();
+
+        let invoice_id = db.convert_quote_to_invoice(id, "2099-02-28").unwrap();
+        let (quote, invoice) = (db.get_quote(id).unwrap(), db.get_invoice(invoice_id).unwrap());
+        assert_eq!(quote.status, QuoteStatus::Accepted);
+        assert_eq!(quote.invoice_id, Some(invoice_id));
+        assert_eq!(invoice.status, InvoiceStatus::Draft);
+        assert_eq!(invoice.quote_id, Some(id));
+        assert_eq!(invoice.items.iter().map(|i| i.amount).collect::<Vec<_>>(), vec![150.0, 360.0]);
+        assert_eq!((invoice.subtotal, invoice.total), (quote.subtotal, quote.total));
+        assert_eq!(invoice.total, 600.0);
+
+        let mut quote = new_quote("Acme");
+        quote.tax_rate_ids = vec![999];
+        assert_eq!(db.create_quote(&quote).unwrap_err().to_string(), "Tax rate 999 not found");
+    }
+
+    #[test]
+    fn sent_quotes_expire_after_their_validity() {
+        let db = test_db();
+        let sent = db.create_quote(&new_quote("Acme")).unwrap();
+        db.update_quote_status(sent, QuoteStatus::Sent).unwrap();
+        let draft = db.create_quote(&new_quote("Acme")).unwrap();
+
+        assert!(db.mark_expired_quotes("2099-01-31").unwrap().is_empty());
+        let expired = db.mark_expired_quotes("2099-02-01").unwrap();
+        assert_eq!(expired.iter().map(|q| q.id).collect::<Vec<_>>(), vec![sent]);
+        assert_eq!(db.get_quote(draft).unwrap().status, QuoteStatus::Draft);
+    }
+
+    #[test]
+    fn quotes_are_converted_once() {
+        let db = test_db();
+        let id = db.create_quote(&new_quote("Acme")).unwrap();
+        assert!(db.convert_quote_to_invoice(id, "2099-02-28").is_err());
+        db.update_quote_status(id, QuoteStatus::Sent).unwrap();
+
+        let invoice_id = db.convert_quote_to_invoice(id, "2099-02-28").unwrap();
+        assert!(db.convert_quote_to_invoice(id, "2099-02-28").is_err());
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM invoices"), 1);
+        assert_eq!(db.get_quote(id).unwrap().invoice_id, Some(invoice_id));
+    }
+
+    #[test]
+    fn only_draft_quotes_can_be_deleted() {
+        let db = test_db();
+        let sent = db.create_quote(&new_quote("Acme")).unwrap();
+        db.update_quote_status(sent, QuoteStatus::Sent).unwrap();
+        assert!(db.delete_quote(sent).is_err());
+        assert!(db.get_quote(sent).is_ok());
+
+        let draft = db.create_quote(&new_quote("Acme")).unwrap();
+        db.delete_quote(draft).unwrap();
+        assert!(db.get_quote(draft).is_err());
+    }
+
+    fn reminder_schedule(client_name: Option<&str>, offsets: Vec<i64>) -> NewReminderSchedule {
+        NewReminderSchedule {
+            client_name: client_name.map(str::to_string),
+            offsets,
+            channel: ReminderChannel::Notification,
+            subject_template: "Invoice {{invoice.invoice_number}}".to_string(),
+            body_template: "Please pay".to_string(),
+            active: true,
+        }
+    }
+
+    #[test]
+    fn due_reminders_use_the_client_schedule_and_skip_logged_steps() {
+        let db = test_db();
+        db.add_reminder_schedule(&reminder_schedule(None, vec![1, 7])).unwrap();
+        let client = db.add_reminder_schedule(&reminder_schedule(Some("Acme"), vec![3])).unwrap();
+        let id = issued_invoice_due(&db, "2024-03-01");
+
+        assert!(db.get_due_reminders("2024-03-03").unwrap().is_empty());
+        let due = db.get_due_reminders("2024-03-04").unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!((due[0].invoice.id, due[0].schedule.id, due[0].offset_days), (id, client, 3));
+
+        db.record_invoice_reminder(&due[0], ReminderChannel::Notification, None).unwrap();
+        db.record_invoice_reminder(&due[0], ReminderChannel::Notification, None).unwrap();
+        assert!(db.get_due_reminders("2024-03-20").unwrap().is_empty());
+        assert_eq!(db.get_invoice_reminders(id).unwrap().len(), 1);
+    }
+
+
+    #[test]
+    fn income_counts_on_each_basis_net_of_tax() {
+        let db = test_db();
+        let today = logic::get_today();
+        let a = add_hourly_session(&db, "2024-03-01", "Acme", 4.0, 50.0);
+        add_hourly_session(&db, "2024-03-02", "Acme", 2.0, 50.0);
+        let mut invoice = new_invoice("Acme", vec![a], Vec::new());
+        invoice.tax_rate_ids = vec![
+            add_tax(&db, "Sales tax", 20.0, TaxKind::Inclusive),
+            add_tax(&db, "Withholding", 10.0, TaxKind::Withholding),
+        ];
+        let id = db.create_invoice(invoice).unwrap();
+        db.writer().execute("UPDATE invoices SET created_date = '2024-03-05' WHERE id = ?", params![id]).unwrap();
+        let total = |basis, start: &str, end: &str| db.get_income_total(basis, start, end).unwrap();
+        assert_eq!(total(ReportingBasis::Invoiced, "2000-01-01", "2099-12-31"), 0.0);
+
+        // 200 with 33.33 inclusive tax and 16.67 withheld: 166.67 is income
+        let invoice = db.issue_invoice(id).unwrap();
+        assert_eq!((invoice.tax_amount, invoice.withholding_amount, invoice.total), (33.33, 16.67, 183.33));
+        assert_eq!(total(ReportingBasis::Earned, "2024-03-01", "2024-03-31"), 300.0);
+        assert_eq!(total(ReportingBasis::Invoiced, "2024-03-01", "2024-03-31"), 0.0);
+        assert_eq!(total(ReportingBasis::Invoiced, &today, &today), 166.67);
+
+        let item_id = invoice.items[0].id;
+        db.create_credit_note(id, &[CreditedLine { item_id, quantity: 2.0 }], None, false).unwrap();
+        assert_eq!(total(ReportingBasis::Invoiced, &today, &today), 83.34);
+        assert_eq!(total(ReportingBasis::Earned, "2024-03-01", "2024-03-31"), 300.0);
+
+        let balance = db.get_invoice(id).unwrap().balance_due;
+        db.record_payment(id, &NewInvoicePayment { payment_date: "2024-04-10".to_string(), ..payment(balance) }).unwrap();
+        assert_eq!(total(ReportingBasis::Received, "2024-04-10", "2024-04-10"), 83.33);
+        assert_eq!(total(ReportingBasis::Received, &today, &today), 0.0);
+    }
+
+
+    #[test]
+    fn journal_exports_are_remembered_per_format() {
+        let db = test_db();
+        let a = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        let b = db.create_invoice(new_invoice("Acme", Vec::new(), vec![manual_item("Setup", 1.0, 150.0)])).unwrap();
+        let accounts = vec!["Assets:Bank".to_string()];
+        db.record_journal_export(JournalFormat::Beancount, &[(a, JournalEntryKind::Issued)], &accounts, false).unwrap();
+        db.record_journal_export(JournalFormat::Beancount, &[(a, JournalEntryKind::Settled), (b, JournalEntryKind::Issued)], &accounts, false).unwrap();
+        let mut exports = db.get_journal_exports(JournalFormat::Beancount).unwrap();
+        exports.sort_by_key(|(id, kind)| (*id, kind.to_string()));
+        assert_eq!(exports, vec![(a, JournalEntryKind::Issued), (a, JournalEntryKind::Settled), (b, JournalEntryKind::Issued)]);
+        assert_eq!(db.get_journal_accounts(JournalFormat::Beancount).unwrap(), accounts);
+        assert!(db.get_journal_exports(JournalFormat::Ledger).unwrap().is_empty());
+
+        // A full export starts over
+        db.record_journal_export(JournalFormat::Beancount, &[(b, JournalEntryKind::Issued)], &[], true).unwrap();
+        assert_eq!(db.get_journal_exports(JournalFormat::Beancount).unwrap(), vec![(b, JournalEntryKind::Issued)]);
+        assert!(db.get_journal_accounts(JournalFormat::Beancount).unwrap().is_empty());
+
+        db.writer().execute("INSERT INTO settings (key, value) VALUES ('accounting_settings', '{')", []).unwrap();
+        assert!(db.get_accounting_settings().is_err());
+    }
 }
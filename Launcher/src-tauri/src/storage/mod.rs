@@ -1 +1,3 @@
 pub mod db;
+pub mod pool;
+pub mod secrets;
@@ -0,0 +1,103 @@
+use rusqlite::{Connection, OpenFlags, Result as SqlResult};
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// How long a connection waits on a locked database before returning SQLITE_BUSY
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Lock a mutex, recovering the guard if a previous holder panicked.
+/// The data behind our mutexes (connections, idle lists) stays valid after a panic.
+pub fn lock_recover<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Lock the writer connection. If a previous holder panicked mid-transaction,
+/// roll that transaction back instead of propagating the poison.
+pub fn lock_writer(mutex: &Mutex<Connection>) -> MutexGuard<'_, Connection> {
+    match mutex.lock() {
+        Ok(conn) => conn,
+        Err(poisoned) => {
+            let conn = poisoned.into_inner();
+            mutex.clear_poison();
+            if !conn.is_autocommit() {
+                let _ = conn.execute_batch("ROLLBACK");
+            }
+            conn
+        }
+    }
+}
+
+/// Open the read-write connection and switch the database to WAL journaling
+pub fn open_writer(path: &Path) -> SqlResult<Connection> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    Ok(conn)
+}
+
+/// Fixed-size pool of read-only connections
+pub struct ReadPool {
+    idle: Mutex<Vec<Connection>>,
+    available: Condvar,
+}
+
+impl ReadPool {
+    pub fn open(path: &Path, size: usize) -> SqlResult<Self> {
+        let mut idle = Vec::with_capacity(size);
+        for _ in 0..size.max(1) {
+            let conn = Connection::open_with_flags(
+                path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI,
+            )?;
+            conn.busy_timeout(BUSY_TIMEOUT)?;
+            idle.push(conn);
+        }
+        Ok(ReadPool {
+            idle: Mutex::new(idle),
+            available: Condvar::new(),
+        })
+    }
+
+    /// Check out a connection, blocking until one is free
+    pub fn get(&self) -> PooledConnection<'_> {
+        let mut idle = lock_recover(&self.idle);
+        loop {
+            if let Some(conn) = idle.pop() {
+                return PooledConnection { pool: self, conn: Some(conn) };
+            }
+            idle = self.available.wait(idle).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+}
+
+/// Read connection that returns itself to the pool on drop
+pub struct PooledConnection<'a> {
+    pool: &'a ReadPool,
+    conn: Option<Connection>,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("connection taken")
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().expect("connection taken")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            lock_recover(&self.pool.idle).push(conn);
+            self.pool.available.notify_one();
+        }
+    }
+}
@@ -26,6 +26,83 @@ CREATE INDEX IF NOT EXISTS idx_sessions_date ON sessions(date);
 CREATE INDEX IF NOT EXISTS idx_sessions_type_date ON sessions(session_type_id, date);
 CREATE INDEX IF NOT EXISTS idx_sessions_project ON sessions(project_name);
 
+-- Daily rollups (date x type x project), maintained by triggers on sessions
+CREATE TABLE IF NOT EXISTS daily_rollups (
+    date TEXT NOT NULL,
+    session_type_id INTEGER NOT NULL,
+    project_name TEXT NOT NULL,
+    hours REAL NOT NULL DEFAULT 0,
+    pay REAL NOT NULL DEFAULT 0,
+    session_count INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (date, session_type_id, project_name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_daily_rollups_type ON daily_rollups(session_type_id, date);
+
+CREATE TRIGGER IF NOT EXISTS trg_sessions_rollup_insert AFTER INSERT ON sessions
+BEGIN
+    INSERT INTO daily_rollups (date, session_type_id, project_name, hours, pay, session_count)
+    VALUES (
+        NEW.date, NEW.session_type_id, NEW.project_name, NEW.hours,
+        CASE NEW.pay_type
+            WHEN 'Hourly' THEN COALESCE(NEW.hourly_rate, 0) * NEW.hours
+            WHEN 'Fixed' THEN COALESCE(NEW.fixed_amount, 0)
+            ELSE 0
+        END,
+        1
+    )
+    ON CONFLICT(date, session_type_id, project_name) DO UPDATE SET
+        hours = hours + excluded.hours,
+        pay = pay + excluded.pay,
+        session_count = session_count + 1;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_sessions_rollup_delete AFTER DELETE ON sessions
+BEGIN
+    UPDATE daily_rollups SET
+        hours = hours - OLD.hours,
+        pay = pay - CASE OLD.pay_type
+            WHEN 'Hourly' THEN COALESCE(OLD.hourly_rate, 0) * OLD.hours
+            WHEN 'Fixed' THEN COALESCE(OLD.fixed_amount, 0)
+            ELSE 0
+        END,
+        session_count = session_count - 1
+    WHERE date = OLD.date AND session_type_id = OLD.session_type_id AND project_name = OLD.project_name;
+    DELETE FROM daily_rollups
+    WHERE date = OLD.date AND session_type_id = OLD.session_type_id AND project_name = OLD.project_name
+      AND session_count <= 0;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_sessions_rollup_update AFTER UPDATE OF date, session_type_id, project_name, hours, pay_type, hourly_rate, fixed_amount ON sessions
+BEGIN
+    UPDATE daily_rollups SET
+        hours = hours - OLD.hours,
+        pay = pay - CASE OLD.pay_type
+            WHEN 'Hourly' THEN COALESCE(OLD.hourly_rate, 0) * OLD.hours
+            WHEN 'Fixed' THEN COALESCE(OLD.fixed_amount, 0)
+            ELSE 0
+        END,
+        session_count = session_count - 1
+    WHERE date = OLD.date AND session_type_id = OLD.session_type_id AND project_name = OLD.project_name;
+    DELETE FROM daily_rollups
+    WHERE date = OLD.date AND session_type_id = OLD.session_type_id AND project_name = OLD.project_name
+      AND session_count <= 0;
+    INSERT INTO daily_rollups (date, session_type_id, project_name, hours, pay, session_count)
+    VALUES (
+        NEW.date, NEW.session_type_id, NEW.project_name, NEW.hours,
+        CASE NEW.pay_type
+            WHEN 'Hourly' THEN COALESCE(NEW.hourly_rate, 0) * NEW.hours
+            WHEN 'Fixed' THEN COALESCE(NEW.fixed_amount, 0)
+            ELSE 0
+        END,
+        1
+    )
+    ON CONFLICT(date, session_type_id, project_name) DO UPDATE SET
+        hours = hours + excluded.hours,
+        pay = pay + excluded.pay,
+        session_count = session_count + 1;
+END;
+
 -- Goals table
 CREATE TABLE IF NOT EXISTS goals (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -75,20 +152,68 @@ CREATE INDEX IF NOT EXISTS idx_app_events_date ON app_events(created_at);
 
 -- ========== INVOICING TABLES ==========
 
+-- Sender details printed on invoices
+CREATE TABLE IF NOT EXISTS business_profiles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    business_name TEXT NOT NULL,
+    address TEXT,
+    city TEXT,
+    postal_code TEXT,
+    country_code TEXT,
+    email TEXT,
+    phone TEXT,
+    tax_id TEXT,
+    bank_details TEXT,
+    logo_path TEXT,
+    currency TEXT,
+    is_default INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_business_profiles_default ON business_profiles(is_default) WHERE is_default = 1;
+
+-- User-editable Handlebars templates for HTML invoices (the default one is built in)
+CREATE TABLE IF NOT EXISTS invoice_templates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    body TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
 -- Invoices table
 CREATE TABLE IF NOT EXISTS invoices (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
     invoice_number TEXT NOT NULL UNIQUE,
     client_name TEXT NOT NULL,
     client_email TEXT,
+    client_address TEXT,
+    client_city TEXT,
+    client_postal_code TEXT,
+    client_country_code TEXT,
+    client_tax_id TEXT,
     created_date TEXT NOT NULL DEFAULT (date('now')),
+    issued_date TEXT,  -- set when the invoice leaves Draft
     due_date TEXT NOT NULL,
-    status TEXT NOT NULL DEFAULT 'Draft' CHECK(status IN ('Draft', 'Sent', 'Paid', 'Overdue')),
+    status TEXT NOT NULL DEFAULT 'Draft' CHECK(status IN ('Draft', 'Sent', 'PartiallyPaid', 'Paid', 'Overdue', 'Void')),
     subtotal REAL NOT NULL DEFAULT 0,
     tax_rate REAL,
     tax_amount REAL NOT NULL DEFAULT 0,
     total REAL NOT NULL DEFAULT 0,
     notes TEXT,
+    discount_type TEXT CHECK(discount_type IN ('Percent', 'Fixed')),
+    discount_value REAL,
+    discount_amount REAL NOT NULL DEFAULT 0,
+    withholding_amount REAL NOT NULL DEFAULT 0,
+    overdue_at TEXT,
+    profile_id INTEGER REFERENCES business_profiles(id) ON DELETE SET NULL,
+    template_id INTEGER REFERENCES invoice_templates(id) ON DELETE SET NULL,
+    credited_invoice_id INTEGER REFERENCES invoices(id),  -- set on credit notes
+    voided_at TEXT,
+    paid_date TEXT,
+    line_grouping TEXT NOT NULL DEFAULT 'PerSession',
+    include_timesheet INTEGER NOT NULL DEFAULT 0,
+    seller_snapshot TEXT,  -- business profile as JSON, frozen when the invoice leaves Draft
     created_at TEXT NOT NULL DEFAULT (datetime('now'))
 );
 
@@ -101,16 +226,418 @@ CREATE TABLE IF NOT EXISTS invoice_items (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
     invoice_id INTEGER NOT NULL,
     session_id INTEGER,
+    kind TEXT NOT NULL DEFAULT 'Session' CHECK(kind IN ('Session', 'Manual', 'Expense')),
     description TEXT NOT NULL,
-    hours REAL NOT NULL DEFAULT 0,
-    rate REAL NOT NULL DEFAULT 0,
-    amount REAL NOT NULL DEFAULT 0,
+    hours REAL NOT NULL DEFAULT 0,        -- quantity for Manual/Expense lines
+    unit TEXT,
+    rate REAL NOT NULL DEFAULT 0,         -- unit price for Manual/Expense lines
+    discount_type TEXT CHECK(discount_type IN ('Percent', 'Fixed')),
+    discount_value REAL,
+    taxable INTEGER NOT NULL DEFAULT 1,
+    tax_category TEXT,
+    amount REAL NOT NULL DEFAULT 0,       -- net of the line discount
+    credited_item_id INTEGER,             -- on credit notes, the invoice line credited
+    session_released INTEGER NOT NULL DEFAULT 0, -- session credited or voided, billable again
     FOREIGN KEY (invoice_id) REFERENCES invoices(id) ON DELETE CASCADE,
-    FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE SET NULL
+    FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE SET NULL,
+    FOREIGN KEY (credited_item_id) REFERENCES invoice_items(id) ON DELETE SET NULL
 );
 
 CREATE INDEX IF NOT EXISTS idx_invoice_items_invoice ON invoice_items(invoice_id);
 
+-- Sessions billed on each line; a grouped line bills several
+CREATE TABLE IF NOT EXISTS invoice_item_sessions (
+    item_id INTEGER NOT NULL,
+    session_id INTEGER NOT NULL,
+    PRIMARY KEY (item_id, session_id),
+    FOREIGN KEY (item_id) REFERENCES invoice_items(id) ON DELETE CASCADE,
+    FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_invoice_item_sessions_session ON invoice_item_sessions(session_id);
+
+-- Payments received against invoices
+CREATE TABLE IF NOT EXISTS invoice_payments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    invoice_id INTEGER NOT NULL,
+    payment_date TEXT NOT NULL,
+    amount REAL NOT NULL CHECK(amount > 0),
+    method TEXT,
+    reference TEXT,
+    notes TEXT,
+    bank_transaction_id INTEGER REFERENCES bank_transactions(id) ON DELETE SET NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (invoice_id) REFERENCES invoices(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_invoice_payments_invoice ON invoice_payments(invoice_id);
+CREATE INDEX IF NOT EXISTS idx_invoice_payments_date ON invoice_payments(payment_date);
+
+-- Things that happened to an invoice, e.g. each time it was emailed
+CREATE TABLE IF NOT EXISTS invoice_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    invoice_id INTEGER NOT NULL,
+    event TEXT NOT NULL,
+    recipient TEXT,
+    details TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (invoice_id) REFERENCES invoices(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_invoice_history_invoice ON invoice_history(invoice_id);
+
+-- Named tax rates (VAT, withholding, inclusive sales tax, ...)
+CREATE TABLE IF NOT EXISTS tax_rates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    rate REAL NOT NULL,
+    kind TEXT NOT NULL DEFAULT 'Additive' CHECK(kind IN ('Additive', 'Withholding', 'Inclusive')),
+    category TEXT,
+    note TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Taxes applied to an invoice (snapshot of the rate, with computed base and amount)
+CREATE TABLE IF NOT EXISTS invoice_taxes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    invoice_id INTEGER NOT NULL,
+    tax_rate_id INTEGER,
+    name TEXT NOT NULL,
+    rate REAL NOT NULL,
+    kind TEXT NOT NULL CHECK(kind IN ('Additive', 'Withholding', 'Inclusive')),
+    category TEXT,
+    note TEXT,
+    base REAL NOT NULL DEFAULT 0,
+    amount REAL NOT NULL DEFAULT 0,
+    FOREIGN KEY (invoice_id) REFERENCES invoices(id) ON DELETE CASCADE,
+    FOREIGN KEY (tax_rate_id) REFERENCES tax_rates(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_invoice_taxes_invoice ON invoice_taxes(invoice_id);
+
+-- Only Draft invoices are editable; once sent, content and line items are locked
+CREATE TRIGGER IF NOT EXISTS trg_invoices_lock_content BEFORE UPDATE OF invoice_number, client_name, client_email, client_address, client_city, client_postal_code, client_country_code, client_tax_id, created_date, due_date, subtotal, tax_rate, tax_amount, total, notes, discount_type, discount_value, discount_amount, withholding_amount, template_id, line_grouping, include_timesheet ON invoices
+WHEN OLD.status <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Invoice is locked: only Draft invoices can be edited');
+END;
+
+-- Deleting a profile may still unlink it: the seller snapshot keeps its details
+CREATE TRIGGER IF NOT EXISTS trg_invoices_lock_profile BEFORE UPDATE OF profile_id ON invoices
+WHEN OLD.status <> 'Draft' AND NEW.profile_id IS NOT NULL
+BEGIN
+    SELECT RAISE(ABORT, 'Invoice is locked: only Draft invoices can be edited');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_invoices_lock_seller BEFORE UPDATE OF seller_snapshot ON invoices
+WHEN OLD.status <> 'Draft' AND OLD.seller_snapshot IS NOT NULL
+BEGIN
+    SELECT RAISE(ABORT, 'Invoice is locked: the seller details were frozen when it was issued');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_invoices_lock_issued_date BEFORE UPDATE OF issued_date ON invoices
+WHEN OLD.status <> 'Draft' AND OLD.issued_date IS NOT NULL
+BEGIN
+    SELECT RAISE(ABORT, 'Invoice is locked: only Draft invoices can be edited');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_invoices_lock_delete BEFORE DELETE ON invoices
+WHEN OLD.status <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Invoice is locked: only Draft invoices can be deleted');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_invoices_lock_status BEFORE UPDATE OF status ON invoices
+WHEN OLD.status <> 'Draft' AND NEW.status = 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Invoice is locked: a sent invoice cannot return to Draft');
+END;
+CREATE TRIGGER IF NOT EXISTS trg_invoices_lock_void BEFORE UPDATE OF status ON invoices
+WHEN OLD.status = 'Void' AND NEW.status <> 'Void'
+BEGIN
+    SELECT RAISE(ABORT, 'Invoice is void');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_invoice_items_lock_insert BEFORE INSERT ON invoice_items
+WHEN (SELECT status FROM invoices WHERE id = NEW.invoice_id) <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Invoice is locked: only Draft invoices can be edited');
+END;
+
+-- session_released stays writable: it records a later credit or void
+CREATE TRIGGER IF NOT EXISTS trg_invoice_items_lock_update BEFORE UPDATE OF invoice_id, session_id, kind, description, hours, unit, rate, discount_type, discount_value, taxable, tax_category, amount, credited_item_id ON invoice_items
+WHEN (SELECT status FROM invoices WHERE id = OLD.invoice_id) <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Invoice is locked: only Draft invoices can be edited');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_invoice_items_lock_delete BEFORE DELETE ON invoice_items
+WHEN (SELECT status FROM invoices WHERE id = OLD.invoice_id) <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Invoice is locked: only Draft invoices can be edited');
+END;
+
+-- Links are only inserted with new lines, which the item triggers already lock
+CREATE TRIGGER IF NOT EXISTS trg_invoice_item_sessions_lock_delete BEFORE DELETE ON invoice_item_sessions
+WHEN (SELECT i.status FROM invoices i JOIN invoice_items ii ON ii.invoice_id = i.id WHERE ii.id = OLD.item_id) <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Invoice is locked: only Draft invoices can be edited');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_invoice_taxes_lock_insert BEFORE INSERT ON invoice_taxes
+WHEN (SELECT status FROM invoices WHERE id = NEW.invoice_id) <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Invoice is locked: only Draft invoices can be edited');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_invoice_taxes_lock_update BEFORE UPDATE ON invoice_taxes
+WHEN (SELECT status FROM invoices WHERE id = OLD.invoice_id) <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Invoice is locked: only Draft invoices can be edited');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_invoice_taxes_lock_delete BEFORE DELETE ON invoice_taxes
+WHEN (SELECT status FROM invoices WHERE id = OLD.invoice_id) <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Invoice is locked: only Draft invoices can be edited');
+END;
+
+-- Document number sequences (never decremented, so numbers are never reused)
+CREATE TABLE IF NOT EXISTS number_sequences (
+    scope TEXT PRIMARY KEY,
+    next_value INTEGER NOT NULL DEFAULT 1
+);
+
+-- Quotes (estimates): priced like invoices, converted into one once accepted
+CREATE TABLE IF NOT EXISTS quotes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    quote_number TEXT NOT NULL UNIQUE,
+    client_name TEXT NOT NULL,
+    client_email TEXT,
+    created_date TEXT NOT NULL DEFAULT (date('now')),
+    valid_until TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'Draft' CHECK(status IN ('Draft', 'Sent', 'Accepted', 'Rejected', 'Expired')),
+    subtotal REAL NOT NULL DEFAULT 0,
+    discount_type TEXT CHECK(discount_type IN ('Percent', 'Fixed')),
+    discount_value REAL,
+    discount_amount REAL NOT NULL DEFAULT 0,
+    tax_amount REAL NOT NULL DEFAULT 0,
+    withholding_amount REAL NOT NULL DEFAULT 0,
+    total REAL NOT NULL DEFAULT 0,
+    notes TEXT,
+    profile_id INTEGER REFERENCES business_profiles(id) ON DELETE SET NULL,
+    invoice_id INTEGER REFERENCES invoices(id) ON DELETE SET NULL,  -- invoice converted into
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_quotes_status ON quotes(status);
+CREATE INDEX IF NOT EXISTS idx_quotes_invoice ON quotes(invoice_id);
+
+CREATE TABLE IF NOT EXISTS quote_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    quote_id INTEGER NOT NULL,
+    kind TEXT NOT NULL DEFAULT 'Manual' CHECK(kind IN ('Manual', 'Expense')),
+    description TEXT NOT NULL,
+    hours REAL NOT NULL DEFAULT 0,        -- quantity
+    unit TEXT,
+    rate REAL NOT NULL DEFAULT 0,         -- unit price
+    discount_type TEXT CHECK(discount_type IN ('Percent', 'Fixed')),
+    discount_value REAL,
+    taxable INTEGER NOT NULL DEFAULT 1,
+    tax_category TEXT,
+    amount REAL NOT NULL DEFAULT 0,       -- net of the line discount
+    FOREIGN KEY (quote_id) REFERENCES quotes(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_quote_items_quote ON quote_items(quote_id);
+
+-- Taxes applied to a quote (snapshot of the rate, with computed base and amount)
+CREATE TABLE IF NOT EXISTS quote_taxes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    quote_id INTEGER NOT NULL,
+    tax_rate_id INTEGER,
+    name TEXT NOT NULL,
+    rate REAL NOT NULL,
+    kind TEXT NOT NULL CHECK(kind IN ('Additive', 'Withholding', 'Inclusive')),
+    category TEXT,
+    note TEXT,
+    base REAL NOT NULL DEFAULT 0,
+    amount REAL NOT NULL DEFAULT 0,
+    FOREIGN KEY (quote_id) REFERENCES quotes(id) ON DELETE CASCADE,
+    FOREIGN KEY (tax_rate_id) REFERENCES tax_rates(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_quote_taxes_quote ON quote_taxes(quote_id);
+
+-- Like invoices, only Draft quotes are editable
+CREATE TRIGGER IF NOT EXISTS trg_quotes_lock_content BEFORE UPDATE OF quote_number, client_name, client_email, created_date, valid_until, subtotal, discount_type, discount_value, tax_amount, total, notes, profile_id ON quotes
+WHEN OLD.status <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Quote is locked: only Draft quotes can be edited');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_quote_items_lock_insert BEFORE INSERT ON quote_items
+WHEN (SELECT status FROM quotes WHERE id = NEW.quote_id) <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Quote is locked: only Draft quotes can be edited');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_quote_items_lock_update BEFORE UPDATE ON quote_items
+WHEN (SELECT status FROM quotes WHERE id = OLD.quote_id) <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Quote is locked: only Draft quotes can be edited');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_quote_items_lock_delete BEFORE DELETE ON quote_items
+WHEN (SELECT status FROM quotes WHERE id = OLD.quote_id) <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Quote is locked: only Draft quotes can be edited');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_quote_taxes_lock_insert BEFORE INSERT ON quote_taxes
+WHEN (SELECT status FROM quotes WHERE id = NEW.quote_id) <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Quote is locked: only Draft quotes can be edited');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_quote_taxes_lock_update BEFORE UPDATE ON quote_taxes
+WHEN (SELECT status FROM quotes WHERE id = OLD.quote_id) <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Quote is locked: only Draft quotes can be edited');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_quote_taxes_lock_delete BEFORE DELETE ON quote_taxes
+WHEN (SELECT status FROM quotes WHERE id = OLD.quote_id) <> 'Draft'
+BEGIN
+    SELECT RAISE(ABORT, 'Quote is locked: only Draft quotes can be edited');
+END;
+
+-- Retainer agreements: a fee per period covering some hours, overage billed hourly
+CREATE TABLE IF NOT EXISTS retainers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    client_name TEXT NOT NULL,
+    client_email TEXT,
+    project_names TEXT NOT NULL DEFAULT '[]',  -- JSON array
+    period TEXT NOT NULL CHECK(period IN ('Weekly', 'Monthly', 'Quarterly', 'Yearly')),
+    fee REAL NOT NULL DEFAULT 0,
+    included_hours REAL NOT NULL DEFAULT 0,
+    overage_rate REAL NOT NULL DEFAULT 0,
+    rollover TEXT NOT NULL DEFAULT 'Expire' CHECK(rollover IN ('Expire', 'Carry')),
+    rollover_cap REAL,
+    start_date TEXT NOT NULL,
+    end_date TEXT,
+    payment_terms_days INTEGER NOT NULL DEFAULT 14,
+    profile_id INTEGER REFERENCES business_profiles(id) ON DELETE SET NULL,
+    tax_rate_ids TEXT NOT NULL DEFAULT '[]',   -- JSON array
+    active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Closed retainer periods; a row means the period has been billed
+CREATE TABLE IF NOT EXISTS retainer_periods (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    retainer_id INTEGER NOT NULL,
+    period_start TEXT NOT NULL,
+    period_end TEXT NOT NULL,
+    included_hours REAL NOT NULL,
+    rollover_in REAL NOT NULL DEFAULT 0,
+    hours_used REAL NOT NULL DEFAULT 0,
+    overage_hours REAL NOT NULL DEFAULT 0,
+    rollover_out REAL NOT NULL DEFAULT 0,
+    invoice_id INTEGER,
+    UNIQUE (retainer_id, period_start),
+    FOREIGN KEY (retainer_id) REFERENCES retainers(id) ON DELETE CASCADE,
+    FOREIGN KEY (invoice_id) REFERENCES invoices(id) ON DELETE SET NULL
+);
+
+-- ========== PAYMENT REMINDER TABLES ==========
+
+-- Reminder schedules: one per client, plus a default with no client name
+CREATE TABLE IF NOT EXISTS reminder_schedules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    client_name TEXT,
+    offsets TEXT NOT NULL DEFAULT '[]',
+    channel TEXT NOT NULL DEFAULT 'Notification' CHECK(channel IN ('Notification', 'Email')),
+    subject_template TEXT NOT NULL,
+    body_template TEXT NOT NULL,
+    active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_reminder_schedules_client ON reminder_schedules(COALESCE(client_name, ''));
+
+-- Reminders sent, at most one per step of an invoice
+CREATE TABLE IF NOT EXISTS invoice_reminders (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    invoice_id INTEGER NOT NULL,
+    schedule_id INTEGER,
+    offset_days INTEGER NOT NULL,
+    channel TEXT NOT NULL,
+    recipient TEXT,
+    sent_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (invoice_id, offset_days),
+    FOREIGN KEY (invoice_id) REFERENCES invoices(id) ON DELETE CASCADE,
+    FOREIGN KEY (schedule_id) REFERENCES reminder_schedules(id) ON DELETE SET NULL
+);
+
+-- ========== BANK RECONCILIATION TABLES ==========
+
+-- Imported bank statement lines; payments recorded from them point back here
+CREATE TABLE IF NOT EXISTS bank_transactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    external_id TEXT NOT NULL UNIQUE,
+    date TEXT NOT NULL,
+    amount REAL NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    reference TEXT,
+    counterparty TEXT,
+    ignored INTEGER NOT NULL DEFAULT 0,
+    imported_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_bank_transactions_date ON bank_transactions(date);
+
+-- ========== ACCOUNTING EXPORT TABLES ==========
+
+-- Journal entries already written per format, so exports can continue where they left off
+CREATE TABLE IF NOT EXISTS journal_exports (
+    invoice_id INTEGER NOT NULL,
+    format TEXT NOT NULL CHECK(format IN ('Ledger', 'Beancount')),
+    kind TEXT NOT NULL CHECK(kind IN ('Issued', 'Settled', 'Voided')),
+    exported_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (invoice_id, format, kind),
+    FOREIGN KEY (invoice_id) REFERENCES invoices(id) ON DELETE CASCADE
+);
+
+-- Accounts with an open directive already written (beancount)
+CREATE TABLE IF NOT EXISTS journal_accounts (
+    format TEXT NOT NULL,
+    account TEXT NOT NULL,
+    PRIMARY KEY (format, account)
+);
+
+-- ========== EXPENSE TABLES ==========
+
+-- One-off and recurring expenses; a recurring one repeats from date until end_date
+CREATE TABLE IF NOT EXISTS expenses (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    date TEXT NOT NULL,
+    amount REAL NOT NULL CHECK(amount > 0),
+    description TEXT NOT NULL,
+    category TEXT NOT NULL,
+    is_business INTEGER NOT NULL DEFAULT 1,
+    client_name TEXT,
+    project_name TEXT,
+    recurrence TEXT NOT NULL DEFAULT 'None' CHECK(recurrence IN ('None', 'Weekly', 'Monthly', 'Quarterly', 'Yearly')),
+    end_date TEXT,
+    receipt_paths TEXT NOT NULL DEFAULT '[]',  -- JSON array of file paths
+    notes TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_expenses_date ON expenses(date);
+CREATE INDEX IF NOT EXISTS idx_expenses_category ON expenses(category);
+
 -- ========== AUTO-TRACKING TABLES ==========
 
 -- Activity suggestions from auto-tracking
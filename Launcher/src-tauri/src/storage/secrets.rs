@@ -0,0 +1,143 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
+/// Prefix of sealed values, so the format can change later
+const SEALED_PREFIX: &str = "v1:";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+/// Encrypts setting values with ChaCha20-Poly1305. The key is generated on first
+/// use and kept in a file beside the database, readable only by the current user,
+/// so a copied database alone does not reveal the secrets.
+pub struct SecretStore {
+    key_path: PathBuf,
+}
+
+impl SecretStore {
+    pub fn new(key_path: PathBuf) -> Self {
+        SecretStore { key_path }
+    }
+
+    /// Encrypt `plaintext` into a `v1:`-prefixed base64 string
+    pub fn seal(&self, plaintext: &str) -> Result<String, String> {
+        let cipher = ChaCha20Poly1305::new(&self.load_or_create_key()?);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher.encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| "Failed to encrypt secret".to_string())?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(format!("{}{}", SEALED_PREFIX, BASE64.encode(sealed)))
+    }
+
+    /// Decrypt a value produced by `seal`
+    pub fn open(&self, sealed: &str) -> Result<String, String> {
+        let encoded = sealed.strip_prefix(SEALED_PREFIX).ok_or("Unknown secret format")?;
+        let bytes = BASE64.decode(encoded).map_err(|_| "Corrupt secret")?;
+        if bytes.len() < NONCE_LEN {
+            return Err("Corrupt secret".to_string());
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let cipher = ChaCha20Poly1305::new(&self.load_or_create_key()?);
+        let plaintext = cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Failed to decrypt secret; the key file may have changed".to_string())?;
+        String::from_utf8(plaintext).map_err(|_| "Corrupt secret".to_string())
+    }
+
+    fn load_or_create_key(&self) -> Result<Key, String> {
+        match std::fs::read(&self.key_path) {
+            Ok(bytes) if bytes.len() == KEY_LEN => return Ok(*Key::from_slice(&bytes)),
+            Ok(_) => return Err(format!("Secret key file {} is corrupt", self.key_path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to read {}: {}", self.key_path.display(), e)),
+        }
+
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        match options.open(&self.key_path) {
+            Ok(mut file) => {
+                file.write_all(&key)
+                    .map_err(|e| format!("Failed to write {}: {}", self.key_path.display(), e))?;
+                Ok(key)
+            }
+            // Another caller created it first; use theirs
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => self.load_or_create_key(),
+            Err(e) => Err(format!("Failed to create {}: {}", self.key_path.display(), e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Store with its own key file, removed on drop
+    struct TestStore(SecretStore);
+
+    impl Drop for TestStore {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0.key_path);
+        }
+    }
+
+    fn test_store() -> TestStore {
+        static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let n = NEXT.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let path = std::env::temp_dir().join(format!("chrono-secrets-{}-{}.key", std::process::id(), n));
+        let _ = std::fs::remove_file(&path);
+        TestStore(SecretStore::new(path))
+    }
+
+    #[test]
+    fn sealed_values_open_with_the_same_key() {
+        let store = test_store();
+        let sealed = store.0.seal("hunter2").unwrap();
+        assert!(sealed.starts_with(SEALED_PREFIX));
+        assert!(!sealed.contains("hunter2"));
+        assert_ne!(store.0.seal("hunter2").unwrap(), sealed);
+        assert_eq!(store.0.open(&sealed).unwrap(), "hunter2");
+        assert_eq!(std::fs::read(&store.0.key_path).unwrap().len(), KEY_LEN);
+
+        // A second store on the same file shares the key
+        let other = SecretStore::new(store.0.key_path.clone());
+        assert_eq!(other.open(&sealed).unwrap(), "hunter2");
+    }
+
+    #[test]
+    fn tampered_or_foreign_values_are_rejected() {
+        let store = test_store();
+        let sealed = store.0.seal("hunter2").unwrap();
+        let mut bytes = BASE64.decode(&sealed[SEALED_PREFIX.len()..]).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let tampered = format!("{}{}", SEALED_PREFIX, BASE64.encode(&bytes));
+        assert!(store.0.open(&tampered).unwrap_err().contains("Failed to decrypt"));
+
+        assert_eq!(store.0.open(&sealed.replacen("v1:", "v2:", 1)).unwrap_err(), "Unknown secret format");
+        assert_eq!(store.0.open("hunter2").unwrap_err(), "Unknown secret format");
+        assert_eq!(store.0.open("v1:AAAA").unwrap_err(), "Corrupt secret");
+        assert_eq!(store.0.open("v1:not base64!").unwrap_err(), "Corrupt secret");
+
+        // Sealed under another key
+        let other = test_store();
+        assert!(other.0.open(&sealed).is_err());
+    }
+
+    #[test]
+    fn wrong_length_key_file_is_an_error_and_kept() {
+        let store = test_store();
+        std::fs::write(&store.0.key_path, [7u8; 16]).unwrap();
+        assert!(store.0.seal("hunter2").unwrap_err().contains("is corrupt"));
+        assert!(store.0.open("v1:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA").unwrap_err().contains("is corrupt"));
+        assert_eq!(std::fs::read(&store.0.key_path).unwrap(), vec![7u8; 16]);
+    }
+}
@@ -1,13 +1,17 @@
 import { invoke } from '@tauri-apps/api/tauri';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import type {
   WorkSession,
   NewSession,
+  SessionQuery,
+  SessionPage,
   FinancialGoal,
   NewGoal,
   TodaySummary,
   SessionType,
   NewSessionType,
   PaySummary,
+  ReportingBasis,
   GoalType,
   AnalyticsData,
   Achievement,
@@ -16,6 +20,48 @@ import type {
   BurnoutRisk,
   Invoice,
   NewInvoice,
+  NewCreditNote,
+  InvoiceCriteria,
+  InvoicePreview,
+  InvoiceNumbering,
+  SmtpSettings,
+  InvoiceHistoryEntry,
+  InvoiceDraftUpdate,
+  InvoiceItemUpdate,
+  NewInvoiceItem,
+  TaxRate,
+  NewTaxRate,
+  BusinessProfile,
+  NewBusinessProfile,
+  InvoiceTemplate,
+  NewInvoiceTemplate,
+  NewInvoicePayment,
+  Quote,
+  NewQuote,
+  QuoteDraftUpdate,
+  QuoteStatus,
+  Retainer,
+  NewRetainer,
+  RetainerUsage,
+  RetainerBilling,
+  RetainerBillingFailure,
+  ReminderSchedule,
+  NewReminderSchedule,
+  InvoiceReminder,
+  DueReminder,
+  PaymentReminder,
+  ReceivablesReport,
+  ClientPaymentStats,
+  BankCsvFormat,
+  BankTransaction,
+  BankImportResult,
+  MatchSuggestion,
+  JournalFormat,
+  AccountingSettings,
+  JournalExportResult,
+  Expense,
+  NewExpense,
+  NetIncomeReport,
   Habit,
   NewHabit,
   HabitLog,
@@ -56,6 +102,10 @@ export async function getSessionsByTypeId(sessionTypeId: number): Promise<WorkSe
   return await invoke('get_sessions_by_type_id', { sessionTypeId });
 }
 
+export async function querySessions(query: SessionQuery): Promise<SessionPage> {
+  return await invoke('query_sessions', { query });
+}
+
 export async function addSession(session: NewSession): Promise<number> {
   return await invoke('add_session', { session });
 }
@@ -102,8 +152,8 @@ export async function getTodaySummary(): Promise<TodaySummary> {
   return await invoke('get_today_summary');
 }
 
-export async function getPaySummary(): Promise<PaySummary> {
-  return await invoke('get_pay_summary');
+export async function getPaySummary(basis?: ReportingBasis): Promise<PaySummary> {
+  return await invoke('get_pay_summary', { basis });
 }
 
 export async function getRecentSessions(limit: number): Promise<WorkSession[]> {
@@ -124,8 +174,8 @@ export async function getCurrentDate(): Promise<string> {
 
 // ========== ANALYTICS API ==========
 
-export async function getAnalytics(rangeStart: string, rangeEnd: string): Promise<AnalyticsData> {
-  return await invoke('get_analytics', { rangeStart, rangeEnd });
+export async function getAnalytics(rangeStart: string, rangeEnd: string, basis?: ReportingBasis): Promise<AnalyticsData> {
+  return await invoke('get_analytics', { rangeStart, rangeEnd, basis });
 }
 
 // ========== AI ADVISOR API ==========
@@ -192,15 +242,17 @@ export async function logAppEvent(eventType: string, eventData?: string): Promis
 
 // ========== FINANCIAL SIMULATOR API ==========
 
+// weeklyExpenses defaults to the average from the expense history
 export async function simulateFinancialScenario(
   hoursPerWeek: number,
   hourlyRate: number,
-  weeklyExpenses: number
+  weeklyExpenses?: number
 ): Promise<SimulationResult> {
   return await invoke('simulate_financial_scenario', { hoursPerWeek, hourlyRate, weeklyExpenses });
 }
 
-export async function getCurrentFinancialBaseline(): Promise<[number, number]> {
+// [weekly hours, hourly rate, weekly expenses]
+export async function getCurrentFinancialBaseline(): Promise<[number, number, number]> {
   return await invoke('get_current_financial_baseline');
 }
 
@@ -228,6 +280,45 @@ export async function createInvoice(invoice: NewInvoice): Promise<number> {
   return await invoke('create_invoice', { invoice });
 }
 
+// Client fields left blank are filled from the last invoice for the same projects
+export async function previewInvoiceFromCriteria(criteria: InvoiceCriteria, invoice: NewInvoice): Promise<InvoicePreview> {
+  return await invoke('preview_invoice_from_criteria', { criteria, invoice });
+}
+
+// Bills exactly the previewed sessions: pass the preview's session ids as invoice.session_ids
+export async function createInvoiceFromCriteria(criteria: InvoiceCriteria, invoice: NewInvoice): Promise<Invoice> {
+  return await invoke('create_invoice_from_criteria', { criteria, invoice });
+}
+
+// Tax rates
+export async function getAllTaxRates(): Promise<TaxRate[]> {
+  return await invoke('get_all_tax_rates');
+}
+
+export async function addTaxRate(taxRate: NewTaxRate): Promise<number> {
+  return await invoke('add_tax_rate', { taxRate });
+}
+
+export async function updateTaxRate(id: number, taxRate: NewTaxRate): Promise<void> {
+  return await invoke('update_tax_rate', { id, taxRate });
+}
+
+export async function deleteTaxRate(id: number): Promise<void> {
+  return await invoke('delete_tax_rate', { id });
+}
+
+export async function getInvoiceNumbering(): Promise<InvoiceNumbering> {
+  return await invoke('get_invoice_numbering');
+}
+
+export async function setInvoiceNumbering(numbering: InvoiceNumbering): Promise<void> {
+  return await invoke('set_invoice_numbering', { numbering });
+}
+
+export async function previewInvoiceNumber(clientName: string): Promise<string> {
+  return await invoke('preview_invoice_number', { clientName });
+}
+
 export async function updateInvoiceStatus(id: number, status: string): Promise<void> {
   return await invoke('update_invoice_status', { id, status });
 }
@@ -236,10 +327,341 @@ export async function deleteInvoice(id: number): Promise<void> {
   return await invoke('delete_invoice', { id });
 }
 
+export async function getCreditNotes(invoiceId: number): Promise<Invoice[]> {
+  return await invoke('get_credit_notes', { invoiceId });
+}
+
+export async function createCreditNote(invoiceId: number, creditNote: NewCreditNote): Promise<Invoice> {
+  return await invoke('create_credit_note', { invoiceId, creditNote });
+}
+
+export async function voidInvoice(id: number, releaseSessions: boolean): Promise<Invoice> {
+  return await invoke('void_invoice', { id, releaseSessions });
+}
+
+// Draft editing (invoices are locked once they leave Draft)
+export async function updateDraftInvoice(id: number, update: InvoiceDraftUpdate): Promise<Invoice> {
+  return await invoke('update_draft_invoice', { id, update });
+}
+
+export async function addSessionsToInvoice(invoiceId: number, sessionIds: number[]): Promise<Invoice> {
+  return await invoke('add_sessions_to_invoice', { invoiceId, sessionIds });
+}
+
+export async function addInvoiceItem(invoiceId: number, item: NewInvoiceItem): Promise<Invoice> {
+  return await invoke('add_invoice_item', { invoiceId, item });
+}
+
+export async function updateInvoiceItem(invoiceId: number, item: InvoiceItemUpdate): Promise<Invoice> {
+  return await invoke('update_invoice_item', { invoiceId, item });
+}
+
+export async function removeInvoiceItem(invoiceId: number, itemId: number): Promise<Invoice> {
+  return await invoke('remove_invoice_item', { invoiceId, itemId });
+}
+
+// Payments (status moves to PartiallyPaid/Paid automatically)
+export async function recordPayment(invoiceId: number, payment: NewInvoicePayment): Promise<Invoice> {
+  return await invoke('record_payment', { invoiceId, payment });
+}
+
+export async function deletePayment(invoiceId: number, paymentId: number): Promise<Invoice> {
+  return await invoke('delete_payment', { invoiceId, paymentId });
+}
+
+// Overdue detection (runs at startup and daily in the backend)
+export async function checkOverdueInvoices(): Promise<Invoice[]> {
+  return await invoke('check_overdue_invoices');
+}
+
+export async function onInvoicesOverdue(handler: (invoices: Invoice[]) => void): Promise<UnlistenFn> {
+  return await listen<Invoice[]>('invoices-overdue', event => handler(event.payload));
+}
+
+// Native PDF rendering (font from the 'pdf_font_path' setting, else a system font)
+export async function renderInvoicePdf(id: number, path: string): Promise<void> {
+  return await invoke('render_invoice_pdf', { id, path });
+}
+
+// HTML rendering with the invoice's template and sender profile
+export async function renderInvoiceHtml(id: number): Promise<string> {
+  return await invoke('render_invoice_html', { id });
+}
+
+export async function exportInvoiceUbl(id: number, path: string): Promise<void> {
+  return await invoke('export_invoice_ubl', { id, path });
+}
+
+export async function exportInvoiceFacturx(id: number, path: string): Promise<void> {
+  return await invoke('export_invoice_facturx', { id, path });
+}
+
+export async function getSmtpSettings(): Promise<SmtpSettings | null> {
+  return await invoke('get_smtp_settings');
+}
+
+export async function setSmtpSettings(settings: SmtpSettings): Promise<void> {
+  return await invoke('set_smtp_settings', { settings });
+}
+
+export async function getInvoiceHistory(invoiceId: number): Promise<InvoiceHistoryEntry[]> {
+  return await invoke('get_invoice_history', { invoiceId });
+}
+
+// Emails the invoice PDF; `to` defaults to the client's email. Drafts become Sent.
+export async function sendInvoiceEmail(id: number, to?: string): Promise<Invoice> {
+  return await invoke('send_invoice_email', { id, to });
+}
+
 export async function getUninvoicedSessions(): Promise<WorkSession[]> {
   return await invoke('get_uninvoiced_sessions');
 }
 
+// ========== QUOTE API ==========
+
+export async function getAllQuotes(): Promise<Quote[]> {
+  return await invoke('get_all_quotes');
+}
+
+export async function getQuote(id: number): Promise<Quote> {
+  return await invoke('get_quote', { id });
+}
+
+export async function createQuote(quote: NewQuote): Promise<number> {
+  return await invoke('create_quote', { quote });
+}
+
+// Draft editing (quotes are locked once they leave Draft)
+export async function updateDraftQuote(id: number, update: QuoteDraftUpdate): Promise<Quote> {
+  return await invoke('update_draft_quote', { id, update });
+}
+
+export async function addQuoteItem(quoteId: number, item: NewInvoiceItem): Promise<Quote> {
+  return await invoke('add_quote_item', { quoteId, item });
+}
+
+export async function updateQuoteItem(quoteId: number, item: InvoiceItemUpdate): Promise<Quote> {
+  return await invoke('update_quote_item', { quoteId, item });
+}
+
+export async function removeQuoteItem(quoteId: number, itemId: number): Promise<Quote> {
+  return await invoke('remove_quote_item', { quoteId, itemId });
+}
+
+export async function updateQuoteStatus(id: number, status: QuoteStatus): Promise<Quote> {
+  return await invoke('update_quote_status', { id, status });
+}
+
+// Only draft quotes can be deleted
+export async function deleteQuote(id: number): Promise<void> {
+  return await invoke('delete_quote', { id });
+}
+
+// Creates a draft invoice from a sent or accepted quote and links the two
+export async function convertQuoteToInvoice(id: number, dueDate: string): Promise<Invoice> {
+  return await invoke('convert_quote_to_invoice', { id, dueDate });
+}
+
+// Expiry (runs at startup and daily in the backend)
+export async function checkExpiredQuotes(): Promise<Quote[]> {
+  return await invoke('check_expired_quotes');
+}
+
+export async function onQuotesExpired(handler: (quotes: Quote[]) => void): Promise<UnlistenFn> {
+  return await listen<Quote[]>('quotes-expired', event => handler(event.payload));
+}
+
+// ========== BUSINESS PROFILE & TEMPLATE API ==========
+
+export async function getAllBusinessProfiles(): Promise<BusinessProfile[]> {
+  return await invoke('get_all_business_profiles');
+}
+
+export async function addBusinessProfile(profile: NewBusinessProfile): Promise<number> {
+  return await invoke('add_business_profile', { profile });
+}
+
+export async function updateBusinessProfile(id: number, profile: NewBusinessProfile): Promise<void> {
+  return await invoke('update_business_profile', { id, profile });
+}
+
+export async function deleteBusinessProfile(id: number): Promise<void> {
+  return await invoke('delete_business_profile', { id });
+}
+
+export async function getAllInvoiceTemplates(): Promise<InvoiceTemplate[]> {
+  return await invoke('get_all_invoice_templates');
+}
+
+export async function addInvoiceTemplate(template: NewInvoiceTemplate): Promise<number> {
+  return await invoke('add_invoice_template', { template });
+}
+
+export async function updateInvoiceTemplate(id: number, template: NewInvoiceTemplate): Promise<void> {
+  return await invoke('update_invoice_template', { id, template });
+}
+
+export async function deleteInvoiceTemplate(id: number): Promise<void> {
+  return await invoke('delete_invoice_template', { id });
+}
+
+// Editable copy of a template, including the built-in one (id 0)
+export async function duplicateInvoiceTemplate(id: number): Promise<number> {
+  return await invoke('duplicate_invoice_template', { id });
+}
+
+// ========== RETAINER API ==========
+
+export async function getAllRetainers(): Promise<Retainer[]> {
+  return await invoke('get_all_retainers');
+}
+
+export async function addRetainer(retainer: NewRetainer): Promise<number> {
+  return await invoke('add_retainer', { retainer });
+}
+
+export async function updateRetainer(id: number, retainer: NewRetainer): Promise<void> {
+  return await invoke('update_retainer', { id, retainer });
+}
+
+export async function deleteRetainer(id: number): Promise<void> {
+  return await invoke('delete_retainer', { id });
+}
+
+// Hours used versus included, per period
+export async function getRetainerUsage(id: number): Promise<RetainerUsage[]> {
+  return await invoke('get_retainer_usage', { id });
+}
+
+// Retainer billing (runs at startup and daily in the backend)
+export async function billDueRetainers(): Promise<RetainerBilling> {
+  return await invoke('bill_due_retainers');
+}
+
+export async function onRetainerInvoicesCreated(handler: (invoices: Invoice[]) => void): Promise<UnlistenFn> {
+  return await listen<Invoice[]>('retainer-invoices-created', event => handler(event.payload));
+}
+
+export async function onRetainerBillingFailed(handler: (failures: RetainerBillingFailure[]) => void): Promise<UnlistenFn> {
+  return await listen<RetainerBillingFailure[]>('retainer-billing-failed', event => handler(event.payload));
+}
+
+// ========== PAYMENT REMINDER API ==========
+
+export async function getReminderSchedules(): Promise<ReminderSchedule[]> {
+  return await invoke('get_reminder_schedules');
+}
+
+export async function addReminderSchedule(schedule: NewReminderSchedule): Promise<number> {
+  return await invoke('add_reminder_schedule', { schedule });
+}
+
+export async function updateReminderSchedule(id: number, schedule: NewReminderSchedule): Promise<void> {
+  return await invoke('update_reminder_schedule', { id, schedule });
+}
+
+export async function deleteReminderSchedule(id: number): Promise<void> {
+  return await invoke('delete_reminder_schedule', { id });
+}
+
+export async function getDueReminders(): Promise<DueReminder[]> {
+  return await invoke('get_due_reminders');
+}
+
+export async function getInvoiceReminders(invoiceId: number): Promise<InvoiceReminder[]> {
+  return await invoke('get_invoice_reminders', { invoiceId });
+}
+
+export async function sendDueReminders(): Promise<PaymentReminder[]> {
+  return await invoke('send_due_reminders');
+}
+
+export async function onPaymentReminders(handler: (reminders: PaymentReminder[]) => void): Promise<UnlistenFn> {
+  return await listen<PaymentReminder[]>('payment-reminders', event => handler(event.payload));
+}
+
+// ========== RECEIVABLES API ==========
+
+export async function getReceivablesReport(): Promise<ReceivablesReport> {
+  return await invoke('get_receivables_report');
+}
+
+export async function getClientPaymentStats(): Promise<ClientPaymentStats[]> {
+  return await invoke('get_client_payment_stats');
+}
+
+// ========== BANK RECONCILIATION API ==========
+
+// csvFormat is remembered for the next CSV import; OFX files don't need one
+export async function importBankStatement(path: string, csvFormat?: BankCsvFormat): Promise<BankImportResult> {
+  return await invoke('import_bank_statement', { path, csvFormat });
+}
+
+export async function getBankCsvFormat(): Promise<BankCsvFormat | null> {
+  return await invoke('get_bank_csv_format');
+}
+
+export async function getBankTransactions(unreconciledOnly: boolean): Promise<BankTransaction[]> {
+  return await invoke('get_bank_transactions', { unreconciledOnly });
+}
+
+export async function suggestBankMatches(): Promise<MatchSuggestion[]> {
+  return await invoke('suggest_bank_matches');
+}
+
+export async function confirmBankMatch(transactionId: number, invoiceId: number, amount?: number): Promise<Invoice> {
+  return await invoke('confirm_bank_match', { transactionId, invoiceId, amount });
+}
+
+export async function ignoreBankTransaction(id: number, ignored: boolean): Promise<void> {
+  return await invoke('ignore_bank_transaction', { id, ignored });
+}
+
+export async function deleteBankTransaction(id: number): Promise<void> {
+  return await invoke('delete_bank_transaction', { id });
+}
+
+// ========== ACCOUNTING EXPORT API ==========
+
+export async function getAccountingSettings(): Promise<AccountingSettings> {
+  return await invoke('get_accounting_settings');
+}
+
+export async function setAccountingSettings(settings: AccountingSettings): Promise<void> {
+  return await invoke('set_accounting_settings', { settings });
+}
+
+// incremental appends only what changed since the last export in this format
+export async function exportJournal(path: string, format: JournalFormat, incremental: boolean): Promise<JournalExportResult> {
+  return await invoke('export_journal', { path, format, incremental });
+}
+
+// ========== EXPENSE API ==========
+
+export async function getExpenses(): Promise<Expense[]> {
+  return await invoke('get_expenses');
+}
+
+export async function addExpense(expense: NewExpense): Promise<number> {
+  return await invoke('add_expense', { expense });
+}
+
+export async function updateExpense(id: number, expense: NewExpense): Promise<void> {
+  return await invoke('update_expense', { id, expense });
+}
+
+export async function deleteExpense(id: number): Promise<void> {
+  return await invoke('delete_expense', { id });
+}
+
+export async function getExpenseCategories(): Promise<string[]> {
+  return await invoke('get_expense_categories');
+}
+
+export async function getNetIncome(start: string, end: string, basis?: ReportingBasis): Promise<NetIncomeReport> {
+  return await invoke('get_net_income', { start, end, basis });
+}
+
 // ========== HABIT API ==========
 
 export async function getAllHabits(): Promise<Habit[]> {
@@ -324,8 +746,8 @@ export async function deleteTrackingRule(id: number): Promise<void> {
 
 // ========== ENHANCED AI ADVISOR API ==========
 
-export async function getFinancialAnalysis(): Promise<FinancialAnalysis> {
-  return await invoke('get_financial_analysis');
+export async function getFinancialAnalysis(basis?: ReportingBasis): Promise<FinancialAnalysis> {
+  return await invoke('get_financial_analysis', { basis });
 }
 
 // ========== LICENSE API ==========
@@ -49,6 +49,40 @@ export interface NewSession {
   fixed_amount?: number;
 }
 
+// ========== SESSION QUERY TYPES ==========
+
+export type SessionSortKey = 'Date' | 'Hours' | 'Pay' | 'Project' | 'SessionType';
+
+export type SortDirection = 'Asc' | 'Desc';
+
+export interface SessionFilter {
+  date_from?: string;
+  date_to?: string;
+  session_type_ids?: number[];
+  project_names?: string[];
+  pay_types?: PayType[];
+  invoiced?: boolean;
+  min_hours?: number;
+  max_hours?: number;
+  text?: string;
+}
+
+export interface SessionQuery {
+  filter?: SessionFilter;
+  sort_by?: SessionSortKey;
+  sort_direction?: SortDirection;
+  cursor?: string | null;       // next_cursor from the previous page
+  limit?: number;               // default 50, max 500
+}
+
+export interface SessionPage {
+  sessions: WorkSession[];
+  next_cursor: string | null;
+  total_count: number;          // totals cover the whole filtered set
+  total_hours: number;
+  total_pay: number;
+}
+
 export interface FinancialGoal {
   id: number;
   goal_type: GoalType;
@@ -75,11 +109,23 @@ export interface TodaySummary {
   session_hours: Record<string, number>;
 }
 
+// When income counts: Earned = when worked, Invoiced = invoice date (net of
+// tax, less credit notes), Received = payment date (cash basis)
+export type ReportingBasis = 'Earned' | 'Invoiced' | 'Received';
+
+export interface IncomePipeline {
+  unbilled: number;         // earned, not on an issued invoice
+  unbilled_hours: number;
+  invoiced_unpaid: number;  // balance due on open invoices
+}
+
 export interface PaySummary {
   today: number;
   this_month: number;
   this_year: number;
   all_time: number;
+  basis: ReportingBasis;
+  pipeline: IncomePipeline;
 }
 
 // ========== ANALYTICS TYPES ==========
@@ -113,6 +159,7 @@ export interface WeekdayBreakdown {
 }
 
 export interface AnalyticsData {
+  basis: ReportingBasis;    // pay is counted on this basis, hours when worked
   summary: AnalyticsSummary;
   daily_hours: DailyHours[];
   category_breakdown: CategoryBreakdown[];
@@ -199,17 +246,80 @@ export interface BurnoutRisk {
 export enum InvoiceStatus {
   Draft = 'Draft',
   Sent = 'Sent',
+  PartiallyPaid = 'PartiallyPaid',
   Paid = 'Paid',
-  Overdue = 'Overdue'
+  Overdue = 'Overdue',
+  Void = 'Void'
+}
+
+export type InvoiceItemKind = 'Session' | 'Manual' | 'Expense';
+
+export interface Discount {
+  discount_type: 'Percent' | 'Fixed';
+  value: number;               // percentage (0-100) or fixed amount
+}
+
+// How sessions become invoice lines; sessions in a group merge per rate
+export type LineGrouping = 'PerSession' | 'PerProject' | 'PerDay' | 'PerSessionType' | 'Summary';
+
+export interface TimesheetEntry {
+  session_id: number;
+  date: string;
+  session_type: string | null;
+  project_name: string;
+  description: string | null;
+  hours: number;
 }
 
 export interface InvoiceItem {
   id: number;
   invoice_id: number;
-  session_id: number | null;
+  session_id: number | null;   // set when the line bills exactly one session
+  session_ids: number[];       // every session billed on the line
+  kind: InvoiceItemKind;
   description: string;
-  hours: number;
+  hours: number;               // quantity for Manual/Expense lines
+  unit: string | null;
+  rate: number;                // unit price for Manual/Expense lines
+  discount: Discount | null;
+  taxable: boolean;
+  tax_category: string | null;
+  amount: number;              // net of the line discount
+  credited_item_id: number | null; // on credit notes, the invoice line credited
+  session_released: boolean;   // session credited or voided, billable again
+}
+
+export type TaxKind = 'Additive' | 'Withholding' | 'Inclusive';
+
+// Additive/Inclusive taxes apply to taxable lines with the same category
+// (null matches uncategorized lines); Withholding applies to all taxable lines
+export interface TaxRate {
+  id: number;
+  name: string;
+  rate: number;                // percentage, e.g. 20 for 20%
+  kind: TaxKind;
+  category: string | null;
+  note: string | null;         // printed on the invoice, e.g. reverse charge
+}
+
+export interface NewTaxRate {
+  name: string;
   rate: number;
+  kind: TaxKind;
+  category?: string;
+  note?: string;
+}
+
+export interface InvoiceTax {
+  id: number;
+  invoice_id: number;
+  tax_rate_id: number | null;
+  name: string;
+  rate: number;
+  kind: TaxKind;
+  category: string | null;
+  note: string | null;
+  base: number;
   amount: number;
 }
 
@@ -218,24 +328,556 @@ export interface Invoice {
   invoice_number: string;
   client_name: string;
   client_email: string | null;
+  client_address: ClientAddress; // buyer address, required for e-invoices
   created_date: string;
+  issued_date: string | null;  // when it left Draft
   due_date: string | null;
-  status: string;              // "Draft", "Sent", "Paid", "Overdue"
+  status: string;              // "Draft", "Sent", "PartiallyPaid", "Paid", "Overdue"
   subtotal: number;
-  tax_rate: number | null;
+  tax_rate: number | null;     // ad-hoc additive rate, as a percentage
+  tax_amount: number;          // additive + inclusive tax
+  total: number;
+  notes: string | null;
+  discount: Discount | null;
+  discount_amount: number;
+  withholding_amount: number;  // deducted from total
+  amount_paid: number;
+  balance_due: number;         // 0 once Paid
+  overdue_at: string | null;   // when it was last marked Overdue
+  profile_id: number | null;   // null renders with the default profile
+  template_id: number | null;  // null renders with the built-in template
+  credited_invoice_id: number | null;     // set on credit notes
+  credited_invoice_number: string | null;
+  amount_credited: number;     // sum of non-void credit notes
+  voided_at: string | null;
+  paid_date: string | null;    // last payment date once Paid
+  line_grouping: LineGrouping;
+  include_timesheet: boolean;
+  quote_id: number | null;     // quote this invoice was converted from
+  quote_number: string | null;
+  items: InvoiceItem[];
+  taxes: InvoiceTax[];         // tax summary
+  payments: InvoicePayment[];
+  timesheet: TimesheetEntry[]; // only loaded with include_timesheet
+}
+
+// Postal address and VAT ID of the invoiced client
+export interface ClientAddress {
+  address?: string | null;     // street lines
+  city?: string | null;
+  postal_code?: string | null;
+  country_code?: string | null; // ISO 3166-1 alpha-2
+  tax_id?: string | null;
+}
+
+export interface InvoicePayment {
+  id: number;
+  invoice_id: number;
+  payment_date: string;        // YYYY-MM-DD
+  amount: number;
+  method: string | null;       // e.g. "Bank transfer", "Card", "Cash"
+  reference: string | null;
+  notes: string | null;
+  bank_transaction_id: number | null;  // set when recorded from a bank transaction
+}
+
+export interface CreditedLine {
+  item_id: number;
+  quantity: number;
+}
+
+export interface NewCreditNote {
+  lines?: CreditedLine[];      // empty credits everything not yet credited
+  notes?: string;              // reason printed on the credit note
+  release_sessions?: boolean;  // fully credited sessions become billable again
+}
+
+export interface NewInvoicePayment {
+  payment_date: string;
+  amount: number;
+  method?: string;
+  reference?: string;
+  notes?: string;
+}
+
+// Sender details printed on invoices; exactly one profile is the default
+export interface BusinessProfile {
+  id: number;
+  name: string;                // label shown in the app
+  business_name: string;
+  address: string | null;
+  city: string | null;
+  postal_code: string | null;
+  country_code: string | null; // ISO 3166-1 alpha-2, required for e-invoices
+  email: string | null;
+  phone: string | null;
+  tax_id: string | null;
+  bank_details: string | null; // payment instructions
+  logo_path: string | null;    // PNG or JPEG
+  currency: string | null;     // ISO 4217, required for e-invoices
+  is_default: boolean;
+}
+
+export interface NewBusinessProfile {
+  name: string;
+  business_name: string;
+  address?: string;
+  city?: string;
+  postal_code?: string;
+  country_code?: string;
+  email?: string;
+  phone?: string;
+  tax_id?: string;
+  bank_details?: string;
+  logo_path?: string;
+  currency?: string;
+  is_default?: boolean;
+}
+
+// Handlebars template for HTML invoices. The built-in one has id 0 and is read-only.
+export interface InvoiceTemplate {
+  id: number;
+  name: string;
+  body: string;
+  is_builtin: boolean;
+}
+
+export interface NewInvoiceTemplate {
+  name: string;
+  body: string;
+}
+
+// Quotes: Draft -> Sent -> Accepted / Rejected / Expired (after valid_until)
+export enum QuoteStatus {
+  Draft = 'Draft',
+  Sent = 'Sent',
+  Accepted = 'Accepted',
+  Rejected = 'Rejected',
+  Expired = 'Expired'
+}
+
+// Estimate priced like an invoice; lines and taxes use the invoice types
+export interface Quote {
+  id: number;
+  quote_number: string;
+  client_name: string;
+  client_email: string | null;
+  created_date: string;
+  valid_until: string;         // YYYY-MM-DD
+  status: QuoteStatus;
+  subtotal: number;
+  discount: Discount | null;
+  discount_amount: number;
   tax_amount: number;
+  withholding_amount: number;
   total: number;
   notes: string | null;
+  profile_id: number | null;
+  invoice_id: number | null;   // invoice the quote was converted into
+  invoice_number: string | null;
   items: InvoiceItem[];
+  taxes: InvoiceTax[];
+}
+
+export interface NewQuote {
+  client_name: string;
+  client_email?: string;
+  valid_until: string;
+  notes?: string;
+  items?: NewInvoiceItem[];
+  discount?: Discount;
+  tax_rate_ids?: number[];
+  profile_id?: number;
+}
+
+export interface QuoteDraftUpdate {
+  client_name: string;
+  client_email?: string;
+  valid_until: string;
+  notes?: string;
+  discount?: Discount;
+  tax_rate_ids?: number[];
+  profile_id?: number;
+}
+
+// Retainer: a fee per period covering included hours; extra hours billed at the
+// overage rate. A draft invoice is generated the day after each period ends.
+export type RetainerPeriod = 'Weekly' | 'Monthly' | 'Quarterly' | 'Yearly';
+export type RolloverPolicy = 'Expire' | 'Carry';
+
+export interface Retainer {
+  id: number;
+  client_name: string;
+  client_email?: string;
+  project_names: string[];     // sessions on these projects count against the hours
+  period: RetainerPeriod;
+  fee: number;
+  included_hours: number;
+  overage_rate: number;
+  rollover: RolloverPolicy;
+  rollover_cap?: number;       // most hours carried into a period
+  start_date: string;
+  end_date?: string;
+  payment_terms_days: number;
+  profile_id?: number;
+  tax_rate_ids: number[];
+  active: boolean;
+}
+
+export interface NewRetainer {
+  client_name: string;
+  client_email?: string;
+  project_names: string[];
+  period: RetainerPeriod;
+  fee: number;
+  included_hours: number;
+  overage_rate: number;
+  rollover: RolloverPolicy;
+  rollover_cap?: number;
+  start_date: string;
+  end_date?: string;
+  payment_terms_days: number;
+  profile_id?: number;
+  tax_rate_ids?: number[];
+  active?: boolean;
+}
+
+// Outcome of a billing run; each retainer is billed on its own
+export interface RetainerBilling {
+  invoices: Invoice[];
+  failures: RetainerBillingFailure[];
+}
+
+export interface RetainerBillingFailure {
+  retainer_id: number;
+  client_name: string;
+  error: string;
+}
+
+// Hours used against a retainer in one period; is_current marks the one still running
+export interface RetainerUsage {
+  retainer_id: number;
+  period_start: string;
+  period_end: string;
+  included_hours: number;
+  rollover_in: number;
+  hours_used: number;
+  overage_hours: number;
+  rollover_out: number;
+  invoice_id?: number;
+  invoice_number?: string;
+  is_current: boolean;
+}
+
+export type SmtpSecurity = 'None' | 'StartTls' | 'Tls';
+
+// Outgoing mail server. password is write-only: leave it empty to keep the
+// stored one; has_password tells whether one is saved.
+// Templates use Handlebars with `invoice` and `profile`, e.g. {{invoice.invoice_number}}, {{money invoice.total}}
+export interface SmtpSettings {
+  host: string;
+  port: number;
+  security: SmtpSecurity;
+  username?: string;
+  password?: string;
+  has_password: boolean;
+  from_address: string;
+  from_name?: string;
+  subject_template: string;
+  body_template: string;
+}
+
+export type InvoiceEvent = 'EmailSent';
+
+export interface InvoiceHistoryEntry {
+  id: number;
+  invoice_id: number;
+  event: InvoiceEvent;
+  recipient?: string;
+  details?: string;   // e.g. the email subject
+  created_at: string;
+}
+
+export type ReminderChannel = 'Notification' | 'Email';
+
+// client_name set: that client's schedule (inactive = no reminders for them);
+// unset: the default schedule. offsets are days from the due date, e.g. [-3, 0, 7, 14]
+export interface ReminderSchedule {
+  id: number;
+  client_name?: string;
+  offsets: number[];
+  channel: ReminderChannel;
+  subject_template: string;
+  body_template: string;
+  active: boolean;
+}
+
+// Templates see invoice, profile and days_overdue; omit them for the defaults
+export interface NewReminderSchedule {
+  client_name?: string;
+  offsets: number[];
+  channel: ReminderChannel;
+  subject_template?: string;
+  body_template?: string;
+  active?: boolean;
+}
+
+export interface InvoiceReminder {
+  id: number;
+  invoice_id: number;
+  schedule_id?: number;
+  offset_days: number;
+  channel: ReminderChannel;
+  recipient?: string;
+  sent_at: string;
+}
+
+export interface DueReminder {
+  invoice: Invoice;
+  schedule: ReminderSchedule;
+  offset_days: number;
+}
+
+// error: why an email reminder didn't go out (fell back to a notification, or will be retried)
+export interface PaymentReminder {
+  invoice: Invoice;
+  offset_days: number;
+  channel: ReminderChannel;
+  recipient?: string;
+  error?: string;
+}
+
+// Outstanding amounts by days past due; current = not yet due
+export interface AgingBuckets {
+  current: number;
+  days_1_30: number;
+  days_31_60: number;
+  days_61_90: number;
+  days_over_90: number;
+  total: number;
+}
+
+export interface ClientReceivables {
+  client_name: string;
+  aging: AgingBuckets;
+  invoice_count: number;
+  oldest_due_date: string;
+}
+
+export interface ReceivablesReport {
+  as_of: string;
+  totals: AgingBuckets;
+  clients: ClientReceivables[];  // largest balance first
+}
+
+export interface ClientPaymentStats {
+  client_name: string;
+  paid_count: number;
+  avg_days_to_pay: number;  // invoice date to paid date
+  on_time_rate: number;     // 0..1
+  avg_days_late: number;    // late invoices only
+}
+
+// Column indexes are 0-based. Give amount_column for a signed amount, or
+// credit_column (and debit_column) when the bank splits them.
+export interface BankCsvFormat {
+  delimiter: string;
+  skip_rows: number;           // rows before the header or first transaction
+  has_header: boolean;
+  date_column: number;
+  date_format: string;         // chrono format, e.g. "%d/%m/%Y"
+  amount_column?: number;
+  credit_column?: number;
+  debit_column?: number;
+  description_column: number;
+  reference_column?: number;
+  counterparty_column?: number;
+  decimal_comma: boolean;      // amounts written as 1.234,56
+}
+
+export interface BankTransaction {
+  id: number;
+  external_id: string;
+  date: string;
+  amount: number;
+  description: string;
+  reference: string | null;
+  counterparty: string | null;
+  ignored: boolean;
+  allocated: number;           // recorded as invoice payments so far
+  invoice_numbers: string[];
+  imported_at: string;
+}
+
+export interface BankImportResult {
+  imported: number;
+  duplicates: number;          // already imported from an earlier statement
+  skipped_outgoing: number;
+}
+
+export type MatchReason = 'ExactAmount' | 'PartialAmount' | 'InvoiceNumber' | 'ClientName';
+
+export interface MatchSuggestion {
+  transaction_id: number;
+  invoice_id: number;
+  invoice_number: string;
+  client_name: string;
+  balance_due: number;
+  amount: number;              // what confirming would record
+  score: number;               // invoice number + exact amount = 100
+  reasons: MatchReason[];
+}
+
+export type JournalFormat = 'Ledger' | 'Beancount';  // Ledger is also read by hledger
+
+// {client} in an account is replaced by the client name, e.g. "Assets:Receivable:{client}"
+export interface AccountingSettings {
+  receivable_account: string;
+  income_account: string;
+  tax_account: string;
+  withholding_account: string;
+  bank_account: string;
+  default_currency: string;    // for invoices whose profile has no currency
+}
+
+export interface JournalExportResult {
+  entries: number;             // 0 when nothing changed since the last export
+  accounts_opened: number;     // beancount open directives written
+}
+
+export type ExpenseRecurrence = 'None' | 'Weekly' | 'Monthly' | 'Quarterly' | 'Yearly';
+
+// A recurring expense is due on date and every period after it, until end_date
+export interface Expense {
+  id: number;
+  date: string;                // YYYY-MM-DD
+  amount: number;
+  description: string;
+  category: string;
+  is_business: boolean;        // personal expenses only reduce savings
+  client_name: string | null;
+  project_name: string | null;
+  recurrence: ExpenseRecurrence;
+  end_date: string | null;
+  receipt_paths: string[];
+  notes: string | null;
+  created_at: string;
+}
+
+export interface NewExpense {
+  date: string;
+  amount: number;
+  description: string;
+  category: string;
+  is_business?: boolean;       // default true
+  client_name?: string | null;
+  project_name?: string | null;
+  recurrence?: ExpenseRecurrence;
+  end_date?: string | null;
+  receipt_paths?: string[];
+  notes?: string | null;
+}
+
+export interface ExpenseCategoryTotal {
+  category: string;
+  business: number;
+  personal: number;
+}
+
+export interface MonthlyNetIncome {
+  month: string;               // YYYY-MM
+  income: number;
+  business_expenses: number;
+  personal_expenses: number;
+  net_income: number;          // income - business expenses
+}
+
+export interface NetIncomeReport {
+  start: string;
+  end: string;
+  basis: ReportingBasis;
+  income: number;
+  business_expenses: number;
+  personal_expenses: number;
+  net_income: number;
+  savings: number;             // net income - personal expenses
+  by_category: ExpenseCategoryTotal[];  // largest first
+  months: MonthlyNetIncome[];
+}
+
+// Pattern tokens: {YYYY}, {YY}, {MM}, {prefix}, {seq}, {seq:N}
+export interface InvoiceNumbering {
+  pattern: string;                          // e.g. "INV-{seq:4}", "{YYYY}-{seq:4}"
+  yearly_reset: boolean;
+  client_prefixes: Record<string, string>;  // client name -> prefix
+  credit_note_pattern: string;              // e.g. "CN-{seq:4}"
+  quote_pattern: string;                    // e.g. "Q-{seq:4}"
 }
 
 export interface NewInvoice {
   client_name: string;
   client_email?: string;
+  client_address?: ClientAddress; // defaults to the client's latest invoice
+  due_date: string;
+  tax_rate?: number;
+  notes?: string;
+  session_ids?: number[];
+  items?: NewInvoiceItem[];
+  discount?: Discount;
+  tax_rate_ids?: number[];
+  profile_id?: number;         // defaults to the default profile
+  template_id?: number;        // 0 or omitted for the built-in template
+  line_grouping?: LineGrouping; // defaults to PerSession
+  include_timesheet?: boolean;
+}
+
+// Uninvoiced paid sessions to bill; empty lists mean "any"
+export interface InvoiceCriteria {
+  project_names?: string[];
+  session_type_ids?: number[];
+  date_from?: string;          // YYYY-MM-DD, inclusive
+  date_to?: string;
+}
+
+export interface InvoicePreview {
+  invoice: Invoice;            // not saved; ids and number are not reserved
+  sessions: WorkSession[];
+}
+
+export interface NewInvoiceItem {
+  kind: 'Manual' | 'Expense';
+  description: string;
+  hours: number;               // quantity
+  unit?: string;
+  rate: number;                // unit price
+  discount?: Discount;
+  taxable?: boolean;           // defaults to true
+  tax_category?: string;
+}
+
+export interface InvoiceDraftUpdate {
+  client_name: string;
+  client_email?: string;
+  client_address?: ClientAddress;
   due_date: string;
   tax_rate?: number;
   notes?: string;
-  session_ids: number[];
+  discount?: Discount;
+  tax_rate_ids?: number[];
+  profile_id?: number;
+  template_id?: number;
+  include_timesheet?: boolean;
+}
+
+export interface InvoiceItemUpdate {
+  id: number;
+  description: string;
+  hours: number;
+  unit?: string;
+  rate: number;
+  discount?: Discount;
+  taxable?: boolean;           // defaults to true
+  tax_category?: string;
 }
 
 // ========== AUTO-TRACKING TYPES ==========
@@ -308,9 +950,12 @@ export interface FinancialInsight {
 export interface FinancialAnalysis {
   avg_weekly_hours: number;
   avg_weekly_income: number;
+  avg_weekly_expenses: number;  // from the expense history
   projected_monthly_income: number;
   projected_yearly_income: number;
   income_trend: string;
+  basis: ReportingBasis;
+  pipeline: IncomePipeline;
   insights: FinancialInsight[];
   recommendations: string[];
 }